@@ -0,0 +1,10 @@
+use crate::modules::audit_db::{self, AuditEntry, AuditQuery};
+
+/// 查询审计日志 (按时间倒序)
+#[tauri::command]
+pub async fn get_audit_logs(query: Option<AuditQuery>) -> Result<Vec<AuditEntry>, String> {
+    let query = query.unwrap_or_default();
+    tokio::task::spawn_blocking(move || audit_db::query(&query))
+        .await
+        .map_err(|e| e.to_string())?
+}
@@ -15,6 +15,8 @@ pub mod security;
 pub mod proxy_pool;
 // 导出 user_token 命令
 pub mod user_token;
+// 导出 audit 命令 (审计日志)
+pub mod audit;
 
 /// 列出所有账号
 #[tauri::command]
@@ -376,6 +378,20 @@ pub async fn save_config(
         crate::proxy::update_global_system_prompt_config(config.proxy.global_system_prompt.clone());
         // [NEW] 更新全局图像思维模式配置
         crate::proxy::update_image_thinking_mode(config.proxy.image_thinking_mode.clone());
+        // [NEW] 更新上游端点配置
+        crate::proxy::update_upstream_endpoints(config.proxy.upstream_endpoints.clone());
+        // [NEW] 更新模型路由规则
+        crate::proxy::update_routing_rules(config.proxy.routing_rules.clone());
+        // [NEW] 更新跨模型降级链
+        crate::proxy::update_model_fallbacks(config.proxy.model_fallbacks.clone());
+        // [NEW] 更新模型单价表
+        crate::proxy::update_model_prices(config.proxy.model_prices.clone());
+        // [NEW] 更新批处理配置
+        crate::proxy::update_batch_config(config.proxy.batch.clone());
+        // [NEW] 更新账号并发上限
+        crate::proxy::update_account_concurrency(config.proxy.account_concurrency.clone());
+        // [NEW] 更新准入排队配置
+        crate::proxy::update_admission_config(config.proxy.admission.clone());
         // 更新代理池配置
         instance
             .axum_server
@@ -987,6 +1003,13 @@ pub async fn get_token_stats_by_model(
     crate::modules::token_stats::get_model_stats(hours)
 }
 
+#[tauri::command]
+pub async fn get_token_stats_by_user(
+    hours: i64,
+) -> Result<Vec<crate::modules::token_stats::UserTokenStats>, String> {
+    crate::modules::token_stats::get_user_token_stats(hours)
+}
+
 #[tauri::command]
 pub async fn get_token_stats_model_trend_hourly(
     hours: i64,
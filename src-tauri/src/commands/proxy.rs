@@ -236,7 +236,7 @@ pub async fn ensure_admin_server(
 
     // 默认空 TokenManager 用于管理界面
     let app_data_dir = crate::modules::account::get_data_dir()?;
-    let token_manager = Arc::new(TokenManager::new(app_data_dir));
+    let token_manager = Arc::new(TokenManager::new(app_data_dir).with_rate_limit_persistence());
     // [NEW] 加载账号数据，否则管理界面统计为 0
     let _ = token_manager.load_accounts().await;
 
@@ -274,6 +274,20 @@ pub async fn ensure_admin_server(
     crate::proxy::update_global_system_prompt_config(config.global_system_prompt.clone());
     // [NEW] 初始化全局图像思维模式配置
     crate::proxy::update_image_thinking_mode(config.image_thinking_mode.clone());
+    // [NEW] 更新上游端点配置
+    crate::proxy::update_upstream_endpoints(config.upstream_endpoints.clone());
+    // [NEW] 初始化模型路由规则
+    crate::proxy::update_routing_rules(config.routing_rules.clone());
+    // [NEW] 初始化跨模型降级链
+    crate::proxy::update_model_fallbacks(config.model_fallbacks.clone());
+    // [NEW] 初始化模型单价表
+    crate::proxy::update_model_prices(config.model_prices.clone());
+    // [NEW] 初始化批处理配置
+    crate::proxy::update_batch_config(config.batch.clone());
+    // [NEW] 初始化账号并发上限
+    crate::proxy::update_account_concurrency(config.account_concurrency.clone());
+    // [NEW] 初始化准入排队配置
+    crate::proxy::update_admission_config(config.admission.clone());
 
     Ok(())
 }
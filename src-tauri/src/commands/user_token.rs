@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use crate::modules::user_token_db::{self, UserToken, TokenIpBinding};
+use crate::modules::user_token_db::{
+    self, TokenIpBinding, TokenLimits, TokenLimitsPatch, TokenUsageSummary, UserToken,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTokenRequest {
@@ -9,7 +11,13 @@ pub struct CreateTokenRequest {
     pub max_ips: i32,
     pub curfew_start: Option<String>,
     pub curfew_end: Option<String>,
+    #[serde(default)]
+    pub curfew_timezone: Option<String>, // IANA 名称或固定偏移，空 = UTC+8
+    #[serde(default)]
+    pub curfew_days: Vec<u8>,            // ISO 星期 (1=周一 ... 7=周日)，空 = 每天
     pub custom_expires_at: Option<i64>,  // 自定义过期时间戳 (秒)
+    #[serde(flatten)]
+    pub limits: TokenLimits,             // [NEW] 日/月 token 预算、RPM、模型白名单
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,6 +28,12 @@ pub struct UpdateTokenRequest {
     pub max_ips: Option<i32>,
     pub curfew_start: Option<Option<String>>,
     pub curfew_end: Option<Option<String>>,
+    #[serde(default)]
+    pub curfew_timezone: Option<Option<String>>,
+    #[serde(default)]
+    pub curfew_days: Option<Vec<u8>>,
+    #[serde(flatten)]
+    pub limits: TokenLimitsPatch,
 }
 
 // 命令实现
@@ -33,20 +47,26 @@ pub async fn list_user_tokens() -> Result<Vec<UserToken>, String> {
 /// 创建新令牌
 #[tauri::command]
 pub async fn create_user_token(request: CreateTokenRequest) -> Result<UserToken, String> {
-    user_token_db::create_token(
+    let token = user_token_db::create_token(
         request.username,
         request.expires_type,
         request.description,
         request.max_ips,
         request.curfew_start,
         request.curfew_end,
+        request.curfew_timezone,
+        request.curfew_days,
         request.custom_expires_at,
-    )
+        request.limits,
+    )?;
+    audit_token_change("user_token.create", &token.username, None, Some(&token));
+    Ok(token)
 }
 
 /// 更新令牌
 #[tauri::command]
 pub async fn update_user_token(id: String, request: UpdateTokenRequest) -> Result<(), String> {
+    let before = user_token_db::get_token_by_id(&id)?;
     user_token_db::update_token(
         &id,
         request.username,
@@ -55,19 +75,57 @@ pub async fn update_user_token(id: String, request: UpdateTokenRequest) -> Resul
         request.max_ips,
         request.curfew_start,
         request.curfew_end,
-    )
+        request.curfew_timezone,
+        request.curfew_days,
+        request.limits,
+    )?;
+    let after = user_token_db::get_token_by_id(&id)?;
+    if let Some(token) = after.as_ref().or(before.as_ref()) {
+        audit_token_change("user_token.update", &token.username, before.as_ref(), after.as_ref());
+    }
+    Ok(())
 }
 
 /// 删除令牌
 #[tauri::command]
 pub async fn delete_user_token(id: String) -> Result<(), String> {
-    user_token_db::delete_token(&id)
+    let before = user_token_db::get_token_by_id(&id)?;
+    user_token_db::delete_token(&id)?;
+    if let Some(token) = before.as_ref() {
+        audit_token_change("user_token.delete", &token.username, Some(token), None);
+    }
+    Ok(())
 }
 
 /// 续期令牌
 #[tauri::command]
 pub async fn renew_user_token(id: String, expires_type: String) -> Result<(), String> {
-    user_token_db::renew_token(&id, &expires_type)
+    let before = user_token_db::get_token_by_id(&id)?;
+    user_token_db::renew_token(&id, &expires_type)?;
+    let after = user_token_db::get_token_by_id(&id)?;
+    if let Some(token) = after.as_ref() {
+        audit_token_change("user_token.renew", &token.username, before.as_ref(), Some(token));
+    }
+    Ok(())
+}
+
+/// [NEW] 记录令牌变更审计 (令牌值只记录指纹，用量计数不参与 diff)
+fn audit_token_change(action: &str, username: &str, before: Option<&UserToken>, after: Option<&UserToken>) {
+    let snapshot = |token: &UserToken| {
+        let mut value = serde_json::to_value(token).unwrap_or_default();
+        if let Some(map) = value.as_object_mut() {
+            for volatile in ["last_used_at", "total_requests", "total_tokens_used", "updated_at"] {
+                map.remove(volatile);
+            }
+        }
+        value
+    };
+    crate::modules::audit::record_change(
+        action,
+        Some(username.to_string()),
+        before.map(snapshot),
+        after.map(snapshot),
+    );
 }
 
 /// 获取令牌 IP 绑定
@@ -82,6 +140,8 @@ pub struct UserTokenStats {
     pub active_tokens: usize,
     pub total_users: usize,
     pub today_requests: i64,
+    /// [NEW] 每个令牌的当日/当月用量与剩余预算
+    pub usage: Vec<TokenUsageSummary>,
 }
 
 /// 获取简单的统计信息
@@ -96,13 +156,14 @@ pub async fn get_user_token_summary() -> Result<UserTokenStats, String> {
         users.insert(t.username.clone());
     }
     
-    // 这里简单返回一些数据，请求数最好从数据库聚合查询
-    // 目前仅作为演示，请求数暂不精确统计今日的
-    
+    let today_requests = user_token_db::get_today_requests()?;
+    let usage = user_token_db::get_usage_summaries(&tokens)?;
+
     Ok(UserTokenStats {
         total_tokens: tokens.len(),
         active_tokens,
         total_users: users.len(),
-        today_requests: 0, // TODO: Implement daily stats query
+        today_requests,
+        usage,
     })
 }
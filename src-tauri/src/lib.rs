@@ -136,6 +136,31 @@ pub fn run() {
         error!("Failed to initialize user token database: {}", e);
     }
 
+    // [NEW] Audit actor for changes made outside admin API / HTTP API / scheduler contexts
+    modules::audit::set_default_actor(if is_headless {
+        modules::audit::AuditActor::system()
+    } else {
+        modules::audit::AuditActor::desktop()
+    });
+
+    // [NEW] Initialize admin user & audit databases
+    if let Err(e) = modules::admin_user_db::init_db() {
+        error!("Failed to initialize admin user database: {}", e);
+    }
+    if let Err(e) = modules::audit_db::init_db() {
+        error!("Failed to initialize audit database: {}", e);
+    }
+
+    // Initialize Responses API store
+    if let Err(e) = modules::response_store::init_db() {
+        error!("Failed to initialize response store: {}", e);
+    }
+
+    // Initialize Batch API store
+    if let Err(e) = modules::batch_db::init_db() {
+        error!("Failed to initialize batch store: {}", e);
+    }
+
     if is_headless {
         info!("Starting in HEADLESS mode...");
 
@@ -388,10 +413,10 @@ pub fn run() {
                 }
             });
 
-            // [DISABLED] Start smart scheduler (Automatic warmup disabled as per user request)
-            // let scheduler_state = app.handle().state::<commands::proxy::ProxyServiceState>();
-            // modules::scheduler::start_scheduler(Some(app.handle().clone()), scheduler_state.inner().clone());
-            info!("Smart scheduler (Automatic Warmup) is DISABLED.");
+            // Start smart scheduler (auto switch only; automatic warmup stays disabled as per user request)
+            let scheduler_state = app.handle().state::<commands::proxy::ProxyServiceState>();
+            modules::scheduler::start_scheduler(Some(app.handle().clone()), scheduler_state.inner().clone());
+            info!("Smart scheduler started (auto switch only, Automatic Warmup is DISABLED).");
 
             // [PHASE 1] 已整合至 Axum 端口 (8045)，不再单独启动 19527 端口
             info!("Management API integrated into main proxy server (port 8045)");
@@ -528,6 +553,7 @@ pub fn run() {
             commands::get_token_stats_by_account,
             commands::get_token_stats_summary,
             commands::get_token_stats_by_model,
+            commands::get_token_stats_by_user,
             commands::get_token_stats_model_trend_hourly,
             commands::get_token_stats_model_trend_daily,
             commands::get_token_stats_account_trend_hourly,
@@ -562,6 +588,8 @@ pub fn run() {
             commands::security::check_ip_in_whitelist,
             commands::security::get_security_config,
             commands::security::update_security_config,
+            // Audit log
+            commands::audit::get_audit_logs,
             // Cloudflared commands
             commands::cloudflared::cloudflared_check,
             commands::cloudflared::cloudflared_install,
@@ -611,6 +639,8 @@ pub fn run() {
                             }
                         });
                     }
+                    // Flush pending trace spans
+                    crate::proxy::telemetry::shutdown();
                 }
                 // Handle macOS dock icon click to reopen window
                 #[cfg(target_os = "macos")]
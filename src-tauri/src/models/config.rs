@@ -23,6 +23,8 @@ pub struct AppConfig {
     #[serde(default)]
     pub quota_protection: QuotaProtectionConfig, // [NEW] Quota protection configuration
     #[serde(default)]
+    pub auto_switch: AutoSwitchConfig, // [NEW] Automatic IDE account switching
+    #[serde(default)]
     pub pinned_quota_models: PinnedQuotaModelsConfig, // [NEW] Pinned quota models list
     #[serde(default)]
     pub circuit_breaker: CircuitBreakerConfig, // [NEW] Circuit breaker configuration
@@ -30,6 +32,8 @@ pub struct AppConfig {
     pub hidden_menu_items: Vec<String>, // Hidden menu item path list
     #[serde(default)]
     pub cloudflared: CloudflaredConfig, // [NEW] Cloudflared configuration
+    #[serde(default)]
+    pub audit_log: AuditLogConfig, // [NEW] Audit log retention
 }
 
 /// Scheduled warmup configuration
@@ -106,6 +110,85 @@ impl Default for QuotaProtectionConfig {
     }
 }
 
+/// Automatic account switching configuration (desktop IDE account)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoSwitchConfig {
+    /// Whether automatic switching is enabled
+    pub enabled: bool,
+
+    /// Switch when any monitored model falls to or below this remaining percentage (0-99)
+    pub threshold_percentage: u32,
+
+    /// List of monitored model groups (same ids as quota protection)
+    #[serde(default = "default_monitored_models")]
+    pub monitored_models: Vec<String>,
+
+    /// Whether quiet hours are enabled (no automatic switching in this window)
+    #[serde(default)]
+    pub quiet_hours_enabled: bool,
+
+    /// Quiet hours start, local time "HH:MM"
+    #[serde(default = "default_quiet_hours_start")]
+    pub quiet_hours_start: String,
+
+    /// Quiet hours end, local time "HH:MM" (may be earlier than start to span midnight)
+    #[serde(default = "default_quiet_hours_end")]
+    pub quiet_hours_end: String,
+}
+
+fn default_quiet_hours_start() -> String {
+    "22:00".to_string()
+}
+
+fn default_quiet_hours_end() -> String {
+    "08:00".to_string()
+}
+
+impl AutoSwitchConfig {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            threshold_percentage: 10,
+            monitored_models: default_monitored_models(),
+            quiet_hours_enabled: false,
+            quiet_hours_start: default_quiet_hours_start(),
+            quiet_hours_end: default_quiet_hours_end(),
+        }
+    }
+}
+
+impl Default for AutoSwitchConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Audit log configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLogConfig {
+    /// Days to keep audit entries (entries younger than 7 days are never deleted)
+    #[serde(default = "default_audit_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_audit_retention_days() -> u32 {
+    180
+}
+
+impl AuditLogConfig {
+    pub fn new() -> Self {
+        Self {
+            retention_days: default_audit_retention_days(),
+        }
+    }
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Pinned quota models configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PinnedQuotaModelsConfig {
@@ -184,10 +267,12 @@ impl AppConfig {
             auto_launch: false,
             scheduled_warmup: ScheduledWarmupConfig::default(),
             quota_protection: QuotaProtectionConfig::default(),
+            auto_switch: AutoSwitchConfig::default(),
             pinned_quota_models: PinnedQuotaModelsConfig::default(),
             circuit_breaker: CircuitBreakerConfig::default(),
             hidden_menu_items: Vec::new(),
             cloudflared: CloudflaredConfig::default(),
+            audit_log: AuditLogConfig::default(),
         }
     }
 }
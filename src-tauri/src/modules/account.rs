@@ -83,6 +83,30 @@ mod tests {
         fs::write(&account_path, content).expect("Failed to write account file");
     }
 
+    #[test]
+    fn test_account_tokens_encrypted_at_rest() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+        let test_dir = TestDataDir::new();
+        create_account_file(test_dir.path(), "legacy", "legacy@example.com");
+        let legacy_path = test_dir.path().join("accounts").join("legacy.json");
+
+        // 旧版明文文件：可读取，并标记为需要迁移
+        let (account, needs_migration) = read_account_file(&legacy_path).unwrap();
+        assert!(needs_migration);
+        assert_eq!(account.token.refresh_token, "test_refresh_token");
+
+        // 重新编码后 token 不再以明文落盘，且可还原
+        let encoded = encode_account_file(&account).unwrap();
+        assert!(!encoded.contains("test_refresh_token"));
+        assert!(!encoded.contains("test_access_token"));
+        fs::write(&legacy_path, &encoded).unwrap();
+
+        let (reloaded, needs_migration) = read_account_file(&legacy_path).unwrap();
+        assert!(!needs_migration);
+        assert_eq!(reloaded.token.access_token, "test_access_token");
+        assert_eq!(reloaded.token.refresh_token, "test_refresh_token");
+    }
+
     #[test]
     fn test_load_account_index_with_bom_prefix() {
         let _guard = TEST_MUTEX.lock().unwrap();
@@ -317,12 +341,27 @@ mod tests {
 
         println!("Backup creation on parse failure: successfully created backup");
     }
+
+    #[test]
+    fn test_decrypt_failure_is_an_error() {
+        let mut value = serde_json::json!({
+            "token": {"access_token": "ag_enc_v2:z:AAAA", "refresh_token": "plain"}
+        });
+        let err = decrypt_account_tokens(&mut value).unwrap_err();
+        assert!(err.starts_with("failed_to_decrypt_account_access_token"));
+
+        let sealed = crate::utils::crypto::encrypt_string("secret").unwrap();
+        let mut value = serde_json::json!({"token": {"access_token": sealed, "refresh_token": "plain"}});
+        assert!(decrypt_account_tokens(&mut value).unwrap());
+        assert_eq!(value["token"]["access_token"], "secret");
+    }
 }
 
 /// Global account write lock to prevent corruption during concurrent operations
 static ACCOUNT_INDEX_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
 // ... existing constants ...
+#[cfg_attr(test, allow(dead_code))]
 const DATA_DIR: &str = ".antigravity_tools";
 const ACCOUNTS_INDEX: &str = "accounts.json";
 const ACCOUNTS_DIR: &str = "accounts";
@@ -340,8 +379,11 @@ pub fn get_data_dir() -> Result<PathBuf, String> {
         }
     }
 
-    let home = dirs::home_dir().ok_or("failed_to_get_home_dir")?;
-    let data_dir = home.join(DATA_DIR);
+    // [FIX] 测试进程不得读写开发者的真实数据目录 (token_stats / proxy_db / security_db 等均经由此处)
+    #[cfg(test)]
+    let data_dir = test_data_dir().clone();
+    #[cfg(not(test))]
+    let data_dir = dirs::home_dir().ok_or("failed_to_get_home_dir")?.join(DATA_DIR);
 
     // Ensure directory exists
     if !data_dir.exists() {
@@ -351,6 +393,17 @@ pub fn get_data_dir() -> Result<PathBuf, String> {
     Ok(data_dir)
 }
 
+/// 测试进程专用的临时数据目录 (每个进程一个，首次使用时创建)
+#[cfg(test)]
+fn test_data_dir() -> &'static PathBuf {
+    static DIR: once_cell::sync::OnceCell<PathBuf> = once_cell::sync::OnceCell::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("abv_test-{}-{}", std::process::id(), Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).expect("create test data dir");
+        dir
+    })
+}
+
 /// Get accounts directory path
 pub fn get_accounts_dir() -> Result<PathBuf, String> {
     let data_dir = get_data_dir()?;
@@ -509,11 +562,73 @@ fn rebuild_index_from_accounts_in_dir(data_dir: &PathBuf) -> Result<AccountIndex
     })
 }
 
-/// Load account from a specific path (internal helper)
-fn load_account_at_path(account_path: &PathBuf) -> Result<Account, String> {
+/// 账号文件中落盘加密的 token 字段
+const ENCRYPTED_TOKEN_FIELDS: [&str; 2] = ["access_token", "refresh_token"];
+
+/// 解密账号 JSON 中的 token 字段
+///
+/// 返回 true 表示存在明文 token，需要迁移为加密格式。
+/// 解密失败 (如口令未设置、密钥环不可用) 时返回错误，绝不把密文当作凭据使用
+pub fn decrypt_account_tokens(value: &mut serde_json::Value) -> Result<bool, String> {
+    let mut has_plaintext = false;
+    if let Some(token) = value.get_mut("token").and_then(|t| t.as_object_mut()) {
+        for field in ENCRYPTED_TOKEN_FIELDS {
+            let Some(raw) = token.get(field).and_then(|v| v.as_str()) else { continue };
+            if raw.is_empty() {
+                continue;
+            }
+            if crate::utils::crypto::is_encrypted(raw) {
+                let plain = crate::utils::crypto::decrypt_string(raw)
+                    .map_err(|e| format!("failed_to_decrypt_account_{}: {}", field, e))?;
+                token.insert(field.to_string(), serde_json::Value::String(plain));
+            } else {
+                has_plaintext = true;
+            }
+        }
+    }
+    Ok(has_plaintext)
+}
+
+/// 加密账号 JSON 中的 token 字段 (已加密的值保持不变)
+pub fn encrypt_account_tokens(value: &mut serde_json::Value) -> Result<(), String> {
+    if let Some(token) = value.get_mut("token").and_then(|t| t.as_object_mut()) {
+        for field in ENCRYPTED_TOKEN_FIELDS {
+            let Some(raw) = token.get(field).and_then(|v| v.as_str()) else { continue };
+            if raw.is_empty() || crate::utils::crypto::is_encrypted(raw) {
+                continue;
+            }
+            let sealed = crate::utils::crypto::encrypt_string(raw)?;
+            token.insert(field.to_string(), serde_json::Value::String(sealed));
+        }
+    }
+    Ok(())
+}
+
+/// 序列化账号为落盘格式 (token 字段加密)
+fn encode_account_file(account: &Account) -> Result<String, String> {
+    let mut value = serde_json::to_value(account)
+        .map_err(|e| format!("failed_to_serialize_account_data: {}", e))?;
+    encrypt_account_tokens(&mut value)
+        .map_err(|e| format!("failed_to_encrypt_account_tokens: {}", e))?;
+    serde_json::to_string_pretty(&value)
+        .map_err(|e| format!("failed_to_serialize_account_data: {}", e))
+}
+
+/// 读取账号文件并解密 token，同时返回是否需要迁移 (存在明文 token)
+fn read_account_file(account_path: &PathBuf) -> Result<(Account, bool), String> {
     let content = fs::read_to_string(account_path)
         .map_err(|e| format!("failed_to_read_account_data: {}", e))?;
-    serde_json::from_str(&content).map_err(|e| format!("failed_to_parse_account_data: {}", e))
+    let mut value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("failed_to_parse_account_data: {}", e))?;
+    let needs_migration = decrypt_account_tokens(&mut value)?;
+    let account = serde_json::from_value(value)
+        .map_err(|e| format!("failed_to_parse_account_data: {}", e))?;
+    Ok((account, needs_migration))
+}
+
+/// Load account from a specific path (internal helper)
+fn load_account_at_path(account_path: &PathBuf) -> Result<Account, String> {
+    read_account_file(account_path).map(|(account, _)| account)
 }
 
 /// Load account index with recovery support
@@ -646,7 +761,22 @@ fn atomic_replace_file(src: &PathBuf, dst: &PathBuf) -> Result<(), String> {
 pub fn load_account(account_id: &str) -> Result<Account, String> {
     let accounts_dir = get_accounts_dir()?;
     let account_path = accounts_dir.join(format!("{}.json", account_id));
-    load_account_at_path(&account_path)
+    let (account, needs_migration) = read_account_file(&account_path)?;
+
+    // [NEW] 透明迁移：旧版明文 token 在首次读取时重新以加密格式落盘
+    if needs_migration {
+        match save_account(&account) {
+            Ok(()) => crate::modules::logger::log_info(&format!(
+                "Migrated account {} tokens to encrypted storage",
+                account.email
+            )),
+            Err(e) => crate::modules::logger::log_warn(&format!(
+                "Failed to migrate account {} tokens: {}",
+                account.email, e
+            )),
+        }
+    }
+    Ok(account)
 }
 
 /// Save account data
@@ -657,8 +787,7 @@ pub fn save_account(account: &Account) -> Result<(), String> {
     let temp_filename = format!("{}.tmp.{}", account.id, Uuid::new_v4());
     let temp_path = accounts_dir.join(&temp_filename);
 
-    let content = serde_json::to_string_pretty(account)
-        .map_err(|e| format!("failed_to_serialize_account_data: {}", e))?;
+    let content = encode_account_file(account)?;
 
     if let Err(e) = std::fs::write(&temp_path, content) {
         let _ = std::fs::remove_file(&temp_path);
@@ -740,6 +869,14 @@ pub fn add_account(
 
     save_account_index(&index)?;
 
+    // [NEW] 审计
+    crate::modules::audit::record_change(
+        "account.add",
+        Some(account.email.clone()),
+        None,
+        Some(serde_json::json!({ "id": account.id, "email": account.email, "name": account.name })),
+    );
+
     Ok(account)
 }
 
@@ -828,12 +965,10 @@ pub fn delete_account(account_id: &str) -> Result<(), String> {
     let mut index = load_account_index()?;
 
     // Remove from index
-    let original_len = index.accounts.len();
-    index.accounts.retain(|s| s.id != account_id);
-
-    if index.accounts.len() == original_len {
+    let Some(removed) = index.accounts.iter().find(|s| s.id == account_id).cloned() else {
         return Err(format!("Account ID not found: {}", account_id));
-    }
+    };
+    index.accounts.retain(|s| s.id != account_id);
 
     // Clear current account if it's being deleted
     if index.current_account_id.as_deref() == Some(account_id) {
@@ -854,6 +989,14 @@ pub fn delete_account(account_id: &str) -> Result<(), String> {
     // [FIX #1477] Trigger TokenManager cache cleanup signal
     crate::proxy::server::trigger_account_delete(account_id);
 
+    // [NEW] 审计
+    crate::modules::audit::record_change(
+        "account.delete",
+        Some(removed.email.clone()),
+        Some(serde_json::json!({ "id": removed.id, "email": removed.email, "name": removed.name })),
+        None,
+    );
+
     Ok(())
 }
 
@@ -866,8 +1009,12 @@ pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
 
     let accounts_dir = get_accounts_dir()?;
 
+    let mut removed = Vec::new();
     for account_id in account_ids {
         // Remove from index
+        if let Some(summary) = index.accounts.iter().find(|s| &s.id == account_id) {
+            removed.push(summary.clone());
+        }
         index.accounts.retain(|s| &s.id != account_id);
 
         // Clear current account if it's being deleted
@@ -890,7 +1037,18 @@ pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
         index.current_account_id = index.accounts.first().map(|s| s.id.clone());
     }
 
-    save_account_index(&index)
+    save_account_index(&index)?;
+
+    // [NEW] 审计 (每个账号一条)
+    for summary in removed {
+        crate::modules::audit::record_change(
+            "account.delete",
+            Some(summary.email.clone()),
+            Some(serde_json::json!({ "id": summary.id, "email": summary.email, "name": summary.name })),
+            None,
+        );
+    }
+    Ok(())
 }
 
 /// Reorder account list
@@ -1000,6 +1158,14 @@ pub async fn switch_account(
     account.update_last_used();
     save_account(&account)?;
 
+    // [NEW] 审计
+    crate::modules::audit::record_change(
+        "account.switch",
+        Some(account.email.clone()),
+        Some(serde_json::json!({ "current_account_id": index.current_account_id })),
+        Some(serde_json::json!({ "current_account_id": account_id })),
+    );
+
     crate::modules::logger::log_info(&format!(
         "Account switch core logic completed: {}",
         account.email
@@ -1297,6 +1463,10 @@ pub fn toggle_proxy_status(
         .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
 
     let mut account = load_account(account_id)?;
+    let before = serde_json::json!({
+        "proxy_disabled": account.proxy_disabled,
+        "proxy_disabled_reason": account.proxy_disabled_reason,
+    });
 
     account.proxy_disabled = !enable;
     account.proxy_disabled_reason = if !enable {
@@ -1319,6 +1489,17 @@ pub fn toggle_proxy_status(
         save_account_index(&index)?;
     }
 
+    // [NEW] 审计
+    crate::modules::audit::record_change(
+        "account.toggle_proxy",
+        Some(account.email.clone()),
+        Some(before),
+        Some(serde_json::json!({
+            "proxy_disabled": account.proxy_disabled,
+            "proxy_disabled_reason": account.proxy_disabled_reason,
+        })),
+    );
+
     Ok(())
 }
 
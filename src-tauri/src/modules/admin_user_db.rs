@@ -0,0 +1,571 @@
+//! Admin User Database Module
+//! 管理接口 (/api) 多用户与角色：用户、口令哈希与登录会话
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 口令哈希格式: pbkdf2_sha256$<rounds>$<salt hex>$<hash hex>
+const PASSWORD_SCHEME: &str = "pbkdf2_sha256";
+const PASSWORD_ROUNDS: u32 = 100_000;
+
+/// 会话令牌前缀，便于与管理密码 / API Key 区分
+pub const SESSION_TOKEN_PREFIX: &str = "agsess_";
+/// 会话有效期 (秒)
+pub const SESSION_TTL_SECS: i64 = 12 * 3600;
+
+/// 管理角色 (按权限从低到高排序)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    /// 只读：统计、日志、状态
+    Viewer,
+    /// 运维：启停反代、预热、清除限流等
+    Operator,
+    /// 所有者：账号、导出、配置、用户令牌、管理用户
+    Owner,
+}
+
+impl AdminRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminRole::Viewer => "viewer",
+            AdminRole::Operator => "operator",
+            AdminRole::Owner => "owner",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "viewer" => Some(AdminRole::Viewer),
+            "operator" => Some(AdminRole::Operator),
+            "owner" => Some(AdminRole::Owner),
+            _ => None,
+        }
+    }
+}
+
+/// 管理用户 (不含口令哈希)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminUser {
+    pub id: String,
+    pub username: String,
+    pub role: AdminRole,
+    pub disabled: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_login_at: Option<i64>,
+}
+
+/// 登录成功后返回的会话
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminSession {
+    pub token: String,
+    pub user: AdminUser,
+    pub expires_at: i64,
+}
+
+/// 获取管理用户数据库路径
+pub fn get_admin_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    Ok(data_dir.join("admin_users.db"))
+}
+
+/// 连接数据库
+fn connect_db() -> Result<Connection, String> {
+    let db_path = get_admin_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000)
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "foreign_keys", "ON")
+        .map_err(|e| e.to_string())?;
+
+    Ok(conn)
+}
+
+/// 初始化管理用户数据库
+pub fn init_db() -> Result<(), String> {
+    let conn = connect_db()?;
+    create_tables(&conn)
+}
+
+fn create_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS admin_users (
+            id TEXT PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL,
+            disabled INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            last_login_at INTEGER
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // 只保存会话令牌的 SHA-256，数据库泄露时无法直接复用会话
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS admin_sessions (
+            token_hash TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL REFERENCES admin_users(id) ON DELETE CASCADE,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_admin_sessions_user ON admin_sessions (user_id)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// ============================================================================
+// 口令与令牌
+// ============================================================================
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// 常量时间比较，避免按字节提前返回泄露匹配长度
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn derive(password: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, rounds, &mut out);
+    out
+}
+
+/// 生成口令哈希 (随机 16 字节盐)
+pub fn hash_password(password: &str) -> String {
+    let salt: [u8; 16] = rand::random();
+    let hash = derive(password, &salt, PASSWORD_ROUNDS);
+    format!("{}${}${}${}", PASSWORD_SCHEME, PASSWORD_ROUNDS, to_hex(&salt), to_hex(&hash))
+}
+
+/// 校验口令
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    let parts: Vec<&str> = stored.split('$').collect();
+    let [scheme, rounds, salt, hash] = parts.as_slice() else {
+        return false;
+    };
+    if *scheme != PASSWORD_SCHEME {
+        return false;
+    }
+    let (Ok(rounds), Some(salt), Some(hash)) = (rounds.parse::<u32>(), from_hex(salt), from_hex(hash)) else {
+        return false;
+    };
+    constant_time_eq(&derive(password, &salt, rounds), &hash)
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn generate_session_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    format!("{}{}", SESSION_TOKEN_PREFIX, to_hex(&bytes))
+}
+
+fn validate_username(username: &str) -> Result<String, String> {
+    let username = username.trim();
+    if username.is_empty() || username.len() > 64 {
+        return Err("Username must be 1-64 characters".to_string());
+    }
+    if username.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("Username must not contain whitespace".to_string());
+    }
+    Ok(username.to_string())
+}
+
+fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < 8 {
+        return Err("Password must be at least 8 characters".to_string());
+    }
+    Ok(())
+}
+
+// ============================================================================
+// 用户管理
+// ============================================================================
+
+fn row_to_user(row: &rusqlite::Row) -> rusqlite::Result<AdminUser> {
+    let role: String = row.get(2)?;
+    Ok(AdminUser {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        // 未知角色按最低权限处理
+        role: AdminRole::parse(&role).unwrap_or(AdminRole::Viewer),
+        disabled: row.get::<_, i64>(3)? != 0,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        last_login_at: row.get(6)?,
+    })
+}
+
+const USER_COLUMNS: &str = "id, username, role, disabled, created_at, updated_at, last_login_at";
+
+/// 列出所有管理用户
+pub fn list_users() -> Result<Vec<AdminUser>, String> {
+    let conn = connect_db()?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM admin_users ORDER BY created_at ASC", USER_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    let users = stmt
+        .query_map([], row_to_user)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(users)
+}
+
+/// 创建管理用户
+pub fn create_user(username: &str, password: &str, role: AdminRole) -> Result<AdminUser, String> {
+    let username = validate_username(username)?;
+    validate_password(password)?;
+    let conn = connect_db()?;
+    let now = chrono::Utc::now().timestamp();
+    let user = AdminUser {
+        id: Uuid::new_v4().to_string(),
+        username,
+        role,
+        disabled: false,
+        created_at: now,
+        updated_at: now,
+        last_login_at: None,
+    };
+    conn.execute(
+        "INSERT INTO admin_users (id, username, password_hash, role, disabled, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, 0, ?5, ?5)",
+        params![user.id, user.username, hash_password(password), role.as_str(), now],
+    )
+    .map_err(|e| {
+        if e.to_string().contains("UNIQUE") {
+            format!("Admin user '{}' already exists", user.username)
+        } else {
+            e.to_string()
+        }
+    })?;
+    Ok(user)
+}
+
+/// 更新管理用户；修改口令、角色或禁用时吊销该用户的全部会话
+pub fn update_user(
+    id: &str,
+    password: Option<&str>,
+    role: Option<AdminRole>,
+    disabled: Option<bool>,
+) -> Result<AdminUser, String> {
+    if let Some(password) = password {
+        validate_password(password)?;
+    }
+    let mut conn = connect_db()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp();
+
+    let exists: bool = tx
+        .query_row("SELECT 1 FROM admin_users WHERE id = ?1", [id], |_| Ok(()))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if !exists {
+        return Err(format!("Admin user not found: {}", id));
+    }
+
+    if let Some(password) = password {
+        tx.execute(
+            "UPDATE admin_users SET password_hash = ?1, updated_at = ?2 WHERE id = ?3",
+            params![hash_password(password), now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if let Some(role) = role {
+        tx.execute(
+            "UPDATE admin_users SET role = ?1, updated_at = ?2 WHERE id = ?3",
+            params![role.as_str(), now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if let Some(disabled) = disabled {
+        tx.execute(
+            "UPDATE admin_users SET disabled = ?1, updated_at = ?2 WHERE id = ?3",
+            params![disabled, now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if password.is_some() || role.is_some() || disabled == Some(true) {
+        tx.execute("DELETE FROM admin_sessions WHERE user_id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+    }
+
+    let user = tx
+        .query_row(
+            &format!("SELECT {} FROM admin_users WHERE id = ?1", USER_COLUMNS),
+            [id],
+            row_to_user,
+        )
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(user)
+}
+
+/// 删除管理用户 (会话随外键级联删除)
+pub fn delete_user(id: &str) -> Result<(), String> {
+    let conn = connect_db()?;
+    let deleted = conn
+        .execute("DELETE FROM admin_users WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    if deleted == 0 {
+        return Err(format!("Admin user not found: {}", id));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// 会话
+// ============================================================================
+
+/// 登录：校验用户名与口令，成功时创建会话。口令错误与用户不存在返回相同结果
+pub fn login(username: &str, password: &str) -> Result<Option<AdminSession>, String> {
+    let conn = connect_db()?;
+    let now = chrono::Utc::now().timestamp();
+
+    let row: Option<(String, i64)> = conn
+        .query_row(
+            "SELECT password_hash, disabled FROM admin_users WHERE username = ?1",
+            [username.trim()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let Some((password_hash, disabled)) = row else {
+        // 仍执行一次派生，避免通过响应时间枚举用户名
+        let _ = derive(password, b"admin-user-missing", PASSWORD_ROUNDS);
+        return Ok(None);
+    };
+    if !verify_password(password, &password_hash) || disabled != 0 {
+        return Ok(None);
+    }
+
+    let user = conn
+        .query_row(
+            &format!("SELECT {} FROM admin_users WHERE username = ?1", USER_COLUMNS),
+            [username.trim()],
+            row_to_user,
+        )
+        .map_err(|e| e.to_string())?;
+
+    // 顺带清理过期会话
+    conn.execute("DELETE FROM admin_sessions WHERE expires_at <= ?1", [now])
+        .map_err(|e| e.to_string())?;
+
+    let token = generate_session_token();
+    let expires_at = now + SESSION_TTL_SECS;
+    conn.execute(
+        "INSERT INTO admin_sessions (token_hash, user_id, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+        params![hash_token(&token), user.id, now, expires_at],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE admin_users SET last_login_at = ?1 WHERE id = ?2",
+        params![now, user.id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(Some(AdminSession {
+        token,
+        user: AdminUser { last_login_at: Some(now), ..user },
+        expires_at,
+    }))
+}
+
+// ============================================================================
+// 登录失败退避
+// ============================================================================
+
+/// 同一 (用户名, IP) 连续失败达到该次数后开始退避
+const LOGIN_FREE_ATTEMPTS: u32 = 3;
+/// 单次退避上限
+const LOGIN_MAX_BACKOFF_SECS: u64 = 300;
+/// 记录数超过该值时清理长时间无失败的条目
+const LOGIN_FAILURES_PRUNE_AT: usize = 10_000;
+
+/// (用户名, IP) -> (连续失败次数, 最近一次失败时间)
+static LOGIN_FAILURES: Lazy<DashMap<String, (u32, Instant)>> = Lazy::new(DashMap::new);
+
+fn login_key(username: &str, ip: Option<&str>) -> String {
+    format!("{}|{}", username.trim().to_lowercase(), ip.unwrap_or("-"))
+}
+
+/// 连续失败 count 次后的退避时长：前几次不限制，之后按 1s, 2s, 4s... 翻倍，最长 5 分钟
+fn login_backoff(count: u32) -> Duration {
+    if count < LOGIN_FREE_ATTEMPTS {
+        return Duration::ZERO;
+    }
+    let exp = (count - LOGIN_FREE_ATTEMPTS).min(16);
+    Duration::from_secs((1u64 << exp).min(LOGIN_MAX_BACKOFF_SECS))
+}
+
+/// 仍处于退避期时返回需要等待的秒数 (在执行口令派生之前调用)
+pub fn login_retry_after(username: &str, ip: Option<&str>) -> Option<u64> {
+    let entry = LOGIN_FAILURES.get(&login_key(username, ip))?;
+    let (count, last) = *entry;
+    let remaining = login_backoff(count).checked_sub(last.elapsed())?;
+    (!remaining.is_zero()).then(|| remaining.as_secs().max(1))
+}
+
+/// 记录一次登录失败
+pub fn record_login_failure(username: &str, ip: Option<&str>) {
+    if LOGIN_FAILURES.len() >= LOGIN_FAILURES_PRUNE_AT {
+        let idle = Duration::from_secs(LOGIN_MAX_BACKOFF_SECS * 2);
+        LOGIN_FAILURES.retain(|_, (_, last)| last.elapsed() < idle);
+    }
+    let mut entry = LOGIN_FAILURES
+        .entry(login_key(username, ip))
+        .or_insert((0, Instant::now()));
+    entry.0 = entry.0.saturating_add(1);
+    entry.1 = Instant::now();
+}
+
+/// 登录成功后清除失败记录
+pub fn clear_login_failures(username: &str, ip: Option<&str>) {
+    LOGIN_FAILURES.remove(&login_key(username, ip));
+}
+
+/// 按会话令牌查询用户 (过期会话或已禁用用户返回 None)
+pub fn get_session_user(token: &str) -> Result<Option<AdminUser>, String> {
+    if !token.starts_with(SESSION_TOKEN_PREFIX) {
+        return Ok(None);
+    }
+    let conn = connect_db()?;
+    let now = chrono::Utc::now().timestamp();
+    conn.query_row(
+        "SELECT u.id, u.username, u.role, u.disabled, u.created_at, u.updated_at, u.last_login_at
+         FROM admin_sessions s JOIN admin_users u ON u.id = s.user_id
+         WHERE s.token_hash = ?1 AND s.expires_at > ?2 AND u.disabled = 0",
+        params![hash_token(token), now],
+        row_to_user,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// 注销会话
+pub fn revoke_session(token: &str) -> Result<(), String> {
+    let conn = connect_db()?;
+    conn.execute(
+        "DELETE FROM admin_sessions WHERE token_hash = ?1",
+        [hash_token(token)],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_hash_roundtrip() {
+        let stored = hash_password("correct horse");
+        assert!(stored.starts_with("pbkdf2_sha256$100000$"));
+        assert!(verify_password("correct horse", &stored));
+        assert!(!verify_password("correct horsE", &stored));
+        // 相同口令每次使用不同的盐
+        assert_ne!(stored, hash_password("correct horse"));
+        assert!(!verify_password("x", "plaintext"));
+        assert!(!verify_password("x", "pbkdf2_sha256$1$zz$00"));
+    }
+
+    #[test]
+    fn test_login_backoff() {
+        assert_eq!(login_backoff(0), Duration::ZERO);
+        assert_eq!(login_backoff(LOGIN_FREE_ATTEMPTS - 1), Duration::ZERO);
+        assert_eq!(login_backoff(LOGIN_FREE_ATTEMPTS), Duration::from_secs(1));
+        assert_eq!(login_backoff(LOGIN_FREE_ATTEMPTS + 3), Duration::from_secs(8));
+        assert_eq!(login_backoff(u32::MAX), Duration::from_secs(LOGIN_MAX_BACKOFF_SECS));
+
+        let user = format!("backoff-{}", Uuid::new_v4());
+        let ip = Some("203.0.113.9");
+        for _ in 0..LOGIN_FREE_ATTEMPTS {
+            assert_eq!(login_retry_after(&user, ip), None);
+            record_login_failure(&user, ip);
+        }
+        assert_eq!(login_retry_after(&user, ip), Some(1));
+        // 用户名不区分大小写；其他 IP 独立计数
+        assert_eq!(login_retry_after(&user.to_uppercase(), ip), Some(1));
+        assert_eq!(login_retry_after(&user, Some("203.0.113.10")), None);
+
+        clear_login_failures(&user, ip);
+        assert_eq!(login_retry_after(&user, ip), None);
+    }
+
+    #[test]
+    fn test_role_order_and_parse() {
+        assert!(AdminRole::Viewer < AdminRole::Operator);
+        assert!(AdminRole::Operator < AdminRole::Owner);
+        assert_eq!(AdminRole::parse(" Operator "), Some(AdminRole::Operator));
+        assert_eq!(AdminRole::parse("root"), None);
+        assert_eq!(serde_json::to_string(&AdminRole::Owner).unwrap(), "\"owner\"");
+    }
+
+    #[test]
+    fn test_sessions_cascade_on_user_delete() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO admin_users (id, username, password_hash, role, created_at, updated_at)
+             VALUES ('u1', 'Alice', 'h', 'viewer', 0, 0)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO admin_sessions (token_hash, user_id, created_at, expires_at) VALUES ('t', 'u1', 0, 1)",
+            [],
+        )
+        .unwrap();
+        // 用户名大小写不敏感唯一
+        assert!(conn
+            .execute(
+                "INSERT INTO admin_users (id, username, password_hash, role, created_at, updated_at)
+                 VALUES ('u2', 'alice', 'h', 'viewer', 0, 0)",
+                [],
+            )
+            .is_err());
+        conn.execute("DELETE FROM admin_users WHERE id = 'u1'", []).unwrap();
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM admin_sessions", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
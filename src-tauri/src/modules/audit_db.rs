@@ -0,0 +1,369 @@
+//! Audit Log Database Module
+//! 审计日志：记录管理接口请求与账号 / 配置变更 (谁、何时、从哪里、改了什么)
+//!
+//! audit_log 只允许追加：UPDATE 一律被触发器拒绝，DELETE 仅允许删除超过最短保留期的条目 (用于保留策略)
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// 最短保留天数：触发器拒绝删除更新的条目，保留策略也不会低于该值
+pub const MIN_RETENTION_DAYS: u32 = 7;
+
+/// 两次自动清理之间的最小间隔 (秒)
+const RETENTION_CHECK_INTERVAL_SECS: i64 = 24 * 3600;
+
+/// 审计日志条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: i64,
+    /// 操作者类型: desktop / admin_user / admin_password / anonymous / http_api / scheduler / system
+    pub actor_type: String,
+    /// 操作者名称 (管理用户名、调度任务名等)
+    pub actor: String,
+    pub role: Option<String>,
+    /// 操作，例如 "config.update"、"account.switch" 或管理请求 "POST /api/accounts/switch"
+    pub action: String,
+    pub target: Option<String>,
+    pub client_ip: Option<String>,
+    pub status: Option<i32>,
+    /// 变更前 / 后 (只包含发生变化的字段，敏感值已脱敏)
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// 审计日志查询条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    pub actor_type: Option<String>,
+    pub actor: Option<String>,
+    /// 前缀匹配，例如 "account." 匹配所有账号操作
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// 获取审计数据库路径
+pub fn get_audit_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    Ok(data_dir.join("audit.db"))
+}
+
+/// 连接数据库
+fn connect_db() -> Result<Connection, String> {
+    let db_path = get_audit_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000)
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL")
+        .map_err(|e| e.to_string())?;
+
+    Ok(conn)
+}
+
+/// 初始化审计数据库
+pub fn init_db() -> Result<(), String> {
+    let conn = connect_db()?;
+    create_tables(&conn)
+}
+
+fn create_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            actor_type TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            role TEXT,
+            action TEXT NOT NULL,
+            target TEXT,
+            client_ip TEXT,
+            status INTEGER
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_audit_timestamp ON audit_log (timestamp DESC)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Migration: before/after diff columns
+    let _ = conn.execute("ALTER TABLE audit_log ADD COLUMN before_json TEXT", []);
+    let _ = conn.execute("ALTER TABLE audit_log ADD COLUMN after_json TEXT", []);
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_audit_action ON audit_log (action)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // 只允许追加
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+         BEGIN
+             SELECT RAISE(ABORT, 'audit_log is append-only');
+         END",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        &format!(
+            "CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+             WHEN OLD.timestamp > CAST(strftime('%s', 'now') AS INTEGER) - {}
+             BEGIN
+                 SELECT RAISE(ABORT, 'audit_log entries within the minimum retention period cannot be deleted');
+             END",
+            MIN_RETENTION_DAYS as i64 * 86400
+        ),
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+fn to_json(value: &Option<serde_json::Value>) -> Option<String> {
+    value.as_ref().map(|v| v.to_string())
+}
+
+fn insert(conn: &Connection, entry: &AuditEntry) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO audit_log (timestamp, actor_type, actor, role, action, target, client_ip, status, before_json, after_json)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            entry.timestamp,
+            entry.actor_type,
+            entry.actor,
+            entry.role,
+            entry.action,
+            entry.target,
+            entry.client_ip,
+            entry.status,
+            to_json(&entry.before),
+            to_json(&entry.after),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 写入一条审计日志 (id 由数据库分配)
+pub fn record(entry: &AuditEntry) -> Result<(), String> {
+    let conn = connect_db()?;
+    insert(&conn, entry)?;
+    maybe_apply_retention(&conn, entry.timestamp);
+    Ok(())
+}
+
+/// 后台写入审计日志，不阻塞请求 (不在 tokio 运行时内时同步写入)
+pub fn record_async(entry: AuditEntry) {
+    let write = move || {
+        if let Err(e) = record(&entry) {
+            tracing::error!("[Audit] Failed to record {}: {}", entry.action, e);
+        }
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(write);
+        }
+        Err(_) => write(),
+    }
+}
+
+fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<AuditEntry> {
+    let parse = |s: Option<String>| s.and_then(|s| serde_json::from_str(&s).ok());
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        actor_type: row.get(2)?,
+        actor: row.get(3)?,
+        role: row.get(4)?,
+        action: row.get(5)?,
+        target: row.get(6)?,
+        client_ip: row.get(7)?,
+        status: row.get(8)?,
+        before: parse(row.get(9)?),
+        after: parse(row.get(10)?),
+    })
+}
+
+fn query_entries(conn: &Connection, query: &AuditQuery) -> Result<Vec<AuditEntry>, String> {
+    let mut sql = String::from(
+        "SELECT id, timestamp, actor_type, actor, role, action, target, client_ip, status, before_json, after_json
+         FROM audit_log WHERE 1=1",
+    );
+    let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(actor_type) = &query.actor_type {
+        sql.push_str(" AND actor_type = ?");
+        args.push(Box::new(actor_type.clone()));
+    }
+    if let Some(actor) = &query.actor {
+        sql.push_str(" AND actor = ?");
+        args.push(Box::new(actor.clone()));
+    }
+    if let Some(action) = &query.action {
+        sql.push_str(" AND substr(action, 1, length(?)) = ?");
+        args.push(Box::new(action.clone()));
+        args.push(Box::new(action.clone()));
+    }
+    if let Some(target) = &query.target {
+        sql.push_str(" AND target = ?");
+        args.push(Box::new(target.clone()));
+    }
+    if let Some(since) = query.since {
+        sql.push_str(" AND timestamp >= ?");
+        args.push(Box::new(since));
+    }
+    if let Some(until) = query.until {
+        sql.push_str(" AND timestamp < ?");
+        args.push(Box::new(until));
+    }
+    sql.push_str(" ORDER BY id DESC LIMIT ? OFFSET ?");
+    args.push(Box::new(query.limit.unwrap_or(100).min(1000) as i64));
+    args.push(Box::new(query.offset.unwrap_or(0) as i64));
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params_from_iter(args.iter()), row_to_entry)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// 查询审计日志 (按 id 倒序，默认 100 条，最多 1000 条)
+pub fn query(query: &AuditQuery) -> Result<Vec<AuditEntry>, String> {
+    let conn = connect_db()?;
+    query_entries(&conn, query)
+}
+
+fn purge_before(conn: &Connection, cutoff: i64) -> Result<usize, String> {
+    conn.execute("DELETE FROM audit_log WHERE timestamp < ?1", [cutoff])
+        .map_err(|e| e.to_string())
+}
+
+/// 写入时顺带执行保留策略 (不少于 MIN_RETENTION_DAYS)，每个进程启动后首次写入时及此后每天最多一次
+fn maybe_apply_retention(conn: &Connection, now: i64) {
+    static LAST_RETENTION_RUN: AtomicI64 = AtomicI64::new(0);
+    let last = LAST_RETENTION_RUN.load(Ordering::Relaxed);
+    if now - last < RETENTION_CHECK_INTERVAL_SECS
+        || LAST_RETENTION_RUN
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+    let retention_days = crate::modules::config::load_app_config()
+        .map(|c| c.audit_log.retention_days)
+        .unwrap_or_else(|_| crate::models::config::AuditLogConfig::default().retention_days);
+    let days = retention_days.max(MIN_RETENTION_DAYS) as i64;
+    match purge_before(conn, now - days * 86400) {
+        Ok(deleted) if deleted > 0 => {
+            tracing::info!("[Audit] Retention: removed {} entries older than {} days", deleted, days);
+        }
+        Ok(_) => {}
+        Err(e) => tracing::error!("[Audit] Retention cleanup failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: i64, action: &str) -> AuditEntry {
+        AuditEntry {
+            id: 0,
+            timestamp,
+            actor_type: "admin_user".to_string(),
+            actor: "alice".to_string(),
+            role: Some("operator".to_string()),
+            action: action.to_string(),
+            target: None,
+            client_ip: Some("10.0.0.2".to_string()),
+            status: Some(200),
+            before: Some(serde_json::json!({"enabled": false})),
+            after: Some(serde_json::json!({"enabled": true})),
+        }
+    }
+
+    #[test]
+    fn test_insert_assigns_increasing_ids() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        insert(&conn, &entry(1_700_000_000, "POST /api/proxy/stop")).unwrap();
+        insert(&conn, &entry(1_700_000_000, "POST /api/proxy/stop")).unwrap();
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM audit_log ORDER BY id")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_append_only_and_retention() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        insert(&conn, &entry(now - 30 * 86400, "account.delete")).unwrap();
+        insert(&conn, &entry(now, "account.switch")).unwrap();
+
+        assert!(conn.execute("UPDATE audit_log SET actor = 'mallory'", []).is_err());
+        // 最短保留期内的条目不能删除
+        assert!(conn.execute("DELETE FROM audit_log", []).is_err());
+
+        assert_eq!(purge_before(&conn, now - 7 * 86400).unwrap(), 1);
+        let remaining = query_entries(&conn, &AuditQuery::default()).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].action, "account.switch");
+        assert_eq!(remaining[0].after, Some(serde_json::json!({"enabled": true})));
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        insert(&conn, &entry(100, "account.switch")).unwrap();
+        insert(&conn, &entry(200, "account.delete")).unwrap();
+        insert(&conn, &entry(300, "config.update")).unwrap();
+
+        let by_prefix = query_entries(
+            &conn,
+            &AuditQuery { action: Some("account.".to_string()), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(
+            by_prefix.iter().map(|e| e.action.as_str()).collect::<Vec<_>>(),
+            vec!["account.delete", "account.switch"]
+        );
+
+        let by_time = query_entries(
+            &conn,
+            &AuditQuery { since: Some(150), until: Some(300), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(by_time.len(), 1);
+        assert_eq!(by_time[0].action, "account.delete");
+
+        let paged = query_entries(
+            &conn,
+            &AuditQuery { limit: Some(1), offset: Some(1), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(paged[0].action, "account.delete");
+    }
+}
@@ -0,0 +1,270 @@
+// 自动切换 IDE 账号
+//
+// 由调度器 (scheduler::start_scheduler) 每 10 分钟调用：当前账号的监控模型配额降到阈值以下，或账号被 403 禁止 / 验证阻止时，
+// 自动将 Antigravity IDE 切换到剩余配额最多的可用账号，并通过 SystemManager 发送通知。
+// 静默时段内只记录日志，不执行切换 (切换会重启 IDE)。
+
+use chrono::Timelike;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::models::{Account, AutoSwitchConfig};
+use crate::modules::integration::SystemManager;
+use crate::modules::{account, logger};
+use crate::proxy::common::model_mapping::normalize_to_standard_id;
+
+// 上一次"没有可切换账号"通知对应的当前账号，避免每轮扫描重复通知
+static LAST_NO_CANDIDATE: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// 锁中毒时继续使用内部数据 (仅用于通知去重，不应让后台任务 panic)
+fn last_no_candidate() -> std::sync::MutexGuard<'static, Option<String>> {
+    LAST_NO_CANDIDATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 监控模型组中的最低剩余百分比；没有配额数据或未包含任何监控模型时返回 None
+pub fn monitored_min_percentage(account: &Account, monitored_models: &[String]) -> Option<i32> {
+    let quota = account.quota.as_ref()?;
+    let mut group_min: HashMap<String, i32> = HashMap::new();
+    for model in &quota.models {
+        if let Some(std_id) = normalize_to_standard_id(&model.name) {
+            let entry = group_min.entry(std_id).or_insert(100);
+            *entry = (*entry).min(model.percentage);
+        }
+    }
+    monitored_models
+        .iter()
+        .filter_map(|id| group_min.get(id).copied())
+        .min()
+}
+
+/// 账号当前是否无法在 IDE 中使用 (禁用 / 403 / 验证阻止)，返回原因
+fn unusable_reason(account: &Account, now: i64) -> Option<String> {
+    if account.disabled {
+        return Some("account disabled".to_string());
+    }
+    if account.quota.as_ref().is_some_and(|q| q.is_forbidden) {
+        return Some("403 Forbidden".to_string());
+    }
+    if account.validation_blocked && !account.validation_blocked_until.is_some_and(|t| t <= now) {
+        return Some("validation required".to_string());
+    }
+    None
+}
+
+/// 当前账号需要切换的原因；无需切换时返回 None
+pub fn switch_reason(account: &Account, config: &AutoSwitchConfig, now: i64) -> Option<String> {
+    if let Some(reason) = unusable_reason(account, now) {
+        return Some(reason);
+    }
+    let min_pct = monitored_min_percentage(account, &config.monitored_models)?;
+    (min_pct <= config.threshold_percentage as i32)
+        .then(|| format!("quota {}% <= {}%", min_pct, config.threshold_percentage))
+}
+
+/// 选择切换目标：可用、所有监控模型都高于阈值，且最低剩余配额最高的账号 (同分时按列表顺序)
+pub fn pick_best_account<'a>(
+    accounts: &'a [Account],
+    current_id: &str,
+    config: &AutoSwitchConfig,
+    now: i64,
+) -> Option<&'a Account> {
+    let mut best: Option<(&Account, i32)> = None;
+    for candidate in accounts {
+        if candidate.id == current_id || unusable_reason(candidate, now).is_some() {
+            continue;
+        }
+        let Some(min_pct) = monitored_min_percentage(candidate, &config.monitored_models) else {
+            continue;
+        };
+        if min_pct <= config.threshold_percentage as i32 {
+            continue;
+        }
+        if !best.is_some_and(|(_, best_pct)| min_pct <= best_pct) {
+            best = Some((candidate, min_pct));
+        }
+    }
+    best.map(|(account, _)| account)
+}
+
+fn parse_hhmm(value: &str) -> Option<u32> {
+    let (h, m) = value.trim().split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+/// 给定时刻 (当天分钟数) 是否处于静默时段；支持跨午夜 (start > end)，格式无效时视为不在静默时段
+pub fn in_quiet_hours(config: &AutoSwitchConfig, minute_of_day: u32) -> bool {
+    if !config.quiet_hours_enabled {
+        return false;
+    }
+    let (Some(start), Some(end)) = (
+        parse_hhmm(&config.quiet_hours_start),
+        parse_hhmm(&config.quiet_hours_end),
+    ) else {
+        return false;
+    };
+    if start <= end {
+        start <= minute_of_day && minute_of_day < end
+    } else {
+        minute_of_day >= start || minute_of_day < end
+    }
+}
+
+/// 检查当前账号并在需要时自动切换 (由调度器调用)
+pub async fn check_and_switch(config: &AutoSwitchConfig, integration: &SystemManager) {
+    if !config.enabled {
+        return;
+    }
+    let Ok(Some(mut current)) = account::get_current_account() else {
+        return;
+    };
+
+    // 刷新当前账号配额 (其他账号使用已缓存的配额数据)
+    if !current.quota.as_ref().is_some_and(|q| q.is_forbidden) {
+        match account::fetch_quota_with_retry(&mut current).await {
+            Ok(quota) => {
+                if let Err(e) = account::update_account_quota(&current.id, quota) {
+                    logger::log_warn(&format!("[AutoSwitch] Failed to save quota for {}: {}", current.email, e));
+                }
+                if let Ok(reloaded) = account::load_account(&current.id) {
+                    current = reloaded;
+                }
+            }
+            Err(e) => {
+                logger::log_warn(&format!("[AutoSwitch] Failed to refresh quota for {}: {}", current.email, e));
+            }
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let Some(reason) = switch_reason(&current, config, now) else {
+        *last_no_candidate() = None;
+        return;
+    };
+
+    let local_now = chrono::Local::now();
+    if in_quiet_hours(config, local_now.hour() * 60 + local_now.minute()) {
+        logger::log_info(&format!(
+            "[AutoSwitch] {} needs switching ({}), but quiet hours are active",
+            current.email, reason
+        ));
+        return;
+    }
+
+    let Ok(accounts) = account::list_accounts() else {
+        return;
+    };
+    let Some(target) = pick_best_account(&accounts, &current.id, config, now) else {
+        logger::log_warn(&format!(
+            "[AutoSwitch] {} needs switching ({}), but no eligible account is available",
+            current.email, reason
+        ));
+        let mut last = last_no_candidate();
+        if last.as_deref() != Some(current.id.as_str()) {
+            *last = Some(current.id.clone());
+            integration.show_notification(
+                "Auto switch skipped",
+                &format!("{}: {}. No other account has enough quota.", current.email, reason),
+            );
+        }
+        return;
+    };
+
+    logger::log_info(&format!(
+        "[AutoSwitch] Switching IDE account {} -> {} ({})",
+        current.email, target.email, reason
+    ));
+    match account::switch_account(&target.id, integration).await {
+        Ok(()) => {
+            *last_no_candidate() = None;
+            if let SystemManager::Desktop(handle) = integration {
+                use tauri::Emitter;
+                let _ = handle.emit("tray://account-switched", target.id.clone());
+            }
+            integration.show_notification(
+                "Account switched",
+                &format!("{} -> {} ({})", current.email, target.email, reason),
+            );
+        }
+        Err(e) => {
+            logger::log_error(&format!("[AutoSwitch] Switch to {} failed: {}", target.email, e));
+            integration.show_notification(
+                "Auto switch failed",
+                &format!("{} -> {}: {}", current.email, target.email, e),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::quota::ModelQuota;
+    use crate::models::{QuotaData, TokenData};
+
+    fn account(id: &str, quotas: &[(&str, i32)]) -> Account {
+        let token = TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None);
+        let mut account = Account::new(id.to_string(), format!("{}@example.com", id), token);
+        let mut quota = QuotaData::new();
+        for (name, percentage) in quotas {
+            quota.add_model(ModelQuota {
+                name: name.to_string(),
+                percentage: *percentage,
+                reset_time: String::new(),
+                display_name: None,
+                supports_images: None,
+                supports_thinking: None,
+                thinking_budget: None,
+                recommended: None,
+                max_tokens: None,
+                max_output_tokens: None,
+                supported_mime_types: None,
+            });
+        }
+        account.quota = Some(quota);
+        account
+    }
+
+    #[test]
+    fn test_switch_reason_and_pick_best() {
+        let config = AutoSwitchConfig {
+            enabled: true,
+            monitored_models: vec!["claude".to_string(), "gemini-3-pro-high".to_string()],
+            ..AutoSwitchConfig::default()
+        };
+        let current = account("cur", &[("claude-sonnet-4-6", 5), ("gemini-3-pro-high", 80)]);
+        assert!(switch_reason(&current, &config, 0).unwrap().contains("5%"));
+        // 未监控的模型不触发
+        let healthy = account("ok", &[("claude-sonnet-4-6", 50), ("gemini-3-flash", 0)]);
+        assert_eq!(switch_reason(&healthy, &config, 0), None);
+
+        let mut blocked = account("blocked", &[("claude-opus-4-6-thinking", 100)]);
+        blocked.validation_blocked = true;
+        assert_eq!(switch_reason(&blocked, &config, 0).as_deref(), Some("validation required"));
+
+        let low = account("low", &[("claude-sonnet-4-6", 8)]);
+        let accounts = vec![current.clone(), blocked, low, healthy, account("best", &[("claude-sonnet-4-6", 90)])];
+        assert_eq!(pick_best_account(&accounts, "cur", &config, 0).unwrap().id, "best");
+        assert!(pick_best_account(&accounts[..3], "cur", &config, 0).is_none());
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let mut config = AutoSwitchConfig {
+            quiet_hours_enabled: true,
+            quiet_hours_start: "22:00".to_string(),
+            quiet_hours_end: "08:00".to_string(),
+            ..AutoSwitchConfig::default()
+        };
+        assert!(in_quiet_hours(&config, 23 * 60));
+        assert!(in_quiet_hours(&config, 7 * 60 + 59));
+        assert!(!in_quiet_hours(&config, 8 * 60));
+        config.quiet_hours_start = "12:00".to_string();
+        config.quiet_hours_end = "13:30".to_string();
+        assert!(in_quiet_hours(&config, 13 * 60));
+        assert!(!in_quiet_hours(&config, 14 * 60));
+        config.quiet_hours_end = "bad".to_string();
+        assert!(!in_quiet_hours(&config, 12 * 60 + 30));
+    }
+}
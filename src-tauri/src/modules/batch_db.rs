@@ -0,0 +1,543 @@
+//! Batch API Store Module
+//! OpenAI Batch API 模拟：上传文件 / 批次状态 / 逐行执行结果持久化 (重启后继续执行)
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// 上传的 JSONL 文件或生成的结果文件 (内容存放在 data_dir/batch_files/{id}.jsonl)
+#[derive(Debug, Clone)]
+pub struct BatchFile {
+    pub id: String,
+    pub filename: String,
+    /// `batch` (上传的输入) / `batch_output` (结果与错误文件)
+    pub purpose: String,
+    pub bytes: i64,
+    pub created_at: i64,
+    /// 上传者的用户令牌 (为空表示管理员 API Key)
+    pub user_token_id: Option<String>,
+}
+
+impl BatchFile {
+    /// OpenAI File 对象
+    pub fn to_openai(&self) -> Value {
+        json!({
+            "id": self.id,
+            "object": "file",
+            "bytes": self.bytes,
+            "created_at": self.created_at,
+            "filename": self.filename,
+            "purpose": self.purpose,
+        })
+    }
+}
+
+/// 批次
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    pub id: String,
+    pub endpoint: String,
+    pub input_file_id: String,
+    pub completion_window: String,
+    /// validating / in_progress / finalizing / completed / failed / expired / cancelling / cancelled
+    pub status: String,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    /// 校验失败时的错误列表 (OpenAI `errors.data`)
+    pub errors: Vec<Value>,
+    pub metadata: Option<Value>,
+    pub created_at: i64,
+    pub in_progress_at: Option<i64>,
+    pub expires_at: i64,
+    pub finalizing_at: Option<i64>,
+    pub completed_at: Option<i64>,
+    pub failed_at: Option<i64>,
+    pub expired_at: Option<i64>,
+    pub cancelling_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+    pub total: i64,
+    pub completed: i64,
+    pub failed: i64,
+    /// 创建批次的用户令牌 (执行时用于用量归属)
+    pub user_token_id: Option<String>,
+}
+
+impl Batch {
+    /// OpenAI Batch 对象
+    pub fn to_openai(&self) -> Value {
+        json!({
+            "id": self.id,
+            "object": "batch",
+            "endpoint": self.endpoint,
+            "errors": if self.errors.is_empty() {
+                Value::Null
+            } else {
+                json!({ "object": "list", "data": self.errors })
+            },
+            "input_file_id": self.input_file_id,
+            "completion_window": self.completion_window,
+            "status": self.status,
+            "output_file_id": self.output_file_id,
+            "error_file_id": self.error_file_id,
+            "created_at": self.created_at,
+            "in_progress_at": self.in_progress_at,
+            "expires_at": self.expires_at,
+            "finalizing_at": self.finalizing_at,
+            "completed_at": self.completed_at,
+            "failed_at": self.failed_at,
+            "expired_at": self.expired_at,
+            "cancelling_at": self.cancelling_at,
+            "cancelled_at": self.cancelled_at,
+            "request_counts": {
+                "total": self.total,
+                "completed": self.completed,
+                "failed": self.failed,
+            },
+            "metadata": self.metadata,
+        })
+    }
+}
+
+/// 待执行的批次请求行
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    pub idx: i64,
+    pub custom_id: String,
+    pub body: Value,
+}
+
+pub fn get_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    Ok(data_dir.join("batches.db"))
+}
+
+/// 批处理文件目录
+pub fn get_files_dir() -> Result<PathBuf, String> {
+    let dir = crate::modules::account::get_data_dir()?.join("batch_files");
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create batch files dir: {}", e))?;
+    }
+    Ok(dir)
+}
+
+/// 文件内容路径
+pub fn get_file_path(file_id: &str) -> Result<PathBuf, String> {
+    Ok(get_files_dir()?.join(format!("{}.jsonl", file_id)))
+}
+
+fn connect_db() -> Result<Connection, String> {
+    let db_path = get_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL").map_err(|e| e.to_string())?;
+
+    Ok(conn)
+}
+
+pub fn init_db() -> Result<(), String> {
+    let conn = connect_db()?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS batch_files (
+            id TEXT PRIMARY KEY,
+            filename TEXT NOT NULL,
+            purpose TEXT NOT NULL,
+            bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            user_token_id TEXT
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS batches (
+            id TEXT PRIMARY KEY,
+            endpoint TEXT NOT NULL,
+            input_file_id TEXT NOT NULL,
+            completion_window TEXT NOT NULL,
+            status TEXT NOT NULL,
+            output_file_id TEXT,
+            error_file_id TEXT,
+            errors TEXT,
+            metadata TEXT,
+            created_at INTEGER NOT NULL,
+            in_progress_at INTEGER,
+            expires_at INTEGER NOT NULL,
+            finalizing_at INTEGER,
+            completed_at INTEGER,
+            failed_at INTEGER,
+            expired_at INTEGER,
+            cancelling_at INTEGER,
+            cancelled_at INTEGER,
+            total INTEGER NOT NULL DEFAULT 0,
+            completed INTEGER NOT NULL DEFAULT 0,
+            failed INTEGER NOT NULL DEFAULT 0,
+            user_token_id TEXT
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS batch_items (
+            batch_id TEXT NOT NULL,
+            idx INTEGER NOT NULL,
+            custom_id TEXT NOT NULL,
+            body TEXT NOT NULL,
+            status TEXT NOT NULL,
+            result TEXT,
+            PRIMARY KEY (batch_id, idx)
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_batches_status ON batches (status, created_at)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// ===== 文件 =====
+
+fn row_to_file(row: &rusqlite::Row) -> rusqlite::Result<BatchFile> {
+    Ok(BatchFile {
+        id: row.get(0)?,
+        filename: row.get(1)?,
+        purpose: row.get(2)?,
+        bytes: row.get(3)?,
+        created_at: row.get(4)?,
+        user_token_id: row.get(5)?,
+    })
+}
+
+pub fn insert_file(file: &BatchFile) -> Result<(), String> {
+    let conn = connect_db()?;
+    conn.execute(
+        "INSERT INTO batch_files (id, filename, purpose, bytes, created_at, user_token_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            file.id,
+            file.filename,
+            file.purpose,
+            file.bytes,
+            file.created_at,
+            file.user_token_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn get_file(id: &str) -> Result<Option<BatchFile>, String> {
+    let conn = connect_db()?;
+    conn.query_row(
+        "SELECT id, filename, purpose, bytes, created_at, user_token_id FROM batch_files WHERE id = ?1",
+        params![id],
+        row_to_file,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// 列出文件 (`user_token_id` 不为空时仅返回该令牌上传/生成的文件)
+pub fn list_files(purpose: Option<&str>, user_token_id: Option<&str>) -> Result<Vec<BatchFile>, String> {
+    let conn = connect_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, filename, purpose, bytes, created_at, user_token_id FROM batch_files
+             WHERE (?1 IS NULL OR purpose = ?1) AND (?2 IS NULL OR user_token_id = ?2)
+             ORDER BY created_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let files = stmt
+        .query_map(params![purpose, user_token_id], row_to_file)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(files)
+}
+
+/// 删除文件记录与内容，返回是否存在
+pub fn delete_file(id: &str) -> Result<bool, String> {
+    let conn = connect_db()?;
+    let deleted = conn
+        .execute("DELETE FROM batch_files WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    if deleted > 0 {
+        let path = get_file_path(id)?;
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(deleted > 0)
+}
+
+// ===== 批次 =====
+
+const BATCH_COLUMNS: &str = "id, endpoint, input_file_id, completion_window, status, output_file_id, error_file_id,
+    errors, metadata, created_at, in_progress_at, expires_at, finalizing_at, completed_at, failed_at,
+    expired_at, cancelling_at, cancelled_at, total, completed, failed, user_token_id";
+
+fn row_to_batch(row: &rusqlite::Row) -> rusqlite::Result<Batch> {
+    let errors: Option<String> = row.get(7)?;
+    let metadata: Option<String> = row.get(8)?;
+    Ok(Batch {
+        id: row.get(0)?,
+        endpoint: row.get(1)?,
+        input_file_id: row.get(2)?,
+        completion_window: row.get(3)?,
+        status: row.get(4)?,
+        output_file_id: row.get(5)?,
+        error_file_id: row.get(6)?,
+        errors: errors
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        metadata: metadata.and_then(|s| serde_json::from_str(&s).ok()),
+        created_at: row.get(9)?,
+        in_progress_at: row.get(10)?,
+        expires_at: row.get(11)?,
+        finalizing_at: row.get(12)?,
+        completed_at: row.get(13)?,
+        failed_at: row.get(14)?,
+        expired_at: row.get(15)?,
+        cancelling_at: row.get(16)?,
+        cancelled_at: row.get(17)?,
+        total: row.get(18)?,
+        completed: row.get(19)?,
+        failed: row.get(20)?,
+        user_token_id: row.get(21)?,
+    })
+}
+
+/// 插入或整体覆盖批次 (计数器由 complete_item 维护)
+pub fn save_batch(batch: &Batch) -> Result<(), String> {
+    let conn = connect_db()?;
+    let errors = if batch.errors.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&batch.errors).map_err(|e| e.to_string())?)
+    };
+    let metadata = batch.metadata.as_ref().map(|m| m.to_string());
+
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO batches ({}) VALUES
+             (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
+            BATCH_COLUMNS
+        ),
+        params![
+            batch.id,
+            batch.endpoint,
+            batch.input_file_id,
+            batch.completion_window,
+            batch.status,
+            batch.output_file_id,
+            batch.error_file_id,
+            errors,
+            metadata,
+            batch.created_at,
+            batch.in_progress_at,
+            batch.expires_at,
+            batch.finalizing_at,
+            batch.completed_at,
+            batch.failed_at,
+            batch.expired_at,
+            batch.cancelling_at,
+            batch.cancelled_at,
+            batch.total,
+            batch.completed,
+            batch.failed,
+            batch.user_token_id,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn get_batch(id: &str) -> Result<Option<Batch>, String> {
+    let conn = connect_db()?;
+    conn.query_row(
+        &format!("SELECT {} FROM batches WHERE id = ?1", BATCH_COLUMNS),
+        params![id],
+        row_to_batch,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// 按创建时间倒序分页 (`after` 为上一页最后一个批次 ID，`user_token_id` 不为空时仅返回该令牌创建的批次)
+pub fn list_batches(limit: usize, after: Option<&str>, user_token_id: Option<&str>) -> Result<Vec<Batch>, String> {
+    let conn = connect_db()?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM batches
+             WHERE (?2 IS NULL
+                    OR (created_at, id) < (SELECT created_at, id FROM batches WHERE id = ?2))
+               AND (?3 IS NULL OR user_token_id = ?3)
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?1",
+            BATCH_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let batches = stmt
+        .query_map(params![limit as i64, after, user_token_id], row_to_batch)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(batches)
+}
+
+/// 请求取消：仅未结束的批次会进入 cancelling，返回最新状态
+pub fn request_cancel(id: &str) -> Result<Option<Batch>, String> {
+    let conn = connect_db()?;
+    conn.execute(
+        "UPDATE batches SET status = 'cancelling', cancelling_at = ?2
+         WHERE id = ?1 AND status IN ('validating', 'in_progress')",
+        params![id, chrono::Utc::now().timestamp()],
+    )
+    .map_err(|e| e.to_string())?;
+    drop(conn);
+    get_batch(id)
+}
+
+/// 最早创建的未结束批次 (后台任务逐个处理)
+pub fn next_active_batch() -> Result<Option<Batch>, String> {
+    let conn = connect_db()?;
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM batches
+             WHERE status IN ('validating', 'in_progress', 'finalizing', 'cancelling')
+             ORDER BY created_at ASC LIMIT 1",
+            BATCH_COLUMNS
+        ),
+        [],
+        row_to_batch,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// 读取批次当前状态 (执行过程中检测取消)
+pub fn get_batch_status(id: &str) -> Result<Option<String>, String> {
+    let conn = connect_db()?;
+    conn.query_row("SELECT status FROM batches WHERE id = ?1", params![id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+// ===== 请求行 =====
+
+/// 写入校验通过的请求行并将批次置为 in_progress (同一事务，重启时不会重复写入)
+pub fn start_batch(batch: &Batch, items: &[BatchItem]) -> Result<(), String> {
+    let mut conn = connect_db()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT OR IGNORE INTO batch_items (batch_id, idx, custom_id, body, status)
+                 VALUES (?1, ?2, ?3, ?4, 'pending')",
+            )
+            .map_err(|e| e.to_string())?;
+        for item in items {
+            stmt.execute(params![batch.id, item.idx, item.custom_id, item.body.to_string()])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.execute(
+        "UPDATE batches SET status = 'in_progress', in_progress_at = ?2, total = ?3
+         WHERE id = ?1 AND status = 'validating'",
+        params![batch.id, chrono::Utc::now().timestamp(), items.len() as i64],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 按顺序取出待执行的请求行
+pub fn pending_items(batch_id: &str, limit: usize) -> Result<Vec<BatchItem>, String> {
+    let conn = connect_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT idx, custom_id, body FROM batch_items
+             WHERE batch_id = ?1 AND status = 'pending'
+             ORDER BY idx ASC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map(params![batch_id, limit as i64], |row| {
+            let body: String = row.get(2)?;
+            Ok(BatchItem {
+                idx: row.get(0)?,
+                custom_id: row.get(1)?,
+                body: serde_json::from_str(&body).unwrap_or(Value::Null),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(items)
+}
+
+/// 记录单行执行结果 (`result` 为结果文件中的完整一行) 并更新批次计数
+pub fn complete_item(batch_id: &str, idx: i64, success: bool, result: &Value) -> Result<(), String> {
+    let mut conn = connect_db()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let updated = tx
+        .execute(
+            "UPDATE batch_items SET status = ?3, result = ?4
+             WHERE batch_id = ?1 AND idx = ?2 AND status = 'pending'",
+            params![
+                batch_id,
+                idx,
+                if success { "completed" } else { "failed" },
+                result.to_string()
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated > 0 {
+        let column = if success { "completed" } else { "failed" };
+        tx.execute(
+            &format!("UPDATE batches SET {0} = {0} + 1 WHERE id = ?1", column),
+            params![batch_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 按行号顺序读取结果行：status 为 `completed` / `failed` 时返回结果，`pending` 时返回 custom_id
+pub fn item_results(batch_id: &str, status: &str) -> Result<Vec<(String, Option<Value>)>, String> {
+    let conn = connect_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT custom_id, result FROM batch_items
+             WHERE batch_id = ?1 AND status = ?2
+             ORDER BY idx ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![batch_id, status], |row| {
+            let result: Option<String> = row.get(1)?;
+            Ok((row.get(0)?, result.and_then(|s| serde_json::from_str(&s).ok())))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+/// 批次结束后清理请求行 (结果已写入输出文件)
+pub fn delete_items(batch_id: &str) -> Result<(), String> {
+    let conn = connect_db()?;
+    conn.execute("DELETE FROM batch_items WHERE batch_id = ?1", params![batch_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -15,7 +15,7 @@ pub fn load_app_config() -> Result<AppConfig, String> {
     if !config_path.exists() {
         let config = AppConfig::new();
         // [FIX #1460] Persist initial config to prevent new API Key on every refresh
-        let _ = save_app_config(&config);
+        let _ = write_app_config(&config);
         return Ok(config);
     }
     
@@ -80,14 +80,31 @@ pub fn load_app_config() -> Result<AppConfig, String> {
     
     // If migration occurred, auto-save once to clean up the file
     if modified {
-        let _ = save_app_config(&config);
+        let _ = write_app_config(&config);
     }
 
     Ok(config)
 }
 
 /// Save application configuration
+///
+/// [NEW] Changes against the file on disk are written to the audit log (see modules::audit)
 pub fn save_app_config(config: &AppConfig) -> Result<(), String> {
+    let previous = get_data_dir()
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join(CONFIG_FILE)).ok())
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok());
+
+    write_app_config(config)?;
+
+    if let (Some(before), Ok(after)) = (previous, serde_json::to_value(config)) {
+        crate::modules::audit::record_config_change(&before, &after);
+    }
+    Ok(())
+}
+
+/// Write configuration without auditing (initial file and load-time migrations)
+fn write_app_config(config: &AppConfig) -> Result<(), String> {
     let data_dir = get_data_dir()?;
     let config_path = data_dir.join(CONFIG_FILE);
     
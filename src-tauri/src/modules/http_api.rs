@@ -337,7 +337,8 @@ async fn switch_account(
     let state_clone = state.clone();
 
     // Execute switch asynchronously (non-blocking response)
-    tokio::spawn(async move {
+    // [NEW] 审计操作者：派生任务不会继承 task-local，需要在任务内设置
+    tokio::spawn(crate::modules::audit::scope(crate::modules::audit::AuditActor::http_api(), async move {
         logger::log_info(&format!("[HTTP API] Starting account switch: {}", account_id));
         
         match account::switch_account(&account_id, &state_clone.integration).await {
@@ -352,7 +353,7 @@ async fn switch_account(
         // Mark switch ended
         let mut switching = state_clone.switching.write().await;
         *switching = false;
-    });
+    }));
 
     // Immediately return 202 Accepted
     Ok((
@@ -0,0 +1,368 @@
+//! IP Access Control List
+//! 黑/白名单的内存前缀树 (IPv4 + IPv6)
+//!
+//! - 所有地址统一为 128 位：IPv4 映射为 ::ffff:a.b.c.d，因此 IPv4 规则同样匹配 IPv4-mapped IPv6 客户端
+//! - 名单变更时 (security_db 的增删接口) 仅标记失效，下次查询时从 security.db 重新编译；
+//!   最早的临时封禁到期时同样重新编译并清理过期记录
+//! - 黑名单命中计数先在内存累加，首次命中后由定时任务在固定间隔后批量写回 security.db
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use crate::modules::security_db::{self, IpBlacklistEntry};
+
+/// 命中计数写回间隔
+const HIT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 客户端 IP 转为 128 位地址 (IPv4 映射到 ::ffff:0:0/96)
+fn ip_key(ip: &str) -> Option<u128> {
+    match ip.trim().parse::<IpAddr>().ok()? {
+        IpAddr::V4(v4) => Some(u128::from(v4.to_ipv6_mapped())),
+        IpAddr::V6(v6) => Some(u128::from(v6)),
+    }
+}
+
+/// 解析单个 IP 或 CIDR 为 (128 位地址, 前缀长度)；格式无效时返回 None
+pub fn parse_prefix(pattern: &str) -> Option<(u128, u8)> {
+    let pattern = pattern.trim();
+    let (addr, len) = match pattern.split_once('/') {
+        Some((addr, len)) => (addr, Some(len.trim().parse::<u8>().ok()?)),
+        None => (pattern, None),
+    };
+    match addr.trim().parse::<IpAddr>().ok()? {
+        IpAddr::V4(v4) => {
+            let len = len.unwrap_or(32);
+            (len <= 32).then(|| (u128::from(v4.to_ipv6_mapped()), 96 + len))
+        }
+        IpAddr::V6(v6) => {
+            let len = len.unwrap_or(128);
+            (len <= 128).then(|| (u128::from(v6), len))
+        }
+    }
+}
+
+#[cfg(test)]
+fn prefix_mask(len: u8) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        !0u128 << (128 - len as u32)
+    }
+}
+
+/// IP 是否落在给定 IP / CIDR 范围内 (单条规则匹配，测试中用于核对前缀树结果)
+#[cfg(test)]
+fn prefix_contains(pattern: &str, ip: &str) -> bool {
+    let (Some((net, len)), Some(addr)) = (parse_prefix(pattern), ip_key(ip)) else {
+        return false;
+    };
+    let mask = prefix_mask(len);
+    (addr & mask) == (net & mask)
+}
+
+#[derive(Default, Clone)]
+struct TrieNode {
+    /// 子节点下标 (0 表示无，根节点不会作为子节点)
+    children: [u32; 2],
+    values: Vec<u32>,
+}
+
+/// 128 位二进制前缀树
+struct PrefixTrie<T> {
+    nodes: Vec<TrieNode>,
+    values: Vec<T>,
+}
+
+impl<T> PrefixTrie<T> {
+    fn new() -> Self {
+        Self {
+            nodes: vec![TrieNode::default()],
+            values: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, addr: u128, len: u8, value: T) {
+        let mut node = 0;
+        for depth in 0..len as u32 {
+            let bit = ((addr >> (127 - depth)) & 1) as usize;
+            let next = self.nodes[node].children[bit] as usize;
+            node = if next == 0 {
+                self.nodes.push(TrieNode::default());
+                let idx = self.nodes.len() - 1;
+                self.nodes[node].children[bit] = idx as u32;
+                idx
+            } else {
+                next
+            };
+        }
+        self.values.push(value);
+        let value_idx = (self.values.len() - 1) as u32;
+        self.nodes[node].values.push(value_idx);
+    }
+
+    /// 满足条件的最具体 (前缀最长) 的条目
+    fn longest_match(&self, addr: u128, accept: impl Fn(&T) -> bool) -> Option<&T> {
+        let mut found = None;
+        let mut node = 0;
+        let mut depth = 0u32;
+        loop {
+            if let Some(value) = self.nodes[node]
+                .values
+                .iter()
+                .map(|&v| &self.values[v as usize])
+                .find(|v| accept(v))
+            {
+                found = Some(value);
+            }
+            if depth == 128 {
+                break;
+            }
+            let next = self.nodes[node].children[((addr >> (127 - depth)) & 1) as usize];
+            if next == 0 {
+                break;
+            }
+            node = next as usize;
+            depth += 1;
+        }
+        found
+    }
+}
+
+fn is_expired(entry: &IpBlacklistEntry, now: i64) -> bool {
+    entry.expires_at.is_some_and(|t| t < now)
+}
+
+/// 编译后的访问控制列表
+struct CompiledAcl {
+    generation: u64,
+    blacklist: PrefixTrie<IpBlacklistEntry>,
+    /// 无法解析为 IP / CIDR 的模式，按原字符串精确匹配 (兼容旧数据)
+    blacklist_exact: HashMap<String, IpBlacklistEntry>,
+    whitelist: PrefixTrie<()>,
+    whitelist_exact: HashSet<String>,
+    /// 最早的临时封禁到期时间，之后需要重新编译
+    next_expiry: Option<i64>,
+}
+
+impl CompiledAcl {
+    fn build(
+        generation: u64,
+        blacklist: Vec<IpBlacklistEntry>,
+        whitelist: Vec<String>,
+        now: i64,
+    ) -> Self {
+        let mut acl = CompiledAcl {
+            generation,
+            blacklist: PrefixTrie::new(),
+            blacklist_exact: HashMap::new(),
+            whitelist: PrefixTrie::new(),
+            whitelist_exact: HashSet::new(),
+            next_expiry: None,
+        };
+        for entry in blacklist {
+            if is_expired(&entry, now) {
+                continue;
+            }
+            if let Some(expires_at) = entry.expires_at {
+                acl.next_expiry = Some(acl.next_expiry.map_or(expires_at, |t| t.min(expires_at)));
+            }
+            match parse_prefix(&entry.ip_pattern) {
+                Some((addr, len)) => acl.blacklist.insert(addr, len, entry),
+                None => {
+                    acl.blacklist_exact.insert(entry.ip_pattern.clone(), entry);
+                }
+            }
+        }
+        for pattern in whitelist {
+            match parse_prefix(&pattern) {
+                Some((addr, len)) => acl.whitelist.insert(addr, len, ()),
+                None => {
+                    acl.whitelist_exact.insert(pattern);
+                }
+            }
+        }
+        acl
+    }
+
+    fn blacklist_entry(&self, ip: &str, now: i64) -> Option<&IpBlacklistEntry> {
+        if let Some(entry) = self.blacklist_exact.get(ip).filter(|e| !is_expired(e, now)) {
+            return Some(entry);
+        }
+        self.blacklist
+            .longest_match(ip_key(ip)?, |e| !is_expired(e, now))
+    }
+
+    fn is_whitelisted(&self, ip: &str) -> bool {
+        self.whitelist_exact.contains(ip)
+            || ip_key(ip).is_some_and(|addr| self.whitelist.longest_match(addr, |_| true).is_some())
+    }
+}
+
+static GENERATION: AtomicU64 = AtomicU64::new(1);
+static ACL: RwLock<Option<Arc<CompiledAcl>>> = RwLock::new(None);
+
+/// 标记黑/白名单已变更，下次查询时重新编译
+pub fn invalidate() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+fn current(now: i64) -> Result<Arc<CompiledAcl>, String> {
+    let generation = GENERATION.load(Ordering::SeqCst);
+    if let Some(acl) = ACL.read().ok().and_then(|slot| slot.clone()) {
+        if acl.generation == generation && !acl.next_expiry.is_some_and(|t| t < now) {
+            return Ok(acl);
+        }
+    }
+
+    // 清理过期的临时封禁 (原先在每个请求上执行)
+    if let Err(e) = security_db::purge_expired_blacklist(now) {
+        tracing::warn!("[IP ACL] Failed to purge expired blacklist entries: {}", e);
+    }
+    let blacklist = security_db::get_blacklist()?;
+    let whitelist = security_db::get_whitelist()?
+        .into_iter()
+        .map(|e| e.ip_pattern)
+        .collect();
+    let acl = Arc::new(CompiledAcl::build(generation, blacklist, whitelist, now));
+    tracing::debug!(
+        "[IP ACL] Compiled access list (generation {}, {} blacklist nodes, {} whitelist nodes)",
+        generation,
+        acl.blacklist.nodes.len(),
+        acl.whitelist.nodes.len()
+    );
+    if let Ok(mut slot) = ACL.write() {
+        *slot = Some(acl.clone());
+    }
+    Ok(acl)
+}
+
+/// 获取 IP 命中的黑名单条目 (最具体的规则优先)，并累加命中计数
+pub fn blacklist_entry(ip: &str) -> Result<Option<IpBlacklistEntry>, String> {
+    let now = chrono::Utc::now().timestamp();
+    let acl = current(now)?;
+    let entry = acl.blacklist_entry(ip, now).cloned();
+    if let Some(entry) = &entry {
+        record_hit(&entry.id);
+    }
+    Ok(entry)
+}
+
+/// 检查 IP 是否在白名单中
+pub fn is_whitelisted(ip: &str) -> Result<bool, String> {
+    let now = chrono::Utc::now().timestamp();
+    Ok(current(now)?.is_whitelisted(ip))
+}
+
+fn hit_buffer() -> &'static Mutex<HashMap<String, i64>> {
+    static HITS: OnceLock<Mutex<HashMap<String, i64>>> = OnceLock::new();
+    HITS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn write_hits(hits: HashMap<String, i64>) {
+    if hits.is_empty() {
+        return;
+    }
+    if let Err(e) = security_db::add_blacklist_hits(&hits) {
+        tracing::warn!("[IP ACL] Failed to flush blacklist hit counts: {}", e);
+    }
+}
+
+fn record_hit(entry_id: &str) {
+    let first_pending = {
+        let Ok(mut pending) = hit_buffer().lock() else {
+            return;
+        };
+        let first = pending.is_empty();
+        *pending.entry(entry_id.to_string()).or_insert(0) += 1;
+        first
+    };
+    if !first_pending {
+        return;
+    }
+    // 缓冲区由空变为非空时安排一次定时写回，之后不再有命中也能按时落盘
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(async {
+                tokio::time::sleep(HIT_FLUSH_INTERVAL).await;
+                let _ = tokio::task::spawn_blocking(flush_hits).await;
+            });
+        }
+        // 无运行时 (如同步调用方) 时立即写回
+        Err(_) => flush_hits(),
+    }
+}
+
+/// 立即写回所有未落盘的命中计数 (定时任务与反代服务停止时调用)
+pub fn flush_hits() {
+    let batch = match hit_buffer().lock() {
+        Ok(mut pending) => std::mem::take(&mut *pending),
+        Err(_) => return,
+    };
+    write_hits(batch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, pattern: &str, expires_at: Option<i64>) -> IpBlacklistEntry {
+        IpBlacklistEntry {
+            id: id.to_string(),
+            ip_pattern: pattern.to_string(),
+            reason: None,
+            created_at: 0,
+            expires_at,
+            created_by: "test".to_string(),
+            hit_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_prefix_matching() {
+        assert!(prefix_contains("192.168.1.0/24", "192.168.1.77"));
+        assert!(!prefix_contains("192.168.1.0/24", "192.168.2.1"));
+        assert!(prefix_contains("10.0.0.0/8", "::ffff:10.1.2.3"));
+        assert!(prefix_contains("2001:db8::/32", "2001:db8:abcd::1"));
+        assert!(!prefix_contains("2001:db8::/32", "2001:db9::1"));
+        assert!(prefix_contains("0.0.0.0/0", "8.8.8.8"));
+        assert!(!prefix_contains("0.0.0.0/0", "2001:db8::1"));
+        assert!(prefix_contains("::/0", "2001:db8::1"));
+        assert_eq!(parse_prefix("10.0.0.0/33"), None);
+        assert_eq!(parse_prefix("not-an-ip"), None);
+    }
+
+    #[test]
+    fn test_compiled_acl() {
+        let acl = CompiledAcl::build(
+            1,
+            vec![
+                entry("net", "10.0.0.0/8", None),
+                entry("host", "10.1.2.3", Some(200)),
+                entry("old", "172.16.0.0/12", Some(50)),
+                entry("v6", "2001:db8::/48", None),
+                entry("name", "legacy.pattern", None),
+            ],
+            vec!["192.168.0.0/16".to_string(), "::1".to_string()],
+            100,
+        );
+        // 最具体的规则优先；IPv4-mapped IPv6 与 IPv4 等价
+        assert_eq!(acl.blacklist_entry("10.1.2.3", 100).unwrap().id, "host");
+        assert_eq!(acl.blacklist_entry("::ffff:10.1.2.3", 100).unwrap().id, "host");
+        assert_eq!(acl.blacklist_entry("10.9.9.9", 100).unwrap().id, "net");
+        // 临时封禁到期后回退到更宽的规则
+        assert_eq!(acl.blacklist_entry("10.1.2.3", 300).unwrap().id, "net");
+        assert!(acl.blacklist_entry("172.16.5.5", 100).is_none());
+        assert_eq!(acl.blacklist_entry("2001:db8:0:1::5", 100).unwrap().id, "v6");
+        assert!(acl.blacklist_entry("2001:db8:1::5", 100).is_none());
+        assert_eq!(acl.blacklist_entry("legacy.pattern", 100).unwrap().id, "name");
+        assert_eq!(acl.next_expiry, Some(200));
+
+        assert!(acl.is_whitelisted("192.168.10.20"));
+        assert!(acl.is_whitelisted("::ffff:192.168.10.20"));
+        assert!(acl.is_whitelisted("::1"));
+        assert!(!acl.is_whitelisted("127.0.0.1"));
+    }
+}
@@ -1,5 +1,5 @@
 use tracing::{info, warn, error};
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{filter::filter_fn, fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 use std::fs;
 use std::path::PathBuf;
 use crate::modules::account::get_data_dir;
@@ -48,7 +48,8 @@ pub fn init_logger() {
         .with_target(false)
         .with_thread_ids(false)
         .with_level(true)
-        .with_timer(LocalTimer);
+        .with_timer(LocalTimer)
+        .with_filter(filter_fn(crate::proxy::telemetry::is_not_trace_span));
         
     // 3. File output layer (disable ANSI formatting, use local timezone)
     let file_layer = fmt::Layer::new()
@@ -56,7 +57,8 @@ pub fn init_logger() {
         .with_ansi(false)
         .with_target(true)
         .with_level(true)
-        .with_timer(LocalTimer);
+        .with_timer(LocalTimer)
+        .with_filter(filter_fn(crate::proxy::telemetry::is_not_trace_span));
 
     // 4. Set filtering layer (default to INFO level to reduce log size)
     let filter_layer = EnvFilter::try_from_default_env()
@@ -65,12 +67,22 @@ pub fn init_logger() {
     // 6. Log bridge layer
     let bridge_layer = crate::modules::log_bridge::TauriLogBridgeLayer::new();
 
+    // 7. OpenTelemetry trace export (ProxyConfig.telemetry, applied at startup)
+    let telemetry_config = crate::modules::config::load_app_config()
+        .map(|config| config.proxy.telemetry)
+        .unwrap_or_default();
+    let (otel_layer, otel_error) = match crate::proxy::telemetry::init_layer(&telemetry_config) {
+        Ok(layer) => (layer, None),
+        Err(e) => (None, Some(e)),
+    };
+
     // 5. Initialize global subscriber (use try_init to avoid crash on repeated initialization)
     let _ = tracing_subscriber::registry()
         .with(filter_layer)
         .with(console_layer)
         .with(file_layer)
         .with(bridge_layer)
+        .with(otel_layer)
         .try_init();
 
     // Leak _guard to ensure its lifetime lasts until program exit
@@ -78,6 +90,11 @@ pub fn init_logger() {
     std::mem::forget(_guard);
     
     info!("Log system initialized (Console + File persistence)");
+    if let Some(e) = otel_error {
+        warn!("[Telemetry] {}", e);
+    } else if telemetry_config.enabled {
+        info!("OpenTelemetry trace export enabled: {}", telemetry_config.otlp_endpoint);
+    }
     
     // Auto-cleanup logs older than 7 days
     if let Err(e) = cleanup_old_logs(7) {
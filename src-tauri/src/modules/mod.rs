@@ -13,6 +13,7 @@ pub mod proxy_db;
 pub mod device;
 pub mod update_checker;
 pub mod scheduler;
+pub mod auto_switch;
 pub mod token_stats;
 pub mod cloudflared;
 pub mod integration;
@@ -22,7 +23,14 @@ pub mod http_api;
 pub mod cache;
 pub mod log_bridge;
 pub mod security_db;
+pub mod ip_acl;
 pub mod user_token_db;
+pub mod admin_user_db;
+pub mod audit_db;
+pub mod audit;
+pub mod response_store;
+pub mod batch_db;
+pub mod rate_limit_db;
 pub mod version;
 
 use crate::models;
@@ -3,10 +3,6 @@ use serde::{Deserialize, Serialize};
 // Google OAuth configuration
 const CLIENT_ID: &str = "1071006060591-tmhssin2h21lcre235vtolojh4g403ep.apps.googleusercontent.com";
 const CLIENT_SECRET: &str = "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";
-const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
-const USERINFO_URL: &str = "https://www.googleapis.com/oauth2/v2/userinfo";
-
-const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenResponse {
@@ -69,7 +65,8 @@ pub fn get_auth_url(redirect_uri: &str, state: &str) -> String {
         ("state", state),
     ];
     
-    let url = url::Url::parse_with_params(AUTH_URL, &params).expect("Invalid Auth URL");
+    let auth_url = crate::proxy::config::get_upstream_endpoints().oauth_auth_url;
+    let url = url::Url::parse_with_params(&auth_url, &params).expect("Invalid Auth URL");
     url.to_string()
 }
 
@@ -96,7 +93,7 @@ pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenRespon
     );
 
     let response = client
-        .post(TOKEN_URL)
+        .post(crate::proxy::config::get_upstream_endpoints().oauth_token_url)
         .header(rquest::header::USER_AGENT, crate::constants::NATIVE_OAUTH_USER_AGENT.as_str())
         .form(&params)
         .send()
@@ -167,7 +164,7 @@ pub async fn refresh_access_token(refresh_token: &str, account_id: Option<&str>)
     );
 
     let response = client
-        .post(TOKEN_URL)
+        .post(crate::proxy::config::get_upstream_endpoints().oauth_token_url)
         .header(rquest::header::USER_AGENT, crate::constants::NATIVE_OAUTH_USER_AGENT.as_str())
         .form(&params)
         .send()
@@ -203,7 +200,7 @@ pub async fn get_user_info(access_token: &str, account_id: Option<&str>) -> Resu
     };
     
     let response = client
-        .get(USERINFO_URL)
+        .get(crate::proxy::config::get_upstream_endpoints().oauth_userinfo_url)
         .bearer_auth(access_token)
         .send()
         .await
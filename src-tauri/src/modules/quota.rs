@@ -4,8 +4,6 @@ use serde_json::json;
 use crate::models::QuotaData;
 use crate::modules::config;
 
-const QUOTA_API_URL: &str = "https://cloudcode-pa.googleapis.com/v1internal:fetchAvailableModels";
-
 /// Critical retry threshold: considered near recovery when quota reaches 95%
 const NEAR_READY_THRESHOLD: i32 = 95;
 const MAX_RETRIES: u32 = 3;
@@ -106,15 +104,13 @@ async fn create_long_standard_client(account_id: Option<&str>) -> rquest::Client
     }
 }
 
-const CLOUD_CODE_BASE_URL: &str = "https://daily-cloudcode-pa.sandbox.googleapis.com";
-
 /// Fetch project ID and subscription tier
 async fn fetch_project_id(access_token: &str, email: &str, account_id: Option<&str>) -> (Option<String>, Option<String>) {
     let client = create_standard_client(account_id).await;
     let meta = json!({"metadata": {"ideType": "ANTIGRAVITY"}});
 
     let res = client
-        .post(format!("{}/v1internal:loadCodeAssist", CLOUD_CODE_BASE_URL))
+        .post(crate::proxy::config::get_upstream_endpoints().load_code_assist_url())
         .header(rquest::header::AUTHORIZATION, format!("Bearer {}", access_token))
         .header(rquest::header::CONTENT_TYPE, "application/json")
         .header(rquest::header::USER_AGENT, crate::constants::NATIVE_OAUTH_USER_AGENT.as_str())
@@ -207,12 +203,12 @@ pub async fn fetch_quota_with_cache(
         json!({}) // Empty payload fallback
     };
     
-    let url = QUOTA_API_URL;
+    let url = crate::proxy::config::get_upstream_endpoints().quota_url();
     let mut last_error: Option<AppError> = None;
 
     for attempt in 1..=MAX_RETRIES {
         match client
-            .post(url)
+            .post(&url)
             .bearer_auth(access_token)
             .header(rquest::header::USER_AGENT, crate::constants::NATIVE_OAUTH_USER_AGENT.as_str())
             .json(&json!(payload))
@@ -384,6 +380,10 @@ pub async fn warmup_model_directly(
     let resp = client
         .post(&warmup_url)
         .header("Content-Type", "application/json")
+        .header(
+            crate::proxy::security::INTERNAL_SECRET_HEADER,
+            crate::proxy::security::internal_secret(),
+        )
         .json(&body)
         .send()
         .await;
@@ -0,0 +1,176 @@
+//! Rate Limit Database Module
+//! 限流锁定与退避计数持久化 (重启/热重载后恢复 QUOTA_EXHAUSTED 等锁定状态)
+
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+
+/// 限流记录 (key 为 "account_id" 或 "account_id:model")
+#[derive(Debug, Clone)]
+pub struct LimitRow {
+    pub key: String,
+    pub reset_time_ms: i64,
+    pub retry_after_sec: i64,
+    pub detected_at_ms: i64,
+    pub reason: String,
+    pub model: Option<String>,
+}
+
+/// 连续失败计数记录
+#[derive(Debug, Clone)]
+pub struct FailureRow {
+    pub key: String,
+    pub count: u32,
+    pub last_failure_ms: i64,
+}
+
+/// 获取数据库路径
+pub fn get_db_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("rate_limits.db")
+}
+
+/// 连接数据库并确保表结构存在
+pub fn connect_db(db_path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL").map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rate_limits (
+            key TEXT PRIMARY KEY,
+            reset_time_ms INTEGER NOT NULL,
+            retry_after_sec INTEGER NOT NULL,
+            detected_at_ms INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            model TEXT
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rate_limit_failures (
+            key TEXT PRIMARY KEY,
+            count INTEGER NOT NULL,
+            last_failure_ms INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(conn)
+}
+
+/// 清理过期记录后加载全部限流记录与失败计数
+///
+/// - 限流记录：reset_time <= now 视为过期
+/// - 失败计数：最后失败时间早于 `failure_expiry_ms` 视为过期
+pub fn load_and_prune(
+    conn: &Connection,
+    now_ms: i64,
+    failure_expiry_ms: i64,
+) -> Result<(Vec<LimitRow>, Vec<FailureRow>), String> {
+    let pruned_limits = conn
+        .execute("DELETE FROM rate_limits WHERE reset_time_ms <= ?1", params![now_ms])
+        .map_err(|e| e.to_string())?;
+    let pruned_failures = conn
+        .execute(
+            "DELETE FROM rate_limit_failures WHERE last_failure_ms < ?1",
+            params![now_ms - failure_expiry_ms],
+        )
+        .map_err(|e| e.to_string())?;
+    if pruned_limits + pruned_failures > 0 {
+        tracing::debug!(
+            "[RateLimitDB] Pruned {} expired lockout(s), {} stale failure counter(s)",
+            pruned_limits,
+            pruned_failures
+        );
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT key, reset_time_ms, retry_after_sec, detected_at_ms, reason, model FROM rate_limits",
+        )
+        .map_err(|e| e.to_string())?;
+    let limits = stmt
+        .query_map([], |row| {
+            Ok(LimitRow {
+                key: row.get(0)?,
+                reset_time_ms: row.get(1)?,
+                retry_after_sec: row.get(2)?,
+                detected_at_ms: row.get(3)?,
+                reason: row.get(4)?,
+                model: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare("SELECT key, count, last_failure_ms FROM rate_limit_failures")
+        .map_err(|e| e.to_string())?;
+    let failures = stmt
+        .query_map([], |row| {
+            Ok(FailureRow {
+                key: row.get(0)?,
+                count: row.get(1)?,
+                last_failure_ms: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok((limits, failures))
+}
+
+pub fn upsert_limit(conn: &Connection, row: &LimitRow) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO rate_limits (key, reset_time_ms, retry_after_sec, detected_at_ms, reason, model)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            row.key,
+            row.reset_time_ms,
+            row.retry_after_sec,
+            row.detected_at_ms,
+            row.reason,
+            row.model
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn delete_limit(conn: &Connection, key: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM rate_limits WHERE key = ?1", params![key])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn delete_expired_limits(conn: &Connection, now_ms: i64) -> Result<usize, String> {
+    conn.execute("DELETE FROM rate_limits WHERE reset_time_ms <= ?1", params![now_ms])
+        .map_err(|e| e.to_string())
+}
+
+pub fn clear_limits(conn: &Connection) -> Result<(), String> {
+    conn.execute("DELETE FROM rate_limits", [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn upsert_failure(conn: &Connection, row: &FailureRow) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO rate_limit_failures (key, count, last_failure_ms) VALUES (?1, ?2, ?3)",
+        params![row.key, row.count, row.last_failure_ms],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn delete_failure(conn: &Connection, key: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM rate_limit_failures WHERE key = ?1", params![key])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
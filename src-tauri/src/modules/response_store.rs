@@ -0,0 +1,333 @@
+//! Responses API Store Module
+//! OpenAI Responses API 有状态会话存储 (previous_response_id / GET / DELETE / input_items)
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// 沿 previous_response_id 回溯的最大深度，防止环形引用或超长链拖垮请求
+const MAX_CHAIN_DEPTH: usize = 256;
+
+/// 存储的响应默认保留天数 (与 OpenAI 官方 30 天保持一致)
+const RETENTION_DAYS: i64 = 30;
+
+/// 两次自动清理之间的最小间隔 (秒)
+const RETENTION_CHECK_INTERVAL_SECS: i64 = 3600;
+
+/// 已存储的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub id: String,
+    pub created_at: i64,
+    pub model: String,
+    pub status: String,
+    pub previous_response_id: Option<String>,
+    pub instructions: Option<String>,
+    /// 本轮新增的输入项 (不含历史链)
+    pub input_items: Vec<Value>,
+    /// 本轮输出项 (message / function_call / reasoning)
+    pub output: Vec<Value>,
+    /// 完整的 Responses 对象，用于 GET /v1/responses/{id}
+    pub response: Value,
+    /// 创建该响应的用户令牌 (管理员 API Key 创建时为空)
+    #[serde(default)]
+    pub user_token_id: Option<String>,
+}
+
+pub fn get_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    Ok(data_dir.join("responses.db"))
+}
+
+fn connect_db() -> Result<Connection, String> {
+    let db_path = get_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL").map_err(|e| e.to_string())?;
+
+    Ok(conn)
+}
+
+/// 初始化数据库，并清理过期响应
+pub fn init_db() -> Result<(), String> {
+    let conn = connect_db()?;
+    create_tables(&conn)?;
+    purge_before(&conn, chrono::Utc::now().timestamp() - RETENTION_DAYS * 24 * 3600)?;
+    Ok(())
+}
+
+fn create_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS responses (
+            id TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL,
+            model TEXT NOT NULL,
+            status TEXT NOT NULL,
+            previous_response_id TEXT,
+            instructions TEXT,
+            input_items TEXT NOT NULL,
+            output TEXT NOT NULL,
+            response TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_responses_created_at ON responses (created_at)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Migration: 响应归属的用户令牌
+    let _ = conn.execute("ALTER TABLE responses ADD COLUMN user_token_id TEXT", []);
+
+    Ok(())
+}
+
+fn purge_before(conn: &Connection, cutoff: i64) -> Result<usize, String> {
+    conn.execute("DELETE FROM responses WHERE created_at < ?1", params![cutoff])
+        .map_err(|e| e.to_string())
+}
+
+/// 写入时顺带清理过期响应 (长时间运行的进程不依赖启动时的清理)，每小时最多一次
+fn maybe_apply_retention(conn: &Connection, now: i64) {
+    static LAST_RETENTION_RUN: AtomicI64 = AtomicI64::new(0);
+    let last = LAST_RETENTION_RUN.load(Ordering::Relaxed);
+    if now - last < RETENTION_CHECK_INTERVAL_SECS
+        || LAST_RETENTION_RUN
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+    match purge_before(conn, now - RETENTION_DAYS * 24 * 3600) {
+        Ok(deleted) if deleted > 0 => {
+            tracing::info!("[ResponseStore] Retention: removed {} responses older than {} days", deleted, RETENTION_DAYS);
+        }
+        Ok(_) => {}
+        Err(e) => tracing::error!("[ResponseStore] Retention cleanup failed: {}", e),
+    }
+}
+
+/// 保存 (或覆盖) 一条响应
+pub fn save_response(resp: &StoredResponse) -> Result<(), String> {
+    let conn = connect_db()?;
+    save_response_with_conn(&conn, resp)?;
+    maybe_apply_retention(&conn, chrono::Utc::now().timestamp());
+    Ok(())
+}
+
+fn save_response_with_conn(conn: &Connection, resp: &StoredResponse) -> Result<(), String> {
+    let input_json = serde_json::to_string(&resp.input_items).map_err(|e| e.to_string())?;
+    let output_json = serde_json::to_string(&resp.output).map_err(|e| e.to_string())?;
+    let response_json = serde_json::to_string(&resp.response).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO responses
+            (id, created_at, model, status, previous_response_id, instructions, input_items, output, response, user_token_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            resp.id,
+            resp.created_at,
+            resp.model,
+            resp.status,
+            resp.previous_response_id,
+            resp.instructions,
+            input_json,
+            output_json,
+            response_json,
+            resp.user_token_id,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// `user_token_id` 不为空时仅返回该令牌创建的响应 (其他令牌的响应视为不存在)
+fn get_response_with_conn(
+    conn: &Connection,
+    id: &str,
+    user_token_id: Option<&str>,
+) -> Result<Option<StoredResponse>, String> {
+    conn.query_row(
+        "SELECT id, created_at, model, status, previous_response_id, instructions, input_items, output, response, user_token_id
+         FROM responses WHERE id = ?1 AND (?2 IS NULL OR user_token_id = ?2)",
+        params![id, user_token_id],
+        |row| {
+            let input_json: String = row.get(6)?;
+            let output_json: String = row.get(7)?;
+            let response_json: String = row.get(8)?;
+            Ok(StoredResponse {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                model: row.get(2)?,
+                status: row.get(3)?,
+                previous_response_id: row.get(4)?,
+                instructions: row.get(5)?,
+                input_items: serde_json::from_str(&input_json).unwrap_or_default(),
+                output: serde_json::from_str(&output_json).unwrap_or_default(),
+                response: serde_json::from_str(&response_json).unwrap_or(Value::Null),
+                user_token_id: row.get(9)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// 根据 ID 获取响应
+pub fn get_response(id: &str, user_token_id: Option<&str>) -> Result<Option<StoredResponse>, String> {
+    let conn = connect_db()?;
+    get_response_with_conn(&conn, id, user_token_id)
+}
+
+/// 删除响应，返回是否存在
+pub fn delete_response(id: &str, user_token_id: Option<&str>) -> Result<bool, String> {
+    let conn = connect_db()?;
+    let affected = conn
+        .execute(
+            "DELETE FROM responses WHERE id = ?1 AND (?2 IS NULL OR user_token_id = ?2)",
+            params![id, user_token_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+/// 获取以 `id` 结尾的完整会话项 (按时间顺序：每轮的输入项后跟输出项)
+///
+/// 返回 None 表示 `id` 本身不存在 (或不属于 `user_token_id`)；链中更早的响应缺失 (已删除/过期) 时截断。
+pub fn get_conversation_items(id: &str, user_token_id: Option<&str>) -> Result<Option<Vec<Value>>, String> {
+    let conn = connect_db()?;
+    conversation_items_with_conn(&conn, id, user_token_id)
+}
+
+fn conversation_items_with_conn(
+    conn: &Connection,
+    id: &str,
+    user_token_id: Option<&str>,
+) -> Result<Option<Vec<Value>>, String> {
+    let mut chain = Vec::new();
+    let mut current = Some(id.to_string());
+    while let Some(cur_id) = current {
+        if chain.len() >= MAX_CHAIN_DEPTH {
+            tracing::warn!("[ResponseStore] Chain depth limit reached at {}", cur_id);
+            break;
+        }
+        match get_response_with_conn(conn, &cur_id, user_token_id)? {
+            Some(resp) => {
+                current = resp.previous_response_id.clone();
+                chain.push(resp);
+            }
+            None => {
+                if chain.is_empty() {
+                    return Ok(None);
+                }
+                break;
+            }
+        }
+    }
+
+    let mut items = Vec::new();
+    for resp in chain.into_iter().rev() {
+        items.extend(resp.input_items);
+        items.extend(resp.output);
+    }
+    Ok(Some(items))
+}
+
+/// 获取某个响应的输入项 (包含历史链中的全部上下文 + 本轮输入)
+pub fn get_input_items(id: &str, user_token_id: Option<&str>) -> Result<Option<Vec<Value>>, String> {
+    let resp = match get_response(id, user_token_id)? {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+
+    let mut items = match resp.previous_response_id.as_deref() {
+        Some(prev) => get_conversation_items(prev, user_token_id)?.unwrap_or_default(),
+        None => Vec::new(),
+    };
+    items.extend(resp.input_items);
+    Ok(Some(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn stored(id: &str, created_at: i64, previous: Option<&str>, user: Option<&str>) -> StoredResponse {
+        StoredResponse {
+            id: id.to_string(),
+            created_at,
+            model: "gemini-3-flash".to_string(),
+            status: "completed".to_string(),
+            previous_response_id: previous.map(|p| p.to_string()),
+            instructions: None,
+            input_items: vec![json!({"role": "user", "content": format!("in-{}", id)})],
+            output: vec![json!({"type": "message", "content": format!("out-{}", id)})],
+            response: json!({"id": id, "object": "response"}),
+            user_token_id: user.map(|u| u.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_store_and_load_scoped_by_user_token() {
+        let conn = conn();
+        save_response_with_conn(&conn, &stored("resp_a", 1_700_000_000, None, Some("tok1"))).unwrap();
+
+        let loaded = get_response_with_conn(&conn, "resp_a", None).unwrap().unwrap();
+        assert_eq!(loaded.model, "gemini-3-flash");
+        assert_eq!(loaded.input_items, vec![json!({"role": "user", "content": "in-resp_a"})]);
+        assert_eq!(loaded.response["object"], "response");
+        assert!(get_response_with_conn(&conn, "resp_a", Some("tok1")).unwrap().is_some());
+        // 其他令牌看不到该响应
+        assert!(get_response_with_conn(&conn, "resp_a", Some("tok2")).unwrap().is_none());
+        assert!(get_response_with_conn(&conn, "missing", None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_conversation_chain_order_and_truncation() {
+        let conn = conn();
+        save_response_with_conn(&conn, &stored("r1", 1, None, None)).unwrap();
+        save_response_with_conn(&conn, &stored("r2", 2, Some("r1"), None)).unwrap();
+        save_response_with_conn(&conn, &stored("r3", 3, Some("gone"), None)).unwrap();
+
+        let items = conversation_items_with_conn(&conn, "r2", None).unwrap().unwrap();
+        let contents: Vec<&str> = items.iter().filter_map(|i| i["content"].as_str()).collect();
+        assert_eq!(contents, vec!["in-r1", "out-r1", "in-r2", "out-r2"]);
+
+        // 链中更早的响应缺失时截断，起点不存在时返回 None
+        assert_eq!(conversation_items_with_conn(&conn, "r3", None).unwrap().unwrap().len(), 2);
+        assert!(conversation_items_with_conn(&conn, "gone", None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_retention_purges_expired_responses() {
+        let conn = conn();
+        // 清理时间戳是进程级的；使用未来时间，避免与其他测试中的真实写入互相影响
+        let now = chrono::Utc::now().timestamp() + 365 * 86400;
+        save_response_with_conn(&conn, &stored("old", now - (RETENTION_DAYS + 1) * 86400, None, None)).unwrap();
+        save_response_with_conn(&conn, &stored("fresh", now - 86400, None, None)).unwrap();
+
+        maybe_apply_retention(&conn, now);
+        assert!(get_response_with_conn(&conn, "old", None).unwrap().is_none());
+        assert!(get_response_with_conn(&conn, "fresh", None).unwrap().is_some());
+
+        // 间隔内不会重复清理
+        save_response_with_conn(&conn, &stored("old2", now - (RETENTION_DAYS + 1) * 86400, None, None)).unwrap();
+        maybe_apply_retention(&conn, now + 60);
+        assert!(get_response_with_conn(&conn, "old2", None).unwrap().is_some());
+    }
+}
@@ -3,10 +3,14 @@ use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::Mutex;
 use tokio::time::{self, Duration};
-use crate::modules::{config, logger, quota, account};
+use crate::modules::{config, logger, quota, account, auto_switch};
+use crate::modules::integration::SystemManager;
 use crate::models::Account;
 use std::path::PathBuf;
 
+/// [DISABLED] 自动预热 (Automatic warmup disabled as per user request)；调度器只执行自动切换
+const AUTO_WARMUP_ENABLED: bool = false;
+
 // Warmup history: key = "email:model_name:100", value = warmup timestamp
 static WARMUP_HISTORY: Lazy<Mutex<HashMap<String, i64>>> = Lazy::new(|| Mutex::new(load_warmup_history()));
 
@@ -54,6 +58,12 @@ pub fn check_cooldown(key: &str, cooldown_seconds: i64) -> bool {
 pub fn start_scheduler(app_handle: Option<tauri::AppHandle>, proxy_state: crate::commands::proxy::ProxyServiceState) {
     tauri::async_runtime::spawn(async move {
         logger::log_info("Smart Warmup Scheduler started. Monitoring quota at 100%...");
+
+        // [NEW] 自动切换账号所用的系统集成 (通知 / IDE 注入)
+        let integration = match app_handle.as_ref() {
+            Some(handle) => SystemManager::Desktop(handle.clone()),
+            None => SystemManager::Headless,
+        };
         
         // Scan every 10 minutes
         let mut interval = time::interval(Duration::from_secs(600));
@@ -66,7 +76,14 @@ pub fn start_scheduler(app_handle: Option<tauri::AppHandle>, proxy_state: crate:
                 continue;
             };
 
-            if !app_config.auto_refresh {
+            // [NEW] 配额阈值 / 禁用状态触发的自动切换 (auto_switch.enabled 关闭时直接返回)
+            crate::modules::audit::scope(
+                crate::modules::audit::AuditActor::scheduler("auto_switch"),
+                auto_switch::check_and_switch(&app_config.auto_switch, &integration),
+            )
+            .await;
+
+            if !AUTO_WARMUP_ENABLED || !app_config.auto_refresh {
                 continue;
             }
             
@@ -399,6 +399,7 @@ pub fn add_to_blacklist(
         params![id, ip_pattern, reason, now, expires_at, created_by],
     )
     .map_err(|e| e.to_string())?;
+    crate::modules::ip_acl::invalidate();
 
     Ok(IpBlacklistEntry {
         id,
@@ -417,6 +418,7 @@ pub fn remove_from_blacklist(id: &str) -> Result<(), String> {
 
     conn.execute("DELETE FROM ip_blacklist WHERE id = ?1", [id])
         .map_err(|e| e.to_string())?;
+    crate::modules::ip_acl::invalidate();
 
     Ok(())
 }
@@ -460,97 +462,35 @@ pub fn is_ip_in_blacklist(ip: &str) -> Result<bool, String> {
 }
 
 /// 获取 IP 对应的黑名单条目（如果存在）
+///
+/// 与 ip_filter 中间件使用同一份内存前缀树 (最具体的规则优先，命中计数批量写回)
 pub fn get_blacklist_entry_for_ip(ip: &str) -> Result<Option<IpBlacklistEntry>, String> {
-    let conn = connect_db()?;
-    let now = chrono::Utc::now().timestamp();
+    crate::modules::ip_acl::blacklist_entry(ip)
+}
 
-    // 清理过期的黑名单条目
-    let _ = conn.execute(
+/// 删除已过期的黑名单条目
+pub fn purge_expired_blacklist(now: i64) -> Result<usize, String> {
+    let conn = connect_db()?;
+    conn.execute(
         "DELETE FROM ip_blacklist WHERE expires_at IS NOT NULL AND expires_at < ?1",
         [now],
-    );
-
-    // 精确匹配
-    let entry_result = conn.query_row(
-        "SELECT id, ip_pattern, reason, created_at, expires_at, created_by, hit_count
-         FROM ip_blacklist WHERE ip_pattern = ?1",
-        [ip],
-        |row| {
-            Ok(IpBlacklistEntry {
-                id: row.get(0)?,
-                ip_pattern: row.get(1)?,
-                reason: row.get(2)?,
-                created_at: row.get(3)?,
-                expires_at: row.get(4)?,
-                created_by: row.get(5)?,
-                hit_count: row.get(6)?,
-            })
-        },
-    );
-
-    if let Ok(entry) = entry_result {
-        // 增加命中计数
-        let _ = conn.execute(
-            "UPDATE ip_blacklist SET hit_count = hit_count + 1 WHERE ip_pattern = ?1",
-            [ip],
-        );
-        return Ok(Some(entry));
-    }
-
-    // CIDR 匹配
-    let entries = get_blacklist()?;
-    for entry in entries {
-        if entry.ip_pattern.contains('/') {
-            if cidr_match(ip, &entry.ip_pattern) {
-                // 增加命中计数
-                let _ = conn.execute(
-                    "UPDATE ip_blacklist SET hit_count = hit_count + 1 WHERE id = ?1",
-                    [&entry.id],
-                );
-                return Ok(Some(entry));
-            }
-        }
-    }
-
-    Ok(None)
+    )
+    .map_err(|e| e.to_string())
 }
 
-/// 简单的 CIDR 匹配
-fn cidr_match(ip: &str, cidr: &str) -> bool {
-    let parts: Vec<&str> = cidr.split('/').collect();
-    if parts.len() != 2 {
-        return false;
-    }
-
-    let network = parts[0];
-    let prefix_len: u8 = match parts[1].parse() {
-        Ok(p) => p,
-        Err(_) => return false,
-    };
-
-    let ip_parts: Vec<u8> = ip
-        .split('.')
-        .filter_map(|s| s.parse().ok())
-        .collect();
-    let net_parts: Vec<u8> = network
-        .split('.')
-        .filter_map(|s| s.parse().ok())
-        .collect();
-
-    if ip_parts.len() != 4 || net_parts.len() != 4 {
-        return false;
+/// 批量累加黑名单命中计数 (entry id -> 增量)
+pub fn add_blacklist_hits(hits: &std::collections::HashMap<String, i64>) -> Result<(), String> {
+    let mut conn = connect_db()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare("UPDATE ip_blacklist SET hit_count = hit_count + ?1 WHERE id = ?2")
+            .map_err(|e| e.to_string())?;
+        for (id, count) in hits {
+            stmt.execute(params![count, id]).map_err(|e| e.to_string())?;
+        }
     }
-
-    let ip_u32 = u32::from_be_bytes([ip_parts[0], ip_parts[1], ip_parts[2], ip_parts[3]]);
-    let net_u32 = u32::from_be_bytes([net_parts[0], net_parts[1], net_parts[2], net_parts[3]]);
-
-    let mask = if prefix_len == 0 {
-        0
-    } else {
-        !0u32 << (32 - prefix_len)
-    };
-
-    (ip_u32 & mask) == (net_u32 & mask)
+    tx.commit().map_err(|e| e.to_string())
 }
 
 // ============================================================================
@@ -570,6 +510,7 @@ pub fn add_to_whitelist(ip_pattern: &str, description: Option<&str>) -> Result<I
         params![id, ip_pattern, description, now],
     )
     .map_err(|e| e.to_string())?;
+    crate::modules::ip_acl::invalidate();
 
     Ok(IpWhitelistEntry {
         id,
@@ -585,6 +526,7 @@ pub fn remove_from_whitelist(id: &str) -> Result<(), String> {
 
     conn.execute("DELETE FROM ip_whitelist WHERE id = ?1", [id])
         .map_err(|e| e.to_string())?;
+    crate::modules::ip_acl::invalidate();
 
     Ok(())
 }
@@ -619,34 +561,9 @@ pub fn get_whitelist() -> Result<Vec<IpWhitelistEntry>, String> {
     Ok(entries)
 }
 
-/// 检查 IP 是否在白名单中
+/// 检查 IP 是否在白名单中 (与 ip_filter 中间件使用同一份内存前缀树)
 pub fn is_ip_in_whitelist(ip: &str) -> Result<bool, String> {
-    let conn = connect_db()?;
-
-    // 精确匹配
-    let count: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM ip_whitelist WHERE ip_pattern = ?1",
-            [ip],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
-
-    if count > 0 {
-        return Ok(true);
-    }
-
-    // CIDR 匹配
-    let entries = get_whitelist()?;
-    for entry in entries {
-        if entry.ip_pattern.contains('/') {
-            if cidr_match(ip, &entry.ip_pattern) {
-                return Ok(true);
-            }
-        }
-    }
-
-    Ok(false)
+    crate::modules::ip_acl::is_whitelisted(ip)
 }
 
 /// 清空所有 IP 访问日志
@@ -6,9 +6,12 @@
 
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::path::PathBuf;
 use uuid::Uuid;
-use chrono::{Utc, Local, Timelike, FixedOffset};
+use chrono::{Datelike, Duration, FixedOffset, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
 
 /// 用户令牌结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,13 +26,312 @@ pub struct UserToken {
     pub max_ips: i32,              // 0 = unlimited
     pub curfew_start: Option<String>, // "HH:MM" 宵禁开始时间
     pub curfew_end: Option<String>,   // "HH:MM" 宵禁结束时间
+    // [NEW] 宵禁/配额周期所用时区：IANA 名称 (如 "Europe/Berlin") 或固定偏移 (如 "+08:00")
+    // None = 默认 UTC+8 (兼容旧版本行为)
+    #[serde(default)]
+    pub curfew_timezone: Option<String>,
+    // [NEW] 宵禁生效的星期 (ISO: 1=周一 ... 7=周日)，空 = 每天
+    #[serde(default)]
+    pub curfew_days: Vec<u8>,
     pub created_at: i64,
     pub updated_at: i64,
     pub last_used_at: Option<i64>,
     pub total_requests: i64,
     pub total_tokens_used: i64,
+    // [NEW] 用量限制 (0 / 空 = 不限制)
+    #[serde(default)]
+    pub daily_token_limit: i64,        // 每日 input+output token 预算
+    #[serde(default)]
+    pub monthly_token_limit: i64,      // 每月 input+output token 预算
+    #[serde(default)]
+    pub rpm_limit: i32,                // 每分钟请求数上限
+    #[serde(default)]
+    pub allowed_models: Vec<String>,   // 允许的模型 glob 列表 (如 "gemini-*")
 }
 
+/// 令牌用量限制 (创建令牌时使用)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenLimits {
+    #[serde(default)]
+    pub daily_token_limit: i64,
+    #[serde(default)]
+    pub monthly_token_limit: i64,
+    #[serde(default)]
+    pub rpm_limit: i32,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+}
+
+/// 令牌用量限制的部分更新 (None = 不修改)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenLimitsPatch {
+    pub daily_token_limit: Option<i64>,
+    pub monthly_token_limit: Option<i64>,
+    pub rpm_limit: Option<i32>,
+    pub allowed_models: Option<Vec<String>>,
+}
+
+/// 令牌用量摘要 (用于 /api/user-tokens/summary)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenUsageSummary {
+    pub token_id: String,
+    pub username: String,
+    pub daily_token_limit: i64,
+    pub daily_used: i64,
+    pub daily_remaining: Option<i64>,
+    pub monthly_token_limit: i64,
+    pub monthly_used: i64,
+    pub monthly_remaining: Option<i64>,
+    pub rpm_limit: i32,
+    pub allowed_models: Vec<String>,
+}
+
+/// 令牌限制校验失败原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenLimitViolation {
+    /// 请求的模型不在白名单内 (403)
+    ModelNotAllowed(String),
+    /// 令牌配置了模型白名单，但无法确定请求的模型 (403)
+    ModelUnresolved,
+    /// 每日 token 预算耗尽 (429)
+    DailyBudgetExceeded { used: i64, limit: i64 },
+    /// 每月 token 预算耗尽 (429)
+    MonthlyBudgetExceeded { used: i64, limit: i64 },
+    /// 超过每分钟请求数 (429)
+    RpmExceeded { limit: i32, retry_after_secs: u64 },
+}
+
+impl TokenLimitViolation {
+    /// 是否为权限类拒绝 (403)，否则为限流类 (429)
+    pub fn is_forbidden(&self) -> bool {
+        matches!(
+            self,
+            TokenLimitViolation::ModelNotAllowed(_) | TokenLimitViolation::ModelUnresolved
+        )
+    }
+
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            TokenLimitViolation::RpmExceeded { retry_after_secs, .. } => Some(*retry_after_secs),
+            _ => None,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            TokenLimitViolation::ModelNotAllowed(model) => format!(
+                "Model '{}' is not allowed for this token. Please contact the administrator.",
+                model
+            ),
+            TokenLimitViolation::ModelUnresolved => {
+                "This token is restricted to specific models, but the requested model could not be determined. Please specify the model explicitly.".to_string()
+            }
+            TokenLimitViolation::DailyBudgetExceeded { used, limit } => format!(
+                "Daily token budget exhausted ({}/{}). The budget resets at midnight.",
+                used, limit
+            ),
+            TokenLimitViolation::MonthlyBudgetExceeded { used, limit } => format!(
+                "Monthly token budget exhausted ({}/{}). The budget resets at the start of next month.",
+                used, limit
+            ),
+            TokenLimitViolation::RpmExceeded { limit, retry_after_secs } => format!(
+                "Rate limit reached ({} requests per minute). Please retry after {} seconds.",
+                limit, retry_after_secs
+            ),
+        }
+    }
+}
+
+/// 未配置时区时的默认偏移 (UTC+8，兼容旧版本)
+const DEFAULT_TIMEZONE_OFFSET_SECS: i32 = 8 * 3600;
+
+/// 令牌时区 (宵禁与配额周期使用)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenTimezone {
+    Fixed(FixedOffset),
+    Named(chrono_tz::Tz),
+}
+
+impl Default for TokenTimezone {
+    fn default() -> Self {
+        TokenTimezone::Fixed(FixedOffset::east_opt(DEFAULT_TIMEZONE_OFFSET_SECS).unwrap())
+    }
+}
+
+impl TokenTimezone {
+    /// 解析时区字符串
+    ///
+    /// 支持 IANA 名称 ("America/New_York")、"UTC"/"Z"，
+    /// 以及固定偏移 ("+08:00" / "-0530" / "UTC+8" / "GMT-05:30")
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let s = raw.trim();
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+        if s.eq_ignore_ascii_case("utc") || s.eq_ignore_ascii_case("gmt") || s == "Z" {
+            return Ok(TokenTimezone::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+
+        let offset_part = ["UTC", "GMT", "utc", "gmt"]
+            .iter()
+            .find_map(|p| s.strip_prefix(p))
+            .unwrap_or(s);
+        if offset_part.starts_with('+') || offset_part.starts_with('-') {
+            return parse_fixed_offset(offset_part)
+                .map(TokenTimezone::Fixed)
+                .ok_or_else(|| format!("Invalid timezone offset: {}", raw));
+        }
+
+        s.parse::<chrono_tz::Tz>()
+            .map(TokenTimezone::Named)
+            .map_err(|_| format!("Unknown timezone: {}", raw))
+    }
+
+    /// 从令牌配置解析，非法值回退到默认时区
+    pub fn from_token(token: &UserToken) -> Self {
+        match token.curfew_timezone.as_deref() {
+            Some(tz) => Self::parse(tz).unwrap_or_else(|e| {
+                tracing::warn!("UserToken {} has {}, falling back to UTC+8", token.username, e);
+                Self::default()
+            }),
+            None => Self::default(),
+        }
+    }
+
+    /// 当前本地时间
+    pub fn now_local(&self) -> NaiveDateTime {
+        let now = Utc::now();
+        match self {
+            TokenTimezone::Fixed(offset) => now.with_timezone(offset).naive_local(),
+            TokenTimezone::Named(tz) => now.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// 本地时间 → Unix 秒 (夏令时跳变导致的不存在时刻返回 None，重叠时刻取较早者)
+    pub fn local_to_timestamp(&self, local: &NaiveDateTime) -> Option<i64> {
+        match self {
+            TokenTimezone::Fixed(offset) => offset.from_local_datetime(local).earliest().map(|dt| dt.timestamp()),
+            TokenTimezone::Named(tz) => tz.from_local_datetime(local).earliest().map(|dt| dt.timestamp()),
+        }
+    }
+
+    /// 用于错误提示的时区名称
+    pub fn label(&self) -> String {
+        match self {
+            TokenTimezone::Fixed(offset) => format!("UTC{}", offset),
+            TokenTimezone::Named(tz) => tz.name().to_string(),
+        }
+    }
+}
+
+/// 解析 "+08:00" / "-0530" / "+8" 形式的偏移
+fn parse_fixed_offset(s: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = if let Some((h, m)) = rest.split_once(':') {
+        (h.parse::<i32>().ok()?, m.parse::<i32>().ok()?)
+    } else if rest.len() == 4 {
+        (rest[..2].parse::<i32>().ok()?, rest[2..].parse::<i32>().ok()?)
+    } else {
+        (rest.parse::<i32>().ok()?, 0)
+    };
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// 校验宵禁相关配置 (创建/更新令牌时调用)
+fn validate_curfew_settings(
+    start: Option<&str>,
+    end: Option<&str>,
+    timezone: Option<&str>,
+    days: Option<&[u8]>,
+) -> Result<(), String> {
+    for t in [start, end].into_iter().flatten().filter(|t| !t.is_empty()) {
+        NaiveTime::parse_from_str(t, "%H:%M")
+            .map_err(|_| format!("Invalid curfew time '{}', expected HH:MM", t))?;
+    }
+    if let Some(tz) = timezone {
+        TokenTimezone::parse(tz)?;
+    }
+    if let Some(days) = days {
+        if let Some(d) = days.iter().find(|d| !(1..=7).contains(*d)) {
+            return Err(format!("Invalid curfew weekday {}, expected 1 (Monday) to 7 (Sunday)", d));
+        }
+    }
+    Ok(())
+}
+
+/// 宵禁时间统一存储为补零的 HH:MM (如 "9:00" → "09:00")；空字符串表示未设置
+fn normalize_curfew_time(time: String) -> String {
+    match NaiveTime::parse_from_str(time.trim(), "%H:%M") {
+        Ok(t) => t.format("%H:%M").to_string(),
+        Err(_) => time,
+    }
+}
+
+fn normalize_curfew_days(mut days: Vec<u8>) -> Vec<u8> {
+    days.sort_unstable();
+    days.dedup();
+    days
+}
+
+fn serialize_curfew_days(days: &[u8]) -> Option<String> {
+    if days.is_empty() {
+        None
+    } else {
+        serde_json::to_string(days).ok()
+    }
+}
+
+fn parse_curfew_days(raw: Option<String>) -> Vec<u8> {
+    raw.and_then(|s| serde_json::from_str::<Vec<u8>>(&s).ok())
+        .unwrap_or_default()
+}
+
+/// 判断本地时间是否处于宵禁窗口
+///
+/// 跨午夜窗口 (如 23:00-06:00) 的凌晨部分归属前一天，
+/// 因此 `days = [5]` (周五) 时，周六 02:00 仍处于宵禁。
+/// 按时间值比较 (而非字符串)，兼容旧版本保存的未补零时间 (如 "9:00")。
+fn is_in_curfew(start: &str, end: &str, days: &[u8], local: &NaiveDateTime) -> bool {
+    let (Ok(start), Ok(end)) = (
+        NaiveTime::parse_from_str(start.trim(), "%H:%M"),
+        NaiveTime::parse_from_str(end.trim(), "%H:%M"),
+    ) else {
+        return false;
+    };
+    let current = local.time();
+
+    // 跨午夜处理: start > end (e.g. 23:00 to 06:00)
+    // 正常: start < end (e.g. 09:00 to 18:00)
+    let window_date = if start > end {
+        if current >= start {
+            Some(local.date())
+        } else if current < end {
+            Some(local.date() - Duration::days(1))
+        } else {
+            None
+        }
+    } else if current >= start && current < end {
+        Some(local.date())
+    } else {
+        None
+    };
+
+    match window_date {
+        Some(date) => days.is_empty() || days.contains(&(date.weekday().number_from_monday() as u8)),
+        None => false,
+    }
+}
+
+/// 每个令牌最近 60 秒内的请求时间戳 (毫秒)，用于 RPM 滑动窗口
+static RPM_WINDOWS: Lazy<DashMap<String, VecDeque<i64>>> = Lazy::new(DashMap::new);
+
 /// 令牌 IP 绑定结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenIpBinding {
@@ -91,7 +393,13 @@ pub fn init_db() -> Result<(), String> {
             total_requests INTEGER NOT NULL DEFAULT 0,
             total_tokens_used INTEGER NOT NULL DEFAULT 0,
             curfew_start TEXT,
-            curfew_end TEXT
+            curfew_end TEXT,
+            curfew_timezone TEXT,
+            curfew_days TEXT,
+            daily_token_limit INTEGER NOT NULL DEFAULT 0,
+            monthly_token_limit INTEGER NOT NULL DEFAULT 0,
+            rpm_limit INTEGER NOT NULL DEFAULT 0,
+            allowed_models TEXT
         )",
         [],
     ).map_err(|e| format!("Failed to create user_tokens table: {}", e))?;
@@ -105,6 +413,13 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN last_used_at INTEGER", []);
     let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN curfew_start TEXT", []);
     let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN curfew_end TEXT", []);
+    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN daily_token_limit INTEGER DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN monthly_token_limit INTEGER DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN rpm_limit INTEGER DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN allowed_models TEXT", []);
+    // 旧令牌的 curfew_timezone 保持 NULL，即沿用 UTC+8
+    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN curfew_timezone TEXT", []);
+    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN curfew_days TEXT", []);
 
     // 创建 token_ip_bindings 表
     conn.execute(
@@ -149,6 +464,9 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("UPDATE user_tokens SET total_requests = 0 WHERE total_requests IS NULL", []);
     let _ = conn.execute("UPDATE user_tokens SET total_tokens_used = 0 WHERE total_tokens_used IS NULL", []);
     let _ = conn.execute("UPDATE user_tokens SET enabled = 1 WHERE enabled IS NULL", []);
+    let _ = conn.execute("UPDATE user_tokens SET daily_token_limit = 0 WHERE daily_token_limit IS NULL", []);
+    let _ = conn.execute("UPDATE user_tokens SET monthly_token_limit = 0 WHERE monthly_token_limit IS NULL", []);
+    let _ = conn.execute("UPDATE user_tokens SET rpm_limit = 0 WHERE rpm_limit IS NULL", []);
 
     Ok(())
 }
@@ -161,8 +479,21 @@ pub fn create_token(
     max_ips: i32,
     curfew_start: Option<String>,
     curfew_end: Option<String>,
-    custom_expires_at: Option<i64>  // 自定义过期时间戳 (秒)
+    curfew_timezone: Option<String>,
+    curfew_days: Vec<u8>,
+    custom_expires_at: Option<i64>,  // 自定义过期时间戳 (秒)
+    limits: TokenLimits,
 ) -> Result<UserToken, String> {
+    let curfew_timezone = curfew_timezone.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    validate_curfew_settings(
+        curfew_start.as_deref(),
+        curfew_end.as_deref(),
+        curfew_timezone.as_deref(),
+        Some(&curfew_days),
+    )?;
+    let curfew_start = curfew_start.map(normalize_curfew_time);
+    let curfew_end = curfew_end.map(normalize_curfew_time);
+
     let conn = connect_db()?;
     let id = Uuid::new_v4().to_string();
     let token = format!("sk-{}", Uuid::new_v4().to_string().replace("-", ""));
@@ -187,19 +518,26 @@ pub fn create_token(
         max_ips,
         curfew_start: curfew_start.clone(),
         curfew_end: curfew_end.clone(),
+        curfew_timezone,
+        curfew_days: normalize_curfew_days(curfew_days),
         created_at: now,
         updated_at: now,
         last_used_at: None,
         total_requests: 0,
         total_tokens_used: 0,
+        daily_token_limit: limits.daily_token_limit.max(0),
+        monthly_token_limit: limits.monthly_token_limit.max(0),
+        rpm_limit: limits.rpm_limit.max(0),
+        allowed_models: normalize_allowed_models(limits.allowed_models),
     };
 
     conn.execute(
         "INSERT INTO user_tokens (
             id, token, username, description, enabled, expires_type, expires_at, max_ips,
-            curfew_start, curfew_end,
-            created_at, updated_at, total_requests, total_tokens_used
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            curfew_start, curfew_end, curfew_timezone, curfew_days,
+            created_at, updated_at, total_requests, total_tokens_used,
+            daily_token_limit, monthly_token_limit, rpm_limit, allowed_models
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
         params![
             user_token.id,
             user_token.token,
@@ -211,10 +549,16 @@ pub fn create_token(
             user_token.max_ips,
             user_token.curfew_start,
             user_token.curfew_end,
+            user_token.curfew_timezone,
+            serialize_curfew_days(&user_token.curfew_days),
             user_token.created_at,
             user_token.updated_at,
             user_token.total_requests,
             user_token.total_tokens_used,
+            user_token.daily_token_limit,
+            user_token.monthly_token_limit,
+            user_token.rpm_limit,
+            serialize_allowed_models(&user_token.allowed_models),
         ],
     ).map_err(|e| format!("Failed to insert user token: {}", e))?;
 
@@ -239,11 +583,17 @@ pub fn list_tokens() -> Result<Vec<UserToken>, String> {
             max_ips: row.get("max_ips").unwrap_or(0),
             curfew_start: row.get("curfew_start").unwrap_or(None),
             curfew_end: row.get("curfew_end").unwrap_or(None),
+            curfew_timezone: row.get("curfew_timezone").unwrap_or(None),
+            curfew_days: parse_curfew_days(row.get("curfew_days").unwrap_or(None)),
             created_at: row.get("created_at")?,
             updated_at: row.get("updated_at")?,
             last_used_at: row.get("last_used_at").unwrap_or(None),
             total_requests: row.get("total_requests").unwrap_or(0),
             total_tokens_used: row.get("total_tokens_used").unwrap_or(0),
+            daily_token_limit: row.get("daily_token_limit").unwrap_or(0),
+            monthly_token_limit: row.get("monthly_token_limit").unwrap_or(0),
+            rpm_limit: row.get("rpm_limit").unwrap_or(0),
+            allowed_models: parse_allowed_models(row.get("allowed_models").unwrap_or(None)),
         })
     }).map_err(|e| format!("Failed to query tokens: {}", e))?;
 
@@ -273,11 +623,17 @@ pub fn get_token_by_id(id: &str) -> Result<Option<UserToken>, String> {
             max_ips: row.get("max_ips")?,
             curfew_start: row.get("curfew_start").unwrap_or(None),
             curfew_end: row.get("curfew_end").unwrap_or(None),
+            curfew_timezone: row.get("curfew_timezone").unwrap_or(None),
+            curfew_days: parse_curfew_days(row.get("curfew_days").unwrap_or(None)),
             created_at: row.get("created_at")?,
             updated_at: row.get("updated_at")?,
             last_used_at: row.get("last_used_at")?,
             total_requests: row.get("total_requests")?,
             total_tokens_used: row.get("total_tokens_used")?,
+            daily_token_limit: row.get("daily_token_limit").unwrap_or(0),
+            monthly_token_limit: row.get("monthly_token_limit").unwrap_or(0),
+            rpm_limit: row.get("rpm_limit").unwrap_or(0),
+            allowed_models: parse_allowed_models(row.get("allowed_models").unwrap_or(None)),
         })
     }).optional().map_err(|e| format!("Failed to query token: {}", e))?;
     
@@ -302,11 +658,17 @@ pub fn get_token_by_value(token: &str) -> Result<Option<UserToken>, String> {
             max_ips: row.get("max_ips")?,
             curfew_start: row.get("curfew_start").unwrap_or(None),
             curfew_end: row.get("curfew_end").unwrap_or(None),
+            curfew_timezone: row.get("curfew_timezone").unwrap_or(None),
+            curfew_days: parse_curfew_days(row.get("curfew_days").unwrap_or(None)),
             created_at: row.get("created_at")?,
             updated_at: row.get("updated_at")?,
             last_used_at: row.get("last_used_at")?,
             total_requests: row.get("total_requests")?,
             total_tokens_used: row.get("total_tokens_used")?,
+            daily_token_limit: row.get("daily_token_limit").unwrap_or(0),
+            monthly_token_limit: row.get("monthly_token_limit").unwrap_or(0),
+            rpm_limit: row.get("rpm_limit").unwrap_or(0),
+            allowed_models: parse_allowed_models(row.get("allowed_models").unwrap_or(None)),
         })
     }).optional().map_err(|e| format!("Failed to query token: {}", e))?;
     
@@ -321,8 +683,22 @@ pub fn update_token(
     enabled: Option<bool>,
     max_ips: Option<i32>,
     curfew_start: Option<Option<String>>,
-    curfew_end: Option<Option<String>>
+    curfew_end: Option<Option<String>>,
+    curfew_timezone: Option<Option<String>>,
+    curfew_days: Option<Vec<u8>>,
+    limits: TokenLimitsPatch,
 ) -> Result<(), String> {
+    let curfew_timezone =
+        curfew_timezone.map(|tz| tz.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()));
+    validate_curfew_settings(
+        curfew_start.as_ref().and_then(|s| s.as_deref()),
+        curfew_end.as_ref().and_then(|s| s.as_deref()),
+        curfew_timezone.as_ref().and_then(|s| s.as_deref()),
+        curfew_days.as_deref(),
+    )?;
+    let curfew_start = curfew_start.map(|t| t.map(normalize_curfew_time));
+    let curfew_end = curfew_end.map(|t| t.map(normalize_curfew_time));
+
     let conn = connect_db()?;
     let now = Utc::now().timestamp();
 
@@ -366,6 +742,42 @@ pub fn update_token(
         param_idx += 1;
     }
 
+    if let Some(tz) = curfew_timezone {
+        query.push_str(&format!(", curfew_timezone = ?{}", param_idx));
+        params_vec.push(Box::new(tz));
+        param_idx += 1;
+    }
+
+    if let Some(days) = curfew_days {
+        query.push_str(&format!(", curfew_days = ?{}", param_idx));
+        params_vec.push(Box::new(serialize_curfew_days(&normalize_curfew_days(days))));
+        param_idx += 1;
+    }
+
+    if let Some(daily) = limits.daily_token_limit {
+        query.push_str(&format!(", daily_token_limit = ?{}", param_idx));
+        params_vec.push(Box::new(daily.max(0)));
+        param_idx += 1;
+    }
+
+    if let Some(monthly) = limits.monthly_token_limit {
+        query.push_str(&format!(", monthly_token_limit = ?{}", param_idx));
+        params_vec.push(Box::new(monthly.max(0)));
+        param_idx += 1;
+    }
+
+    if let Some(rpm) = limits.rpm_limit {
+        query.push_str(&format!(", rpm_limit = ?{}", param_idx));
+        params_vec.push(Box::new(rpm.max(0)));
+        param_idx += 1;
+    }
+
+    if let Some(models) = limits.allowed_models {
+        query.push_str(&format!(", allowed_models = ?{}", param_idx));
+        params_vec.push(Box::new(serialize_allowed_models(&normalize_allowed_models(models))));
+        param_idx += 1;
+    }
+
     query.push_str(&format!(" WHERE id = ?{}", param_idx));
     params_vec.push(Box::new(id.to_string()));
 
@@ -505,10 +917,8 @@ pub fn validate_token(token_str: &str, ip: &str) -> Result<(bool, Option<String>
 
     if let Some(token) = token_opt {
         // 1. 检查过期时间
-        if let Some(expires_at) = token.expires_at {
-            if expires_at < Utc::now().timestamp() {
-                return Ok((false, Some("Your token has expired. Please contact the administrator to renew it.".to_string())));
-            }
+        if let Some(reason) = check_token_expiry(&token) {
+            return Ok((false, Some(reason)));
         }
 
         // 2. 检查 IP 限制
@@ -537,27 +947,8 @@ pub fn validate_token(token_str: &str, ip: &str) -> Result<(bool, Option<String>
         }
 
         // 3. 检查宵禁时间 (Curfew)
-        // 逻辑：如果当前北京时间在 start 和 end 之间，则拒绝
-        // 格式：HH:MM
-        // 使用固定 UTC+8 (北京时间)，不依赖服务器本地时区
-        if let (Some(start_str), Some(end_str)) = (&token.curfew_start, &token.curfew_end) {
-            if !start_str.is_empty() && !end_str.is_empty() {
-                let beijing_offset = FixedOffset::east_opt(8 * 3600).unwrap();
-                let now_beijing = Utc::now().with_timezone(&beijing_offset);
-                let current_time_str = format!("{:02}:{:02}", now_beijing.hour(), now_beijing.minute());
-
-                // 跨午夜处理: start > end (e.g. 23:00 to 06:00)
-                // 正常: start < end (e.g. 09:00 to 18:00)
-                let is_curfew = if start_str > end_str {
-                    current_time_str >= *start_str || current_time_str < *end_str
-                } else {
-                    current_time_str >= *start_str && current_time_str < *end_str
-                };
-
-                if is_curfew {
-                     return Ok((false, Some(format!("Service is not available between {} and {} Beijing Time (Curfew enabled). Current Beijing time: {}", start_str, end_str, current_time_str))));
-                }
-            }
+        if let Some(reason) = check_token_curfew(&token) {
+            return Ok((false, Some(reason)));
         }
 
         // 一切正常，Token 有效
@@ -567,6 +958,218 @@ pub fn validate_token(token_str: &str, ip: &str) -> Result<(bool, Option<String>
     }
 }
 
+fn check_token_expiry(token: &UserToken) -> Option<String> {
+    match token.expires_at {
+        Some(expires_at) if expires_at < Utc::now().timestamp() => Some(
+            "Your token has expired. Please contact the administrator to renew it.".to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// 逻辑：如果令牌时区的当前时间在 start 和 end 之间 (且星期命中 curfew_days)，则拒绝
+/// 格式：HH:MM；时区未配置时默认 UTC+8，不依赖服务器本地时区
+fn check_token_curfew(token: &UserToken) -> Option<String> {
+    let (Some(start_str), Some(end_str)) = (&token.curfew_start, &token.curfew_end) else {
+        return None;
+    };
+    if start_str.is_empty() || end_str.is_empty() {
+        return None;
+    }
+    let tz = TokenTimezone::from_token(token);
+    let now_local = tz.now_local();
+    if !is_in_curfew(start_str, end_str, &token.curfew_days, &now_local) {
+        return None;
+    }
+    Some(format!(
+        "Service is not available between {} and {} ({}) (Curfew enabled). Current time: {}",
+        start_str,
+        end_str,
+        tz.label(),
+        now_local.format("%a %H:%M")
+    ))
+}
+
+/// 不经过 HTTP 鉴权的请求 (如批处理后台任务) 使用：令牌已禁用、过期或处于宵禁时返回拒绝原因
+///
+/// IP 限制只对在线请求有意义，这里不检查
+pub fn check_token_availability(token: &UserToken) -> Option<String> {
+    if !token.enabled {
+        return Some("Your token has been disabled. Please contact the administrator.".to_string());
+    }
+    check_token_expiry(token).or_else(|| check_token_curfew(token))
+}
+
+/// 清洗模型白名单：去空白、去空项、去重
+fn normalize_allowed_models(models: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for m in models {
+        let m = m.trim().to_string();
+        if !m.is_empty() && !out.contains(&m) {
+            out.push(m);
+        }
+    }
+    out
+}
+
+fn serialize_allowed_models(models: &[String]) -> Option<String> {
+    if models.is_empty() {
+        None
+    } else {
+        serde_json::to_string(models).ok()
+    }
+}
+
+fn parse_allowed_models(raw: Option<String>) -> Vec<String> {
+    raw.and_then(|s| serde_json::from_str::<Vec<String>>(&s).ok())
+        .unwrap_or_default()
+}
+
+/// 检查模型是否命中白名单 (glob，忽略大小写；空白名单表示全部允许)
+pub fn is_model_allowed(allowed_models: &[String], model: &str) -> bool {
+    if allowed_models.is_empty() {
+        return true;
+    }
+    let model_lower = model.to_lowercase();
+    allowed_models.iter().any(|pattern| {
+        crate::proxy::common::model_mapping::wildcard_match(&pattern.to_lowercase(), &model_lower)
+    })
+}
+
+/// 计算配额统计周期的起点 (当日 0 点 / 当月 1 日 0 点)，返回 Unix 秒
+/// 周期边界使用令牌时区，与宵禁时间保持一致
+fn quota_period_starts(tz: &TokenTimezone) -> (i64, i64) {
+    let now = tz.now_local();
+    let day_start = tz
+        .local_to_timestamp(&now.date().and_time(NaiveTime::MIN))
+        .unwrap_or_else(|| Utc::now().timestamp() - now.num_seconds_from_midnight() as i64);
+    let month_start = now
+        .date()
+        .with_day(1)
+        .and_then(|d| tz.local_to_timestamp(&d.and_time(NaiveTime::MIN)))
+        .unwrap_or(day_start);
+    (day_start, month_start)
+}
+
+/// 统计令牌自 `since` (Unix 秒) 起消耗的 input+output token 数
+pub fn get_token_usage_since(conn: &Connection, token_id: &str, since: i64) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0)), 0)
+         FROM token_usage_logs WHERE token_id = ?1 AND request_time >= ?2",
+        params![token_id, since],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to query token usage: {}", e))
+}
+
+/// 统计今日全部令牌的请求数
+pub fn get_today_requests() -> Result<i64, String> {
+    let conn = connect_db()?;
+    let (day_start, _) = quota_period_starts(&TokenTimezone::default());
+    conn.query_row(
+        "SELECT COUNT(*) FROM token_usage_logs WHERE request_time >= ?1",
+        params![day_start],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to query today requests: {}", e))
+}
+
+/// RPM 滑动窗口检查；通过时占用一个请求名额，超限时返回建议的重试秒数
+fn check_and_record_rpm(token_id: &str, limit: i32) -> Option<u64> {
+    let now_ms = Utc::now().timestamp_millis();
+    let window_start = now_ms - 60_000;
+    let mut window = RPM_WINDOWS.entry(token_id.to_string()).or_default();
+    while window.front().map(|t| *t <= window_start).unwrap_or(false) {
+        window.pop_front();
+    }
+    if window.len() >= limit as usize {
+        let oldest = window.front().copied().unwrap_or(now_ms);
+        let retry_after_ms = (oldest + 60_000 - now_ms).max(0) as u64;
+        return Some(retry_after_ms.div_ceil(1000).max(1));
+    }
+    window.push_back(now_ms);
+    None
+}
+
+/// 校验令牌的用量限制 (模型白名单 → 日/月 token 预算 → RPM)
+///
+/// `model` 为客户端请求的原始模型名；None 表示请求不涉及模型，跳过白名单检查。
+/// 需要模型但无法解析时由调用方拒绝 (见 `TokenLimitViolation::ModelUnresolved`)。
+/// 预算基于 token_usage_logs 统计，请求完成后才计入，因此最后一个请求可能略微超出预算。
+pub fn check_token_limits(
+    token: &UserToken,
+    model: Option<&str>,
+) -> Result<Option<TokenLimitViolation>, String> {
+    if let Some(m) = model {
+        if !is_model_allowed(&token.allowed_models, m) {
+            return Ok(Some(TokenLimitViolation::ModelNotAllowed(m.to_string())));
+        }
+    }
+
+    if token.daily_token_limit > 0 || token.monthly_token_limit > 0 {
+        let conn = connect_db()?;
+        let (day_start, month_start) = quota_period_starts(&TokenTimezone::from_token(token));
+
+        if token.daily_token_limit > 0 {
+            let used = get_token_usage_since(&conn, &token.id, day_start)?;
+            if used >= token.daily_token_limit {
+                return Ok(Some(TokenLimitViolation::DailyBudgetExceeded {
+                    used,
+                    limit: token.daily_token_limit,
+                }));
+            }
+        }
+
+        if token.monthly_token_limit > 0 {
+            let used = get_token_usage_since(&conn, &token.id, month_start)?;
+            if used >= token.monthly_token_limit {
+                return Ok(Some(TokenLimitViolation::MonthlyBudgetExceeded {
+                    used,
+                    limit: token.monthly_token_limit,
+                }));
+            }
+        }
+    }
+
+    if token.rpm_limit > 0 {
+        if let Some(retry_after_secs) = check_and_record_rpm(&token.id, token.rpm_limit) {
+            return Ok(Some(TokenLimitViolation::RpmExceeded {
+                limit: token.rpm_limit,
+                retry_after_secs,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// 获取令牌的当日/当月用量与剩余预算
+pub fn get_usage_summaries(tokens: &[UserToken]) -> Result<Vec<TokenUsageSummary>, String> {
+    let conn = connect_db()?;
+
+    let remaining = |limit: i64, used: i64| if limit > 0 { Some((limit - used).max(0)) } else { None };
+
+    let mut summaries = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let (day_start, month_start) = quota_period_starts(&TokenTimezone::from_token(token));
+        let daily_used = get_token_usage_since(&conn, &token.id, day_start)?;
+        let monthly_used = get_token_usage_since(&conn, &token.id, month_start)?;
+        summaries.push(TokenUsageSummary {
+            token_id: token.id.clone(),
+            username: token.username.clone(),
+            daily_token_limit: token.daily_token_limit,
+            daily_used,
+            daily_remaining: remaining(token.daily_token_limit, daily_used),
+            monthly_token_limit: token.monthly_token_limit,
+            monthly_used,
+            monthly_remaining: remaining(token.monthly_token_limit, monthly_used),
+            rpm_limit: token.rpm_limit,
+            allowed_models: token.allowed_models.clone(),
+        });
+    }
+    Ok(summaries)
+}
+
 /// 获取 IP 关联的用户名 (用于 IP 管理页面)
 /// 返回最近一次使用该 IP 的 Token 所属的用户名
 pub fn get_username_for_ip(ip: &str) -> Result<Option<String>, String> {
@@ -595,7 +1198,7 @@ mod tests {
         
         // Use a random username to avoid collisions in existing DB runs during dev
         let username = format!("TestUser_{}", Uuid::new_v4());
-        let token_res = create_token(username.clone(), "day".to_string(), Some("Test token".to_string()), 0, None, None, None);
+        let token_res = create_token(username.clone(), "day".to_string(), Some("Test token".to_string()), 0, None, None, None, Vec::new(), None, TokenLimits::default());
         assert!(token_res.is_ok());
 
         let token = token_res.unwrap();
@@ -606,4 +1209,87 @@ mod tests {
         assert!(fetched.is_ok());
         assert_eq!(fetched.unwrap().unwrap().username, username);
     }
+
+    #[test]
+    fn test_token_availability_for_background_requests() {
+        let _ = init_db();
+        let username = format!("BatchUser_{}", Uuid::new_v4());
+        let token = create_token(username, "never".to_string(), None, 0, None, None, None, Vec::new(), None, TokenLimits::default()).unwrap();
+        assert_eq!(check_token_availability(&token), None);
+
+        let mut disabled = token.clone();
+        disabled.enabled = false;
+        assert!(check_token_availability(&disabled).unwrap().contains("disabled"));
+
+        let mut expired = token.clone();
+        expired.expires_at = Some(Utc::now().timestamp() - 60);
+        assert!(check_token_availability(&expired).unwrap().contains("expired"));
+
+        // 覆盖当前时间前后一小时的宵禁窗口 (跨午夜时同样成立)
+        let now_local = TokenTimezone::from_token(&token).now_local();
+        let mut curfew = token;
+        curfew.curfew_start = Some((now_local - Duration::hours(1)).format("%H:%M").to_string());
+        curfew.curfew_end = Some((now_local + Duration::hours(1)).format("%H:%M").to_string());
+        curfew.curfew_days = Vec::new();
+        assert!(check_token_availability(&curfew).unwrap().contains("Curfew"));
+    }
+
+    #[test]
+    fn test_timezone_parse() {
+        assert_eq!(TokenTimezone::parse("").unwrap(), TokenTimezone::default());
+        assert_eq!(
+            TokenTimezone::parse("+05:30").unwrap(),
+            TokenTimezone::Fixed(FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap())
+        );
+        assert_eq!(
+            TokenTimezone::parse("UTC-8").unwrap(),
+            TokenTimezone::Fixed(FixedOffset::west_opt(8 * 3600).unwrap())
+        );
+        assert_eq!(
+            TokenTimezone::parse("Europe/Berlin").unwrap(),
+            TokenTimezone::Named(chrono_tz::Europe::Berlin)
+        );
+        assert!(TokenTimezone::parse("Mars/Olympus").is_err());
+        assert!(TokenTimezone::parse("+25:00").is_err());
+    }
+
+    #[test]
+    fn test_curfew_window_and_weekdays() {
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        // 2025-01-03 是周五
+        assert!(is_in_curfew("09:00", "18:00", &[], &at("2025-01-03 12:00")));
+        assert!(!is_in_curfew("09:00", "18:00", &[], &at("2025-01-03 18:00")));
+        assert!(!is_in_curfew("09:00", "18:00", &[1, 2, 3, 4], &at("2025-01-03 12:00")));
+        // 跨午夜：周五 23:00 开始的窗口延续到周六凌晨
+        assert!(is_in_curfew("23:00", "06:00", &[5], &at("2025-01-04 02:00")));
+        assert!(!is_in_curfew("23:00", "06:00", &[5], &at("2025-01-03 02:00")));
+        // 未补零的时间按时间值比较
+        assert!(is_in_curfew("9:00", "18:00", &[], &at("2025-01-03 09:30")));
+        assert!(!is_in_curfew("9:00", "18:00", &[], &at("2025-01-03 08:59")));
+    }
+
+    #[test]
+    fn test_normalize_curfew_time() {
+        assert_eq!(normalize_curfew_time("9:05".to_string()), "09:05");
+        assert_eq!(normalize_curfew_time(" 23:00 ".to_string()), "23:00");
+        assert_eq!(normalize_curfew_time(String::new()), "");
+    }
+
+    #[test]
+    fn test_model_allowlist_glob() {
+        let allowed = vec!["gemini-*".to_string(), "claude-sonnet-4-5".to_string()];
+        assert!(is_model_allowed(&allowed, "gemini-2.5-flash"));
+        assert!(is_model_allowed(&allowed, "Claude-Sonnet-4-5"));
+        assert!(!is_model_allowed(&allowed, "claude-opus-4-5-thinking"));
+        assert!(is_model_allowed(&[], "anything"));
+    }
+
+    #[test]
+    fn test_rpm_sliding_window() {
+        let token_id = format!("rpm-test-{}", Uuid::new_v4());
+        assert_eq!(check_and_record_rpm(&token_id, 2), None);
+        assert_eq!(check_and_record_rpm(&token_id, 2), None);
+        let retry = check_and_record_rpm(&token_id, 2);
+        assert!(matches!(retry, Some(s) if (1..=60).contains(&s)));
+    }
 }
@@ -0,0 +1,414 @@
+// 准入排队 (Admission Control)
+//
+// 账号池中目标模型的全部账号都被限流锁定 (或达到并发上限) 时，请求在此排队等待最早的解锁，而不是立即失败：
+// - 同一模型的等待者按 (优先级, 用户内序号, 入队顺序) 出队：interactive 先于 background，
+//   同优先级下各用户令牌轮流出队，单个用户的大量请求不会挤占其他用户
+// - 解锁后按可用账号数控制放行间隔，避免所有等待者同时打到刚解锁的账号
+// - 等待超时、队列已满或客户端断开时退出队列，后续仍由 TokenManager 按原逻辑返回错误
+// - 每个请求只排队一次：重试切换账号时不再重复排队，避免单个请求累计等待 N 倍的 max_wait
+
+use axum::http::HeaderMap;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::proxy::TokenManager;
+
+/// 客户端声明请求优先级的 Header (`interactive` / `background`)
+pub const PRIORITY_HEADER: &str = "x-request-priority";
+
+/// 同一模型连续放行的最小间隔 (按可用账号数均分)
+const ADMIT_SPACING: Duration = Duration::from_millis(200);
+
+/// 全部锁定时重新检查可用性的最长间隔
+const MAX_RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 请求优先级 (数值越小越先出队)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    #[default]
+    Interactive,
+    Background,
+}
+
+impl Priority {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "interactive" | "high" => Some(Priority::Interactive),
+            "background" | "batch" | "low" => Some(Priority::Background),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Background => "background",
+        }
+    }
+}
+
+/// 请求的排队类别 (由 admission 中间件在请求作用域内设置)
+#[derive(Debug, Clone, Default)]
+pub struct RequestClass {
+    pub priority: Priority,
+    /// 用户令牌 ID (未使用用户令牌的请求共享同一个公平份额)
+    pub user: Option<String>,
+    /// 本请求是否已经过准入 (重试的各次尝试共享)
+    admitted: Arc<AtomicBool>,
+}
+
+impl RequestClass {
+    pub fn from_headers(headers: &HeaderMap, user: Option<String>) -> Self {
+        let priority = headers
+            .get(PRIORITY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Priority::parse)
+            .unwrap_or_default();
+        Self {
+            priority,
+            user,
+            admitted: Arc::default(),
+        }
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_CLASS: RequestClass;
+}
+
+/// 在指定排队类别作用域内执行请求
+pub async fn scope_request_class<F: std::future::Future>(class: RequestClass, fut: F) -> F::Output {
+    REQUEST_CLASS.scope(class, fut).await
+}
+
+fn current_request_class() -> RequestClass {
+    REQUEST_CLASS.try_with(|c| c.clone()).unwrap_or_default()
+}
+
+/// 标记当前请求已进入准入流程；同一请求的后续尝试返回 false
+fn claim_admission(class: &RequestClass) -> bool {
+    !class.admitted.swap(true, Ordering::SeqCst)
+}
+
+struct Waiter {
+    id: u64,
+    model: String,
+    priority: Priority,
+    user: String,
+    enqueued_at: Instant,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// 按入队顺序排列 (id 递增)
+    waiters: Vec<Waiter>,
+    next_id: u64,
+    last_admit: HashMap<String, Instant>,
+    admitted: u64,
+    timed_out: u64,
+    rejected: u64,
+    total_wait_ms: u64,
+    max_wait_ms: u64,
+}
+
+impl QueueState {
+    fn has_waiters(&self, model: &str) -> bool {
+        self.waiters.iter().any(|w| w.model == model)
+    }
+
+    /// 同一模型等待者的公平出队顺序
+    fn fair_order(&self, model: &str) -> Vec<u64> {
+        let mut user_rank: HashMap<(&str, Priority), usize> = HashMap::new();
+        let mut keyed: Vec<(Priority, usize, u64)> = self
+            .waiters
+            .iter()
+            .filter(|w| w.model == model)
+            .map(|w| {
+                let rank = user_rank.entry((w.user.as_str(), w.priority)).or_insert(0);
+                let key = (w.priority, *rank, w.id);
+                *rank += 1;
+                key
+            })
+            .collect();
+        keyed.sort();
+        keyed.into_iter().map(|(_, _, id)| id).collect()
+    }
+
+    fn remove(&mut self, id: u64) -> Option<Waiter> {
+        let pos = self.waiters.iter().position(|w| w.id == id)?;
+        Some(self.waiters.remove(pos))
+    }
+}
+
+struct AdmissionQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+static QUEUE: OnceLock<AdmissionQueue> = OnceLock::new();
+
+fn queue() -> &'static AdmissionQueue {
+    QUEUE.get_or_init(|| AdmissionQueue {
+        state: Mutex::new(QueueState::default()),
+        notify: Notify::new(),
+    })
+}
+
+/// 退出队列 (客户端断开导致 future 被丢弃时同样生效)
+struct WaiterGuard {
+    id: u64,
+}
+
+impl Drop for WaiterGuard {
+    fn drop(&mut self) {
+        let q = queue();
+        if let Ok(mut state) = q.state.lock() {
+            if state.remove(self.id).is_some() {
+                q.notify.notify_waiters();
+            }
+        }
+    }
+}
+
+/// 唤醒等待者重新检查可用性 (账号并发名额释放时调用)
+pub fn wake_waiters() {
+    if let Some(q) = QUEUE.get() {
+        q.notify.notify_waiters();
+    }
+}
+
+/// 候选模型 (目标模型 + 降级链) 的合并可用情况
+async fn availability(token_manager: &TokenManager, models: &[String]) -> (usize, u64) {
+    let mut free = 0;
+    let mut min_wait: Option<u64> = None;
+    for model in models {
+        let (model_free, wait) = token_manager.pool_availability(model).await;
+        free = free.max(model_free);
+        if wait > 0 {
+            min_wait = Some(min_wait.map_or(wait, |m| m.min(wait)));
+        }
+    }
+    (free, if free > 0 { 0 } else { min_wait.unwrap_or(0) })
+}
+
+/// 等待账号池中出现可用账号
+///
+/// `models[0]` 为目标模型，其余为降级链中的候选模型 (任一可用即放行)。
+/// 本函数不返回错误：超时或无法排队时直接返回，由 TokenManager 给出原有的错误信息。
+/// 同一请求 (admission 中间件作用域) 内只在首次调用时排队，重试尝试直接返回。
+pub async fn admit(token_manager: &TokenManager, models: &[String], trace_id: &str) {
+    let config = crate::proxy::config::get_admission_config();
+    let Some(model) = models.first() else {
+        return;
+    };
+    if !config.enabled || config.max_wait_seconds == 0 {
+        return;
+    }
+    let class = current_request_class();
+    if !claim_admission(&class) {
+        return;
+    }
+
+    let q = queue();
+    let (free, min_wait) = availability(token_manager, models).await;
+    // 快速路径：有可用账号且没有人排队；或者没有可等待的解锁 (无候选账号)
+    if free == 0 && min_wait == 0 {
+        return;
+    }
+    if free > 0 && !q.state.lock().map(|s| s.has_waiters(model)).unwrap_or(false) {
+        return;
+    }
+
+    let id = {
+        let Ok(mut state) = q.state.lock() else {
+            return;
+        };
+        if state.waiters.len() >= config.max_queue_depth {
+            state.rejected += 1;
+            tracing::warn!(
+                "[{}] [Admission] Queue full ({}), not waiting for {}",
+                trace_id,
+                config.max_queue_depth,
+                model
+            );
+            return;
+        }
+        state.next_id += 1;
+        let id = state.next_id;
+        state.waiters.push(Waiter {
+            id,
+            model: model.clone(),
+            priority: class.priority,
+            user: class.user.clone().unwrap_or_default(),
+            enqueued_at: Instant::now(),
+        });
+        id
+    };
+    let _guard = WaiterGuard { id };
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(config.max_wait_seconds);
+
+    tracing::info!(
+        "[{}] [Admission] All accounts locked for {}, queued ({}, shortest lockout {}s)",
+        trace_id,
+        model,
+        class.priority.as_str(),
+        min_wait
+    );
+
+    loop {
+        // 先注册通知再检查状态，避免错过其他等待者退出时的唤醒
+        let notified = q.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        let (free, min_wait) = availability(token_manager, models).await;
+        let sleep_for = {
+            let Ok(mut state) = q.state.lock() else {
+                return;
+            };
+            if free > 0 {
+                let spacing = ADMIT_SPACING / free as u32;
+                let spaced = state
+                    .last_admit
+                    .get(model)
+                    .map(|t| t.elapsed() >= spacing)
+                    .unwrap_or(true);
+                let is_head = state.fair_order(model).first() == Some(&id);
+                if is_head && spaced {
+                    state.remove(id);
+                    state.last_admit.insert(model.clone(), Instant::now());
+                    let waited = started.elapsed().as_millis() as u64;
+                    state.admitted += 1;
+                    state.total_wait_ms += waited;
+                    state.max_wait_ms = state.max_wait_ms.max(waited);
+                    q.notify.notify_waiters();
+                    tracing::info!("[{}] [Admission] Admitted after {}ms", trace_id, waited);
+                    return;
+                }
+                spacing
+            } else {
+                Duration::from_secs(min_wait.max(1)).min(MAX_RECHECK_INTERVAL)
+            }
+        };
+
+        let now = Instant::now();
+        if now >= deadline {
+            if let Ok(mut state) = q.state.lock() {
+                if state.remove(id).is_some() {
+                    state.timed_out += 1;
+                }
+            }
+            q.notify.notify_waiters();
+            tracing::warn!(
+                "[{}] [Admission] Gave up waiting for {} after {}s",
+                trace_id,
+                model,
+                config.max_wait_seconds
+            );
+            return;
+        }
+
+        tokio::select! {
+            _ = &mut notified => {}
+            _ = tokio::time::sleep(sleep_for.min(deadline - now)) => {}
+        }
+    }
+}
+
+/// 队列状态 (用于 /api/proxy/status)
+pub fn snapshot() -> Value {
+    let config = crate::proxy::config::get_admission_config();
+    let Ok(state) = queue().state.lock() else {
+        return Value::Null;
+    };
+    let mut by_priority: HashMap<&str, usize> = HashMap::new();
+    for waiter in &state.waiters {
+        *by_priority.entry(waiter.priority.as_str()).or_insert(0) += 1;
+    }
+    let oldest_wait_ms = state
+        .waiters
+        .iter()
+        .map(|w| w.enqueued_at.elapsed().as_millis() as u64)
+        .max()
+        .unwrap_or(0);
+
+    json!({
+        "enabled": config.enabled,
+        "max_wait_seconds": config.max_wait_seconds,
+        "queue_depth": state.waiters.len(),
+        "queued_by_priority": {
+            "interactive": by_priority.get("interactive").copied().unwrap_or(0),
+            "background": by_priority.get("background").copied().unwrap_or(0),
+        },
+        "oldest_wait_ms": oldest_wait_ms,
+        "admitted_after_wait": state.admitted,
+        "timed_out": state.timed_out,
+        "rejected": state.rejected,
+        "avg_wait_ms": if state.admitted > 0 { state.total_wait_ms / state.admitted } else { 0 },
+        "max_wait_ms": state.max_wait_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiter(id: u64, priority: Priority, user: &str) -> Waiter {
+        Waiter {
+            id,
+            model: "gemini-3-pro".to_string(),
+            priority,
+            user: user.to_string(),
+            enqueued_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_fair_order() {
+        let state = QueueState {
+            waiters: vec![
+                waiter(1, Priority::Background, "batch"),
+                waiter(2, Priority::Interactive, "alice"),
+                waiter(3, Priority::Interactive, "alice"),
+                waiter(4, Priority::Interactive, "alice"),
+                waiter(5, Priority::Interactive, "bob"),
+                waiter(6, Priority::Interactive, "bob"),
+            ],
+            ..Default::default()
+        };
+        // interactive 优先；alice 与 bob 轮流；background 最后
+        assert_eq!(state.fair_order("gemini-3-pro"), vec![2, 5, 3, 6, 4, 1]);
+        assert!(state.fair_order("other").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_admission_claimed_once_per_request() {
+        let class = RequestClass::from_headers(&HeaderMap::new(), None);
+        scope_request_class(class, async {
+            assert!(claim_admission(&current_request_class()));
+            // 重试尝试共享同一请求作用域，不再重复排队
+            assert!(!claim_admission(&current_request_class()));
+        })
+        .await;
+
+        // 另一请求拥有独立的准入状态
+        let other = RequestClass::from_headers(&HeaderMap::new(), None);
+        scope_request_class(other, async {
+            assert!(claim_admission(&current_request_class()));
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_priority_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(RequestClass::from_headers(&headers, None).priority, Priority::Interactive);
+        headers.insert(PRIORITY_HEADER, "Background".parse().unwrap());
+        assert_eq!(RequestClass::from_headers(&headers, None).priority, Priority::Background);
+    }
+}
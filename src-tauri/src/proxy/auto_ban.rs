@@ -0,0 +1,269 @@
+// 自动临时封禁 (Auto Ban)
+//
+// 按 IP 统计滑动窗口内的事件，触发规则时写入带过期时间的黑名单条目 (created_by = "auto")：
+// - 鉴权失败：auth_middleware 记录 (错误的 API Key / 管理密码、被拒绝的用户令牌)
+// - 4xx 爆发：ip_filter_middleware 按响应状态记录 (429 不计入，避免账号池限流时误封客户端)
+// - 请求速率：ip_filter_middleware 按请求计数
+// 封禁通过黑名单生效，因此仅在黑名单启用时工作；白名单 IP (白名单模式或白名单优先) 不参与统计
+// 同一次鉴权拒绝只计为鉴权失败，其 401/403 响应不再重复计入 4xx 爆发规则
+
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::modules::integration::SystemManager;
+use crate::modules::ip_acl;
+use crate::modules::security_db::{self, IpBlacklistEntry};
+use crate::proxy::config::{AutoBanConfig, SecurityMonitorConfig};
+
+/// 自动封禁条目的 created_by
+pub const AUTO_BAN_CREATED_BY: &str = "auto";
+
+/// 每记录多少次事件清理一次长时间无活动的 IP
+const PRUNE_EVERY: u64 = 1024;
+const IDLE_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rule {
+    AuthFailures,
+    ClientErrors,
+    RequestRate,
+}
+
+impl Rule {
+    /// (阈值, 窗口)；阈值为 0 时规则关闭
+    fn limits(&self, config: &AutoBanConfig) -> (u32, Duration) {
+        match self {
+            Rule::AuthFailures => (
+                config.auth_failure_threshold,
+                Duration::from_secs(config.auth_failure_window_secs),
+            ),
+            Rule::ClientErrors => (
+                config.client_error_threshold,
+                Duration::from_secs(config.client_error_window_secs),
+            ),
+            Rule::RequestRate => (config.max_requests_per_minute, Duration::from_secs(60)),
+        }
+    }
+
+    fn reason(&self, count: usize, window: Duration) -> String {
+        let what = match self {
+            Rule::AuthFailures => "auth failures",
+            Rule::ClientErrors => "4xx responses",
+            Rule::RequestRate => "requests",
+        };
+        format!("{} {} within {}s", count, what, window.as_secs())
+    }
+}
+
+#[derive(Default)]
+struct IpActivity {
+    auth_failures: VecDeque<Instant>,
+    client_errors: VecDeque<Instant>,
+    requests: VecDeque<Instant>,
+    last_seen: Option<Instant>,
+}
+
+impl IpActivity {
+    fn events(&mut self, rule: Rule) -> &mut VecDeque<Instant> {
+        match rule {
+            Rule::AuthFailures => &mut self.auth_failures,
+            Rule::ClientErrors => &mut self.client_errors,
+            Rule::RequestRate => &mut self.requests,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tracker {
+    ips: DashMap<String, IpActivity>,
+    records: AtomicU64,
+}
+
+impl Tracker {
+    /// 记录一次事件，达到阈值时返回封禁原因
+    fn record(&self, ip: &str, rule: Rule, config: &AutoBanConfig, now: Instant) -> Option<String> {
+        let (threshold, window) = rule.limits(config);
+        if threshold == 0 || window.is_zero() {
+            return None;
+        }
+        let count = {
+            let mut activity = self.ips.entry(ip.to_string()).or_default();
+            activity.last_seen = Some(now);
+            let events = activity.events(rule);
+            while events.front().is_some_and(|t| now.duration_since(*t) >= window) {
+                events.pop_front();
+            }
+            events.push_back(now);
+            events.len()
+        };
+        if self.records.fetch_add(1, Ordering::Relaxed) % PRUNE_EVERY == PRUNE_EVERY - 1 {
+            self.ips
+                .retain(|_, a| a.last_seen.is_some_and(|t| now.duration_since(t) < IDLE_TTL));
+        }
+        (count >= threshold as usize).then(|| rule.reason(count, window))
+    }
+
+    fn forget(&self, ip: &str) {
+        self.ips.remove(ip);
+    }
+}
+
+fn tracker() -> &'static Tracker {
+    static TRACKER: OnceLock<Tracker> = OnceLock::new();
+    TRACKER.get_or_init(Tracker::default)
+}
+
+static NOTIFIER: RwLock<Option<SystemManager>> = RwLock::new(None);
+
+/// 设置封禁通知使用的系统集成 (反代服务启动时调用)
+pub fn set_notifier(integration: SystemManager) {
+    if let Ok(mut notifier) = NOTIFIER.write() {
+        *notifier = Some(integration);
+    }
+}
+
+fn is_loopback(ip: &str) -> bool {
+    ip.parse::<IpAddr>()
+        .is_ok_and(crate::proxy::middleware::client_ip::is_loopback)
+}
+
+/// 白名单 IP 不会被黑名单拦截 (白名单模式或白名单优先)，也不参与自动封禁统计
+fn is_whitelisted(ip: &str, config: &SecurityMonitorConfig) -> bool {
+    (config.whitelist.enabled || config.whitelist.whitelist_priority)
+        && ip_acl::is_whitelisted(ip).unwrap_or(false)
+}
+
+fn observe(ip: &str, rule: Rule, config: &SecurityMonitorConfig) -> Option<IpBlacklistEntry> {
+    let auto_ban = &config.auto_ban;
+    if !auto_ban.enabled || !config.blacklist.enabled || (auto_ban.exempt_loopback && is_loopback(ip)) {
+        return None;
+    }
+    if is_whitelisted(ip, config) {
+        return None;
+    }
+    let reason = tracker().record(ip, rule, auto_ban, Instant::now())?;
+    tracker().forget(ip);
+    ban(ip, &reason, auto_ban)
+}
+
+fn ban(ip: &str, reason: &str, config: &AutoBanConfig) -> Option<IpBlacklistEntry> {
+    let now = chrono::Utc::now().timestamp();
+    // 先清理过期条目，避免同一 IP 的旧封禁记录导致唯一约束冲突
+    let _ = security_db::purge_expired_blacklist(now);
+    let minutes = config.ban_duration_minutes.max(1);
+    let reason = format!("Auto ban: {}", reason);
+    match security_db::add_to_blacklist(
+        ip,
+        Some(&reason),
+        Some(now + minutes as i64 * 60),
+        AUTO_BAN_CREATED_BY,
+    ) {
+        Ok(entry) => {
+            tracing::warn!("[AutoBan] {} banned for {} minute(s): {}", ip, minutes, reason);
+            if let Some(integration) = NOTIFIER.read().ok().and_then(|n| n.clone()) {
+                integration.show_notification(
+                    "IP auto-banned",
+                    &format!("{} banned for {} minute(s). {}", ip, minutes, reason),
+                );
+            }
+            Some(entry)
+        }
+        Err(e) => {
+            // 通常是并发请求已写入同一 IP 的封禁
+            tracing::debug!("[AutoBan] Skipped banning {}: {}", ip, e);
+            None
+        }
+    }
+}
+
+/// 请求级标记：ip_filter_middleware 注入请求扩展，auth_middleware 记录鉴权失败时置位
+#[derive(Debug, Clone, Default)]
+pub struct AuthFailureMark(Arc<AtomicBool>);
+
+impl AuthFailureMark {
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 记录一次鉴权失败 (auth_middleware)
+pub fn record_auth_failure(ip: &str, config: &SecurityMonitorConfig) {
+    observe(ip, Rule::AuthFailures, config);
+}
+
+/// 记录一次请求 (ip_filter_middleware)；触发速率规则时返回新的封禁条目
+pub fn record_request(ip: &str, config: &SecurityMonitorConfig) -> Option<IpBlacklistEntry> {
+    observe(ip, Rule::RequestRate, config)
+}
+
+/// 响应是否计入 4xx 爆发规则：429 不计入；已计为鉴权失败的 401/403 不重复计入
+fn counts_as_client_error(status: u16, auth_failure_recorded: bool) -> bool {
+    if auth_failure_recorded && matches!(status, 401 | 403) {
+        return false;
+    }
+    (400..500).contains(&status) && status != 429
+}
+
+/// 记录响应状态 (ip_filter_middleware)
+pub fn record_response(ip: &str, status: u16, mark: &AuthFailureMark, config: &SecurityMonitorConfig) {
+    if counts_as_client_error(status, mark.is_set()) {
+        observe(ip, Rule::ClientErrors, config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sliding_window_rules() {
+        let config = AutoBanConfig {
+            enabled: true,
+            auth_failure_threshold: 3,
+            auth_failure_window_secs: 60,
+            client_error_threshold: 0,
+            ..AutoBanConfig::default()
+        };
+        let tracker = Tracker::default();
+        let start = Instant::now();
+
+        assert!(tracker.record("1.2.3.4", Rule::AuthFailures, &config, start).is_none());
+        assert!(tracker.record("1.2.3.4", Rule::AuthFailures, &config, start + Duration::from_secs(10)).is_none());
+        // 第一次失败已滑出窗口
+        assert!(tracker.record("1.2.3.4", Rule::AuthFailures, &config, start + Duration::from_secs(61)).is_none());
+        let reason = tracker.record("1.2.3.4", Rule::AuthFailures, &config, start + Duration::from_secs(62));
+        assert_eq!(reason.as_deref(), Some("3 auth failures within 60s"));
+        // 其他 IP 独立计数；阈值为 0 的规则关闭
+        assert!(tracker.record("5.6.7.8", Rule::AuthFailures, &config, start).is_none());
+        assert!(tracker.record("1.2.3.4", Rule::ClientErrors, &config, start).is_none());
+
+        tracker.forget("1.2.3.4");
+        assert!(tracker.record("1.2.3.4", Rule::AuthFailures, &config, start + Duration::from_secs(63)).is_none());
+    }
+
+    #[test]
+    fn test_auth_rejections_not_double_counted() {
+        assert!(counts_as_client_error(401, false));
+        assert!(counts_as_client_error(404, true));
+        assert!(!counts_as_client_error(401, true));
+        assert!(!counts_as_client_error(403, true));
+        assert!(!counts_as_client_error(429, false));
+        assert!(!counts_as_client_error(500, false));
+    }
+
+    #[test]
+    fn test_loopback_detection() {
+        assert!(is_loopback("127.0.0.1"));
+        assert!(is_loopback("::1"));
+        assert!(is_loopback("::ffff:127.0.0.1"));
+        assert!(!is_loopback("192.168.1.10"));
+    }
+}
@@ -0,0 +1,538 @@
+// Batch API 后台任务
+//
+// - 批次按创建顺序逐个处理：validating -> in_progress -> finalizing -> completed
+// - 每行请求通过内部 Router 走与在线请求相同的 handler (账号轮换 / 重试 / 监控 / token 统计)
+// - 所有账号都被限流锁定时暂停派发，直到最早的锁定解除
+// - 状态与逐行结果持久化在 batches.db，重启后从未完成的行继续
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Request};
+use axum::response::Response;
+use axum::Router;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::io::BufRead;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tower::ServiceExt;
+use tracing::{debug, error, info, warn};
+
+use crate::modules::batch_db::{self, Batch, BatchFile, BatchItem};
+use crate::proxy::common::model_mapping::RouteContext;
+use crate::proxy::config::RouteProtocol;
+use crate::modules::user_token_db::TokenLimitViolation;
+use crate::proxy::middleware::auth::{
+    check_usage_limits, token_limit_response, token_rejected_response, UserTokenIdentity,
+};
+use crate::proxy::server::AppState;
+
+/// 支持批处理的端点
+pub const SUPPORTED_ENDPOINTS: &[&str] = &["/v1/chat/completions", "/v1/embeddings"];
+
+/// 唯一支持的完成窗口 (与 OpenAI 一致)
+pub const COMPLETION_WINDOW: &str = "24h";
+pub const COMPLETION_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// 限流等待时单次最长休眠 (秒)，期间仍可响应取消
+const MAX_RATE_LIMIT_SLEEP_SECS: u64 = 60;
+
+static WAKE: OnceLock<Notify> = OnceLock::new();
+
+fn notifier() -> &'static Notify {
+    WAKE.get_or_init(Notify::new)
+}
+
+/// 唤醒后台任务 (新批次创建或取消时调用)
+pub fn wake() {
+    notifier().notify_one();
+}
+
+/// 启动后台任务；`router` 为不经过鉴权的内部路由 (仅包含 SUPPORTED_ENDPOINTS)
+pub fn spawn_worker(state: AppState, router: Router) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        info!("[Batch] Worker started");
+        loop {
+            match tokio::task::spawn_blocking(batch_db::next_active_batch).await {
+                Ok(Ok(Some(batch))) => {
+                    let batch_id = batch.id.clone();
+                    if let Err(e) = process_batch(&state, &router, batch).await {
+                        error!("[Batch] Failed to process {}: {}", batch_id, e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+                Ok(Ok(None)) => {
+                    notifier().notified().await;
+                }
+                Ok(Err(e)) => {
+                    error!("[Batch] Failed to load batches: {}", e);
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+                Err(e) => {
+                    error!("[Batch] Worker task panicked: {}", e);
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+            }
+        }
+    })
+}
+
+async fn blocking<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Batch task panicked: {}", e))?
+}
+
+async fn process_batch(state: &AppState, router: &Router, batch: Batch) -> Result<(), String> {
+    match batch.status.as_str() {
+        "validating" => {
+            if !validate_batch(batch.clone()).await? {
+                return Ok(());
+            }
+        }
+        "cancelling" => return finalize_batch(&batch.id, "cancelled").await,
+        "finalizing" => return finalize_batch(&batch.id, "completed").await,
+        _ => {}
+    }
+
+    let identity = match batch.user_token_id.clone() {
+        Some(token_id) => blocking(move || crate::modules::user_token_db::get_token_by_id(&token_id))
+            .await?
+            .map(|t| UserTokenIdentity {
+                token_id: t.id,
+                token: t.token,
+                username: t.username,
+            }),
+        None => None,
+    };
+
+    info!("[Batch] Running {} ({})", batch.id, batch.endpoint);
+    loop {
+        if let Some(final_status) = interrupted_status(&batch).await? {
+            return finalize_batch(&batch.id, final_status).await;
+        }
+
+        let concurrency = crate::proxy::config::get_batch_config().concurrency.max(1);
+        let id = batch.id.clone();
+        let items = blocking(move || batch_db::pending_items(&id, concurrency * 4)).await?;
+        if items.is_empty() {
+            return finalize_batch(&batch.id, "completed").await;
+        }
+
+        futures::stream::iter(items)
+            .for_each_concurrent(concurrency, |item| {
+                let batch = &batch;
+                let identity = identity.as_ref();
+                async move {
+                    if let Err(e) = run_item(state, router, batch, identity, item).await {
+                        warn!("[Batch] {}: {}", batch.id, e);
+                    }
+                }
+            })
+            .await;
+    }
+}
+
+/// 批次被取消或超出完成窗口时返回对应的最终状态
+async fn interrupted_status(batch: &Batch) -> Result<Option<&'static str>, String> {
+    let id = batch.id.clone();
+    let status = blocking(move || batch_db::get_batch_status(&id)).await?;
+    if status.as_deref() == Some("cancelling") {
+        return Ok(Some("cancelled"));
+    }
+    if chrono::Utc::now().timestamp() >= batch.expires_at {
+        return Ok(Some("expired"));
+    }
+    Ok(None)
+}
+
+/// 校验输入文件并写入请求行；校验失败时批次置为 failed 并返回 false
+async fn validate_batch(mut batch: Batch) -> Result<bool, String> {
+    let path = batch_db::get_file_path(&batch.input_file_id)?;
+    let endpoint = batch.endpoint.clone();
+    let max_requests = crate::proxy::config::get_batch_config().max_requests_per_batch;
+
+    let parsed = blocking(move || {
+        let file = std::fs::File::open(&path).map_err(|e| format!("Failed to open input file: {}", e))?;
+        Ok(parse_batch_input(std::io::BufReader::new(file), &endpoint, max_requests))
+    })
+    .await?;
+
+    match parsed {
+        Ok(items) => {
+            info!("[Batch] {} validated: {} requests", batch.id, items.len());
+            blocking(move || batch_db::start_batch(&batch, &items)).await?;
+            Ok(true)
+        }
+        Err(errors) => {
+            warn!("[Batch] {} failed validation: {} errors", batch.id, errors.len());
+            batch.status = "failed".to_string();
+            batch.failed_at = Some(chrono::Utc::now().timestamp());
+            batch.errors = errors;
+            blocking(move || batch_db::save_batch(&batch)).await?;
+            Ok(false)
+        }
+    }
+}
+
+fn line_error(line: usize, code: &str, message: String) -> Value {
+    json!({
+        "code": code,
+        "message": message,
+        "param": null,
+        "line": line,
+    })
+}
+
+/// 解析 JSONL 输入 (每行 `{custom_id, method, url, body}`)，失败时返回 OpenAI 风格的错误列表
+pub fn parse_batch_input<R: BufRead>(reader: R, endpoint: &str, max_requests: usize) -> Result<Vec<BatchItem>, Vec<Value>> {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_no = index + 1;
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                errors.push(line_error(line_no, "invalid_json_line", format!("Unreadable line: {}", e)));
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value: Value = match serde_json::from_str(&line) {
+            Ok(value) => value,
+            Err(e) => {
+                errors.push(line_error(line_no, "invalid_json_line", format!("Invalid JSON: {}", e)));
+                continue;
+            }
+        };
+
+        let custom_id = match value.get("custom_id").and_then(|v| v.as_str()) {
+            Some(id) if !id.is_empty() => id.to_string(),
+            _ => {
+                errors.push(line_error(line_no, "missing_required_parameter", "'custom_id' is required".to_string()));
+                continue;
+            }
+        };
+        if value.get("method").and_then(|v| v.as_str()) != Some("POST") {
+            errors.push(line_error(line_no, "invalid_method", "Only POST requests are supported".to_string()));
+            continue;
+        }
+        if value.get("url").and_then(|v| v.as_str()) != Some(endpoint) {
+            errors.push(line_error(
+                line_no,
+                "mismatched_endpoint",
+                format!("'url' must match the batch endpoint '{}'", endpoint),
+            ));
+            continue;
+        }
+        let mut body = match value.get("body") {
+            Some(body) if body.is_object() => body.clone(),
+            _ => {
+                errors.push(line_error(line_no, "invalid_body", "'body' must be a JSON object".to_string()));
+                continue;
+            }
+        };
+        // 批处理结果按完整响应写入，不支持流式
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("stream");
+            obj.remove("stream_options");
+        }
+        if !seen.insert(custom_id.clone()) {
+            errors.push(line_error(
+                line_no,
+                "duplicate_custom_id",
+                format!("Duplicate custom_id '{}'", custom_id),
+            ));
+            continue;
+        }
+
+        items.push(BatchItem {
+            idx: items.len() as i64,
+            custom_id,
+            body,
+        });
+    }
+
+    if errors.is_empty() && items.is_empty() {
+        errors.push(line_error(0, "empty_file", "The input file contains no requests".to_string()));
+    }
+    if items.len() > max_requests {
+        errors.push(line_error(
+            0,
+            "too_many_requests",
+            format!("A batch may contain at most {} requests", max_requests),
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(items)
+    } else {
+        Err(errors)
+    }
+}
+
+/// 全部账号都被限流锁定时等待；批次被取消或过期时返回 false
+async fn wait_for_capacity(state: &AppState, batch: &Batch, identity: Option<&UserTokenIdentity>, model: &str) -> Result<bool, String> {
+    let route_ctx = RouteContext::new(RouteProtocol::Openai, &HeaderMap::new()).with_identity(identity);
+    loop {
+        if interrupted_status(batch).await?.is_some() {
+            return Ok(false);
+        }
+        let mapped = crate::proxy::common::model_mapping::resolve_model_route_with_context(
+            model,
+            &*state.custom_mapping.read().await,
+            &route_ctx,
+        );
+        let wait = state.token_manager.min_rate_limit_wait(&mapped).await;
+        if wait == 0 {
+            return Ok(true);
+        }
+        debug!("[Batch] All accounts rate limited for {}, waiting {}s", mapped, wait);
+        tokio::time::sleep(Duration::from_secs(wait.min(MAX_RATE_LIMIT_SLEEP_SECS))).await;
+    }
+}
+
+/// 单行请求的令牌检查结果
+enum LineCheck {
+    Allowed,
+    Rejected(Response),
+    /// 等待 RPM 期间批次被取消或过期
+    Interrupted,
+}
+
+/// 批处理请求不经过鉴权中间件，逐行检查令牌状态与用量限制 (禁用 / 过期 / 宵禁 / 白名单 / 预算 / RPM)
+///
+/// 超过 RPM 时等待而不是直接失败，其余拒绝原因使该行失败
+async fn check_line_limits(batch: &Batch, model: &str) -> Result<LineCheck, String> {
+    let Some(token_id) = batch.user_token_id.clone() else {
+        return Ok(LineCheck::Allowed);
+    };
+    loop {
+        if interrupted_status(batch).await?.is_some() {
+            return Ok(LineCheck::Interrupted);
+        }
+        let id = token_id.clone();
+        let Some(token) = blocking(move || crate::modules::user_token_db::get_token_by_id(&id)).await? else {
+            return Ok(LineCheck::Rejected(token_rejected_response(
+                "Invalid token. Please check your API key.",
+            )));
+        };
+        if let Some(reason) = crate::modules::user_token_db::check_token_availability(&token) {
+            return Ok(LineCheck::Rejected(token_rejected_response(&reason)));
+        }
+
+        // 与在线请求共用同一套检查 (每行都是模型请求，必须能确定模型)
+        let model = (!model.is_empty()).then(|| model.to_string());
+        let check = blocking(move || Ok(check_usage_limits(&token, model.as_deref(), true))).await?;
+        match check {
+            Some(TokenLimitViolation::RpmExceeded { retry_after_secs, .. }) => {
+                debug!("[Batch] {} hit token RPM limit, waiting {}s", batch.id, retry_after_secs);
+                tokio::time::sleep(Duration::from_secs(retry_after_secs.clamp(1, MAX_RATE_LIMIT_SLEEP_SECS))).await;
+            }
+            Some(violation) => {
+                return Ok(LineCheck::Rejected(token_limit_response(&batch.endpoint, &violation)))
+            }
+            None => return Ok(LineCheck::Allowed),
+        }
+    }
+}
+
+/// 执行单行请求并记录结果
+async fn run_item(
+    state: &AppState,
+    router: &Router,
+    batch: &Batch,
+    identity: Option<&UserTokenIdentity>,
+    item: BatchItem,
+) -> Result<(), String> {
+    let model = item.body.get("model").and_then(|m| m.as_str()).unwrap_or("");
+    let response = match check_line_limits(batch, model).await? {
+        LineCheck::Interrupted => return Ok(()),
+        LineCheck::Rejected(response) => response,
+        LineCheck::Allowed => {
+            if !wait_for_capacity(state, batch, identity, model).await? {
+                return Ok(());
+            }
+            dispatch_item(router, batch, identity, &item).await?
+        }
+    };
+    record_item_response(batch, item, response).await
+}
+
+/// 通过内部 Router 派发单行请求
+async fn dispatch_item(
+    router: &Router,
+    batch: &Batch,
+    identity: Option<&UserTokenIdentity>,
+    item: &BatchItem,
+) -> Result<Response, String> {
+    let mut request = Request::post(batch.endpoint.as_str())
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(crate::proxy::admission::PRIORITY_HEADER, "background")
+        .body(Body::from(item.body.to_string()))
+        .map_err(|e| e.to_string())?;
+    if let Some(identity) = identity {
+        request.extensions_mut().insert(identity.clone());
+    }
+
+    match router.clone().oneshot(request).await {
+        Ok(response) => Ok(response),
+        Err(never) => match never {},
+    }
+}
+
+/// 记录单行结果；2xx 计入 completed，其余计入 failed
+async fn record_item_response(batch: &Batch, item: BatchItem, response: Response) -> Result<(), String> {
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| format!("Failed to read response for {}: {}", item.custom_id, e))?;
+    // 错误响应可能是纯文本，统一包装为 OpenAI 错误结构
+    let body = serde_json::from_slice::<Value>(&bytes).unwrap_or_else(|_| {
+        json!({ "error": { "message": String::from_utf8_lossy(&bytes), "type": "upstream_error" } })
+    });
+
+    let result = json!({
+        "id": format!("batch_req_{}", uuid::Uuid::new_v4().simple()),
+        "custom_id": item.custom_id,
+        "response": {
+            "status_code": status.as_u16(),
+            "request_id": format!("req_{}", uuid::Uuid::new_v4().simple()),
+            "body": body,
+        },
+        "error": null,
+    });
+
+    let batch_id = batch.id.clone();
+    let success = status.is_success();
+    blocking(move || batch_db::complete_item(&batch_id, item.idx, success, &result)).await
+}
+
+/// 写入结果文件，返回文件 ID (无内容时不创建)
+fn write_result_file(batch: &Batch, kind: &str, lines: &[Value]) -> Result<Option<String>, String> {
+    if lines.is_empty() {
+        return Ok(None);
+    }
+    let mut content = String::new();
+    for line in lines {
+        content.push_str(&line.to_string());
+        content.push('\n');
+    }
+
+    let file = BatchFile {
+        id: format!("file-{}", uuid::Uuid::new_v4().simple()),
+        filename: format!("{}_{}.jsonl", batch.id, kind),
+        purpose: "batch_output".to_string(),
+        bytes: content.len() as i64,
+        created_at: chrono::Utc::now().timestamp(),
+        user_token_id: batch.user_token_id.clone(),
+    };
+    std::fs::write(batch_db::get_file_path(&file.id)?, content)
+        .map_err(|e| format!("Failed to write {} file: {}", kind, e))?;
+    batch_db::insert_file(&file)?;
+    Ok(Some(file.id))
+}
+
+/// 生成结果/错误文件并将批次置为最终状态 (completed / cancelled / expired)
+async fn finalize_batch(batch_id: &str, final_status: &'static str) -> Result<(), String> {
+    let batch_id = batch_id.to_string();
+    blocking(move || {
+        let mut batch = batch_db::get_batch(&batch_id)?
+            .ok_or_else(|| format!("Batch {} not found", batch_id))?;
+        let now = chrono::Utc::now().timestamp();
+
+        if final_status == "completed" && batch.status != "finalizing" {
+            batch.status = "finalizing".to_string();
+            batch.finalizing_at = Some(now);
+            batch_db::save_batch(&batch)?;
+        }
+
+        let output: Vec<Value> = batch_db::item_results(&batch_id, "completed")?
+            .into_iter()
+            .filter_map(|(_, result)| result)
+            .collect();
+        let mut errors: Vec<Value> = batch_db::item_results(&batch_id, "failed")?
+            .into_iter()
+            .filter_map(|(_, result)| result)
+            .collect();
+
+        // 未执行的行 (取消或过期) 写入错误文件
+        let (code, message) = match final_status {
+            "cancelled" => ("batch_cancelled", "This request was not executed because the batch was cancelled."),
+            _ => ("batch_expired", "This request could not be executed before the completion window expired."),
+        };
+        for (custom_id, _) in batch_db::item_results(&batch_id, "pending")? {
+            errors.push(json!({
+                "id": format!("batch_req_{}", uuid::Uuid::new_v4().simple()),
+                "custom_id": custom_id,
+                "response": null,
+                "error": { "code": code, "message": message },
+            }));
+        }
+
+        batch.output_file_id = write_result_file(&batch, "output", &output)?;
+        batch.error_file_id = write_result_file(&batch, "error", &errors)?;
+        batch.status = final_status.to_string();
+        match final_status {
+            "cancelled" => batch.cancelled_at = Some(now),
+            "expired" => batch.expired_at = Some(now),
+            _ => batch.completed_at = Some(now),
+        }
+        batch_db::save_batch(&batch)?;
+        batch_db::delete_items(&batch_id)?;
+
+        info!(
+            "[Batch] {} {}: {} completed, {} failed",
+            batch_id, final_status, batch.completed, batch.failed
+        );
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch_input() {
+        let input = concat!(
+            r#"{"custom_id":"a","method":"POST","url":"/v1/chat/completions","body":{"model":"gpt-4o","stream":true}}"#,
+            "\n\n",
+            r#"{"custom_id":"b","method":"POST","url":"/v1/chat/completions","body":{"model":"gpt-4o"}}"#,
+            "\n"
+        );
+        let items = parse_batch_input(input.as_bytes(), "/v1/chat/completions", 10).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].idx, 1);
+        assert_eq!(items[1].custom_id, "b");
+        assert!(items[0].body.get("stream").is_none());
+
+        // 超出单批上限
+        assert!(parse_batch_input(input.as_bytes(), "/v1/chat/completions", 1).is_err());
+
+        let invalid = concat!(
+            r#"{"custom_id":"a","method":"POST","url":"/v1/embeddings","body":{}}"#,
+            "\n",
+            r#"{"custom_id":"a","method":"GET","url":"/v1/chat/completions","body":{}}"#,
+            "\n",
+            "not json\n"
+        );
+        let errors = parse_batch_input(invalid.as_bytes(), "/v1/chat/completions", 10).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0]["code"], "mismatched_endpoint");
+        assert_eq!(errors[1]["code"], "invalid_method");
+        assert_eq!(errors[2]["line"], 3);
+
+        assert!(parse_batch_input("".as_bytes(), "/v1/embeddings", 10).is_err());
+    }
+}
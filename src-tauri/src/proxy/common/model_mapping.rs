@@ -1,7 +1,10 @@
 // 模型名称映射
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use once_cell::sync::Lazy;
 use dashmap::DashMap;
+use rand::Rng;
+
+use crate::proxy::config::{RouteMatch, RouteProtocol, RouteTarget, RoutingRule};
 
 // 动态官方废弃模型转发表 (old_model_id -> new_model_id)
 pub static DYNAMIC_MODEL_FORWARDING_RULES: Lazy<DashMap<String, String>> = Lazy::new(|| DashMap::new());
@@ -62,6 +65,11 @@ static CLAUDE_TO_GEMINI: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|
     m.insert("gpt-3.5-turbo-1106", "gemini-2.5-flash");
     m.insert("gpt-3.5-turbo-0613", "gemini-2.5-flash");
 
+    // OpenAI Embeddings 映射表
+    m.insert("text-embedding-3-small", "gemini-embedding-001");
+    m.insert("text-embedding-3-large", "gemini-embedding-001");
+    m.insert("text-embedding-ada-002", "gemini-embedding-001");
+
     // Gemini 协议映射表
     m.insert("gemini-2.5-flash-lite", "gemini-2.5-flash");
     m.insert("gemini-2.5-flash-thinking", "gemini-2.5-flash-thinking");
@@ -128,18 +136,73 @@ pub fn get_supported_models() -> Vec<String> {
     CLAUDE_TO_GEMINI.keys().map(|s| s.to_string()).collect()
 }
 
+/// 内置模型 ID：映射表中的模型 + 常用的 Gemini/画画模型 ID
+static BUILTIN_MODEL_IDS: Lazy<HashSet<String>> = Lazy::new(|| {
+    let mut ids: HashSet<String> = get_supported_models().into_iter().collect();
+
+    // [NEW] Issue #247: Dynamically generate all Image Gen Combinations
+    let base = "gemini-3-pro-image";
+    let resolutions = vec!["", "-2k", "-4k"];
+    let ratios = vec!["", "-1x1", "-4x3", "-3x4", "-16x9", "-9x16", "-21x9"];
+    
+    for res in resolutions {
+        for ratio in ratios.iter() {
+            let mut id = base.to_string();
+            id.push_str(res);
+            id.push_str(ratio);
+            ids.insert(id);
+        }
+    }
+
+    ids.insert("gemini-2.0-flash-exp".to_string());
+    ids.insert("gemini-2.5-flash".to_string());
+    // gemini-2.5-pro removed 
+    ids.insert("gemini-3-flash".to_string());
+    ids.insert("gemini-3.1-pro-high".to_string());
+    ids.insert("gemini-3.1-pro-low".to_string());
+    ids.insert("gemini-embedding-001".to_string());
+    ids
+});
+
+/// 模型是否为已知模型：内置、自定义映射 / 路由规则 / 降级链中出现、官方转发目标，或账号配额中下发的动态模型
+///
+/// 未知模型 ID 会被原样透传给上游，指标等需要有限取值的场景据此把它们归为 "other"
+pub fn is_known_model(
+    model: &str,
+    custom_mapping: &HashMap<String, String>,
+    token_manager: Option<&crate::proxy::token_manager::TokenManager>,
+) -> bool {
+    if BUILTIN_MODEL_IDS.contains(model)
+        || custom_mapping.contains_key(model)
+        || custom_mapping.values().any(|target| target == model)
+        || DYNAMIC_MODEL_FORWARDING_RULES.iter().any(|rule| rule.value() == model)
+    {
+        return true;
+    }
+
+    let in_rules = crate::proxy::config::get_routing_rules().iter().any(|rule| match &rule.target {
+        RouteTarget::Model(target) => target == model,
+        RouteTarget::Weighted(targets) => targets.iter().any(|t| t.model == model),
+    });
+    if in_rules {
+        return true;
+    }
+
+    let in_fallbacks = crate::proxy::config::get_model_fallbacks()
+        .values()
+        .any(|chain| chain.iter().any(|m| m == model));
+    in_fallbacks || token_manager.is_some_and(|tm| tm.has_collected_model(model))
+}
+
 /// 动态获取所有可用模型列表 (包含内置与用户自定义与官方端点动态下发)
 pub async fn get_all_dynamic_models(
     custom_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
     token_manager: Option<&crate::proxy::token_manager::TokenManager>,
 ) -> Vec<String> {
-    use std::collections::HashSet;
     let mut model_ids = HashSet::new();
 
-    // 1. 获取所有内置映射模型
-    for m in get_supported_models() {
-        model_ids.insert(m);
-    }
+    // 1. 获取所有内置模型 (映射表 + 常用 Gemini/画画模型)
+    model_ids.extend(BUILTIN_MODEL_IDS.iter().cloned());
 
     // 2. 获取所有自定义映射模型 (Custom)
     {
@@ -156,31 +219,6 @@ pub async fn get_all_dynamic_models(
         }
     }
 
-    // 5. 确保包含常用的 Gemini/画画模型 ID
-    model_ids.insert("gemini-3.1-pro-low".to_string());
-    
-    // [NEW] Issue #247: Dynamically generate all Image Gen Combinations
-    let base = "gemini-3-pro-image";
-    let resolutions = vec!["", "-2k", "-4k"];
-    let ratios = vec!["", "-1x1", "-4x3", "-3x4", "-16x9", "-9x16", "-21x9"];
-    
-    for res in resolutions {
-        for ratio in ratios.iter() {
-            let mut id = base.to_string();
-            id.push_str(res);
-            id.push_str(ratio);
-            model_ids.insert(id);
-        }
-    }
-
-    model_ids.insert("gemini-2.0-flash-exp".to_string());
-    model_ids.insert("gemini-2.5-flash".to_string());
-    // gemini-2.5-pro removed 
-    model_ids.insert("gemini-3-flash".to_string());
-    model_ids.insert("gemini-3.1-pro-high".to_string());
-    model_ids.insert("gemini-3.1-pro-low".to_string());
-
-
     let mut sorted_ids: Vec<_> = model_ids.into_iter().collect();
     sorted_ids.sort();
     sorted_ids
@@ -195,7 +233,7 @@ pub async fn get_all_dynamic_models(
 /// - `claude-*-sonnet-*` matches `claude-3-5-sonnet-20241022` ✓
 /// - `*-thinking` matches `claude-opus-4-5-thinking` ✓
 /// - `a*b*c` matches `a123b456c` ✓
-fn wildcard_match(pattern: &str, text: &str) -> bool {
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
     let parts: Vec<&str> = pattern.split('*').collect();
 
     // No wildcard - exact match
@@ -232,18 +270,178 @@ fn wildcard_match(pattern: &str, text: &str) -> bool {
     true
 }
 
+/// 路由规则匹配所需的请求上下文
+#[derive(Debug, Clone, Default)]
+pub struct RouteContext {
+    pub protocol: Option<RouteProtocol>,
+    /// 用户令牌 ID 与用户名 (未使用用户令牌时为空)
+    pub user_token_id: Option<String>,
+    pub username: Option<String>,
+    pub user_agent: Option<String>,
+    pub has_thinking: bool,
+    pub has_tools: bool,
+}
+
+impl RouteContext {
+    pub fn new(protocol: RouteProtocol, headers: &axum::http::HeaderMap) -> Self {
+        Self {
+            protocol: Some(protocol),
+            user_agent: headers
+                .get(axum::http::header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_identity(
+        mut self,
+        identity: Option<&crate::proxy::middleware::auth::UserTokenIdentity>,
+    ) -> Self {
+        if let Some(identity) = identity {
+            self.user_token_id = Some(identity.token_id.clone());
+            self.username = Some(identity.username.clone());
+        }
+        self
+    }
+
+    pub fn with_features(mut self, has_thinking: bool, has_tools: bool) -> Self {
+        self.has_thinking = has_thinking;
+        self.has_tools = has_tools;
+        self
+    }
+}
+
+/// 编译后的正则缓存 (None 表示正则非法)
+static ROUTE_REGEX_CACHE: Lazy<DashMap<String, Option<regex::Regex>>> = Lazy::new(DashMap::new);
+
+/// 模式匹配：`re:` 前缀为正则，否则为通配符
+fn route_pattern_match(pattern: &str, text: &str, case_insensitive: bool) -> bool {
+    if let Some(expr) = pattern.strip_prefix("re:") {
+        let key = format!("{}{}", if case_insensitive { "i:" } else { "s:" }, expr);
+        let compiled = ROUTE_REGEX_CACHE
+            .entry(key)
+            .or_insert_with(|| {
+                regex::RegexBuilder::new(expr)
+                    .case_insensitive(case_insensitive)
+                    .build()
+                    .map_err(|e| {
+                        tracing::warn!("[Router] Invalid routing rule regex '{}': {}", expr, e);
+                    })
+                    .ok()
+            })
+            .clone();
+        return compiled.map(|re| re.is_match(text)).unwrap_or(false);
+    }
+
+    if case_insensitive {
+        wildcard_match(&pattern.to_lowercase(), &text.to_lowercase())
+    } else {
+        wildcard_match(pattern, text)
+    }
+}
+
+fn route_match(matcher: &RouteMatch, model: &str, ctx: &RouteContext) -> bool {
+    if let Some(pattern) = &matcher.model {
+        if !route_pattern_match(pattern, model, false) {
+            return false;
+        }
+    }
+    if !matcher.protocols.is_empty()
+        && !ctx.protocol.map(|p| matcher.protocols.contains(&p)).unwrap_or(false)
+    {
+        return false;
+    }
+    if !matcher.user_tokens.is_empty() {
+        let hit = matcher.user_tokens.iter().any(|t| {
+            ctx.user_token_id.as_deref() == Some(t.as_str()) || ctx.username.as_deref() == Some(t.as_str())
+        });
+        if !hit {
+            return false;
+        }
+    }
+    if let Some(pattern) = &matcher.user_agent {
+        match &ctx.user_agent {
+            Some(ua) if route_pattern_match(pattern, ua, true) => {}
+            _ => return false,
+        }
+    }
+    if matcher.thinking.is_some_and(|v| v != ctx.has_thinking) {
+        return false;
+    }
+    if matcher.tools.is_some_and(|v| v != ctx.has_tools) {
+        return false;
+    }
+    true
+}
+
+fn pick_route_target(target: &RouteTarget) -> Option<String> {
+    match target {
+        RouteTarget::Model(model) => Some(model.clone()),
+        RouteTarget::Weighted(list) => {
+            let total: u64 = list.iter().map(|w| w.weight as u64).sum();
+            if total == 0 {
+                return list.first().map(|w| w.model.clone());
+            }
+            let mut roll = rand::thread_rng().gen_range(0..total);
+            for candidate in list {
+                if roll < candidate.weight as u64 {
+                    return Some(candidate.model.clone());
+                }
+                roll -= candidate.weight as u64;
+            }
+            None
+        }
+    }
+}
+
+/// 按顺序匹配路由规则，返回首条命中规则的目标模型
+pub fn match_routing_rules(rules: &[RoutingRule], model: &str, ctx: &RouteContext) -> Option<String> {
+    for (idx, rule) in rules.iter().enumerate() {
+        if !rule.enabled || !route_match(&rule.matcher, model, ctx) {
+            continue;
+        }
+        if let Some(target) = pick_route_target(&rule.target) {
+            crate::modules::logger::log_info(&format!(
+                "[Router] 规则路由: {} -> {} (rule: {})",
+                model,
+                target,
+                rule.name.clone().unwrap_or_else(|| format!("#{}", idx + 1))
+            ));
+            return Some(target);
+        }
+    }
+    None
+}
+
+/// 核心模型路由解析引擎 (无请求上下文，仅模型名条件的路由规则可命中)
+pub fn resolve_model_route(
+    original_model: &str,
+    custom_mapping: &std::collections::HashMap<String, String>,
+) -> String {
+    resolve_model_route_with_context(original_model, custom_mapping, &RouteContext::default())
+}
+
 /// 核心模型路由解析引擎
-/// 优先级：精确匹配 > 通配符匹配 > 系统默认映射
+/// 优先级：路由规则 > 精确匹配 > 通配符匹配 > 系统默认映射
 /// 
 /// # 参数
 /// - `original_model`: 原始模型名称
 /// - `custom_mapping`: 用户自定义映射表
+/// - `ctx`: 请求上下文 (协议 / 用户令牌 / User-Agent / thinking / tools)
 /// 
 /// # 返回
 /// 映射后的目标模型名称
-pub fn resolve_model_route(
+#[tracing::instrument(
+    target = "otel",
+    name = "model_mapping",
+    skip_all,
+    fields(model.requested = %original_model, protocol = ?ctx.protocol)
+)]
+pub fn resolve_model_route_with_context(
     original_model: &str,
     custom_mapping: &std::collections::HashMap<String, String>,
+    ctx: &RouteContext,
 ) -> String {
     // 0. API 热更新废弃模型转发 (最高物理优先级，强制纠正)
     // 如果用户非要用已经被移除的模型，并且官方下发了 fallback path，我们在此拦截并纠正
@@ -252,6 +450,11 @@ pub fn resolve_model_route(
         return forwarded.value().clone();
     }
 
+    // 0.5 声明式路由规则 (按配置顺序，首条命中生效)
+    if let Some(target) = match_routing_rules(&crate::proxy::config::get_routing_rules(), original_model, ctx) {
+        return target;
+    }
+
     // 1. 精确匹配 (次高优先级)
     if let Some(target) = custom_mapping.get(original_model) {
         crate::modules::logger::log_info(&format!("[Router] 精确映射: {} -> {}", original_model, target));
@@ -289,6 +492,32 @@ pub fn resolve_model_route(
     result
 }
 
+/// 查找模型的降级链：精确匹配优先，其次为最具体的通配符规则
+///
+/// 返回的链已去除目标模型自身及重复项
+pub fn resolve_fallback_chain(
+    model: &str,
+    chains: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    let chain = chains.get(model).or_else(|| {
+        chains
+            .iter()
+            .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+            .max_by_key(|(pattern, _)| pattern.chars().count() - pattern.matches('*').count())
+            .map(|(_, chain)| chain)
+    });
+
+    let mut result: Vec<String> = Vec::new();
+    for candidate in chain.into_iter().flatten() {
+        let candidate = candidate.trim();
+        if candidate.is_empty() || candidate == model || result.iter().any(|m| m == candidate) {
+            continue;
+        }
+        result.push(candidate.to_string());
+    }
+    result
+}
+
 /// Normalize any physical model name to one of the 3 standard protection IDs.
 /// This ensures quota protection works consistently regardless of API versioning or request variations.
 /// 
@@ -472,4 +701,69 @@ mod tests {
         // Multi-wildcard: "a*b*c" (3)
         assert_eq!(resolve_model_route("a-test-b-foo-c", &custom), "multi-wild");
     }
+
+    #[test]
+    fn test_resolve_fallback_chain() {
+        let mut chains = HashMap::new();
+        chains.insert(
+            "claude-opus-4-6-thinking".to_string(),
+            vec![
+                "claude-sonnet-4-6".to_string(),
+                "claude-opus-4-6-thinking".to_string(),
+                "gemini-3-pro-high".to_string(),
+                "claude-sonnet-4-6".to_string(),
+            ],
+        );
+        chains.insert("claude-*".to_string(), vec!["gemini-3-flash".to_string()]);
+
+        // 自身与重复项被剔除
+        assert_eq!(
+            resolve_fallback_chain("claude-opus-4-6-thinking", &chains),
+            vec!["claude-sonnet-4-6", "gemini-3-pro-high"]
+        );
+        assert_eq!(resolve_fallback_chain("claude-sonnet-4-6", &chains), vec!["gemini-3-flash"]);
+        assert!(resolve_fallback_chain("gemini-3-flash", &chains).is_empty());
+    }
+
+    #[test]
+    fn test_is_known_model() {
+        let mut custom = HashMap::new();
+        custom.insert("my-alias".to_string(), "gemini-3.1-pro-preview-custom".to_string());
+
+        assert!(is_known_model("claude-sonnet-4-6", &custom, None));
+        assert!(is_known_model("gemini-3-pro-image-4k-16x9", &custom, None));
+        assert!(is_known_model("my-alias", &custom, None));
+        assert!(is_known_model("gemini-3.1-pro-preview-custom", &custom, None));
+        // 未知模型会被透传给上游，但不算已知模型
+        assert_eq!(map_claude_model_to_gemini("random-abc-123"), "random-abc-123");
+        assert!(!is_known_model("random-abc-123", &custom, None));
+    }
+
+    #[test]
+    fn test_routing_rules_conditions() {
+        let rules: Vec<RoutingRule> = serde_json::from_value(serde_json::json!([
+            { "name": "disabled", "enabled": false, "match": { "model": "*" }, "target": "never" },
+            { "match": { "model": "re:^gpt-4(o|\\.1)", "protocols": ["openai"], "tools": true }, "target": "gemini-3-pro-high" },
+            { "match": { "model": "claude-*", "user_tokens": ["team-a"] }, "target": "claude-opus-4-6-thinking" },
+            { "match": { "model": "claude-*", "user_agent": "*claude-cli*", "thinking": false }, "target": "claude-sonnet-4-6" },
+            { "match": { "model": "gemini-*-image*" }, "target": [{ "model": "img-a", "weight": 0 }, { "model": "img-b", "weight": 3 }] }
+        ]))
+        .unwrap();
+
+        let openai = RouteContext { protocol: Some(RouteProtocol::Openai), ..Default::default() };
+        assert_eq!(match_routing_rules(&rules, "gpt-4o", &openai.clone().with_features(false, true)).as_deref(), Some("gemini-3-pro-high"));
+        assert_eq!(match_routing_rules(&rules, "gpt-4o", &openai.with_features(false, false)), None);
+
+        let team = RouteContext { username: Some("team-a".to_string()), ..Default::default() };
+        assert_eq!(match_routing_rules(&rules, "claude-sonnet-4-5", &team).as_deref(), Some("claude-opus-4-6-thinking"));
+
+        let cli = RouteContext { user_agent: Some("Claude-CLI/2.0".to_string()), ..Default::default() };
+        assert_eq!(match_routing_rules(&rules, "claude-sonnet-4-5", &cli).as_deref(), Some("claude-sonnet-4-6"));
+        assert_eq!(match_routing_rules(&rules, "claude-sonnet-4-5", &cli.with_features(true, false)), None);
+
+        // 权重为 0 的目标永不被选中
+        for _ in 0..20 {
+            assert_eq!(match_routing_rules(&rules, "gemini-3-pro-image", &RouteContext::default()).as_deref(), Some("img-b"));
+        }
+    }
 }
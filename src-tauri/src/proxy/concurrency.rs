@@ -0,0 +1,168 @@
+// 账号并发控制 - 每个账号 × 模型族的在途请求计数
+//
+// - 模型族由 `proxy.account_concurrency` 中的通配符决定 (精确匹配优先，其次最长通配符)
+// - TokenManager 选中账号时占用名额 (InFlightLease)，名额绑定到当前请求，
+//   在响应体结束或被丢弃时由 admission 中间件释放；重试切换账号时旧名额立即释放
+// - 名额释放时唤醒准入队列，使因饱和而等待的请求及时重新检查
+
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+
+/// 模型族及其单账号并发上限
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelFamily {
+    pub name: String,
+    pub limit: usize,
+}
+
+/// 解析模型所属的模型族；未配置或上限为 0 时返回 None (不限制)
+pub fn resolve_family(model: &str, limits: &HashMap<String, usize>) -> Option<ModelFamily> {
+    let (name, limit) = limits
+        .get_key_value(model)
+        .or_else(|| {
+            limits
+                .iter()
+                .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+                .max_by_key(|(pattern, _)| pattern.len())
+        })?;
+    (*limit > 0).then(|| ModelFamily {
+        name: name.clone(),
+        limit: *limit,
+    })
+}
+
+type Counts = Arc<DashMap<(String, String), usize>>;
+
+/// 在途请求计数器
+#[derive(Clone, Default)]
+pub struct InFlightTracker {
+    counts: Counts,
+}
+
+impl InFlightTracker {
+    /// 账号在该模型族上的在途请求数
+    pub fn in_flight(&self, account_id: &str, family: Option<&ModelFamily>) -> usize {
+        family
+            .and_then(|f| {
+                self.counts
+                    .get(&(account_id.to_string(), f.name.clone()))
+                    .map(|c| *c)
+            })
+            .unwrap_or(0)
+    }
+
+    /// 账号在该模型族上是否已达并发上限
+    pub fn is_saturated(&self, account_id: &str, family: Option<&ModelFamily>) -> bool {
+        family.is_some_and(|f| self.in_flight(account_id, Some(f)) >= f.limit)
+    }
+
+    /// 负载比例 (0.0 = 空闲，1.0 = 饱和)；不限制的模型族始终为 0
+    pub fn load(&self, account_id: &str, family: Option<&ModelFamily>) -> f32 {
+        family.map_or(0.0, |f| self.in_flight(account_id, Some(f)) as f32 / f.limit as f32)
+    }
+
+    /// 未达上限时占用一个名额；不限制的模型族返回空名额
+    pub fn try_acquire(&self, account_id: &str, family: Option<&ModelFamily>) -> Option<InFlightLease> {
+        let Some(family) = family else {
+            return Some(InFlightLease { slot: None });
+        };
+        let key = (account_id.to_string(), family.name.clone());
+        {
+            let mut count = self.counts.entry(key.clone()).or_insert(0);
+            if *count >= family.limit {
+                return None;
+            }
+            *count += 1;
+        }
+        Some(InFlightLease {
+            slot: Some((self.counts.clone(), key)),
+        })
+    }
+
+    /// 当前非零的在途计数 (account_id, 模型族, 数量)
+    pub fn snapshot(&self) -> Vec<(String, String, usize)> {
+        self.counts
+            .iter()
+            .filter(|e| *e.value() > 0)
+            .map(|e| (e.key().0.clone(), e.key().1.clone(), *e.value()))
+            .collect()
+    }
+}
+
+/// 并发名额，Drop 时释放
+pub struct InFlightLease {
+    slot: Option<(Counts, (String, String))>,
+}
+
+impl Drop for InFlightLease {
+    fn drop(&mut self) {
+        let Some((counts, key)) = self.slot.take() else {
+            return;
+        };
+        if let Some(mut count) = counts.get_mut(&key) {
+            *count = count.saturating_sub(1);
+        }
+        counts.remove_if(&key, |_, count| *count == 0);
+        crate::proxy::admission::wake_waiters();
+    }
+}
+
+/// 当前请求持有的名额 (由 admission 中间件创建)
+#[derive(Clone, Default)]
+pub struct LeaseSlot(Arc<Mutex<Option<InFlightLease>>>);
+
+impl LeaseSlot {
+    pub fn is_held(&self) -> bool {
+        self.0.lock().map(|l| l.is_some()).unwrap_or(false)
+    }
+}
+
+tokio::task_local! {
+    static LEASE_SLOT: LeaseSlot;
+}
+
+/// 在指定名额槽作用域内执行请求
+pub async fn scope_lease_slot<F: std::future::Future>(slot: LeaseSlot, fut: F) -> F::Output {
+    LEASE_SLOT.scope(slot, fut).await
+}
+
+/// 将名额绑定到当前请求 (替换并释放同一请求之前的名额)；不在请求作用域内时立即释放
+pub fn attach_to_current_request(lease: InFlightLease) {
+    let _ = LEASE_SLOT.try_with(move |slot| {
+        if let Ok(mut held) = slot.0.lock() {
+            *held = Some(lease);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_family_limits_and_leases() {
+        let limits = HashMap::from([
+            ("claude-*".to_string(), 2),
+            ("claude-opus-*".to_string(), 1),
+            ("gemini-*".to_string(), 0),
+        ]);
+        assert_eq!(resolve_family("claude-sonnet-4-6", &limits).unwrap().name, "claude-*");
+        assert_eq!(resolve_family("claude-opus-4-6-thinking", &limits).unwrap().limit, 1);
+        assert_eq!(resolve_family("gemini-3-pro-high", &limits), None);
+
+        let tracker = InFlightTracker::default();
+        let family = resolve_family("claude-sonnet-4-6", &limits);
+        let first = tracker.try_acquire("acc1", family.as_ref()).unwrap();
+        let _second = tracker.try_acquire("acc1", family.as_ref()).unwrap();
+        assert!(tracker.is_saturated("acc1", family.as_ref()));
+        assert!(tracker.try_acquire("acc1", family.as_ref()).is_none());
+        assert!(tracker.try_acquire("acc2", family.as_ref()).is_some());
+
+        drop(first);
+        assert_eq!(tracker.in_flight("acc1", family.as_ref()), 1);
+        assert!(tracker.try_acquire("acc1", None).is_some());
+    }
+}
@@ -94,6 +94,215 @@ pub fn update_global_system_prompt_config(config: GlobalSystemPromptConfig) {
     }
 }
 
+// ============================================================================
+// 全局上游端点配置存储
+// oauth / quota 等模块不持有 ProxyConfig，因此通过全局读取
+// ============================================================================
+static GLOBAL_UPSTREAM_ENDPOINTS: OnceLock<RwLock<UpstreamEndpointsConfig>> = OnceLock::new();
+
+/// 获取当前生效的上游端点 (已叠加环境变量覆盖)
+pub fn get_upstream_endpoints() -> UpstreamEndpointsConfig {
+    GLOBAL_UPSTREAM_ENDPOINTS
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_else(|| UpstreamEndpointsConfig::default().with_env_overrides())
+}
+
+/// 更新全局上游端点配置
+pub fn update_upstream_endpoints(config: UpstreamEndpointsConfig) {
+    let config = config.with_env_overrides();
+    if let Some(lock) = GLOBAL_UPSTREAM_ENDPOINTS.get() {
+        if let Ok(mut cfg) = lock.write() {
+            if *cfg != config {
+                tracing::info!(
+                    "[Upstream-Endpoints] Config updated: v1internal={:?}",
+                    config.v1internal_base_urls
+                );
+            }
+            *cfg = config;
+        }
+    } else {
+        tracing::info!(
+            "[Upstream-Endpoints] Config initialized: v1internal={:?}",
+            config.v1internal_base_urls
+        );
+        let _ = GLOBAL_UPSTREAM_ENDPOINTS.set(RwLock::new(config));
+    }
+}
+
+// ============================================================================
+// 全局模型路由规则存储
+// model_mapping::resolve_model_route 不持有 ProxyConfig，因此通过全局读取
+// ============================================================================
+static GLOBAL_ROUTING_RULES: OnceLock<RwLock<Vec<RoutingRule>>> = OnceLock::new();
+
+/// 获取当前生效的路由规则 (按配置顺序)
+pub fn get_routing_rules() -> Vec<RoutingRule> {
+    GLOBAL_ROUTING_RULES
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|rules| rules.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局路由规则
+pub fn update_routing_rules(rules: Vec<RoutingRule>) {
+    if let Some(lock) = GLOBAL_ROUTING_RULES.get() {
+        if let Ok(mut current) = lock.write() {
+            *current = rules;
+            tracing::info!("[Routing-Rules] Config updated: {} rule(s)", current.len());
+        }
+    } else {
+        tracing::info!("[Routing-Rules] Config initialized: {} rule(s)", rules.len());
+        let _ = GLOBAL_ROUTING_RULES.set(RwLock::new(rules));
+    }
+}
+
+// ============================================================================
+// 全局跨模型降级链存储
+// TokenManager 无法为目标模型分配账号时 (全部限流/配额保护)，按链依次尝试备选模型
+// ============================================================================
+static GLOBAL_MODEL_FALLBACKS: OnceLock<RwLock<HashMap<String, Vec<String>>>> = OnceLock::new();
+
+/// 获取当前降级链配置 (key 为物理模型名，支持通配符)
+pub fn get_model_fallbacks() -> HashMap<String, Vec<String>> {
+    GLOBAL_MODEL_FALLBACKS
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|chains| chains.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局降级链配置
+pub fn update_model_fallbacks(chains: HashMap<String, Vec<String>>) {
+    if let Some(lock) = GLOBAL_MODEL_FALLBACKS.get() {
+        if let Ok(mut current) = lock.write() {
+            *current = chains;
+            tracing::info!("[Model-Fallback] Config updated: {} chain(s)", current.len());
+        }
+    } else {
+        tracing::info!("[Model-Fallback] Config initialized: {} chain(s)", chains.len());
+        let _ = GLOBAL_MODEL_FALLBACKS.set(RwLock::new(chains));
+    }
+}
+
+// ============================================================================
+// 全局模型单价表 (用于估算按官方 API 价格计费的等价成本)
+// ============================================================================
+static GLOBAL_MODEL_PRICES: OnceLock<RwLock<HashMap<String, ModelPrice>>> = OnceLock::new();
+
+/// 获取当前模型单价表 (key 为模型名，支持通配符)
+pub fn get_model_prices() -> HashMap<String, ModelPrice> {
+    GLOBAL_MODEL_PRICES
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|prices| prices.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局模型单价表
+pub fn update_model_prices(prices: HashMap<String, ModelPrice>) {
+    if let Some(lock) = GLOBAL_MODEL_PRICES.get() {
+        if let Ok(mut current) = lock.write() {
+            *current = prices;
+            tracing::info!("[Model-Prices] Config updated: {} price(s)", current.len());
+        }
+    } else {
+        tracing::info!("[Model-Prices] Config initialized: {} price(s)", prices.len());
+        let _ = GLOBAL_MODEL_PRICES.set(RwLock::new(prices));
+    }
+}
+
+// ============================================================================
+// 全局账号并发上限 (每个账号、每个模型族的最大在途请求数)
+// ============================================================================
+static GLOBAL_ACCOUNT_CONCURRENCY: OnceLock<RwLock<HashMap<String, usize>>> = OnceLock::new();
+
+/// 获取当前账号并发上限表 (key 为模型族通配符，如 `claude-*`)
+pub fn get_account_concurrency() -> HashMap<String, usize> {
+    GLOBAL_ACCOUNT_CONCURRENCY
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|limits| limits.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局账号并发上限表
+pub fn update_account_concurrency(limits: HashMap<String, usize>) {
+    if let Some(lock) = GLOBAL_ACCOUNT_CONCURRENCY.get() {
+        if let Ok(mut current) = lock.write() {
+            *current = limits;
+            tracing::info!("[Concurrency] Config updated: {} limit(s)", current.len());
+        }
+    } else {
+        tracing::info!("[Concurrency] Config initialized: {} limit(s)", limits.len());
+        let _ = GLOBAL_ACCOUNT_CONCURRENCY.set(RwLock::new(limits));
+    }
+}
+
+// ============================================================================
+// 全局批处理 (Batch API) 配置
+// ============================================================================
+static GLOBAL_BATCH_CONFIG: OnceLock<RwLock<BatchConfig>> = OnceLock::new();
+
+/// 获取当前批处理配置
+pub fn get_batch_config() -> BatchConfig {
+    GLOBAL_BATCH_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局批处理配置
+pub fn update_batch_config(config: BatchConfig) {
+    if let Some(lock) = GLOBAL_BATCH_CONFIG.get() {
+        if let Ok(mut current) = lock.write() {
+            *current = config;
+            tracing::info!("[Batch] Config updated: concurrency={}", current.concurrency);
+        }
+    } else {
+        tracing::info!("[Batch] Config initialized: concurrency={}", config.concurrency);
+        let _ = GLOBAL_BATCH_CONFIG.set(RwLock::new(config));
+    }
+}
+
+// ============================================================================
+// 全局准入排队 (Admission) 配置
+// ============================================================================
+static GLOBAL_ADMISSION_CONFIG: OnceLock<RwLock<AdmissionConfig>> = OnceLock::new();
+
+/// 获取当前准入排队配置
+pub fn get_admission_config() -> AdmissionConfig {
+    GLOBAL_ADMISSION_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局准入排队配置
+pub fn update_admission_config(config: AdmissionConfig) {
+    if let Some(lock) = GLOBAL_ADMISSION_CONFIG.get() {
+        if let Ok(mut current) = lock.write() {
+            *current = config;
+            tracing::info!(
+                "[Admission] Config updated: enabled={}, max_wait={}s",
+                current.enabled,
+                current.max_wait_seconds
+            );
+        }
+    } else {
+        tracing::info!(
+            "[Admission] Config initialized: enabled={}, max_wait={}s",
+            config.enabled,
+            config.max_wait_seconds
+        );
+        let _ = GLOBAL_ADMISSION_CONFIG.set(RwLock::new(config));
+    }
+}
+
 // ============================================================================
 // 全局图像思维模式配置存储
 // ============================================================================
@@ -387,6 +596,46 @@ impl Default for DebugLoggingConfig {
     }
 }
 
+/// OpenTelemetry 链路追踪导出配置 (OTLP/HTTP)
+///
+/// 追踪层在日志系统初始化时安装，修改后需重启应用生效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// OTLP/HTTP traces 端点 (本地 collector)
+    #[serde(default = "default_otlp_endpoint")]
+    pub otlp_endpoint: String,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// 采样比例 (0.0 - 1.0)
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            otlp_endpoint: default_otlp_endpoint(),
+            service_name: default_service_name(),
+            sample_ratio: default_sample_ratio(),
+        }
+    }
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://127.0.0.1:4318/v1/traces".to_string()
+}
+
+fn default_service_name() -> String {
+    "antigravity-manager".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
 /// IP 黑名单配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpBlacklistConfig {
@@ -443,6 +692,19 @@ pub struct SecurityMonitorConfig {
     /// IP 白名单配置
     #[serde(default)]
     pub whitelist: IpWhitelistConfig,
+
+    /// [NEW] 可信反向代理 (CIDR 或单个 IP)
+    /// 仅当 TCP 对端属于该列表时才信任 X-Forwarded-For / X-Real-IP，默认仅信任本机
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<String>,
+
+    /// [NEW] 自动临时封禁规则
+    #[serde(default)]
+    pub auto_ban: AutoBanConfig,
+}
+
+fn default_trusted_proxies() -> Vec<String> {
+    vec!["127.0.0.1/32".to_string(), "::1/128".to_string()]
 }
 
 impl Default for SecurityMonitorConfig {
@@ -450,6 +712,83 @@ impl Default for SecurityMonitorConfig {
         Self {
             blacklist: IpBlacklistConfig::default(),
             whitelist: IpWhitelistConfig::default(),
+            trusted_proxies: default_trusted_proxies(),
+            auto_ban: AutoBanConfig::default(),
+        }
+    }
+}
+
+/// 自动临时封禁配置 (阈值为 0 表示关闭对应规则)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoBanConfig {
+    /// 是否启用自动封禁 (需同时启用黑名单)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// 封禁时长 (分钟)
+    #[serde(default = "default_auto_ban_duration_minutes")]
+    pub ban_duration_minutes: u64,
+
+    /// 窗口内鉴权失败次数阈值
+    #[serde(default = "default_auth_failure_threshold")]
+    pub auth_failure_threshold: u32,
+
+    /// 鉴权失败统计窗口 (秒)
+    #[serde(default = "default_auth_failure_window_secs")]
+    pub auth_failure_window_secs: u64,
+
+    /// 窗口内 4xx 响应次数阈值 (429 不计入)
+    #[serde(default = "default_client_error_threshold")]
+    pub client_error_threshold: u32,
+
+    /// 4xx 统计窗口 (秒)
+    #[serde(default = "default_client_error_window_secs")]
+    pub client_error_window_secs: u64,
+
+    /// 单个 IP 每分钟最大请求数
+    #[serde(default = "default_max_requests_per_minute")]
+    pub max_requests_per_minute: u32,
+
+    /// 本机回环地址不参与自动封禁
+    #[serde(default = "default_true")]
+    pub exempt_loopback: bool,
+}
+
+fn default_auto_ban_duration_minutes() -> u64 {
+    30
+}
+
+fn default_auth_failure_threshold() -> u32 {
+    10
+}
+
+fn default_auth_failure_window_secs() -> u64 {
+    300
+}
+
+fn default_client_error_threshold() -> u32 {
+    100
+}
+
+fn default_client_error_window_secs() -> u64 {
+    60
+}
+
+fn default_max_requests_per_minute() -> u32 {
+    600
+}
+
+impl Default for AutoBanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ban_duration_minutes: default_auto_ban_duration_minutes(),
+            auth_failure_threshold: default_auth_failure_threshold(),
+            auth_failure_window_secs: default_auth_failure_window_secs(),
+            client_error_threshold: default_client_error_threshold(),
+            client_error_window_secs: default_client_error_window_secs(),
+            max_requests_per_minute: default_max_requests_per_minute(),
+            exempt_loopback: true,
         }
     }
 }
@@ -502,6 +841,10 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub debug_logging: DebugLoggingConfig,
 
+    /// OpenTelemetry 链路追踪导出
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
     /// 上游代理配置
     #[serde(default)]
     pub upstream_proxy: UpstreamProxyConfig,
@@ -555,6 +898,302 @@ pub struct ProxyConfig {
     /// 代理池配置
     #[serde(default)]
     pub proxy_pool: ProxyPoolConfig,
+
+    /// 上游端点配置 (v1internal / OAuth / 配额)，可指向本地 mock 服务
+    #[serde(default)]
+    pub upstream_endpoints: UpstreamEndpointsConfig,
+
+    /// 声明式模型路由规则 (按顺序匹配，首条命中生效，优先级高于 custom_mapping)
+    #[serde(default)]
+    pub routing_rules: Vec<RoutingRule>,
+
+    /// 跨模型降级链 (如 `claude-opus-4-6-thinking` → [`claude-sonnet-4-6`, `gemini-3-pro-high`])
+    /// 目标模型的所有账号均被限流或配额保护时按顺序尝试
+    #[serde(default)]
+    pub model_fallbacks: HashMap<String, Vec<String>>,
+
+    /// 模型单价表 (美元 / 百万 token，key 支持通配符，如 `claude-opus-*`)
+    /// 用于 token 统计中的预估成本列；为空时不计算成本
+    #[serde(default)]
+    pub model_prices: HashMap<String, ModelPrice>,
+
+    /// 每个账号、每个模型族的最大在途请求数 (key 为模型通配符，如 `claude-*`: 2)
+    /// 未匹配的模型不限制；多个通配符匹配时取最长的一个作为模型族
+    #[serde(default)]
+    pub account_concurrency: HashMap<String, usize>,
+
+    /// OpenAI Batch API 后台执行配置
+    #[serde(default)]
+    pub batch: BatchConfig,
+
+    /// 账号池全部被限流锁定时的排队等待配置
+    #[serde(default)]
+    pub admission: AdmissionConfig,
+}
+
+/// 准入排队配置：全部账号被锁定时请求排队等待最早的解锁，而不是立即失败
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdmissionConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 单次排队的最长等待时间 (秒)，超时后按原逻辑返回限流错误
+    #[serde(default = "default_admission_max_wait")]
+    pub max_wait_seconds: u64,
+    /// 队列上限，超出时不再排队
+    #[serde(default = "default_admission_max_queue")]
+    pub max_queue_depth: usize,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_wait_seconds: default_admission_max_wait(),
+            max_queue_depth: default_admission_max_queue(),
+        }
+    }
+}
+
+fn default_admission_max_wait() -> u64 {
+    30
+}
+
+fn default_admission_max_queue() -> usize {
+    256
+}
+
+/// Batch API 后台执行配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchConfig {
+    /// 同时执行的请求数 (所有批次共享)
+    #[serde(default = "default_batch_concurrency")]
+    pub concurrency: usize,
+    /// 单个输入文件允许的最大请求行数
+    #[serde(default = "default_batch_max_requests")]
+    pub max_requests_per_batch: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: default_batch_concurrency(),
+            max_requests_per_batch: default_batch_max_requests(),
+        }
+    }
+}
+
+fn default_batch_concurrency() -> usize {
+    2
+}
+
+fn default_batch_max_requests() -> usize {
+    50_000
+}
+
+/// 模型单价 (美元 / 百万 token)
+///
+/// 缓存读取 / 缓存写入未配置时按普通输入价格计算
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default)]
+    pub cache_read: Option<f64>,
+    #[serde(default)]
+    pub cache_creation: Option<f64>,
+}
+
+/// 上游端点配置
+///
+/// 默认值为 Google 官方端点；环境变量优先级高于配置文件 (不回写配置文件)：
+/// - `ABV_V1INTERNAL_BASE_URLS`: 逗号分隔，按顺序降级尝试
+/// - `ABV_OAUTH_AUTH_URL` / `ABV_OAUTH_TOKEN_URL` / `ABV_OAUTH_USERINFO_URL`
+/// - `ABV_QUOTA_BASE_URL` / `ABV_CODE_ASSIST_BASE_URL`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpstreamEndpointsConfig {
+    /// v1internal 基础 URL 列表 (含 `/v1internal` 后缀)，失败时按顺序切换
+    #[serde(default = "default_v1internal_base_urls")]
+    pub v1internal_base_urls: Vec<String>,
+    /// OAuth 授权页
+    #[serde(default = "default_oauth_auth_url")]
+    pub oauth_auth_url: String,
+    /// OAuth Token 交换/刷新
+    #[serde(default = "default_oauth_token_url")]
+    pub oauth_token_url: String,
+    /// OAuth 用户信息
+    #[serde(default = "default_oauth_userinfo_url")]
+    pub oauth_userinfo_url: String,
+    /// 配额查询 (fetchAvailableModels) 使用的 v1internal 基础 URL
+    #[serde(default = "default_quota_base_url")]
+    pub quota_base_url: String,
+    /// project_id 解析 (loadCodeAssist) 使用的 v1internal 基础 URL
+    #[serde(default = "default_code_assist_base_url")]
+    pub code_assist_base_url: String,
+}
+
+impl Default for UpstreamEndpointsConfig {
+    fn default() -> Self {
+        Self {
+            v1internal_base_urls: default_v1internal_base_urls(),
+            oauth_auth_url: default_oauth_auth_url(),
+            oauth_token_url: default_oauth_token_url(),
+            oauth_userinfo_url: default_oauth_userinfo_url(),
+            quota_base_url: default_quota_base_url(),
+            code_assist_base_url: default_code_assist_base_url(),
+        }
+    }
+}
+
+impl UpstreamEndpointsConfig {
+    /// 叠加环境变量覆盖，并清洗 URL (去空白、去末尾 `/`、空列表回退默认值)
+    pub fn with_env_overrides(mut self) -> Self {
+        let env = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        if let Some(list) = env("ABV_V1INTERNAL_BASE_URLS") {
+            self.v1internal_base_urls = list.split(',').map(|s| s.to_string()).collect();
+        }
+        if let Some(v) = env("ABV_OAUTH_AUTH_URL") {
+            self.oauth_auth_url = v;
+        }
+        if let Some(v) = env("ABV_OAUTH_TOKEN_URL") {
+            self.oauth_token_url = v;
+        }
+        if let Some(v) = env("ABV_OAUTH_USERINFO_URL") {
+            self.oauth_userinfo_url = v;
+        }
+        if let Some(v) = env("ABV_QUOTA_BASE_URL") {
+            self.quota_base_url = v;
+        }
+        if let Some(v) = env("ABV_CODE_ASSIST_BASE_URL") {
+            self.code_assist_base_url = v;
+        }
+
+        self.v1internal_base_urls = self
+            .v1internal_base_urls
+            .iter()
+            .map(|u| u.trim().trim_end_matches('/').to_string())
+            .filter(|u| !u.is_empty())
+            .collect();
+        if self.v1internal_base_urls.is_empty() {
+            self.v1internal_base_urls = default_v1internal_base_urls();
+        }
+        self.quota_base_url = self.quota_base_url.trim().trim_end_matches('/').to_string();
+        self.code_assist_base_url = self.code_assist_base_url.trim().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// 配额查询完整 URL
+    pub fn quota_url(&self) -> String {
+        format!("{}:fetchAvailableModels", self.quota_base_url)
+    }
+
+    /// loadCodeAssist 完整 URL
+    pub fn load_code_assist_url(&self) -> String {
+        format!("{}:loadCodeAssist", self.code_assist_base_url)
+    }
+}
+
+// Cloud Code v1internal endpoints (fallback order: Sandbox → Daily → Prod)
+// 优先使用 Sandbox/Daily 环境以避免 Prod环境的 429 错误 (Ref: Issue #1176)
+fn default_v1internal_base_urls() -> Vec<String> {
+    vec![
+        "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal".to_string(), // 优先级 1: Sandbox
+        "https://daily-cloudcode-pa.googleapis.com/v1internal".to_string(),         // 优先级 2: Daily
+        "https://cloudcode-pa.googleapis.com/v1internal".to_string(),               // 优先级 3: Prod
+    ]
+}
+
+fn default_oauth_auth_url() -> String {
+    "https://accounts.google.com/o/oauth2/v2/auth".to_string()
+}
+
+fn default_oauth_token_url() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+fn default_oauth_userinfo_url() -> String {
+    "https://www.googleapis.com/oauth2/v2/userinfo".to_string()
+}
+
+fn default_quota_base_url() -> String {
+    "https://cloudcode-pa.googleapis.com/v1internal".to_string()
+}
+
+fn default_code_assist_base_url() -> String {
+    // 使用 Sandbox 环境，避免 Prod 环境的 429 错误
+    "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal".to_string()
+}
+
+/// 路由规则适用的客户端协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteProtocol {
+    Openai,
+    Anthropic,
+    Gemini,
+}
+
+/// 路由规则匹配条件 (所有已设置的条件均满足才命中)
+///
+/// 模式字符串默认为通配符 (`claude-*-thinking`)，以 `re:` 开头时按正则处理
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteMatch {
+    /// 请求的模型名 (区分大小写)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 客户端协议，为空表示不限
+    #[serde(default)]
+    pub protocols: Vec<RouteProtocol>,
+    /// 用户令牌 ID 或用户名，为空表示不限
+    #[serde(default)]
+    pub user_tokens: Vec<String>,
+    /// 客户端 User-Agent (不区分大小写)
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// 是否开启 thinking
+    #[serde(default)]
+    pub thinking: Option<bool>,
+    /// 是否携带工具定义
+    #[serde(default)]
+    pub tools: Option<bool>,
+}
+
+/// 加权目标模型
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightedModel {
+    pub model: String,
+    #[serde(default = "default_route_weight")]
+    pub weight: u32,
+}
+
+fn default_route_weight() -> u32 {
+    1
+}
+
+/// 路由目标：单个模型，或按权重随机选择的模型列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RouteTarget {
+    Model(String),
+    Weighted(Vec<WeightedModel>),
+}
+
+/// 声明式模型路由规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingRule {
+    /// 规则名称 (仅用于日志)
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default, rename = "match")]
+    pub matcher: RouteMatch,
+    pub target: RouteTarget,
 }
 
 /// 上游代理配置
@@ -580,6 +1219,7 @@ impl Default for ProxyConfig {
             request_timeout: default_request_timeout(),
             enable_logging: true, // 默认开启，支持 token 统计功能
             debug_logging: DebugLoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
             upstream_proxy: UpstreamProxyConfig::default(),
             zai: ZaiConfig::default(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
@@ -592,6 +1232,13 @@ impl Default for ProxyConfig {
             global_system_prompt: GlobalSystemPromptConfig::default(),
             proxy_pool: ProxyPoolConfig::default(),
             image_thinking_mode: None,
+            upstream_endpoints: UpstreamEndpointsConfig::default(),
+            routing_rules: Vec::new(),
+            model_fallbacks: HashMap::new(),
+            model_prices: HashMap::new(),
+            account_concurrency: HashMap::new(),
+            batch: BatchConfig::default(),
+            admission: AdmissionConfig::default(),
         }
     }
 }
@@ -721,4 +1368,28 @@ mod tests {
         assert_eq!(normalize_proxy_url(""), "");
         assert_eq!(normalize_proxy_url("   "), "");
     }
+
+    #[test]
+    fn test_upstream_endpoints_normalization() {
+        // 旧配置文件缺少 upstream_endpoints 时使用官方默认端点
+        let cfg: UpstreamEndpointsConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(cfg, UpstreamEndpointsConfig::default());
+        assert_eq!(cfg.v1internal_base_urls.len(), 3);
+
+        let cfg = UpstreamEndpointsConfig {
+            v1internal_base_urls: vec![" http://127.0.0.1:9000/v1internal/ ".to_string(), "".to_string()],
+            quota_base_url: "http://127.0.0.1:9000/v1internal/".to_string(),
+            ..Default::default()
+        }
+        .with_env_overrides();
+        assert_eq!(cfg.v1internal_base_urls, vec!["http://127.0.0.1:9000/v1internal"]);
+        assert_eq!(cfg.quota_url(), "http://127.0.0.1:9000/v1internal:fetchAvailableModels");
+
+        let cfg = UpstreamEndpointsConfig {
+            v1internal_base_urls: vec![],
+            ..Default::default()
+        }
+        .with_env_overrides();
+        assert_eq!(cfg.v1internal_base_urls, default_v1internal_base_urls());
+    }
 }
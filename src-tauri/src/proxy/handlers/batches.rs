@@ -0,0 +1,428 @@
+// Batch API Handler
+// OpenAI `/v1/files` 与 `/v1/batches` 模拟，批次由 proxy::batch 后台任务执行
+use axum::{
+    body::Body,
+    extract::{Multipart, Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+use crate::modules::batch_db::{self, Batch, BatchFile};
+use crate::proxy::batch::{COMPLETION_WINDOW, COMPLETION_WINDOW_SECS, SUPPORTED_ENDPOINTS};
+use crate::proxy::middleware::auth::UserTokenIdentity;
+
+fn batch_error(status: StatusCode, message: &str, param: Option<&str>, code: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": message,
+                "type": "invalid_request_error",
+                "param": param,
+                "code": code
+            }
+        })),
+    )
+        .into_response()
+}
+
+fn server_error(e: String) -> Response {
+    batch_error(StatusCode::INTERNAL_SERVER_ERROR, &e, None, "server_error")
+}
+
+async fn blocking<T, F>(f: F) -> Result<T, Response>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(server_error(e)),
+        Err(e) => Err(server_error(e.to_string())),
+    }
+}
+
+/// 用户令牌只能访问自己创建的文件与批次；管理员 API Key 可访问全部
+fn owner_filter(identity: &Option<Extension<UserTokenIdentity>>) -> Option<String> {
+    identity.as_ref().map(|Extension(id)| id.token_id.clone())
+}
+
+fn is_visible(owner: &Option<String>, filter: &Option<String>) -> bool {
+    filter.is_none() || owner == filter
+}
+
+async fn load_file(file_id: String, filter: &Option<String>) -> Result<BatchFile, Response> {
+    let id = file_id.clone();
+    match blocking(move || batch_db::get_file(&id)).await? {
+        Some(file) if is_visible(&file.user_token_id, filter) => Ok(file),
+        _ => Err(batch_error(
+            StatusCode::NOT_FOUND,
+            &format!("No such File object: {}", file_id),
+            Some("id"),
+            "not_found",
+        )),
+    }
+}
+
+/// 令牌配置了模型白名单时，创建批次前逐行检查模型，返回第一条不允许的 (custom_id, model)
+///
+/// 格式错误的行交给后台校验报告，这里不处理
+fn find_disallowed_model(token_id: &str, input_file_id: &str, endpoint: &str) -> Result<Option<(String, String)>, String> {
+    let Some(token) = crate::modules::user_token_db::get_token_by_id(token_id)? else {
+        return Ok(None);
+    };
+    if token.allowed_models.is_empty() {
+        return Ok(None);
+    }
+    let file = std::fs::File::open(batch_db::get_file_path(input_file_id)?)
+        .map_err(|e| format!("Failed to open input file: {}", e))?;
+    let max_requests = crate::proxy::config::get_batch_config().max_requests_per_batch;
+    let Ok(items) = crate::proxy::batch::parse_batch_input(std::io::BufReader::new(file), endpoint, max_requests) else {
+        return Ok(None);
+    };
+    for item in items {
+        let model = item.body.get("model").and_then(|m| m.as_str()).unwrap_or("");
+        if model.is_empty() || !crate::modules::user_token_db::is_model_allowed(&token.allowed_models, model) {
+            return Ok(Some((item.custom_id, model.to_string())));
+        }
+    }
+    Ok(None)
+}
+
+async fn load_batch(batch_id: String, filter: &Option<String>) -> Result<Batch, Response> {
+    let id = batch_id.clone();
+    match blocking(move || batch_db::get_batch(&id)).await? {
+        Some(batch) if is_visible(&batch.user_token_id, filter) => Ok(batch),
+        _ => Err(batch_error(
+            StatusCode::NOT_FOUND,
+            &format!("No batch found with id '{}'.", batch_id),
+            Some("batch_id"),
+            "not_found",
+        )),
+    }
+}
+
+/// POST /v1/files (multipart: file + purpose)
+pub async fn handle_upload_file(
+    identity: Option<Extension<UserTokenIdentity>>,
+    mut multipart: Multipart,
+) -> Response {
+    let id = format!("file-{}", uuid::Uuid::new_v4().simple());
+    let path = match batch_db::get_file_path(&id) {
+        Ok(path) => path,
+        Err(e) => return server_error(e),
+    };
+
+    let mut purpose: Option<String> = None;
+    let mut filename: Option<String> = None;
+    let mut bytes: i64 = 0;
+
+    let upload = async {
+        while let Some(mut field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| format!("Invalid multipart body: {}", e))?
+        {
+            match field.name().unwrap_or("") {
+                "purpose" => {
+                    purpose = Some(field.text().await.map_err(|e| e.to_string())?);
+                }
+                "file" => {
+                    filename = Some(field.file_name().unwrap_or("batch.jsonl").to_string());
+                    // 分块写入磁盘，避免大文件整体驻留内存
+                    let mut out = tokio::fs::File::create(&path)
+                        .await
+                        .map_err(|e| format!("Failed to create file: {}", e))?;
+                    while let Some(chunk) = field.chunk().await.map_err(|e| e.to_string())? {
+                        out.write_all(&chunk)
+                            .await
+                            .map_err(|e| format!("Failed to write file: {}", e))?;
+                        bytes += chunk.len() as i64;
+                    }
+                    out.flush().await.map_err(|e| e.to_string())?;
+                }
+                _ => {}
+            }
+        }
+        Ok::<(), String>(())
+    };
+
+    let result = upload.await;
+    let invalid = match (&result, purpose.as_deref(), &filename) {
+        (Err(e), _, _) => Some((e.clone(), None)),
+        (_, _, None) => Some(("'file' is a required field".to_string(), Some("file"))),
+        (_, Some("batch"), _) => None,
+        (_, _, _) => Some((
+            "Only purpose 'batch' is supported".to_string(),
+            Some("purpose"),
+        )),
+    };
+    if let Some((message, param)) = invalid {
+        let _ = tokio::fs::remove_file(&path).await;
+        return batch_error(StatusCode::BAD_REQUEST, &message, param, "invalid_request");
+    }
+
+    let file = BatchFile {
+        id,
+        filename: filename.unwrap_or_default(),
+        purpose: "batch".to_string(),
+        bytes,
+        created_at: chrono::Utc::now().timestamp(),
+        user_token_id: owner_filter(&identity),
+    };
+    let stored = file.clone();
+    if let Err(resp) = blocking(move || batch_db::insert_file(&stored)).await {
+        let _ = tokio::fs::remove_file(&path).await;
+        return resp;
+    }
+
+    info!("[Batch] File uploaded: {} ({} bytes)", file.id, file.bytes);
+    Json(file.to_openai()).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListFilesQuery {
+    #[serde(default)]
+    pub purpose: Option<String>,
+}
+
+/// GET /v1/files
+pub async fn handle_list_files(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Query(query): Query<ListFilesQuery>,
+) -> Response {
+    let filter = owner_filter(&identity);
+    match blocking(move || batch_db::list_files(query.purpose.as_deref(), filter.as_deref())).await {
+        Ok(files) => Json(json!({
+            "object": "list",
+            "data": files.iter().map(|f| f.to_openai()).collect::<Vec<Value>>(),
+            "has_more": false,
+        }))
+        .into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// GET /v1/files/:file_id
+pub async fn handle_get_file(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Path(file_id): Path<String>,
+) -> Response {
+    match load_file(file_id, &owner_filter(&identity)).await {
+        Ok(file) => Json(file.to_openai()).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// DELETE /v1/files/:file_id
+pub async fn handle_delete_file(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Path(file_id): Path<String>,
+) -> Response {
+    let file = match load_file(file_id, &owner_filter(&identity)).await {
+        Ok(file) => file,
+        Err(resp) => return resp,
+    };
+    let id = file.id.clone();
+    match blocking(move || batch_db::delete_file(&id)).await {
+        Ok(_) => Json(json!({
+            "id": file.id,
+            "object": "file",
+            "deleted": true
+        }))
+        .into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// GET /v1/files/:file_id/content
+pub async fn handle_get_file_content(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Path(file_id): Path<String>,
+) -> Response {
+    let file = match load_file(file_id, &owner_filter(&identity)).await {
+        Ok(file) => file,
+        Err(resp) => return resp,
+    };
+    let path = match batch_db::get_file_path(&file.id) {
+        Ok(path) => path,
+        Err(e) => return server_error(e),
+    };
+    match tokio::fs::File::open(&path).await {
+        Ok(f) => (
+            [(header::CONTENT_TYPE, "application/jsonl")],
+            Body::from_stream(tokio_util::io::ReaderStream::new(f)),
+        )
+            .into_response(),
+        Err(e) => server_error(format!("Failed to read file: {}", e)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBatchRequest {
+    pub input_file_id: String,
+    pub endpoint: String,
+    pub completion_window: String,
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+/// POST /v1/batches
+pub async fn handle_create_batch(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Json(body): Json<Value>,
+) -> Response {
+    let req: CreateBatchRequest = match serde_json::from_value(body) {
+        Ok(req) => req,
+        Err(e) => {
+            return batch_error(StatusCode::BAD_REQUEST, &format!("Invalid request: {}", e), None, "invalid_request");
+        }
+    };
+    if !SUPPORTED_ENDPOINTS.contains(&req.endpoint.as_str()) {
+        return batch_error(
+            StatusCode::BAD_REQUEST,
+            &format!("Unsupported endpoint. Supported: {}", SUPPORTED_ENDPOINTS.join(", ")),
+            Some("endpoint"),
+            "invalid_value",
+        );
+    }
+    if req.completion_window != COMPLETION_WINDOW {
+        return batch_error(
+            StatusCode::BAD_REQUEST,
+            &format!("completion_window must be '{}'", COMPLETION_WINDOW),
+            Some("completion_window"),
+            "invalid_value",
+        );
+    }
+
+    let filter = owner_filter(&identity);
+    let input = match load_file(req.input_file_id.clone(), &filter).await {
+        Ok(file) => file,
+        Err(resp) => return resp,
+    };
+    if input.purpose != "batch" {
+        return batch_error(
+            StatusCode::BAD_REQUEST,
+            "The input file must be uploaded with purpose 'batch'",
+            Some("input_file_id"),
+            "invalid_value",
+        );
+    }
+
+    // [FIX] 批处理行不经过鉴权中间件，创建时先按令牌白名单检查每一行的模型
+    if let Some(Extension(id)) = &identity {
+        let (token_id, file_id, endpoint) = (id.token_id.clone(), input.id.clone(), req.endpoint.clone());
+        match blocking(move || find_disallowed_model(&token_id, &file_id, &endpoint)).await {
+            Ok(None) => {}
+            Ok(Some((custom_id, model))) => {
+                let message = if model.is_empty() {
+                    format!("Request '{}' does not specify a model, but this token is restricted to specific models.", custom_id)
+                } else {
+                    format!("Request '{}' uses model '{}', which is not allowed for this token.", custom_id, model)
+                };
+                return batch_error(StatusCode::FORBIDDEN, &message, Some("input_file_id"), "model_not_allowed");
+            }
+            Err(resp) => return resp,
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let batch = Batch {
+        id: format!("batch_{}", uuid::Uuid::new_v4().simple()),
+        endpoint: req.endpoint,
+        input_file_id: input.id,
+        completion_window: req.completion_window,
+        status: "validating".to_string(),
+        metadata: req.metadata,
+        created_at: now,
+        expires_at: now + COMPLETION_WINDOW_SECS,
+        user_token_id: filter,
+        ..Default::default()
+    };
+    let stored = batch.clone();
+    if let Err(resp) = blocking(move || batch_db::save_batch(&stored)).await {
+        return resp;
+    }
+
+    info!("[Batch] Created {} for {} ({})", batch.id, batch.input_file_id, batch.endpoint);
+    crate::proxy::batch::wake();
+    Json(batch.to_openai()).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListBatchesQuery {
+    #[serde(default)]
+    pub after: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// GET /v1/batches
+pub async fn handle_list_batches(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Query(query): Query<ListBatchesQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let filter = owner_filter(&identity);
+    // 多取一条用于判断 has_more
+    let batches = match blocking(move || batch_db::list_batches(limit + 1, query.after.as_deref(), filter.as_deref())).await {
+        Ok(batches) => batches,
+        Err(resp) => return resp,
+    };
+    let has_more = batches.len() > limit;
+    let data: Vec<Value> = batches.iter().take(limit).map(|b| b.to_openai()).collect();
+    Json(json!({
+        "object": "list",
+        "first_id": data.first().and_then(|b| b.get("id")).cloned(),
+        "last_id": data.last().and_then(|b| b.get("id")).cloned(),
+        "has_more": has_more,
+        "data": data,
+    }))
+    .into_response()
+}
+
+/// GET /v1/batches/:batch_id
+pub async fn handle_get_batch(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    match load_batch(batch_id, &owner_filter(&identity)).await {
+        Ok(batch) => Json(batch.to_openai()).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// POST /v1/batches/:batch_id/cancel
+pub async fn handle_cancel_batch(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let batch = match load_batch(batch_id, &owner_filter(&identity)).await {
+        Ok(batch) => batch,
+        Err(resp) => return resp,
+    };
+    if !matches!(batch.status.as_str(), "validating" | "in_progress" | "cancelling") {
+        return batch_error(
+            StatusCode::CONFLICT,
+            &format!("Cannot cancel a batch with status '{}'.", batch.status),
+            None,
+            "invalid_state",
+        );
+    }
+
+    let id = batch.id.clone();
+    match blocking(move || batch_db::request_cancel(&id)).await {
+        Ok(Some(batch)) => {
+            info!("[Batch] Cancel requested for {}", batch.id);
+            crate::proxy::batch::wake();
+            Json(batch.to_openai()).into_response()
+        }
+        Ok(None) => server_error(format!("Batch {} disappeared", batch.id)),
+        Err(resp) => resp,
+    }
+}
@@ -10,7 +10,7 @@ use bytes::Bytes;
 use futures::StreamExt;
 use serde_json::{json, Value};
 use tokio::time::Duration;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, Instrument};
 
 use crate::proxy::mappers::claude::{
     transform_claude_request_in, transform_response, create_claude_sse_stream, ClaudeRequest,
@@ -25,7 +25,11 @@ use crate::proxy::debug_logger;
 use crate::proxy::upstream::client::mask_email;
 use crate::proxy::common::client_adapter::CLIENT_ADAPTERS; // [NEW] Import Adapter Registry
 use axum::http::HeaderMap;
+use axum::Extension;
 use std::sync::{atomic::Ordering, Arc};
+use crate::proxy::common::model_mapping::RouteContext;
+use crate::proxy::config::RouteProtocol;
+use crate::proxy::middleware::auth::UserTokenIdentity;
 use crate::proxy::model_specs; // [NEW]
 
 // ===== Task #6: OpenCode variants thinking config mapping =====
@@ -235,7 +239,7 @@ The structure MUST be as follows:
 
 // ===== 统一退避策略模块 =====
 // 移除本地重复定义，使用 common 中的统一实现
-use super::common::{determine_retry_strategy, apply_retry_strategy, should_rotate_account, RetryStrategy};
+use super::common::{determine_retry_strategy, apply_retry_strategy, get_token_with_model_fallback, should_rotate_account, RetryStrategy};
 
 // ===== 退避策略模块结束 =====
 
@@ -245,6 +249,7 @@ use super::common::{determine_retry_strategy, apply_retry_strategy, should_rotat
 pub async fn handle_messages(
     State(state): State<AppState>,
     headers: HeaderMap,
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(body): Json<Value>,
 ) -> Response {
     // [FIX] 保存原始请求体的完整副本，用于日志记录
@@ -253,11 +258,14 @@ pub async fn handle_messages(
     
     tracing::debug!("handle_messages called. Body JSON len: {}", body.to_string().len());
     
-    // 生成随机 Trace ID 用户追踪
-    let trace_id: String = rand::Rng::sample_iter(rand::thread_rng(), &rand::distributions::Alphanumeric)
-        .take(6)
-        .map(char::from)
-        .collect::<String>().to_lowercase();
+    // 生成随机 Trace ID 用户追踪 (优先使用 trace 中间件分配的 trace id)
+    let trace_id: String = crate::proxy::telemetry::current_trace_id().unwrap_or_else(|| {
+        rand::Rng::sample_iter(rand::thread_rng(), &rand::distributions::Alphanumeric)
+            .take(6)
+            .map(char::from)
+            .collect::<String>()
+            .to_lowercase()
+    });
     let debug_cfg = state.debug_logging.read().await.clone();
     
     // [NEW] Detect Client Adapter
@@ -528,13 +536,39 @@ pub async fn handle_messages(
     let mut last_email: Option<String> = None;
     let mut last_mapped_model: Option<String> = None;
     let mut last_status = StatusCode::SERVICE_UNAVAILABLE; // Default to 503 if no response reached
-    
-    for attempt in 0..max_attempts {
-        // 2. 模型路由解析
-        let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+
+    // [NEW] 路由规则上下文 (协议 / 用户令牌 / UA / thinking / tools)
+    let route_ctx = RouteContext::new(RouteProtocol::Anthropic, &headers)
+        .with_identity(identity.as_ref().map(|Extension(id)| id))
+        .with_features(
+            request.thinking.as_ref().is_some_and(|t| t.type_ != "disabled"),
+            request.tools.as_ref().is_some_and(|t| !t.is_empty()),
+        );
+
+    // 2. 模型路由解析 (循环外解析一次，避免加权路由在每次重试时重新抽签)
+    // 仅当重试改写了请求模型 (如剥离 -thinking 后缀) 时才重新解析
+    let mut routed_model = (
+        request_for_body.model.clone(),
+        crate::proxy::common::model_mapping::resolve_model_route_with_context(
             &request_for_body.model,
             &*state.custom_mapping.read().await,
-        );
+            &route_ctx,
+        ),
+    );
+    let mut background_route: Option<(&'static str, String)> = None;
+    
+    for attempt in 0..max_attempts {
+        if routed_model.0 != request_for_body.model {
+            routed_model = (
+                request_for_body.model.clone(),
+                crate::proxy::common::model_mapping::resolve_model_route_with_context(
+                    &request_for_body.model,
+                    &*state.custom_mapping.read().await,
+                    &route_ctx,
+                ),
+            );
+        }
+        let mut mapped_model = routed_model.1.clone();
         last_mapped_model = Some(mapped_model.clone());
         
         // 将 Claude 工具转为 Value 数组以便探测联网
@@ -542,7 +576,7 @@ pub async fn handle_messages(
             list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
         });
 
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        let mut config = crate::proxy::mappers::common_utils::resolve_request_config(
             &request_for_body.model,
             &mapped_model,
             &tools_val,
@@ -558,8 +592,34 @@ pub async fn handle_messages(
         let session_id = Some(session_id_str.as_str());
 
         let force_rotate_token = attempt > 0;
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager.get_token(&config.request_type, force_rotate_token, session_id, &config.final_model).await {
-            Ok(t) => t,
+        let (access_token, project_id, email, account_id, _wait_ms) = match get_token_with_model_fallback(
+            &token_manager,
+            &config.request_type,
+            force_rotate_token,
+            session_id,
+            &mapped_model,
+            &config.final_model,
+            &trace_id,
+        )
+        .await
+        {
+            Ok((t, fallback_model)) => {
+                // [NEW] 跨模型降级：后续转换与 X-Mapped-Model 均使用实际服务的模型
+                if let Some(fallback) = fallback_model {
+                    mapped_model = fallback;
+                    last_mapped_model = Some(mapped_model.clone());
+                    config = crate::proxy::mappers::common_utils::resolve_request_config(
+                        &request_for_body.model,
+                        &mapped_model,
+                        &tools_val,
+                        request.size.as_deref(),
+                        request.quality.as_deref(),
+                        None,
+                        None,
+                    );
+                }
+                t
+            }
             Err(e) => {
                 let safe_message = if e.contains("invalid_grant") {
                     "OAuth refresh failed (invalid_grant): refresh_token likely revoked/expired; reauthorize account(s) to restore service.".to_string()
@@ -583,6 +643,7 @@ pub async fn handle_messages(
             }
         };
 
+        crate::proxy::metrics::record_attempt("anthropic", attempt, last_email.as_deref(), &email);
         last_email = Some(email.clone());
         info!("✓ Using account: {} (type: {})", email, config.request_type);
         
@@ -600,10 +661,18 @@ pub async fn handle_messages(
             
             // [FIX] 必须根据虚拟 ID Re-resolve 路由，以支持用户自定义映射 (如 internal-task -> gemini-3)
             // 否则会直接使用 generic ID 导致下游无法识别或只能使用静态默认值
-            let resolved_model = crate::proxy::common::model_mapping::resolve_model_route(
-                virtual_model_id, 
-                &*state.custom_mapping.read().await
-            );
+            let resolved_model = match &background_route {
+                Some((id, model)) if *id == virtual_model_id => model.clone(),
+                _ => {
+                    let model = crate::proxy::common::model_mapping::resolve_model_route_with_context(
+                        virtual_model_id,
+                        &*state.custom_mapping.read().await,
+                        &route_ctx,
+                    );
+                    background_route = Some((virtual_model_id, model.clone()));
+                    model
+                }
+            };
 
             info!(
                 "[{}][AUTO] 检测到后台任务 (类型: {:?}), 路由重定向: {} -> {} (最终物理模型: {})",
@@ -661,6 +730,14 @@ pub async fn handle_messages(
                 trace_id, usage_ratio * 100.0, raw_estimated, estimated_usage, context_limit, calibrator.get_factor()
             );
 
+            // 链路追踪: 记录上下文压力与最终生效的压缩层
+            let compression_span = tracing::info_span!(
+                target: crate::proxy::telemetry::SPAN_TARGET,
+                "context_compression",
+                context.usage_ratio = usage_ratio as f64,
+                context.layer = tracing::field::Empty,
+            );
+
             // ===== Layer 1: Tool Message Trimming (L1 threshold) =====
             // Borrowed from Practical-Guide-to-Context-Engineering
             // Advantage: Completely cache-friendly (only removes messages, doesn't modify content)
@@ -671,6 +748,7 @@ pub async fn handle_messages(
                         trace_id, usage_ratio * 100.0, threshold_l1 * 100.0
                     );
                     compression_applied = true;
+                    compression_span.record("context.layer", 1);
                     
                     // Re-estimate after trimming (with calibration)
                     let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
@@ -711,6 +789,7 @@ pub async fn handle_messages(
                 ) {
                     is_purified = true; // Still breaks cache, but preserves signatures
                     compression_applied = true;
+                    compression_span.record("context.layer", 2);
                     
                     let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
                     let new_usage = calibrator.calibrate(new_raw);
@@ -737,7 +816,11 @@ pub async fn handle_messages(
                 // Clone token_manager Arc to avoid borrow issues
                 let token_manager_clone = token_manager.clone();
                 
-                match try_compress_with_summary(&request_with_mapped, &trace_id, &token_manager_clone).await {
+                compression_span.record("context.layer", 3);
+                match try_compress_with_summary(&request_with_mapped, &trace_id, &token_manager_clone)
+                    .instrument(compression_span.clone())
+                    .await
+                {
                     Ok(forked_request) => {
                         info!(
                             "[{}] [Layer-3] Fork successful: {} → {} messages",
@@ -947,17 +1030,20 @@ pub async fn handle_messages(
                 // [FIX #530/#529/#859] Enhanced Peek logic to handle heartbeats and slow start
                 // We must pre-read until we find a MEANINGFUL content block (like message_start).
                 // If we only get heartbeats (ping) and then the stream dies, we should rotate account.
-                let mut claude_stream = create_claude_sse_stream(
-                    gemini_stream,
-                    trace_id.clone(),
-                    email.clone(),
-                    Some(session_id_str.clone()),
-                    scaling_enabled,
-                    context_limit,
-                    Some(raw_estimated), // [FIX] Pass estimated tokens for calibrator learning
-                    current_message_count, // [NEW v4.0.0] Pass message count for rewind detection
-                    client_adapter.clone(), // [NEW] Pass client adapter
-                    registered_tool_names, // [FIX #MCP] Pass tool names for fuzzy matching
+                let mut claude_stream = crate::proxy::telemetry::instrument_stream(
+                    create_claude_sse_stream(
+                        gemini_stream,
+                        trace_id.clone(),
+                        email.clone(),
+                        Some(session_id_str.clone()),
+                        scaling_enabled,
+                        context_limit,
+                        Some(raw_estimated), // [FIX] Pass estimated tokens for calibrator learning
+                        current_message_count, // [NEW v4.0.0] Pass message count for rewind detection
+                        client_adapter.clone(), // [NEW] Pass client adapter
+                        registered_tool_names, // [FIX #MCP] Pass tool names for fuzzy matching
+                    ),
+                    crate::proxy::telemetry::stream_conversion_span("anthropic", &mapped_model),
                 );
 
                 let mut first_data_chunk = None;
@@ -1431,10 +1517,12 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
     }))
 }
 
-/// 计算 tokens (占位符)
+/// 计算 tokens
+/// z.ai 接管时透传；否则走 Google 账号池 countTokens，失败时回退到校准估算
 pub async fn handle_count_tokens(
     State(state): State<AppState>,
     headers: HeaderMap,
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(body): Json<Value>,
 ) -> Response {
     let zai = state.zai.read().await.clone();
@@ -1452,11 +1540,73 @@ pub async fn handle_count_tokens(
         .await;
     }
 
-    Json(json!({
-        "input_tokens": 0,
-        "output_tokens": 0
-    }))
-    .into_response()
+    let mut request: ClaudeRequest = match serde_json::from_value(body) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "type": "error",
+                    "error": {
+                        "type": "invalid_request_error",
+                        "message": format!("Invalid request body: {}", e)
+                    }
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let trace_id = format!("count_{}", chrono::Utc::now().timestamp_subsec_millis());
+
+    clean_cache_control_from_messages(&mut request.messages);
+    merge_consecutive_messages(&mut request.messages);
+
+    // 与 /v1/messages 使用相同的路由上下文，保证计数的模型与实际请求一致
+    let route_ctx = RouteContext::new(RouteProtocol::Anthropic, &headers)
+        .with_identity(identity.as_ref().map(|Extension(id)| id))
+        .with_features(
+            request.thinking.as_ref().is_some_and(|t| t.type_ != "disabled"),
+            request.tools.as_ref().is_some_and(|t| !t.is_empty()),
+        );
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route_with_context(
+        &request.model,
+        &*state.custom_mapping.read().await,
+        &route_ctx,
+    );
+
+    // thinking.budget_tokens 是输出侧预留，不计入输入 token
+    let mut estimate_req = request.clone();
+    estimate_req.thinking = None;
+    let raw_estimated = ContextManager::estimate_token_usage(&estimate_req);
+
+    let mut mapped_request = request;
+    mapped_request.model = mapped_model.clone();
+    let input_tokens = match transform_claude_request_in(&mapped_request, "", false, None, &trace_id, None) {
+        Ok(v1_body) => {
+            let inner = v1_body.get("request").cloned().unwrap_or(Value::Null);
+            crate::proxy::handlers::common::count_tokens_with_fallback(
+                &state,
+                &mapped_model,
+                &inner,
+                raw_estimated,
+                true,
+                &trace_id,
+            )
+            .await
+        }
+        Err(e) => {
+            debug!("[{}] countTokens transform failed: {}, using estimate", trace_id, e);
+            get_calibrator().calibrate(raw_estimated)
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [("X-Mapped-Model", mapped_model.as_str())],
+        Json(json!({ "input_tokens": input_tokens })),
+    )
+        .into_response()
 }
 
 // 移除已失效的简单单元测试，后续将补全完整的集成测试
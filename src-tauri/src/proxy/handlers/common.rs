@@ -3,6 +3,7 @@ use tracing::{debug, info};
 use axum::{http::StatusCode, response::{IntoResponse, Response}, Json, extract::State};
 use serde_json::{json, Value};
 use crate::proxy::server::AppState;
+use crate::proxy::token_manager::TokenErrorKind;
 
 // ===== 统一重试与退避策略 =====
 
@@ -76,6 +77,7 @@ pub fn determine_retry_strategy(
 }
 
 /// 执行退避策略并返回是否应该继续重试
+#[tracing::instrument(target = "otel", name = "retry.backoff", skip(trace_id))]
 pub async fn apply_retry_strategy(
     strategy: RetryStrategy,
     attempt: usize,
@@ -145,6 +147,207 @@ pub fn should_rotate_account(status_code: u16) -> bool {
     }
 }
 
+// ===== 跨模型降级 =====
+
+/// (access_token, project_id, email, account_id, wait_ms)
+pub type AcquiredToken = (String, String, String, String, u64);
+
+/// 获取 Token；目标模型没有可用账号 (全部限流 / 配额保护) 时沿降级链尝试备选模型
+///
+/// - `mapped_model`: 用于查找降级链的物理模型名
+/// - `target_model`: 传给 TokenManager 的模型名 (通常为 `config.final_model`)
+///
+/// 返回 Token 以及实际使用的降级模型 (`None` 表示未降级)；全部失败时返回原始错误
+pub async fn get_token_with_model_fallback(
+    token_manager: &crate::proxy::TokenManager,
+    quota_group: &str,
+    force_rotate: bool,
+    session_id: Option<&str>,
+    mapped_model: &str,
+    target_model: &str,
+    trace_id: &str,
+) -> Result<(AcquiredToken, Option<String>), String> {
+    let chains = crate::proxy::config::get_model_fallbacks();
+    let fallbacks = crate::proxy::common::model_mapping::resolve_fallback_chain(mapped_model, &chains);
+
+    // 目标模型与降级链全部被锁定时先排队等待解锁 (每个请求只排队一次，重试尝试直接跳过)
+    let mut candidates = vec![target_model.to_string()];
+    candidates.extend(fallbacks.iter().cloned());
+    crate::proxy::admission::admit(token_manager, &candidates, trace_id).await;
+
+    let original_err = match token_manager
+        .acquire_token(quota_group, force_rotate, session_id, target_model)
+        .await
+    {
+        Ok(token) => return Ok((token, None)),
+        Err(e) => e,
+    };
+
+    // 账号池为空或授权失效时降级没有意义
+    if matches!(
+        original_err.kind,
+        TokenErrorKind::PoolEmpty | TokenErrorKind::AuthRevoked
+    ) {
+        return Err(original_err.message);
+    }
+
+    for fallback in fallbacks {
+        match token_manager
+            .get_token(quota_group, force_rotate, session_id, &fallback)
+            .await
+        {
+            Ok(token) => {
+                info!(
+                    "[{}] [Model-Fallback] {} unavailable ({}), falling back to {}",
+                    trace_id, mapped_model, original_err, fallback
+                );
+                return Ok((token, Some(fallback)));
+            }
+            Err(e) => {
+                debug!("[{}] [Model-Fallback] {} also unavailable: {}", trace_id, fallback, e);
+            }
+        }
+    }
+
+    Err(original_err.message)
+}
+
+// ===== Token 计数 (countTokens) =====
+
+/// 将 generateContent 形式的内层请求转换为 v1internal countTokens 请求体
+///
+/// v1internal countTokens 只接受 `contents`，因此 systemInstruction 和 tools
+/// 会被折叠为额外的 user 文本段，以便上游计数覆盖完整输入。
+pub fn build_count_tokens_request(mapped_model: &str, inner_request: &Value) -> Value {
+    let mut contents: Vec<Value> = Vec::new();
+
+    if let Some(parts) = inner_request
+        .get("systemInstruction")
+        .and_then(|s| s.get("parts"))
+        .and_then(|p| p.as_array())
+    {
+        if !parts.is_empty() {
+            contents.push(json!({ "role": "user", "parts": parts }));
+        }
+    }
+
+    if let Some(arr) = inner_request.get("contents").and_then(|c| c.as_array()) {
+        contents.extend(arr.iter().cloned());
+    }
+
+    if let Some(tools) = inner_request.get("tools").filter(|t| !t.is_null()) {
+        contents.push(json!({
+            "role": "user",
+            "parts": [{ "text": tools.to_string() }]
+        }));
+    }
+
+    json!({
+        "request": {
+            "model": format!("models/{}", mapped_model),
+            "contents": contents,
+        }
+    })
+}
+
+/// 计算输入 token 数：优先调用上游 v1internal countTokens，
+/// 无可用账号或上游失败时回退到经过校准的本地估算值。
+///
+/// `raw_estimated` 为 ContextManager 的原始估算值 (未校准)。
+/// 校准器只针对 Claude 估算器 (`estimate_token_usage`) 学习，
+/// 因此仅当 `calibrated` 为 true 时才校准估算值并记录上游真实计数。
+pub async fn count_tokens_with_fallback(
+    state: &AppState,
+    mapped_model: &str,
+    inner_request: &Value,
+    raw_estimated: u32,
+    calibrated: bool,
+    trace_id: &str,
+) -> u32 {
+    let calibrator = crate::proxy::mappers::estimation_calibrator::get_calibrator();
+    let estimated = if calibrated {
+        calibrator.calibrate(raw_estimated)
+    } else {
+        raw_estimated
+    };
+
+    let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        mapped_model,
+        mapped_model,
+        &None,
+        None,
+        None,
+        None,
+        None,
+    );
+
+    let (access_token, _project_id, email, account_id, _wait_ms) = match state
+        .token_manager
+        .get_token(&config.request_type, false, None, &config.final_model)
+        .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            debug!(
+                "[{}] countTokens: no account available ({}), using estimate {}",
+                trace_id, e, estimated
+            );
+            return estimated;
+        }
+    };
+
+    let body = build_count_tokens_request(&config.final_model, inner_request);
+    let result = match state
+        .upstream
+        .call_v1_internal("countTokens", &access_token, body, None, Some(account_id.as_str()))
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            debug!("[{}] countTokens upstream call failed: {}, using estimate", trace_id, e);
+            return estimated;
+        }
+    };
+
+    let status = result.response.status();
+    if !status.is_success() {
+        debug!(
+            "[{}] countTokens upstream returned {} on {}, using estimate",
+            trace_id,
+            status,
+            crate::proxy::upstream::client::mask_email(&email)
+        );
+        return estimated;
+    }
+
+    let actual = match result.response.json::<Value>().await {
+        Ok(v) => v
+            .get("totalTokens")
+            .or_else(|| v.get("response").and_then(|r| r.get("totalTokens")))
+            .and_then(|t| t.as_u64()),
+        Err(e) => {
+            debug!("[{}] countTokens response parse failed: {}", trace_id, e);
+            None
+        }
+    };
+
+    match actual {
+        Some(total) => {
+            let total = total.min(u32::MAX as u64) as u32;
+            // 真实计数同样可用于校准本地估算 (仅限 Claude 估算器)
+            if calibrated {
+                calibrator.record(raw_estimated, total);
+            }
+            debug!(
+                "[{}] countTokens: upstream={} estimate={} model={}",
+                trace_id, total, estimated, config.final_model
+            );
+            total
+        }
+        None => estimated,
+    }
+}
+
 /// Detects model capabilities and configuration
 /// POST /v1/models/detect
 pub async fn handle_detect_model(
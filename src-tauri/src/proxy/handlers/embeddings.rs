@@ -0,0 +1,322 @@
+// Embeddings Handler
+// OpenAI `/v1/embeddings` 与 Gemini `:embedContent` / `:batchEmbedContents`
+// 统一映射到 v1internal batchEmbedContents，复用 TokenManager 账号轮换与重试策略
+use axum::{
+    extract::{Json, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde_json::{json, Value};
+use tracing::{debug, info};
+
+use crate::proxy::common::model_mapping::RouteContext;
+use crate::proxy::config::RouteProtocol;
+use crate::proxy::handlers::common::{
+    apply_retry_strategy, determine_retry_strategy, get_token_with_model_fallback,
+    should_rotate_account,
+};
+use crate::proxy::mappers::context_manager::ContextManager;
+use crate::proxy::mappers::openai::embeddings::{
+    build_embed_requests, transform_embeddings_response, OpenAIEmbeddingRequest,
+};
+use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::client::mask_email;
+
+const MAX_RETRY_ATTEMPTS: usize = 3;
+
+/// 单次 batchEmbedContents 的最大条目数 (上游限制)
+const MAX_BATCH_SIZE: usize = 100;
+
+/// 上游 embedding 结果
+struct EmbedResult {
+    embeddings: Vec<Value>,
+    email: String,
+    mapped_model: String,
+    prompt_tokens: u32,
+}
+
+/// 处理 OpenAI Embeddings 请求
+pub async fn handle_embeddings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    identity: Option<Extension<UserTokenIdentity>>,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let req: OpenAIEmbeddingRequest = serde_json::from_value(body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+    let requests = build_embed_requests(&req).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let route_ctx = RouteContext::new(RouteProtocol::Openai, &headers)
+        .with_identity(identity.as_ref().map(|Extension(id)| id));
+    let result = embed_with_rotation(&state, &req.model, &route_ctx, requests, "openai").await?;
+
+    let response = transform_embeddings_response(
+        &result.embeddings,
+        &req.model,
+        req.encoding_format.as_deref(),
+        result.prompt_tokens,
+    );
+    Ok((
+        StatusCode::OK,
+        [
+            ("X-Account-Email", result.email.as_str()),
+            ("X-Mapped-Model", result.mapped_model.as_str()),
+        ],
+        Json(response),
+    )
+        .into_response())
+}
+
+/// 处理 Gemini 原生 embedContent / batchEmbedContents (由 gemini handler 按 action 分发)
+pub async fn handle_gemini_embeddings(
+    state: AppState,
+    model_name: &str,
+    method: &str,
+    headers: &HeaderMap,
+    identity: Option<&UserTokenIdentity>,
+    body: Value,
+) -> Response {
+    let single = method == "embedContent";
+    let requests = if single {
+        vec![body]
+    } else {
+        match body.get("requests").and_then(|r| r.as_array()) {
+            Some(requests) if !requests.is_empty() => requests.clone(),
+            _ => {
+                return gemini_error(StatusCode::BAD_REQUEST, "'requests' must be a non-empty array");
+            }
+        }
+    };
+
+    let route_ctx = RouteContext::new(RouteProtocol::Gemini, headers).with_identity(identity);
+    let result = match embed_with_rotation(&state, model_name, &route_ctx, requests, "gemini").await {
+        Ok(result) => result,
+        Err((status, message)) => return gemini_error(status, &message),
+    };
+
+    // usageMetadata 供监控与 token 统计使用 (客户端 SDK 会忽略未知字段)
+    let usage = json!({
+        "promptTokenCount": result.prompt_tokens,
+        "totalTokenCount": result.prompt_tokens,
+    });
+    let response = if single {
+        json!({
+            "embedding": result.embeddings.first().cloned().unwrap_or_else(|| json!({ "values": [] })),
+            "usageMetadata": usage,
+        })
+    } else {
+        json!({
+            "embeddings": result.embeddings,
+            "usageMetadata": usage,
+        })
+    };
+
+    (
+        StatusCode::OK,
+        [
+            ("X-Account-Email", result.email.as_str()),
+            ("X-Mapped-Model", result.mapped_model.as_str()),
+        ],
+        Json(response),
+    )
+        .into_response()
+}
+
+fn gemini_error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": message,
+                "status": "UPSTREAM_ERROR"
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 估算输入 token (上游 embedding 响应不带 usage 时使用)
+fn estimate_prompt_tokens(requests: &[Value]) -> u32 {
+    let contents: Vec<Value> = requests
+        .iter()
+        .filter_map(|r| r.get("content").cloned())
+        .collect();
+    ContextManager::estimate_gemini_request_tokens(&json!({ "contents": contents }))
+}
+
+/// 带账号轮换的 batchEmbedContents 调用
+async fn embed_with_rotation(
+    state: &AppState,
+    original_model: &str,
+    route_ctx: &RouteContext,
+    mut requests: Vec<Value>,
+    protocol: &str,
+) -> Result<EmbedResult, (StatusCode, String)> {
+    if requests.len() > MAX_BATCH_SIZE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} inputs are allowed per request", MAX_BATCH_SIZE),
+        ));
+    }
+
+    let trace_id = crate::proxy::telemetry::current_trace_id()
+        .unwrap_or_else(|| format!("embed_{}", chrono::Utc::now().timestamp_subsec_millis()));
+    let token_manager = state.token_manager.clone();
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(token_manager.len()).max(1);
+    let mut last_error = String::new();
+    let mut last_email: Option<String> = None;
+
+    info!(
+        "[{}] Embeddings request: model={}, inputs={}",
+        trace_id,
+        original_model,
+        requests.len()
+    );
+
+    // 循环外解析一次，避免加权路由在每次重试时重新抽签
+    let routed_model = crate::proxy::common::model_mapping::resolve_model_route_with_context(
+        original_model,
+        &*state.custom_mapping.read().await,
+        route_ctx,
+    );
+
+    for attempt in 0..max_attempts {
+        let mut mapped_model = routed_model.clone();
+
+        let (access_token, project_id, email, account_id, _wait_ms) = match get_token_with_model_fallback(
+            &token_manager,
+            "text",
+            attempt > 0,
+            None,
+            &mapped_model,
+            &mapped_model,
+            &trace_id,
+        )
+        .await
+        {
+            Ok((t, fallback_model)) => {
+                if let Some(fallback) = fallback_model {
+                    mapped_model = fallback;
+                }
+                t
+            }
+            Err(e) => {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                ));
+            }
+        };
+
+        crate::proxy::metrics::record_attempt(protocol, attempt, last_email.as_deref(), &email);
+        last_email = Some(email.clone());
+
+        // 每条请求的 model 必须与外层一致
+        let model_path = format!("models/{}", mapped_model);
+        for request in requests.iter_mut() {
+            request["model"] = json!(model_path);
+        }
+        let wrapped_body = json!({
+            "project": project_id,
+            "requestId": format!("embed-{}", uuid::Uuid::new_v4()),
+            "model": mapped_model,
+            "userAgent": "antigravity",
+            "requestType": "embedding",
+            "request": { "requests": requests },
+        });
+
+        let response = match state
+            .upstream
+            .call_v1_internal("batchEmbedContents", &access_token, wrapped_body, None, Some(account_id.as_str()))
+            .await
+        {
+            Ok(r) => r.response,
+            Err(e) => {
+                debug!("[{}] Embeddings request failed on attempt {}/{}: {}", trace_id, attempt + 1, max_attempts, e);
+                last_error = e;
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            let result: Value = response
+                .json()
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            let inner = result.get("response").unwrap_or(&result);
+            let embeddings = inner
+                .get("embeddings")
+                .and_then(|e| e.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let prompt_tokens = inner
+                .pointer("/usageMetadata/promptTokenCount")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32)
+                .unwrap_or_else(|| estimate_prompt_tokens(&requests));
+
+            info!(
+                "[{}] ✓ Embeddings completed: {} vectors via {} ({})",
+                trace_id,
+                embeddings.len(),
+                mask_email(&email),
+                mapped_model
+            );
+            return Ok(EmbedResult {
+                embeddings,
+                email,
+                mapped_model,
+                prompt_tokens,
+            });
+        }
+
+        let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| format!("HTTP {}", status_code));
+        last_error = format!("HTTP {}: {}", status_code, error_text);
+
+        if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
+            token_manager
+                .mark_rate_limited_async(
+                    &email,
+                    status_code,
+                    retry_after.as_deref(),
+                    &error_text,
+                    Some(&mapped_model),
+                )
+                .await;
+        }
+
+        let strategy = determine_retry_strategy(status_code, &error_text, false);
+        if apply_retry_strategy(strategy, attempt, max_attempts, status_code, &trace_id).await {
+            if !should_rotate_account(status_code) {
+                debug!(
+                    "[{}] Keeping same account for status {} (server-side issue)",
+                    trace_id, status_code
+                );
+            }
+            continue;
+        }
+
+        return Err((
+            StatusCode::from_u16(status_code).unwrap_or(StatusCode::BAD_GATEWAY),
+            error_text,
+        ));
+    }
+
+    Err((
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("All accounts exhausted. Last error: {}", last_error),
+    ))
+}
@@ -9,24 +9,30 @@ use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
 use crate::proxy::common::client_adapter::CLIENT_ADAPTERS;
+use crate::proxy::common::model_mapping::RouteContext;
+use crate::proxy::config::RouteProtocol;
+use crate::proxy::middleware::auth::UserTokenIdentity;
 use crate::proxy::debug_logger;
 use crate::proxy::handlers::common::{
-    apply_retry_strategy, determine_retry_strategy, should_rotate_account,
+    apply_retry_strategy, determine_retry_strategy, get_token_with_model_fallback,
+    should_rotate_account,
 };
 use crate::proxy::mappers::gemini::{unwrap_response, wrap_request};
 use crate::proxy::server::AppState;
 use crate::proxy::session_manager::SessionManager;
 use crate::proxy::upstream::client::mask_email;
 use axum::http::HeaderMap;
+use axum::Extension;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 
-/// 处理 generateContent 和 streamGenerateContent
+/// 处理 generateContent 和 streamGenerateContent (embedContent / batchEmbedContents 转交 embeddings handler)
 /// 路径参数: model_name, method (e.g. "gemini-pro", "generateContent")
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
     headers: HeaderMap,          // [NEW] Extract headers for adapter detection
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(mut body): Json<Value>, // 改为 mut 以支持修复提示词注入
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 解析 model:method
@@ -40,7 +46,9 @@ pub async fn handle_generate(
         "Received Gemini request: {}/{}",
         model_name, method
     ));
-    let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
+    // 优先使用 trace 中间件分配的 trace id，便于与请求日志/链路追踪关联
+    let trace_id = crate::proxy::telemetry::current_trace_id()
+        .unwrap_or_else(|| format!("req_{}", chrono::Utc::now().timestamp_subsec_millis()));
     let debug_cfg = state.debug_logging.read().await.clone();
 
     // [NEW] Detect Client Adapter
@@ -52,6 +60,19 @@ pub async fn handle_generate(
         debug!("[{}] Client Adapter detected", trace_id);
     }
 
+    // [NEW] Embeddings 走独立处理器
+    if method == "embedContent" || method == "batchEmbedContents" {
+        return Ok(crate::proxy::handlers::embeddings::handle_gemini_embeddings(
+            state,
+            &model_name,
+            &method,
+            &headers,
+            identity.as_ref().map(|Extension(id)| id),
+            body,
+        )
+        .await);
+    }
+
     // 1. 验证方法
     if method != "generateContent" && method != "streamGenerateContent" {
         return Err((
@@ -94,12 +115,23 @@ pub async fn handle_generate(
     let mut last_error = String::new();
     let mut last_email: Option<String> = None;
 
-    for attempt in 0..max_attempts {
-        // 3. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &model_name,
-            &*state.custom_mapping.read().await,
+    // [NEW] 路由规则上下文
+    let route_ctx = RouteContext::new(RouteProtocol::Gemini, &headers)
+        .with_identity(identity.as_ref().map(|Extension(id)| id))
+        .with_features(
+            body.pointer("/generationConfig/thinkingConfig").is_some(),
+            body.get("tools").and_then(|t| t.as_array()).is_some_and(|t| !t.is_empty()),
         );
+
+    // 3. 模型路由解析 (循环外解析一次，避免加权路由在每次重试时重新抽签)
+    let routed_model = crate::proxy::common::model_mapping::resolve_model_route_with_context(
+        &model_name,
+        &*state.custom_mapping.read().await,
+        &route_ctx,
+    );
+
+    for attempt in 0..max_attempts {
+        let mut mapped_model = routed_model.clone();
         // 提取 tools 列表以进行联网探测 (Gemini 风格可能是嵌套的)
         let tools_val: Option<Vec<Value>> =
             body.get("tools").and_then(|t| t.as_array()).map(|arr| {
@@ -117,7 +149,7 @@ pub async fn handle_generate(
                 flattened
             });
 
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        let mut config = crate::proxy::mappers::common_utils::resolve_request_config(
             &model_name,
             &mapped_model,
             &tools_val,
@@ -132,16 +164,33 @@ pub async fn handle_generate(
         let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
 
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager
-            .get_token(
-                &config.request_type,
-                attempt > 0,
-                Some(&session_id),
-                &config.final_model,
-            )
-            .await
+        let (access_token, project_id, email, account_id, _wait_ms) = match get_token_with_model_fallback(
+            &token_manager,
+            &config.request_type,
+            attempt > 0,
+            Some(&session_id),
+            &mapped_model,
+            &config.final_model,
+            &trace_id,
+        )
+        .await
         {
-            Ok(t) => t,
+            Ok((t, fallback_model)) => {
+                // [NEW] 跨模型降级
+                if let Some(fallback) = fallback_model {
+                    mapped_model = fallback;
+                    config = crate::proxy::mappers::common_utils::resolve_request_config(
+                        &model_name,
+                        &mapped_model,
+                        &tools_val,
+                        None,
+                        None,
+                        None,
+                        Some(&body),
+                    );
+                }
+                t
+            }
             Err(e) => {
                 return Err((
                     StatusCode::SERVICE_UNAVAILABLE,
@@ -150,6 +199,7 @@ pub async fn handle_generate(
             }
         };
 
+        crate::proxy::metrics::record_attempt("gemini", attempt, last_email.as_deref(), &email);
         last_email = Some(email.clone());
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
@@ -275,12 +325,15 @@ pub async fn handle_generate(
                     "status": status.as_u16(),
                     "upstream_url": upstream_url,
                 });
-                let mut response_stream = debug_logger::wrap_stream_with_debug(
-                    Box::pin(response.bytes_stream()),
-                    debug_cfg.clone(),
-                    trace_id.clone(),
-                    "upstream_response",
-                    meta,
+                let mut response_stream = crate::proxy::telemetry::instrument_stream(
+                    debug_logger::wrap_stream_with_debug(
+                        Box::pin(response.bytes_stream()),
+                        debug_cfg.clone(),
+                        trace_id.clone(),
+                        "upstream_response",
+                        meta,
+                    ),
+                    crate::proxy::telemetry::stream_conversion_span("gemini", &mapped_model),
                 );
                 let mut buffer = BytesMut::new();
                 let s_id = session_id.clone(); // Clone for stream closure
@@ -650,6 +703,11 @@ pub async fn handle_list_models(
     let models: Vec<_> = model_ids
         .into_iter()
         .map(|id| {
+            let methods = if id.contains("embedding") {
+                json!(["embedContent", "batchEmbedContents"])
+            } else {
+                json!(["generateContent", "countTokens"])
+            };
             json!({
                 "name": format!("models/{}", id),
                 "version": "001",
@@ -657,7 +715,7 @@ pub async fn handle_list_models(
                 "description": "",
                 "inputTokenLimit": 128000,
                 "outputTokenLimit": 8192,
-                "supportedGenerationMethods": ["generateContent", "countTokens"],
+                "supportedGenerationMethods": methods,
                 "temperature": 1.0,
                 "topP": 0.95,
                 "topK": 64
@@ -677,20 +735,42 @@ pub async fn handle_get_model(Path(model_name): Path<String>) -> impl IntoRespon
 
 pub async fn handle_count_tokens(
     State(state): State<AppState>,
-    Path(_model_name): Path<String>,
-    Json(_body): Json<Value>,
+    Path(model_name): Path<String>,
+    headers: HeaderMap,
+    identity: Option<Extension<UserTokenIdentity>>,
+    Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let model_group = "gemini";
-    let (_access_token, _project_id, _, _, _wait_ms) = state
-        .token_manager
-        .get_token(model_group, false, None, "gemini")
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::SERVICE_UNAVAILABLE,
-                format!("Token error: {}", e),
-            )
-        })?;
+    let trace_id = format!("count_{}", chrono::Utc::now().timestamp_subsec_millis());
 
-    Ok(Json(json!({"totalTokens": 0})))
+    // 官方 countTokens 同时支持直接的 contents 与 generateContentRequest 包装两种形式
+    let inner = body.get("generateContentRequest").unwrap_or(&body).clone();
+
+    // 与 generateContent 使用相同的路由上下文，保证计数的模型与实际请求一致
+    let route_ctx = RouteContext::new(RouteProtocol::Gemini, &headers)
+        .with_identity(identity.as_ref().map(|Extension(id)| id))
+        .with_features(
+            inner.pointer("/generationConfig/thinkingConfig").is_some(),
+            inner.get("tools").and_then(|t| t.as_array()).is_some_and(|t| !t.is_empty()),
+        );
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route_with_context(
+        &model_name,
+        &*state.custom_mapping.read().await,
+        &route_ctx,
+    );
+
+    let raw_estimated =
+        crate::proxy::mappers::context_manager::ContextManager::estimate_gemini_request_tokens(
+            &inner,
+        );
+    let total_tokens = crate::proxy::handlers::common::count_tokens_with_fallback(
+        &state,
+        &mapped_model,
+        &inner,
+        raw_estimated,
+        false,
+        &trace_id,
+    )
+    .await;
+
+    Ok(Json(json!({ "totalTokens": total_tokens })))
 }
@@ -0,0 +1,41 @@
+// Prometheus 指标处理器
+//
+// 提供 /metrics 端点，挂在 AI 代理路由下，与其它端点一样遵循 auth_mode 鉴权。
+// 指标标签包含账号邮箱，因此用户令牌无权抓取，仅管理员 API Key (或 auth_mode=off 的匿名调用) 可访问
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde_json::json;
+
+use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::server::AppState;
+
+pub async fn handle_metrics(
+    identity: Option<Extension<UserTokenIdentity>>,
+    State(state): State<AppState>,
+) -> Response {
+    if identity.is_some() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": {
+                    "message": "User tokens cannot access /metrics; use the proxy API key",
+                    "type": "permission_error",
+                    "code": "metrics_forbidden"
+                }
+            })),
+        )
+            .into_response();
+    }
+
+    let body = crate::proxy::metrics::render(&state.proxy_pool_manager).await;
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
@@ -7,5 +7,8 @@ pub mod gemini;
 pub mod mcp;
 pub mod common;
 pub mod audio;  // 音频转录处理器
+pub mod embeddings; // Embeddings (OpenAI / Gemini)
+pub mod batches; // Batch API (files / batches)
 pub mod warmup; // 预热处理器
+pub mod metrics; // Prometheus 指标导出
 
@@ -8,7 +8,11 @@ use serde_json::{json, Value};
 use tracing::{debug, error, info}; // Import Engine trait for encode method
 
 use crate::proxy::mappers::openai::{
-    transform_openai_request, transform_openai_response, OpenAIRequest,
+    transform_openai_request, transform_openai_response, OpenAIRequest, OpenAIResponse,
+};
+use crate::proxy::mappers::openai::responses::{
+    build_response_object, chat_response_to_output, normalize_input_items, persist_response,
+    usage_to_responses, ResponsesContext,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
 use crate::proxy::debug_logger;
@@ -17,17 +21,23 @@ use crate::proxy::upstream::client::mask_email;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 use super::common::{
-    apply_retry_strategy, determine_retry_strategy, should_rotate_account, RetryStrategy,
+    apply_retry_strategy, determine_retry_strategy, get_token_with_model_fallback,
+    should_rotate_account, RetryStrategy,
 };
 use crate::proxy::common::client_adapter::CLIENT_ADAPTERS; // [NEW] Adapter Registry
 use crate::proxy::session_manager::SessionManager;
 use axum::http::HeaderMap;
+use axum::Extension;
 use tokio::time::Duration;
+use crate::proxy::common::model_mapping::RouteContext;
+use crate::proxy::config::RouteProtocol;
+use crate::proxy::middleware::auth::UserTokenIdentity;
 use crate::modules::account;
 
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
     headers: HeaderMap, // [CHANGED] Extract headers
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(mut body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // [NEW] Check for Image Model Redirection
@@ -110,7 +120,9 @@ pub async fn handle_chat_completions(
             });
     }
 
-    let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
+    // 优先使用 trace 中间件分配的 trace id，便于与请求日志/链路追踪关联
+    let trace_id = crate::proxy::telemetry::current_trace_id()
+        .unwrap_or_else(|| format!("req_{}", chrono::Utc::now().timestamp_subsec_millis()));
     info!(
         "[{}] OpenAI Chat Request: {} | {} messages | stream: {}",
         trace_id,
@@ -157,18 +169,23 @@ pub async fn handle_chat_completions(
     let mut last_email: Option<String> = None;
 
     // 2. 模型路由解析 (移到循环外以支持在所有路径返回 X-Mapped-Model)
-    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+    let route_ctx = openai_route_context(&headers, identity.as_ref(), &openai_req, &original_body);
+    let routed_model = crate::proxy::common::model_mapping::resolve_model_route_with_context(
         &openai_req.model,
         &*state.custom_mapping.read().await,
+        &route_ctx,
     );
 
+    let mut last_mapped_model = routed_model.clone();
     for attempt in 0..max_attempts {
+        // 每次尝试从路由结果重新开始，上次尝试的降级模型不会带入下一次尝试
+        let mut mapped_model = routed_model.clone();
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
             .tools
             .as_ref()
             .map(|list| list.iter().cloned().collect());
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        let mut config = crate::proxy::mappers::common_utils::resolve_request_config(
             &openai_req.model,
             &mapped_model,
             &tools_val,
@@ -183,16 +200,33 @@ pub async fn handle_chat_completions(
 
         // 4. 获取 Token (使用准确的 request_type)
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager
-            .get_token(
-                &config.request_type,
-                attempt > 0,
-                Some(&session_id),
-                &mapped_model,
-            )
-            .await
+        let (access_token, project_id, email, account_id, _wait_ms) = match get_token_with_model_fallback(
+            &token_manager,
+            &config.request_type,
+            attempt > 0,
+            Some(&session_id),
+            &mapped_model,
+            &mapped_model,
+            &trace_id,
+        )
+        .await
         {
-            Ok(t) => t,
+            Ok((t, fallback_model)) => {
+                // [NEW] 跨模型降级
+                if let Some(fallback) = fallback_model {
+                    mapped_model = fallback;
+                    config = crate::proxy::mappers::common_utils::resolve_request_config(
+                        &openai_req.model,
+                        &mapped_model,
+                        &tools_val,
+                        None,
+                        None,
+                        None,
+                        None,
+                    );
+                }
+                t
+            }
             Err(e) => {
                 // [FIX] Attach headers to error response for logging visibility
                 let headers = [("X-Mapped-Model", mapped_model.as_str())];
@@ -204,10 +238,12 @@ pub async fn handle_chat_completions(
                     .into_response());
             }
         };
+        last_mapped_model.clone_from(&mapped_model);
 
         // [NEW v4.1.28] 获取完整 Token 对象用于动态规格查询
         let proxy_token = token_manager.get_token_by_id(&account_id);
 
+        crate::proxy::metrics::record_attempt("openai", attempt, last_email.as_deref(), &email);
         last_email = Some(email.clone());
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
@@ -360,11 +396,14 @@ pub async fn handle_chat_completions(
                 // [P1 FIX] Enhanced Peek logic to handle heartbeats and slow start
                 // Pre-read until we find meaningful content, skip heartbeats
                 use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
-                let mut openai_stream = create_openai_sse_stream(
-                    gemini_stream,
-                    openai_req.model.clone(),
-                    session_id,
-                    message_count,
+                let mut openai_stream = crate::proxy::telemetry::instrument_stream(
+                    create_openai_sse_stream(
+                        gemini_stream,
+                        openai_req.model.clone(),
+                        session_id,
+                        message_count,
+                    ),
+                    crate::proxy::telemetry::stream_conversion_span("openai", &mapped_model),
                 );
 
                 let mut first_data_chunk = None;
@@ -735,14 +774,14 @@ pub async fn handle_chat_completions(
     if let Some(email) = last_email {
         Ok((
             StatusCode::TOO_MANY_REQUESTS,
-            [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
+            [("X-Account-Email", email), ("X-Mapped-Model", last_mapped_model)],
             format!("All accounts exhausted. Last error: {}", last_error),
         )
             .into_response())
     } else {
         Ok((
             StatusCode::TOO_MANY_REQUESTS,
-            [("X-Mapped-Model", mapped_model)],
+            [("X-Mapped-Model", last_mapped_model)],
             format!("All accounts exhausted. Last error: {}", last_error),
         )
             .into_response())
@@ -753,6 +792,8 @@ pub async fn handle_chat_completions(
 /// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
 pub async fn handle_completions(
     State(state): State<AppState>,
+    headers: HeaderMap,
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(mut body): Json<Value>,
 ) -> Response {
     debug!(
@@ -762,6 +803,69 @@ pub async fn handle_completions(
 
     let is_codex_style = body.get("input").is_some() || body.get("instructions").is_some();
 
+    // [NEW] Responses API: 解析 previous_response_id / store，并将历史会话拼接到 input 之前
+    let responses_ctx = if is_codex_style {
+        Some(ResponsesContext::from_request(
+            &body,
+            identity.as_ref().map(|Extension(id)| id.token_id.clone()),
+        ))
+    } else {
+        None
+    };
+    let mut new_input_items: Vec<Value> = Vec::new();
+    if let Some(ctx) = &responses_ctx {
+        new_input_items = normalize_input_items(body.get("input").unwrap_or(&Value::Null));
+
+        let mut full_items = Vec::new();
+        if let Some(prev_id) = ctx.previous_response_id.clone() {
+            let lookup_id = prev_id.clone();
+            let owner = ctx.user_token_id.clone();
+            let lookup = tokio::task::spawn_blocking(move || {
+                crate::modules::response_store::get_conversation_items(&lookup_id, owner.as_deref())
+            })
+            .await;
+            match lookup {
+                Ok(Ok(Some(items))) => {
+                    debug!(
+                        "[Codex] Loaded {} history items from previous_response_id {}",
+                        items.len(),
+                        prev_id
+                    );
+                    full_items = items;
+                }
+                Ok(Ok(None)) => {
+                    return responses_error(
+                        StatusCode::NOT_FOUND,
+                        &format!("Previous response with id '{}' not found.", prev_id),
+                        Some("previous_response_id"),
+                        "previous_response_not_found",
+                    );
+                }
+                Ok(Err(e)) => {
+                    return responses_error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        &format!("Failed to load previous response: {}", e),
+                        None,
+                        "server_error",
+                    );
+                }
+                Err(e) => {
+                    return responses_error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        &format!("Failed to load previous response: {}", e),
+                        None,
+                        "server_error",
+                    );
+                }
+            }
+        }
+        full_items.extend(new_input_items.iter().cloned());
+
+        if let Some(obj) = body.as_object_mut() {
+            obj.insert("input".to_string(), Value::Array(full_items));
+        }
+    }
+
     // 1. Convert Payload to Messages (Shared Chat Format)
     if is_codex_style {
         let instructions = body
@@ -1137,20 +1241,27 @@ pub async fn handle_completions(
     let mut last_email: Option<String> = None;
 
     // 2. 模型路由解析 (移到循环外以支持在所有路径返回 X-Mapped-Model)
-    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+    let route_ctx = openai_route_context(&headers, identity.as_ref(), &openai_req, &body);
+    let routed_model = crate::proxy::common::model_mapping::resolve_model_route_with_context(
         &openai_req.model,
         &*state.custom_mapping.read().await,
+        &route_ctx,
     );
-    let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
+    // 优先使用 trace 中间件分配的 trace id，便于与请求日志/链路追踪关联
+    let trace_id = crate::proxy::telemetry::current_trace_id()
+        .unwrap_or_else(|| format!("req_{}", chrono::Utc::now().timestamp_subsec_millis()));
 
+    let mut last_mapped_model = routed_model.clone();
     for attempt in 0..max_attempts {
+        // 每次尝试从路由结果重新开始，上次尝试的降级模型不会带入下一次尝试
+        let mut mapped_model = routed_model.clone();
         // 3. 模型配置解析
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
             .tools
             .as_ref()
             .map(|list| list.iter().cloned().collect());
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        let mut config = crate::proxy::mappers::common_utils::resolve_request_config(
             &openai_req.model,
             &mapped_model,
             &tools_val,
@@ -1168,16 +1279,33 @@ pub async fn handle_completions(
         // 重试时强制轮换，除非只是简单的网络抖动但 Claude 逻辑里 attempt > 0 总是 force_rotate
         let force_rotate = attempt > 0;
 
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager
-            .get_token(
-                &config.request_type,
-                force_rotate,
-                session_id,
-                &mapped_model,
-            )
-            .await
+        let (access_token, project_id, email, account_id, _wait_ms) = match get_token_with_model_fallback(
+            &token_manager,
+            &config.request_type,
+            force_rotate,
+            session_id,
+            &mapped_model,
+            &mapped_model,
+            &trace_id,
+        )
+        .await
         {
-            Ok(t) => t,
+            Ok((t, fallback_model)) => {
+                // [NEW] 跨模型降级
+                if let Some(fallback) = fallback_model {
+                    mapped_model = fallback;
+                    config = crate::proxy::mappers::common_utils::resolve_request_config(
+                        &openai_req.model,
+                        &mapped_model,
+                        &tools_val,
+                        None,
+                        None,
+                        None,
+                        None,
+                    );
+                }
+                t
+            }
             Err(e) => {
                 return (
                     StatusCode::SERVICE_UNAVAILABLE,
@@ -1187,7 +1315,9 @@ pub async fn handle_completions(
                     .into_response()
             }
         };
+        last_mapped_model.clone_from(&mapped_model);
 
+        crate::proxy::metrics::record_attempt("openai", attempt, last_email.as_deref(), &email);
         last_email = Some(email.clone());
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
@@ -1259,13 +1389,27 @@ pub async fn handle_completions(
                 // and we already have logic to convert Chat JSON -> Legacy JSON.
 
                 if client_wants_stream {
-                    let mut openai_stream = if is_codex_style {
+                    let openai_stream = if let Some(ctx) = responses_ctx.clone() {
                         use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
+                        let on_complete: Box<
+                            dyn FnOnce(Value) -> futures::future::BoxFuture<'static, ()> + Send,
+                        > = {
+                            let ctx = ctx.clone();
+                            let model = openai_req.model.clone();
+                            let input_items = new_input_items.clone();
+                            Box::new(move |response: Value| {
+                                Box::pin(async move {
+                                    persist_response(&ctx, &model, input_items, &response).await
+                                })
+                            })
+                        };
                         create_codex_sse_stream(
                             Box::pin(gemini_stream),
                             openai_req.model.clone(),
                             session_id,
                             message_count,
+                            ctx,
+                            Some(on_complete),
                         )
                     } else {
                         use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
@@ -1276,6 +1420,10 @@ pub async fn handle_completions(
                             message_count,
                         )
                     };
+                    let mut openai_stream = crate::proxy::telemetry::instrument_stream(
+                        openai_stream,
+                        crate::proxy::telemetry::stream_conversion_span("openai", &mapped_model),
+                    );
 
                     // [P1 FIX] Enhanced Peek logic (Reused from above/standard)
                     let mut first_data_chunk = None;
@@ -1348,11 +1496,14 @@ pub async fn handle_completions(
                     use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
                     // Note: We use create_openai_sse_stream regardless of is_codex_style here,
                     // because we just want the content aggregation which chat stream does well.
-                    let mut openai_stream = create_openai_sse_stream(
-                        Box::pin(gemini_stream),
-                        openai_req.model.clone(),
-                        session_id,
-                        message_count,
+                    let mut openai_stream = crate::proxy::telemetry::instrument_stream(
+                        create_openai_sse_stream(
+                            Box::pin(gemini_stream),
+                            openai_req.model.clone(),
+                            session_id,
+                            message_count,
+                        ),
+                        crate::proxy::telemetry::stream_conversion_span("openai", &mapped_model),
                     );
 
                     // Peek Logic (Repeated for safety/correctness on this stream type)
@@ -1413,6 +1564,26 @@ pub async fn handle_completions(
                     use crate::proxy::mappers::openai::collector::collect_stream_to_json;
                     match collect_stream_to_json(Box::pin(combined_stream)).await {
                         Ok(chat_resp) => {
+                            // [NEW] Responses API: 返回 Responses 形态的对象
+                            if let Some(ctx) = &responses_ctx {
+                                let resp_obj = finalize_codex_response(
+                                    ctx,
+                                    &openai_req.model,
+                                    &chat_resp,
+                                    new_input_items.clone(),
+                                )
+                                .await;
+                                return (
+                                    StatusCode::OK,
+                                    [
+                                        ("X-Account-Email", email.as_str()),
+                                        ("X-Mapped-Model", mapped_model.as_str()),
+                                    ],
+                                    Json(resp_obj),
+                                )
+                                    .into_response();
+                            }
+
                             // NOW: Convert Chat Response -> Legacy Response (Same logic as below)
                             let choices = chat_resp.choices.iter().map(|c| {
                                 json!({
@@ -1470,6 +1641,25 @@ pub async fn handle_completions(
 
             let chat_resp = transform_openai_response(&gemini_resp, Some("session-123"), 1);
 
+            if let Some(ctx) = &responses_ctx {
+                let resp_obj = finalize_codex_response(
+                    ctx,
+                    &openai_req.model,
+                    &chat_resp,
+                    new_input_items.clone(),
+                )
+                .await;
+                return (
+                    StatusCode::OK,
+                    [
+                        ("X-Account-Email", email.as_str()),
+                        ("X-Mapped-Model", mapped_model.as_str()),
+                    ],
+                    Json(resp_obj),
+                )
+                    .into_response();
+            }
+
             // Map Chat Response -> Legacy Completions Response
             let choices = chat_resp.choices.iter().map(|c| {
                 json!({
@@ -1559,20 +1749,192 @@ pub async fn handle_completions(
     if let Some(email) = last_email {
         (
             StatusCode::TOO_MANY_REQUESTS,
-            [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
+            [("X-Account-Email", email), ("X-Mapped-Model", last_mapped_model)],
             format!("All accounts exhausted. Last error: {}", last_error),
         )
             .into_response()
     } else {
         (
             StatusCode::TOO_MANY_REQUESTS,
-            [("X-Mapped-Model", mapped_model)],
+            [("X-Mapped-Model", last_mapped_model)],
             format!("All accounts exhausted. Last error: {}", last_error),
         )
             .into_response()
     }
 }
 
+// ===== Responses API (有状态会话) =====
+
+/// OpenAI 风格的错误响应
+fn responses_error(status: StatusCode, message: &str, param: Option<&str>, code: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": message,
+                "type": "invalid_request_error",
+                "param": param,
+                "code": code
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 将收集到的 Chat 响应转换为 Responses 对象并写入 Response Store
+async fn finalize_codex_response(
+    ctx: &ResponsesContext,
+    model: &str,
+    chat_resp: &OpenAIResponse,
+    input_items: Vec<Value>,
+) -> Value {
+    let output = chat_response_to_output(chat_resp);
+    let usage = chat_resp.usage.as_ref().map(usage_to_responses);
+    let response = build_response_object(ctx, model, "completed", &output, usage);
+    persist_response(ctx, model, input_items, &response).await;
+    response
+}
+
+/// 用户令牌只能访问自己创建的响应；管理员 API Key 可访问全部
+fn response_owner_filter(identity: &Option<Extension<UserTokenIdentity>>) -> Option<String> {
+    identity.as_ref().map(|Extension(id)| id.token_id.clone())
+}
+
+/// GET /v1/responses/:response_id
+pub async fn handle_get_response(
+    identity: Option<Extension<UserTokenIdentity>>,
+    axum::extract::Path(response_id): axum::extract::Path<String>,
+) -> Response {
+    let id = response_id.clone();
+    let owner = response_owner_filter(&identity);
+    match tokio::task::spawn_blocking(move || {
+        crate::modules::response_store::get_response(&id, owner.as_deref())
+    })
+    .await
+    {
+        Ok(Ok(Some(stored))) => Json(stored.response).into_response(),
+        Ok(Ok(None)) => responses_error(
+            StatusCode::NOT_FOUND,
+            &format!("Response with id '{}' not found.", response_id),
+            None,
+            "not_found",
+        ),
+        Ok(Err(e)) => responses_error(StatusCode::INTERNAL_SERVER_ERROR, &e, None, "server_error"),
+        Err(e) => responses_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &e.to_string(),
+            None,
+            "server_error",
+        ),
+    }
+}
+
+/// DELETE /v1/responses/:response_id
+pub async fn handle_delete_response(
+    identity: Option<Extension<UserTokenIdentity>>,
+    axum::extract::Path(response_id): axum::extract::Path<String>,
+) -> Response {
+    let id = response_id.clone();
+    let owner = response_owner_filter(&identity);
+    match tokio::task::spawn_blocking(move || {
+        crate::modules::response_store::delete_response(&id, owner.as_deref())
+    })
+    .await
+    {
+        Ok(Ok(true)) => Json(json!({
+            "id": response_id,
+            "object": "response",
+            "deleted": true
+        }))
+        .into_response(),
+        Ok(Ok(false)) => responses_error(
+            StatusCode::NOT_FOUND,
+            &format!("Response with id '{}' not found.", response_id),
+            None,
+            "not_found",
+        ),
+        Ok(Err(e)) => responses_error(StatusCode::INTERNAL_SERVER_ERROR, &e, None, "server_error"),
+        Err(e) => responses_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &e.to_string(),
+            None,
+            "server_error",
+        ),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct InputItemsQuery {
+    pub limit: Option<usize>,
+    pub order: Option<String>,
+    pub after: Option<String>,
+}
+
+/// GET /v1/responses/:response_id/input_items
+pub async fn handle_list_response_input_items(
+    identity: Option<Extension<UserTokenIdentity>>,
+    axum::extract::Path(response_id): axum::extract::Path<String>,
+    axum::extract::Query(query): axum::extract::Query<InputItemsQuery>,
+) -> Response {
+    let id = response_id.clone();
+    let owner = response_owner_filter(&identity);
+    let mut items =
+        match tokio::task::spawn_blocking(move || {
+            crate::modules::response_store::get_input_items(&id, owner.as_deref())
+        })
+        .await
+        {
+            Ok(Ok(Some(items))) => items,
+            Ok(Ok(None)) => {
+                return responses_error(
+                    StatusCode::NOT_FOUND,
+                    &format!("Response with id '{}' not found.", response_id),
+                    None,
+                    "not_found",
+                )
+            }
+            Ok(Err(e)) => {
+                return responses_error(StatusCode::INTERNAL_SERVER_ERROR, &e, None, "server_error")
+            }
+            Err(e) => {
+                return responses_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &e.to_string(),
+                    None,
+                    "server_error",
+                )
+            }
+        };
+
+    // 与 OpenAI 一致：默认 desc，limit 范围 1..=100，默认 20
+    if query.order.as_deref() != Some("asc") {
+        items.reverse();
+    }
+    if let Some(after) = query.after.as_deref() {
+        if let Some(pos) = items
+            .iter()
+            .position(|item| item.get("id").and_then(|v| v.as_str()) == Some(after))
+        {
+            items.drain(..=pos);
+        }
+    }
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let has_more = items.len() > limit;
+    items.truncate(limit);
+
+    let first_id = items.first().and_then(|i| i.get("id")).cloned().unwrap_or(Value::Null);
+    let last_id = items.last().and_then(|i| i.get("id")).cloned().unwrap_or(Value::Null);
+
+    Json(json!({
+        "object": "list",
+        "data": items,
+        "first_id": first_id,
+        "last_id": last_id,
+        "has_more": has_more
+    }))
+    .into_response()
+}
+
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
@@ -1601,9 +1963,30 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
 pub async fn handle_chat_redirection(
     State(state): State<AppState>,
     headers: HeaderMap,
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    handle_chat_completions(State(state), headers, Json(body)).await
+    handle_chat_completions(State(state), headers, identity, Json(body)).await
+}
+
+/// 构建 OpenAI 协议请求的路由规则上下文
+fn openai_route_context(
+    headers: &HeaderMap,
+    identity: Option<&Extension<UserTokenIdentity>>,
+    req: &OpenAIRequest,
+    raw_body: &Value,
+) -> RouteContext {
+    let has_thinking = req
+        .thinking
+        .as_ref()
+        .is_some_and(|t| t.thinking_type.as_deref() != Some("disabled"))
+        || raw_body.get("reasoning_effort").is_some()
+        || raw_body.get("reasoning").is_some();
+    let has_tools = req.tools.as_ref().is_some_and(|t| !t.is_empty());
+
+    RouteContext::new(RouteProtocol::Openai, headers)
+        .with_identity(identity.map(|Extension(id)| id))
+        .with_features(has_thinking, has_tools)
 }
 
 async fn intercept_chat_to_image(
@@ -258,6 +258,11 @@ pub async fn handle_warmup(
                 output_tokens: Some(0),
                 protocol: Some("warmup".to_string()),
                 username: None,
+                ttft_ms: None,
+                cache_read_tokens: None,
+                cache_creation_tokens: None,
+                reasoning_tokens: None,
+                trace_id: None,
             };
             state.monitor.log_request(log).await;
 
@@ -352,6 +357,11 @@ pub async fn handle_warmup(
                 output_tokens: None,
                 protocol: Some("warmup".to_string()),
                 username: None,
+                ttft_ms: None,
+                cache_read_tokens: None,
+                cache_creation_tokens: None,
+                reasoning_tokens: None,
+                trace_id: None,
             };
             state.monitor.log_request(log).await;
 
@@ -198,6 +198,50 @@ impl ContextManager {
         total
     }
 
+    /// Estimate token usage for a Gemini-native request body
+    ///
+    /// Walks `systemInstruction`, `contents` and `tools` of a generateContent-style
+    /// JSON body. Used where no ClaudeRequest is available (e.g. Gemini countTokens).
+    pub fn estimate_gemini_request_tokens(body: &serde_json::Value) -> u32 {
+        fn estimate_parts(parts: Option<&serde_json::Value>) -> u32 {
+            let mut total = 0;
+            if let Some(parts) = parts.and_then(|p| p.as_array()) {
+                for part in parts {
+                    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                        total += estimate_tokens_from_str(text);
+                    } else if let Some(call) = part.get("functionCall") {
+                        total += 20; // Function call overhead
+                        total += estimate_tokens_from_str(&call.to_string());
+                    } else if let Some(resp) = part.get("functionResponse") {
+                        total += 10; // Result overhead
+                        total += estimate_tokens_from_str(&resp.to_string());
+                    }
+                }
+            }
+            total
+        }
+
+        let mut total = 0;
+
+        if let Some(sys) = body.get("systemInstruction") {
+            total += estimate_parts(sys.get("parts"));
+        }
+
+        if let Some(contents) = body.get("contents").and_then(|c| c.as_array()) {
+            for content in contents {
+                // Message overhead
+                total += 4;
+                total += estimate_parts(content.get("parts"));
+            }
+        }
+
+        if let Some(tools) = body.get("tools") {
+            total += estimate_tokens_from_str(&tools.to_string());
+        }
+
+        total
+    }
+
     // ===== [Layer 2] Thinking Content Compression + Signature Preservation =====
     // Borrowed from learn-claude-code's "append-only log" principle
     // This layer compresses thinking text but PRESERVES signatures
@@ -475,6 +519,22 @@ mod tests {
         assert!(tokens < 50);
     }
 
+    #[test]
+    fn test_estimate_gemini_request_tokens() {
+        let body = serde_json::json!({
+            "systemInstruction": { "parts": [{ "text": "You are helpful." }] },
+            "contents": [
+                { "role": "user", "parts": [{ "text": "Hello World" }] },
+                { "role": "model", "parts": [{ "functionCall": { "name": "ls", "args": {} } }] }
+            ]
+        });
+
+        let tokens = ContextManager::estimate_gemini_request_tokens(&body);
+        assert!(tokens > 20);
+        assert!(tokens < 100);
+        assert_eq!(ContextManager::estimate_gemini_request_tokens(&serde_json::json!({})), 0);
+    }
+
     #[test]
     fn test_purify_history_soft() {
         // Construct history of 6 messages (indices 0-5)
@@ -0,0 +1,147 @@
+// OpenAI Embeddings ↔ Gemini batchEmbedContents 转换
+
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// OpenAI `/v1/embeddings` 请求体
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAIEmbeddingRequest {
+    pub model: String,
+    /// 字符串或字符串数组 (token 数组不支持)
+    pub input: Value,
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+}
+
+impl OpenAIEmbeddingRequest {
+    /// 展开 input 为文本列表
+    pub fn inputs(&self) -> Result<Vec<String>, String> {
+        let texts: Vec<String> = match &self.input {
+            Value::String(s) => vec![s.clone()],
+            Value::Array(items) => items
+                .iter()
+                .map(|item| {
+                    item.as_str().map(|s| s.to_string()).ok_or_else(|| {
+                        "Only string inputs are supported; token array inputs cannot be embedded upstream".to_string()
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err("'input' must be a string or an array of strings".to_string()),
+        };
+
+        if texts.is_empty() {
+            return Err("'input' must not be empty".to_string());
+        }
+        Ok(texts)
+    }
+}
+
+/// 构建 batchEmbedContents 的单条 EmbedContentRequest (model 字段在发送前按映射结果填充)
+pub fn build_embed_requests(req: &OpenAIEmbeddingRequest) -> Result<Vec<Value>, String> {
+    Ok(req
+        .inputs()?
+        .into_iter()
+        .map(|text| {
+            let mut request = json!({
+                "content": { "parts": [{ "text": text }] }
+            });
+            if let Some(dims) = req.dimensions {
+                request["outputDimensionality"] = json!(dims);
+            }
+            request
+        })
+        .collect())
+}
+
+/// 从 ContentEmbedding 中提取向量
+fn embedding_values(embedding: &Value) -> Vec<f32> {
+    embedding
+        .get("values")
+        .and_then(|v| v.as_array())
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v.as_f64())
+                .map(|v| v as f32)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 将 Gemini embeddings 转换为 OpenAI 响应
+///
+/// `encoding_format=base64` 时按 OpenAI 约定输出 little-endian float32 字节的 base64
+pub fn transform_embeddings_response(
+    embeddings: &[Value],
+    model: &str,
+    encoding_format: Option<&str>,
+    prompt_tokens: u32,
+) -> Value {
+    let as_base64 = encoding_format == Some("base64");
+    let data: Vec<Value> = embeddings
+        .iter()
+        .enumerate()
+        .map(|(index, embedding)| {
+            let values = embedding_values(embedding);
+            let encoded = if as_base64 {
+                let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                json!(base64::engine::general_purpose::STANDARD.encode(bytes))
+            } else {
+                json!(values)
+            };
+            json!({
+                "object": "embedding",
+                "index": index,
+                "embedding": encoded,
+            })
+        })
+        .collect();
+
+    json!({
+        "object": "list",
+        "data": data,
+        "model": model,
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "total_tokens": prompt_tokens,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_request_and_response_mapping() {
+        let req: OpenAIEmbeddingRequest = serde_json::from_value(json!({
+            "model": "text-embedding-3-small",
+            "input": ["hello", "world"],
+            "dimensions": 256,
+        }))
+        .unwrap();
+        let requests = build_embed_requests(&req).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["content"]["parts"][0]["text"], "world");
+        assert_eq!(requests[0]["outputDimensionality"], 256);
+
+        let token_input: OpenAIEmbeddingRequest =
+            serde_json::from_value(json!({ "model": "m", "input": [[1, 2, 3]] })).unwrap();
+        assert!(build_embed_requests(&token_input).is_err());
+
+        let embeddings = vec![json!({ "values": [1.0, -0.5] })];
+        let float_resp = transform_embeddings_response(&embeddings, "text-embedding-3-small", None, 3);
+        assert_eq!(float_resp["data"][0]["embedding"], json!([1.0, -0.5]));
+        assert_eq!(float_resp["usage"]["prompt_tokens"], 3);
+
+        let b64_resp = transform_embeddings_response(&embeddings, "m", Some("base64"), 3);
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(b64_resp["data"][0]["embedding"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(bytes.len(), 8);
+        assert_eq!(f32::from_le_bytes(bytes[4..8].try_into().unwrap()), -0.5);
+    }
+}
@@ -7,6 +7,8 @@ pub mod response;
 pub mod streaming;
 pub mod collector; // [NEW]
 pub mod thinking_recovery;
+pub mod responses; // [NEW] Responses API (有状态会话)
+pub mod embeddings; // [NEW] Embeddings API
 
 pub use models::*;
 pub use request::*;
@@ -173,10 +173,16 @@ pub fn transform_openai_response(gemini_response: &Value, session_id: Option<&st
             .get("promptTokenCount")
             .and_then(|v| v.as_u64())
             .unwrap_or(0) as u32;
-        let completion_tokens = u
+        let candidates_tokens = u
             .get("candidatesTokenCount")
             .and_then(|v| v.as_u64())
             .unwrap_or(0) as u32;
+        let reasoning_tokens = u
+            .get("thoughtsTokenCount")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+        // OpenAI 的 completion_tokens 包含 reasoning_tokens，Gemini 则将思考 token 单独计数
+        let completion_tokens = candidates_tokens.saturating_add(reasoning_tokens.unwrap_or(0));
         let total_tokens = u
             .get("totalTokenCount")
             .and_then(|v| v.as_u64())
@@ -193,7 +199,9 @@ pub fn transform_openai_response(gemini_response: &Value, session_id: Option<&st
             prompt_tokens_details: cached_tokens.map(|ct| super::models::PromptTokensDetails {
                 cached_tokens: Some(ct),
             }),
-            completion_tokens_details: None,
+            completion_tokens_details: reasoning_tokens.map(|rt| super::models::CompletionTokensDetails {
+                reasoning_tokens: Some(rt),
+            }),
         })
     });
 
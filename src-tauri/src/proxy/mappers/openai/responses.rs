@@ -0,0 +1,423 @@
+// OpenAI Responses API 辅助
+// 负责 input 标准化、Responses 对象构建，以及 Chat 响应 → Responses output 项转换
+
+use rand::Rng;
+use serde_json::{json, Value};
+
+use super::models::{OpenAIContent, OpenAIContentBlock, OpenAIResponse, OpenAIUsage};
+
+/// 单次 Responses 请求的上下文 (流式与非流式共用)
+#[derive(Debug, Clone)]
+pub struct ResponsesContext {
+    pub response_id: String,
+    pub created_at: i64,
+    pub previous_response_id: Option<String>,
+    pub instructions: Option<String>,
+    pub store: bool,
+    /// 发起请求的用户令牌；存储的响应只对该令牌可见 (管理员 API Key 为空)
+    pub user_token_id: Option<String>,
+}
+
+impl ResponsesContext {
+    pub fn from_request(body: &Value, user_token_id: Option<String>) -> Self {
+        Self {
+            response_id: generate_id("resp"),
+            created_at: chrono::Utc::now().timestamp(),
+            previous_response_id: body
+                .get("previous_response_id")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+            instructions: body
+                .get("instructions")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+            // OpenAI 默认 store=true
+            store: body.get("store").and_then(|v| v.as_bool()).unwrap_or(true),
+            user_token_id,
+        }
+    }
+}
+
+/// 生成形如 `resp_xxx` / `msg_xxx` / `fc_xxx` 的 ID
+pub fn generate_id(prefix: &str) -> String {
+    let charset = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let mut rng = rand::thread_rng();
+    let suffix: String = (0..24)
+        .map(|_| charset[rng.gen_range(0..charset.len())] as char)
+        .collect();
+    format!("{}_{}", prefix, suffix)
+}
+
+fn id_prefix_for(item_type: &str) -> &'static str {
+    match item_type {
+        "function_call" => "fc",
+        "function_call_output" | "custom_tool_call_output" => "fco",
+        "reasoning" => "rs",
+        "local_shell_call" => "lsh",
+        "web_search_call" => "ws",
+        _ => "msg",
+    }
+}
+
+/// 将 `input` 标准化为带 ID 的输入项数组
+///
+/// - 字符串 → 单条 user message
+/// - `{role, content}` (无 type) → `type: message`，字符串 content 展开为 input_text/output_text
+/// - 裸内容块 (`input_text` / `input_image`) → 合并为一条 user message
+pub fn normalize_input_items(input: &Value) -> Vec<Value> {
+    fn text_block(role: &str, text: &str) -> Value {
+        let block_type = if role == "assistant" { "output_text" } else { "input_text" };
+        json!({ "type": block_type, "text": text })
+    }
+
+    let mut items: Vec<Value> = Vec::new();
+    let mut pending_blocks: Vec<Value> = Vec::new();
+
+    let flush = |pending: &mut Vec<Value>, items: &mut Vec<Value>| {
+        if !pending.is_empty() {
+            items.push(json!({
+                "type": "message",
+                "role": "user",
+                "content": std::mem::take(pending)
+            }));
+        }
+    };
+
+    match input {
+        Value::String(s) => {
+            items.push(json!({
+                "type": "message",
+                "role": "user",
+                "content": [text_block("user", s)]
+            }));
+        }
+        Value::Array(arr) => {
+            for raw in arr {
+                if let Some(s) = raw.as_str() {
+                    pending_blocks.push(text_block("user", s));
+                    continue;
+                }
+                let Some(obj) = raw.as_object() else { continue };
+                let item_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
+
+                if obj.get("role").is_none() && item_type.starts_with("input_") {
+                    pending_blocks.push(raw.clone());
+                    continue;
+                }
+                flush(&mut pending_blocks, &mut items);
+
+                let mut item = raw.clone();
+                if obj.contains_key("role") && item_type.is_empty() {
+                    item["type"] = json!("message");
+                }
+                if item["type"] == "message" {
+                    let role = item
+                        .get("role")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("user")
+                        .to_string();
+                    if let Some(s) = item.get("content").and_then(|v| v.as_str()).map(|s| s.to_string()) {
+                        item["content"] = json!([text_block(&role, &s)]);
+                    }
+                }
+                items.push(item);
+            }
+            flush(&mut pending_blocks, &mut items);
+        }
+        Value::Null => {}
+        other => {
+            items.push(json!({
+                "type": "message",
+                "role": "user",
+                "content": [text_block("user", &other.to_string())]
+            }));
+        }
+    }
+
+    for item in items.iter_mut() {
+        if item.get("id").and_then(|v| v.as_str()).is_none() {
+            let prefix = id_prefix_for(item.get("type").and_then(|v| v.as_str()).unwrap_or(""));
+            item["id"] = json!(generate_id(prefix));
+        }
+    }
+    items
+}
+
+/// OpenAI Chat usage → Responses usage
+pub fn usage_to_responses(usage: &OpenAIUsage) -> Value {
+    json!({
+        "input_tokens": usage.prompt_tokens,
+        "input_tokens_details": {
+            "cached_tokens": usage
+                .prompt_tokens_details
+                .as_ref()
+                .and_then(|d| d.cached_tokens)
+                .unwrap_or(0)
+        },
+        "output_tokens": usage.completion_tokens,
+        "output_tokens_details": {
+            "reasoning_tokens": usage
+                .completion_tokens_details
+                .as_ref()
+                .and_then(|d| d.reasoning_tokens)
+                .unwrap_or(0)
+        },
+        "total_tokens": usage.total_tokens
+    })
+}
+
+/// 构建 function_call 输出项
+pub fn function_call_item(item_id: &str, call_id: &str, name: &str, arguments: &str, status: &str) -> Value {
+    json!({
+        "id": item_id,
+        "type": "function_call",
+        "status": status,
+        "call_id": call_id,
+        "name": name,
+        "arguments": arguments
+    })
+}
+
+/// 构建 assistant message 输出项
+pub fn message_item(item_id: &str, text: &str, status: &str) -> Value {
+    let content = if status == "in_progress" {
+        json!([])
+    } else {
+        json!([{ "type": "output_text", "text": text, "annotations": [] }])
+    };
+    json!({
+        "id": item_id,
+        "type": "message",
+        "role": "assistant",
+        "status": status,
+        "content": content
+    })
+}
+
+/// 将收集到的 Chat 响应转换为 Responses output 项
+pub fn chat_response_to_output(chat: &OpenAIResponse) -> Vec<Value> {
+    let mut output = Vec::new();
+    let Some(choice) = chat.choices.first() else {
+        return output;
+    };
+    let msg = &choice.message;
+
+    if let Some(reasoning) = msg.reasoning_content.as_deref().filter(|s| !s.is_empty()) {
+        output.push(json!({
+            "id": generate_id("rs"),
+            "type": "reasoning",
+            "summary": [{ "type": "summary_text", "text": reasoning }]
+        }));
+    }
+
+    let text = match &msg.content {
+        Some(OpenAIContent::String(s)) => s.clone(),
+        Some(OpenAIContent::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|b| match b {
+                OpenAIContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(""),
+        None => String::new(),
+    };
+    let tool_calls = msg.tool_calls.as_deref().unwrap_or(&[]);
+
+    if !text.is_empty() || tool_calls.is_empty() {
+        output.push(message_item(&generate_id("msg"), &text, "completed"));
+    }
+
+    for tc in tool_calls {
+        output.push(function_call_item(
+            &generate_id("fc"),
+            &tc.id,
+            &tc.function.name,
+            &tc.function.arguments,
+            "completed",
+        ));
+    }
+
+    output
+}
+
+/// 构建 Responses API 响应对象
+pub fn build_response_object(
+    ctx: &ResponsesContext,
+    model: &str,
+    status: &str,
+    output: &[Value],
+    usage: Option<Value>,
+) -> Value {
+    json!({
+        "id": &ctx.response_id,
+        "object": "response",
+        "created_at": ctx.created_at,
+        "status": status,
+        "model": model,
+        "output": output,
+        "previous_response_id": &ctx.previous_response_id,
+        "instructions": &ctx.instructions,
+        "store": ctx.store,
+        "error": null,
+        "incomplete_details": null,
+        "usage": usage
+    })
+}
+
+fn is_empty_message(item: &Value) -> bool {
+    item.get("type").and_then(|v| v.as_str()) == Some("message")
+        && item
+            .get("content")
+            .and_then(|c| c.as_array())
+            .map_or(true, |blocks| {
+                blocks
+                    .iter()
+                    .all(|b| b.get("text").and_then(|t| t.as_str()).map_or(true, str::is_empty))
+            })
+}
+
+/// 待存储的 output 项：流式响应固定占位的 message 项在纯工具调用时为空，不写入会话历史
+fn output_for_store(output: &[Value]) -> Vec<Value> {
+    if output.iter().all(is_empty_message) {
+        return output.to_vec();
+    }
+    output.iter().filter(|item| !is_empty_message(item)).cloned().collect()
+}
+
+/// 将完成的响应写入 Response Store (store=false 时跳过)
+///
+/// 调用方需在返回响应 / 发送 response.completed 之前 await，
+/// 保证客户端断开或立即携带 previous_response_id 续写时记录已落库。
+pub async fn persist_response(ctx: &ResponsesContext, model: &str, input_items: Vec<Value>, response: &Value) {
+    if !ctx.store {
+        return;
+    }
+    let output = output_for_store(
+        response
+            .get("output")
+            .and_then(|v| v.as_array())
+            .map(|v| v.as_slice())
+            .unwrap_or_default(),
+    );
+    let mut response = response.clone();
+    response["output"] = json!(&output);
+    let stored = crate::modules::response_store::StoredResponse {
+        id: ctx.response_id.clone(),
+        created_at: ctx.created_at,
+        model: model.to_string(),
+        status: response
+            .get("status")
+            .and_then(|v| v.as_str())
+            .unwrap_or("completed")
+            .to_string(),
+        previous_response_id: ctx.previous_response_id.clone(),
+        instructions: ctx.instructions.clone(),
+        input_items,
+        output,
+        response,
+        user_token_id: ctx.user_token_id.clone(),
+    };
+    let saved = tokio::task::spawn_blocking(move || {
+        crate::modules::response_store::save_response(&stored).map_err(|e| (stored.id, e))
+    })
+    .await;
+    match saved {
+        Ok(Ok(())) => {}
+        Ok(Err((id, e))) => tracing::warn!("[ResponseStore] Failed to save response {}: {}", id, e),
+        Err(e) => tracing::warn!("[ResponseStore] Save task failed for {}: {}", ctx.response_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::mappers::openai::models::{Choice, OpenAIMessage, ToolCall, ToolFunction};
+
+    #[test]
+    fn test_normalize_string_input() {
+        let items = normalize_input_items(&json!("hello"));
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["type"], "message");
+        assert_eq!(items[0]["role"], "user");
+        assert_eq!(items[0]["content"][0]["type"], "input_text");
+        assert_eq!(items[0]["content"][0]["text"], "hello");
+        assert!(items[0]["id"].as_str().unwrap().starts_with("msg_"));
+    }
+
+    #[test]
+    fn test_normalize_mixed_input() {
+        let items = normalize_input_items(&json!([
+            { "role": "assistant", "content": "earlier answer" },
+            { "type": "function_call_output", "call_id": "call_1", "output": "ok" },
+            { "type": "input_text", "text": "part a" },
+            { "type": "input_text", "text": "part b" }
+        ]));
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0]["type"], "message");
+        assert_eq!(items[0]["content"][0]["type"], "output_text");
+        assert!(items[1]["id"].as_str().unwrap().starts_with("fco_"));
+        assert_eq!(items[2]["role"], "user");
+        assert_eq!(items[2]["content"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_chat_response_to_output_with_tool_calls() {
+        let chat = OpenAIResponse {
+            id: "chatcmpl-1".to_string(),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: "gemini-2.5-pro".to_string(),
+            choices: vec![Choice {
+                index: 0,
+                message: OpenAIMessage {
+                    role: "assistant".to_string(),
+                    content: None,
+                    reasoning_content: None,
+                    tool_calls: Some(vec![ToolCall {
+                        id: "call_abc".to_string(),
+                        r#type: "function".to_string(),
+                        function: ToolFunction {
+                            name: "shell".to_string(),
+                            arguments: "{\"command\":[\"ls\"]}".to_string(),
+                        },
+                    }]),
+                    tool_call_id: None,
+                    name: None,
+                },
+                finish_reason: Some("tool_calls".to_string()),
+            }],
+            usage: None,
+        };
+
+        let output = chat_response_to_output(&chat);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0]["type"], "function_call");
+        assert_eq!(output[0]["call_id"], "call_abc");
+        assert_eq!(output[0]["name"], "shell");
+        assert_eq!(output[0]["arguments"], "{\"command\":[\"ls\"]}");
+    }
+
+    #[test]
+    fn test_output_for_store_drops_empty_placeholder_message() {
+        let output = vec![
+            message_item("msg_1", "", "completed"),
+            function_call_item("fc_1", "call_1", "shell", "{}", "completed"),
+        ];
+        let stored = output_for_store(&output);
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0]["type"], "function_call");
+
+        // 没有其他输出项时保留空消息，避免会话轮次丢失
+        let only_message = vec![message_item("msg_2", "", "completed")];
+        assert_eq!(output_for_store(&only_message).len(), 1);
+
+        let with_text = vec![
+            message_item("msg_3", "hi", "completed"),
+            function_call_item("fc_2", "call_2", "shell", "{}", "completed"),
+        ];
+        assert_eq!(output_for_store(&with_text).len(), 2);
+    }
+}
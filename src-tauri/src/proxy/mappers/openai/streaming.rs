@@ -8,6 +8,11 @@ use std::pin::Pin;
 use tracing::debug;
 use uuid::Uuid;
 
+use super::responses::{
+    build_response_object, function_call_item, generate_id, message_item, usage_to_responses,
+    ResponsesContext,
+};
+
 
 
 /// 保存 thoughtSignature 到会话缓存
@@ -33,16 +38,22 @@ pub fn store_thought_signature(sig: &str, session_id: &str, message_count: usize
 
 /// Extract and convert Gemini usageMetadata to OpenAI usage format
 fn extract_usage_metadata(u: &Value) -> Option<super::models::OpenAIUsage> {
-    use super::models::{OpenAIUsage, PromptTokensDetails};
+    use super::models::{CompletionTokensDetails, OpenAIUsage, PromptTokensDetails};
 
     let prompt_tokens = u
         .get("promptTokenCount")
         .and_then(|v| v.as_u64())
         .unwrap_or(0) as u32;
-    let completion_tokens = u
+    let candidates_tokens = u
         .get("candidatesTokenCount")
         .and_then(|v| v.as_u64())
         .unwrap_or(0) as u32;
+    let reasoning_tokens = u
+        .get("thoughtsTokenCount")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32);
+    // OpenAI 的 completion_tokens 包含 reasoning_tokens，Gemini 则将思考 token 单独计数
+    let completion_tokens = candidates_tokens.saturating_add(reasoning_tokens.unwrap_or(0));
     let total_tokens = u
         .get("totalTokenCount")
         .and_then(|v| v.as_u64())
@@ -59,10 +70,31 @@ fn extract_usage_metadata(u: &Value) -> Option<super::models::OpenAIUsage> {
         prompt_tokens_details: cached_tokens.map(|ct| PromptTokensDetails {
             cached_tokens: Some(ct),
         }),
-        completion_tokens_details: None,
+        completion_tokens_details: reasoning_tokens.map(|rt| CompletionTokensDetails {
+            reasoning_tokens: Some(rt),
+        }),
     })
 }
 
+/// [FIX #1575] 标准化 shell 工具参数名称
+/// Gemini 可能使用 cmd/code/script 等替代参数名，统一为 command
+fn normalize_shell_args(name: &str, args: &mut Value) {
+    if name != "shell" && name != "bash" && name != "local_shell" {
+        return;
+    }
+    if let Some(obj) = args.as_object_mut() {
+        if !obj.contains_key("command") {
+            for alt_key in &["cmd", "code", "script", "shell_command"] {
+                if let Some(val) = obj.remove(*alt_key) {
+                    obj.insert("command".to_string(), val);
+                    debug!("[OpenAI-Stream] Normalized shell arg '{}' -> 'command'", alt_key);
+                    break;
+                }
+            }
+        }
+    }
+}
+
 pub fn create_openai_sse_stream<S, E>(
     mut gemini_stream: Pin<Box<S>>,
     model: String,
@@ -139,23 +171,8 @@ where
                                                                     emitted_tool_calls.insert(call_key);
                                                                     let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
                                                                     let mut args = func_call.get("args").unwrap_or(&json!({})).clone();
-                                                                    
-                                                                    // [FIX #1575] 标准化 shell 工具参数名称
-                                                                    // Gemini 可能使用 cmd/code/script 等替代参数名，统一为 command
-                                                                    if name == "shell" || name == "bash" || name == "local_shell" {
-                                                                        if let Some(obj) = args.as_object_mut() {
-                                                                            if !obj.contains_key("command") {
-                                                                                for alt_key in &["cmd", "code", "script", "shell_command"] {
-                                                                                    if let Some(val) = obj.remove(*alt_key) {
-                                                                                        obj.insert("command".to_string(), val);
-                                                                                        debug!("[OpenAI-Stream] Normalized shell arg '{}' -> 'command'", alt_key);
-                                                                                        break;
-                                                                                    }
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                    
+                                                                    normalize_shell_args(name, &mut args);
+
                                                                     let args_str = serde_json::to_string(&args).unwrap_or_default();
                                                                     let mut hasher = std::collections::hash_map::DefaultHasher::new();
                                                                     use std::hash::{Hash, Hasher};
@@ -422,42 +439,41 @@ where
     Box::pin(stream)
 }
 
+/// Codex / Responses API 流式转换
+///
+/// 文本输出固定为 output_index 0 的 message 项，函数调用依次作为独立的 function_call 项
+/// (含 response.function_call_arguments.delta/done 事件)。
+/// `on_complete` 以完整的 Responses 对象回调 (用于持久化)，并在发送 response.completed 之前 await。
 pub fn create_codex_sse_stream<S, E>(
     mut gemini_stream: Pin<Box<S>>,
-    _model: String,
+    model: String,
     session_id: String,
     message_count: usize,
+    ctx: ResponsesContext,
+    on_complete: Option<Box<dyn FnOnce(Value) -> futures::future::BoxFuture<'static, ()> + Send>>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> 
 where
     S: Stream<Item = Result<Bytes, E>> + Send + ?Sized + 'static,
     E: std::fmt::Display + Send + 'static,
 {
     let mut buffer = BytesMut::new();
-    let charset = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-    let mut rng = rand::thread_rng();
-    let random_str: String = (0..24).map(|_| {
-        let idx = rng.gen_range(0..charset.len());
-        charset.chars().nth(idx).unwrap()
-    }).collect();
-    let response_id = format!("resp-{}", random_str);
-    let item_id = format!("item-{}", &random_str[..16]);
+    let item_id = generate_id("msg");
 
     let stream = async_stream::stream! {
+        let mut on_complete = on_complete;
+
         // 1. response.created
-        let created_ev = json!({ "type": "response.created", "response": { "id": &response_id, "object": "response", "status": "in_progress", "output": [] } });
+        let created_ev = json!({
+            "type": "response.created",
+            "response": build_response_object(&ctx, &model, "in_progress", &[], None)
+        });
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&created_ev).unwrap())));
 
         // 2. response.output_item.added - 告诉客户端开始一个输出项
         let output_item_added = json!({
             "type": "response.output_item.added",
             "output_index": 0,
-            "item": {
-                "id": &item_id,
-                "type": "message",
-                "role": "assistant",
-                "status": "in_progress",
-                "content": []
-            }
+            "item": message_item(&item_id, "", "in_progress")
         });
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&output_item_added).unwrap())));
 
@@ -469,13 +485,17 @@ where
             "content_index": 0,
             "part": {
                 "type": "output_text",
-                "text": ""
+                "text": "",
+                "annotations": []
             }
         });
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&content_part_added).unwrap())));
 
         let mut emitted_tool_calls = std::collections::HashSet::new();
+        let mut function_call_items: Vec<Value> = Vec::new();
         let mut accumulated_text = String::new();
+        let mut final_usage: Option<super::models::OpenAIUsage> = None;
+        let mut stream_error: Option<String> = None;
         let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
@@ -495,6 +515,9 @@ where
 
                                     if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
                                         let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
+                                        if let Some(u) = actual_data.get("usageMetadata") {
+                                            final_usage = extract_usage_metadata(u);
+                                        }
                                         if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
                                             if candidates.len() > 0 {
                                                 tracing::debug!("[Codex-Stream-Debug] Raw Candidate: {:?}", candidates[0]);
@@ -534,8 +557,58 @@ where
                                                         }
                                                         if let Some(func_call) = part.get("functionCall") {
                                                             let call_key = serde_json::to_string(func_call).unwrap_or_default();
-                                                            if !emitted_tool_calls.contains(&call_key) {
-                                                                emitted_tool_calls.insert(call_key);
+                                                            if emitted_tool_calls.insert(call_key.clone()) {
+                                                                let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+                                                                let mut args = func_call.get("args").unwrap_or(&json!({})).clone();
+                                                                normalize_shell_args(name, &mut args);
+                                                                let args_str = serde_json::to_string(&args).unwrap_or_default();
+
+                                                                let call_id = func_call
+                                                                    .get("id")
+                                                                    .and_then(|v| v.as_str())
+                                                                    .map(|s| s.to_string())
+                                                                    .unwrap_or_else(|| {
+                                                                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                                                                        use std::hash::{Hash, Hasher};
+                                                                        call_key.hash(&mut hasher);
+                                                                        format!("call_{:x}", hasher.finish())
+                                                                    });
+                                                                let fc_item_id = generate_id("fc");
+                                                                let output_index = function_call_items.len() + 1;
+
+                                                                // function_call 输出项: added → arguments.delta → arguments.done → done
+                                                                let fc_added = json!({
+                                                                    "type": "response.output_item.added",
+                                                                    "output_index": output_index,
+                                                                    "item": function_call_item(&fc_item_id, &call_id, name, "", "in_progress")
+                                                                });
+                                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&fc_added).unwrap())));
+
+                                                                let args_delta = json!({
+                                                                    "type": "response.function_call_arguments.delta",
+                                                                    "item_id": &fc_item_id,
+                                                                    "output_index": output_index,
+                                                                    "delta": &args_str
+                                                                });
+                                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&args_delta).unwrap())));
+
+                                                                let args_done = json!({
+                                                                    "type": "response.function_call_arguments.done",
+                                                                    "item_id": &fc_item_id,
+                                                                    "output_index": output_index,
+                                                                    "arguments": &args_str
+                                                                });
+                                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&args_done).unwrap())));
+
+                                                                let done_item = function_call_item(&fc_item_id, &call_id, name, &args_str, "completed");
+                                                                let fc_done = json!({
+                                                                    "type": "response.output_item.done",
+                                                                    "output_index": output_index,
+                                                                    "item": &done_item
+                                                                });
+                                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&fc_done).unwrap())));
+
+                                                                function_call_items.push(done_item);
                                                             }
                                                         }
                                                     }
@@ -584,7 +657,11 @@ where
                                 }
                             }
                         }
-                        Some(Err(_)) => break,
+                        Some(Err(e)) => {
+                            tracing::error!("Codex Stream Error: {}", e);
+                            stream_error = Some(e.to_string());
+                            break;
+                        }
                         None => break,
                     }
                 }
@@ -610,47 +687,42 @@ where
             "content_index": 0,
             "part": {
                 "type": "output_text",
-                "text": &accumulated_text
+                "text": &accumulated_text,
+                "annotations": []
             }
         });
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&content_part_done).unwrap())));
 
         // 7. response.output_item.done
+        let message_done = message_item(&item_id, &accumulated_text, "completed");
         let output_item_done = json!({
             "type": "response.output_item.done",
             "output_index": 0,
-            "item": {
-                "id": &item_id,
-                "type": "message",
-                "role": "assistant",
-                "status": "completed",
-                "content": [{
-                    "type": "output_text",
-                    "text": &accumulated_text
-                }]
-            }
+            "item": &message_done
         });
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&output_item_done).unwrap())));
 
-        // 8. response.completed
-        let completed_ev = json!({
-            "type": "response.completed",
-            "response": {
-                "id": &response_id,
-                "object": "response",
-                "status": "completed",
-                "output": [{
-                    "id": &item_id,
-                    "type": "message",
-                    "role": "assistant",
-                    "content": [{
-                        "type": "output_text",
-                        "text": &accumulated_text
-                    }]
-                }]
+        // 8. response.completed / response.failed
+        let mut output = vec![message_done];
+        output.extend(function_call_items);
+        let usage = final_usage.as_ref().map(usage_to_responses);
+
+        if let Some(err) = stream_error {
+            use crate::proxy::mappers::error_classifier::classify_stream_error;
+            let (error_type, user_msg, _i18n_key) = classify_stream_error(&err);
+            let mut failed = build_response_object(&ctx, &model, "failed", &output, usage);
+            failed["error"] = json!({ "code": error_type, "message": user_msg });
+            let failed_ev = json!({ "type": "response.failed", "response": failed });
+            yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&failed_ev).unwrap())));
+        } else {
+            let response = build_response_object(&ctx, &model, "completed", &output, usage);
+            // 先落库再通知完成：客户端收到 completed 后立即续写也能找到该响应
+            if let Some(callback) = on_complete.take() {
+                callback(response.clone()).await;
             }
-        });
-        yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&completed_ev).unwrap())));
+            let completed_ev = json!({ "type": "response.completed", "response": &response });
+            yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&completed_ev).unwrap())));
+        }
     };
     Box::pin(stream)
 }
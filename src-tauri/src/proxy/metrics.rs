@@ -0,0 +1,359 @@
+// Prometheus 指标 - /metrics 端点
+// 以 Prometheus 文本格式 (0.0.4) 手写导出，避免引入额外依赖
+//
+// - 计数器/直方图在请求路径上实时累加 (monitor 中间件、handler 重试循环、限流跟踪器)
+// - 账号配额与代理池健康属于状态型数据，在抓取时采集；账号配额读取账号文件 (解密 token)，
+//   因此结果缓存 ACCOUNT_GAUGE_TTL，避免高频抓取反复读取/迁移账号文件
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// 账号配额类指标的缓存时长
+const ACCOUNT_GAUGE_TTL: Duration = Duration::from_secs(60);
+
+/// 已渲染的账号配额指标 (生成时间, 文本)；抓取时持有锁，保证并发抓取只读取一次账号文件
+static ACCOUNT_GAUGES: Lazy<tokio::sync::Mutex<Option<(std::time::Instant, String)>>> =
+    Lazy::new(|| tokio::sync::Mutex::new(None));
+
+/// 延迟直方图桶 (秒)，覆盖从快速首包到长时间思考的完整流式响应
+const LATENCY_BUCKETS: [f64; 12] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// 每个桶的非累积计数，渲染时再做累加
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        if let Some(idx) = LATENCY_BUCKETS.iter().position(|b| secs <= *b) {
+            self.buckets[idx] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+/// (protocol, model, status, account)
+type RequestKey = (String, String, String, String);
+/// (protocol, model)
+type LatencyKey = (String, String);
+
+#[derive(Default)]
+struct ProxyMetrics {
+    requests: DashMap<RequestKey, u64>,
+    ttfb: DashMap<LatencyKey, Histogram>,
+    duration: DashMap<LatencyKey, Histogram>,
+    ttft: DashMap<LatencyKey, Histogram>,
+    retries: DashMap<String, u64>,
+    rotations: DashMap<String, u64>,
+    lockouts: DashMap<String, u64>,
+}
+
+static METRICS: Lazy<ProxyMetrics> = Lazy::new(ProxyMetrics::default);
+
+/// 记录一次已完成的代理请求
+///
+/// `ttfb` 为首字节耗时，`total` 为响应体发送完毕的总耗时，`ttft` 为流式响应的首 token 耗时
+pub fn record_request(
+    protocol: &str,
+    model: &str,
+    status: u16,
+    account: &str,
+    ttfb: Duration,
+    total: Duration,
+    ttft: Option<Duration>,
+) {
+    let key = (
+        protocol.to_string(),
+        model.to_string(),
+        status.to_string(),
+        account.to_string(),
+    );
+    *METRICS.requests.entry(key).or_insert(0) += 1;
+
+    let latency_key = (protocol.to_string(), model.to_string());
+    METRICS
+        .ttfb
+        .entry(latency_key.clone())
+        .or_default()
+        .observe(ttfb.as_secs_f64());
+    METRICS
+        .duration
+        .entry(latency_key.clone())
+        .or_default()
+        .observe(total.as_secs_f64());
+    if let Some(ttft) = ttft {
+        METRICS
+            .ttft
+            .entry(latency_key)
+            .or_default()
+            .observe(ttft.as_secs_f64());
+    }
+}
+
+/// 记录 handler 重试循环中的一次账号获取
+///
+/// attempt > 0 计为一次上游重试；与上一次使用的账号不同则计为一次账号轮换
+pub fn record_attempt(protocol: &str, attempt: usize, previous_email: Option<&str>, email: &str) {
+    if attempt == 0 {
+        return;
+    }
+    *METRICS.retries.entry(protocol.to_string()).or_insert(0) += 1;
+    if previous_email.is_some_and(|prev| prev != email) {
+        *METRICS.rotations.entry(protocol.to_string()).or_insert(0) += 1;
+    }
+}
+
+/// 记录一次限流锁定 (按 RateLimitReason 分类)
+pub fn record_rate_limit_lockout(reason: &str) {
+    *METRICS.lockouts.entry(reason.to_string()).or_insert(0) += 1;
+}
+
+/// 转义 label 值 (反斜杠、双引号、换行)
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_histogram(out: &mut String, name: &str, help: &str, map: &DashMap<LatencyKey, Histogram>) {
+    write_header(out, name, help, "histogram");
+    let mut rows: Vec<(LatencyKey, Histogram)> = map
+        .iter()
+        .map(|e| (e.key().clone(), e.value().clone()))
+        .collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for ((protocol, model), hist) in rows {
+        let labels = format!(
+            "protocol=\"{}\",model=\"{}\"",
+            escape_label(&protocol),
+            escape_label(&model)
+        );
+        let mut cumulative = 0u64;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(hist.buckets.iter()) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, hist.count);
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, hist.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, hist.count);
+    }
+}
+
+fn write_counter(out: &mut String, name: &str, help: &str, label: &str, map: &DashMap<String, u64>) {
+    write_header(out, name, help, "counter");
+    let mut rows: Vec<(String, u64)> = map.iter().map(|e| (e.key().clone(), *e.value())).collect();
+    rows.sort();
+    for (value, count) in rows {
+        let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, escape_label(&value), count);
+    }
+}
+
+/// 渲染请求路径上累加的计数器与直方图
+fn render_counters(out: &mut String) {
+    write_header(
+        out,
+        "antigravity_requests_total",
+        "Proxied requests by protocol, model, status and account.",
+        "counter",
+    );
+    let mut rows: Vec<(RequestKey, u64)> = METRICS
+        .requests
+        .iter()
+        .map(|e| (e.key().clone(), *e.value()))
+        .collect();
+    rows.sort();
+    for ((protocol, model, status, account), count) in rows {
+        let _ = writeln!(
+            out,
+            "antigravity_requests_total{{protocol=\"{}\",model=\"{}\",status=\"{}\",account=\"{}\"}} {}",
+            escape_label(&protocol),
+            escape_label(&model),
+            escape_label(&status),
+            escape_label(&account),
+            count
+        );
+    }
+
+    write_histogram(
+        out,
+        "antigravity_request_ttfb_seconds",
+        "Time to first response byte.",
+        &METRICS.ttfb,
+    );
+    write_histogram(
+        out,
+        "antigravity_request_duration_seconds",
+        "Total request duration including the full response body.",
+        &METRICS.duration,
+    );
+    write_histogram(
+        out,
+        "antigravity_request_ttft_seconds",
+        "Time to first generated token for streaming responses.",
+        &METRICS.ttft,
+    );
+    write_counter(
+        out,
+        "antigravity_upstream_retries_total",
+        "Upstream retry attempts made by request handlers.",
+        "protocol",
+        &METRICS.retries,
+    );
+    write_counter(
+        out,
+        "antigravity_account_rotations_total",
+        "Retries that switched to a different account.",
+        "protocol",
+        &METRICS.rotations,
+    );
+    write_counter(
+        out,
+        "antigravity_rate_limit_lockouts_total",
+        "Account/model lockouts recorded by the rate limit tracker.",
+        "reason",
+        &METRICS.lockouts,
+    );
+}
+
+/// 渲染账号配额 (来自账号文件中的 QuotaData)
+fn render_account_quotas(out: &mut String, accounts: &[crate::models::Account]) {
+    write_header(
+        out,
+        "antigravity_account_quota_percent",
+        "Remaining model quota percentage per account.",
+        "gauge",
+    );
+    for account in accounts {
+        let Some(quota) = &account.quota else { continue };
+        for model in &quota.models {
+            let _ = writeln!(
+                out,
+                "antigravity_account_quota_percent{{account=\"{}\",model=\"{}\"}} {}",
+                escape_label(&account.email),
+                escape_label(&model.name),
+                model.percentage
+            );
+        }
+    }
+
+    write_header(
+        out,
+        "antigravity_account_forbidden",
+        "Whether the account is forbidden (403) by upstream.",
+        "gauge",
+    );
+    for account in accounts {
+        let forbidden = account.quota.as_ref().is_some_and(|q| q.is_forbidden);
+        let _ = writeln!(
+            out,
+            "antigravity_account_forbidden{{account=\"{}\"}} {}",
+            escape_label(&account.email),
+            forbidden as u8
+        );
+    }
+}
+
+/// 渲染代理池健康状态
+fn render_proxy_pool(out: &mut String, proxies: &[crate::proxy::config::ProxyEntry]) {
+    write_header(
+        out,
+        "antigravity_proxy_pool_healthy",
+        "Health of each enabled proxy pool entry (1 = healthy).",
+        "gauge",
+    );
+    for proxy in proxies.iter().filter(|p| p.enabled) {
+        let _ = writeln!(
+            out,
+            "antigravity_proxy_pool_healthy{{proxy=\"{}\",name=\"{}\"}} {}",
+            escape_label(&proxy.id),
+            escape_label(&proxy.name),
+            proxy.is_healthy as u8
+        );
+    }
+
+    write_header(
+        out,
+        "antigravity_proxy_pool_latency_seconds",
+        "Latency measured by the last proxy health check.",
+        "gauge",
+    );
+    for proxy in proxies.iter().filter(|p| p.enabled) {
+        if let Some(ms) = proxy.latency {
+            let _ = writeln!(
+                out,
+                "antigravity_proxy_pool_latency_seconds{{proxy=\"{}\",name=\"{}\"}} {}",
+                escape_label(&proxy.id),
+                escape_label(&proxy.name),
+                ms as f64 / 1000.0
+            );
+        }
+    }
+}
+
+/// 账号配额指标 (缓存 ACCOUNT_GAUGE_TTL)
+async fn account_gauges() -> String {
+    let mut cache = ACCOUNT_GAUGES.lock().await;
+    if let Some((rendered_at, text)) = cache.as_ref() {
+        if rendered_at.elapsed() < ACCOUNT_GAUGE_TTL {
+            return text.clone();
+        }
+    }
+
+    // 账号文件读取为阻塞 IO，放到 blocking 线程执行
+    let mut text = String::new();
+    match tokio::task::spawn_blocking(crate::modules::account::list_accounts).await {
+        Ok(Ok(accounts)) => render_account_quotas(&mut text, &accounts),
+        Ok(Err(e)) => tracing::warn!("[Metrics] Failed to load accounts: {}", e),
+        Err(e) => tracing::warn!("[Metrics] Account loader panicked: {}", e),
+    }
+    *cache = Some((std::time::Instant::now(), text.clone()));
+    text
+}
+
+/// 生成完整的 Prometheus 文本
+pub async fn render(pool: &crate::proxy::proxy_pool::ProxyPoolManager) -> String {
+    let mut out = String::new();
+    render_counters(&mut out);
+
+    out.push_str(&account_gauges().await);
+
+    render_proxy_pool(&mut out, &pool.proxies_snapshot().await);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let map = DashMap::new();
+        let key = ("anthropic".to_string(), "claude-\"x\"".to_string());
+        for secs in [0.01, 0.3, 0.3, 400.0] {
+            map.entry(key.clone()).or_insert_with(Histogram::default).observe(secs);
+        }
+
+        let mut out = String::new();
+        write_histogram(&mut out, "t_seconds", "test", &map);
+
+        let labels = "protocol=\"anthropic\",model=\"claude-\\\"x\\\"\"";
+        assert!(out.contains(&format!("t_seconds_bucket{{{},le=\"0.05\"}} 1", labels)));
+        assert!(out.contains(&format!("t_seconds_bucket{{{},le=\"0.5\"}} 3", labels)));
+        assert!(out.contains(&format!("t_seconds_bucket{{{},le=\"300\"}} 3", labels)));
+        assert!(out.contains(&format!("t_seconds_bucket{{{},le=\"+Inf\"}} 4", labels)));
+        assert!(out.contains(&format!("t_seconds_count{{{}}} 4", labels)));
+    }
+}
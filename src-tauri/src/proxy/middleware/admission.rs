@@ -0,0 +1,39 @@
+// 准入中间件
+//
+// 位于 auth 之后：
+// - 根据用户令牌与 `X-Request-Priority` 确定请求的排队类别，供准入队列 (proxy::admission) 做公平调度
+// - 为请求创建并发名额槽，TokenManager 选中账号时占用的名额在响应体结束 (或被丢弃) 时释放
+
+use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+use futures::StreamExt;
+
+use crate::proxy::admission::{self, RequestClass};
+use crate::proxy::concurrency::{self, LeaseSlot};
+use crate::proxy::middleware::auth::UserTokenIdentity;
+
+pub async fn admission_middleware(request: Request, next: Next) -> Response {
+    let user = request
+        .extensions()
+        .get::<UserTokenIdentity>()
+        .map(|identity| identity.token_id.clone());
+    let class = RequestClass::from_headers(request.headers(), user);
+    let slot = LeaseSlot::default();
+
+    let response = admission::scope_request_class(
+        class,
+        concurrency::scope_lease_slot(slot.clone(), next.run(request)),
+    )
+    .await;
+
+    if !slot.is_held() {
+        return response;
+    }
+
+    // 流式响应在 handler 返回后才真正结束：名额随响应体一起释放
+    let (parts, body) = response.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let _held = &slot;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
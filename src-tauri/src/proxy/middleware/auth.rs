@@ -9,8 +9,47 @@ use axum::{
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::modules::admin_user_db::{AdminRole, AdminUser};
+use crate::modules::user_token_db::TokenLimitViolation;
 use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
 
+/// 鉴权阶段的链路追踪 span
+///
+/// 鉴权结果在放行时记录，span 在进入下游之前结束，
+/// 这样后续 span (模型映射、上游请求等) 仍挂在请求根 span 下；未放行时记录为 denied
+struct AuthSpan {
+    span: tracing::Span,
+    outcome: &'static str,
+}
+
+impl AuthSpan {
+    fn new(force_strict: bool) -> Self {
+        let span = tracing::info_span!(
+            target: crate::proxy::telemetry::SPAN_TARGET,
+            "auth",
+            auth.admin = force_strict,
+            auth.outcome = tracing::field::Empty,
+        );
+        Self { span, outcome: "denied" }
+    }
+
+    /// 记录放行原因，结束 span 后执行下游
+    async fn proceed(mut self, outcome: &'static str, next: Next, request: Request) -> Response {
+        self.outcome = outcome;
+        drop(self);
+        next.run(request).await
+    }
+}
+
+impl Drop for AuthSpan {
+    fn drop(&mut self) {
+        self.span.record("auth.outcome", self.outcome);
+    }
+}
+
+/// 读取请求模型名时允许缓冲的最大请求体
+const MAX_MODEL_PEEK_BODY_SIZE: usize = 100 * 1024 * 1024; // 100MB
+
 /// API Key 认证中间件 (代理接口使用，遵循 auth_mode)
 pub async fn auth_middleware(
     state: State<Arc<RwLock<ProxySecurityConfig>>>,
@@ -38,6 +77,7 @@ async fn auth_middleware_internal(
 ) -> Result<Response, StatusCode> {
     let method = request.method().clone();
     let path = request.uri().path().to_string();
+    let auth_span = AuthSpan::new(force_strict);
 
     // 过滤心跳和健康检查请求,避免日志噪音
     let is_health_check = path == "/healthz" || path == "/api/health" || path == "/health";
@@ -50,12 +90,22 @@ async fn auth_middleware_internal(
 
     // Allow CORS preflight regardless of auth policy.
     if method == axum::http::Method::OPTIONS {
-        return Ok(next.run(request).await);
+        return Ok(auth_span.proceed("preflight", next, request).await);
     }
 
     let security = security.read().await.clone();
     let effective_mode = security.effective_auth_mode();
 
+    // [FIX] 内部端点 (/internal/*) 与 auth_mode 无关：仅允许本机回环客户端或携带进程内部密钥的请求
+    if is_internal_endpoint {
+        if internal_access_allowed(&request, &security) {
+            tracing::debug!("Internal endpoint access allowed: {}", path);
+            return Ok(auth_span.proceed("internal", next, request).await);
+        }
+        reject_internal_access(&request, &security);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // 权限检查逻辑
     if !force_strict {
         // AI 代理接口 (v1/chat/completions 等)
@@ -75,8 +125,12 @@ async fn auth_middleware_internal(
                 });
             
             if let Some(token) = api_key {
-                // 尝试验证是否为 User Token（不阻止请求，只记录）
+                // 尝试验证是否为 User Token（不校验令牌有效性，但用量限制仍然生效）
                 if let Ok(Some(user_token)) = crate::modules::user_token_db::get_token_by_value(token) {
+                    let request = match enforce_token_limits(&user_token, request, &path).await {
+                        Ok(request) => request,
+                        Err(response) => return Ok(response),
+                    };
                     let identity = UserTokenIdentity {
                         token_id: user_token.id,
                         token: user_token.token,
@@ -86,106 +140,91 @@ async fn auth_middleware_internal(
                     let (mut parts, body) = request.into_parts();
                     parts.extensions.insert(identity);
                     let request = Request::from_parts(parts, body);
-                    return Ok(next.run(request).await);
+                    return Ok(auth_span.proceed("user_token", next, request).await);
                 }
             }
             
-            return Ok(next.run(request).await);
+            return Ok(auth_span.proceed("auth_off", next, request).await);
         }
 
         if matches!(effective_mode, ProxyAuthMode::AllExceptHealth) && is_health_check {
-            return Ok(next.run(request).await);
-        }
-
-        // 内部端点 (/internal/*) 豁免鉴权 - 用于 warmup 等内部功能
-        if is_internal_endpoint {
-            tracing::debug!("Internal endpoint bypassed auth: {}", path);
-            return Ok(next.run(request).await);
+            return Ok(auth_span.proceed("health_check", next, request).await);
         }
     } else {
         // 管理接口 (/api/*)
+        let admin_path = original_path(&request);
+
+        // [NEW] 登录接口无需凭据 (口令错误由登录处理函数计入自动封禁)
+        if admin_route(&admin_path) == "/auth/login" {
+            let client_ip = crate::proxy::middleware::client_ip::extract_client_ip(
+                &request,
+                &security.security_monitor.trusted_proxies,
+            );
+            // [FIX] 被封禁的 IP 不能继续尝试登录
+            if let Some(ip) = &client_ip {
+                if let Some(response) = crate::proxy::middleware::ip_filter::login_blocked_response(
+                    ip,
+                    &security.security_monitor,
+                ) {
+                    return Ok(response);
+                }
+            }
+            let (mut parts, body) = request.into_parts();
+            parts.extensions.insert(AdminClientIp(client_ip));
+            let request = Request::from_parts(parts, body);
+            return Ok(auth_span.proceed("admin_login", next, request).await);
+        }
+
         // 1. 如果全局鉴权关闭，则管理接口也放行 (除非是强制局域网模式)
         if matches!(effective_mode, ProxyAuthMode::Off) {
-            return Ok(next.run(request).await);
+            let identity = AdminIdentity::anonymous();
+            return Ok(proceed_admin(identity, &security, admin_path, auth_span, "auth_off", next, request).await);
         }
 
         // 2. 健康检查在所有模式下对管理接口放行
         if is_health_check {
-            return Ok(next.run(request).await);
+            return Ok(auth_span.proceed("health_check", next, request).await);
         }
+
+        // 3. [NEW] 管理密码 / 管理用户会话 + 角色校验
+        return admin_authenticate(&security, admin_path, auth_span, next, request).await;
     }
     
     // 从 header 中提取 API key
-    let api_key = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
-        .or_else(|| {
-            request
-                .headers()
-                .get("x-api-key")
-                .and_then(|h| h.to_str().ok())
-        })
-        .or_else(|| {
-            request
-                .headers()
-                .get("x-goog-api-key")
-                .and_then(|h| h.to_str().ok())
-        });
+    let api_key = extract_api_key(&request);
 
-    if security.api_key.is_empty() && (security.admin_password.is_none() || security.admin_password.as_ref().unwrap().is_empty()) {
-        if force_strict {
-             tracing::error!("Admin auth is required but both api_key and admin_password are empty; denying request");
-             return Err(StatusCode::UNAUTHORIZED);
-        }
+    if security.api_key.is_empty() {
         tracing::error!("Proxy auth is enabled but api_key is empty; denying request");
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    // 认证逻辑
-    let authorized = if force_strict {
-        // 管理接口：优先使用独立的 admin_password，如果没有则回退使用 api_key
-        match &security.admin_password {
-            Some(pwd) if !pwd.is_empty() => {
-                api_key.map(|k| k == pwd).unwrap_or(false)
-            }
-            _ => {
-                // 回退使用 api_key
-                api_key.map(|k| k == security.api_key).unwrap_or(false)
-            }
-        }
-    } else {
-        // AI 代理接口：仅允许使用 api_key
-        api_key.map(|k| k == security.api_key).unwrap_or(false)
-    };
+    // 认证逻辑：AI 代理接口仅允许使用 api_key
+    let authorized = api_key.map(|k| k == security.api_key).unwrap_or(false);
 
     if authorized {
-        Ok(next.run(request).await)
-    } else if !force_strict && api_key.is_some() {
+        Ok(auth_span.proceed("api_key", next, request).await)
+    } else if api_key.is_some() {
         // 尝试验证 UserToken
         let token = api_key.unwrap();
         
-        // 提取 IP (复用逻辑)
-        let client_ip = request
-            .headers()
-            .get("x-forwarded-for")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.split(',').next().unwrap_or(s).trim().to_string())
-            .or_else(|| {
-                request
-                    .headers()
-                    .get("x-real-ip")
-                    .and_then(|v| v.to_str().ok())
-                    .map(|s| s.to_string())
-            })
-            .unwrap_or_else(|| "127.0.0.1".to_string()); // Default fallback
+        // 提取 IP (与 IP 过滤/监控共用，仅信任 trusted_proxies 转发的头)
+        let client_ip = crate::proxy::middleware::client_ip::extract_client_ip(
+            &request,
+            &security.security_monitor.trusted_proxies,
+        )
+        .unwrap_or_else(|| "127.0.0.1".to_string()); // Default fallback
 
         // 验证 Token
         match crate::modules::user_token_db::validate_token(token, &client_ip) {
             Ok((true, _)) => {
                 // Token 有效，查询信息以便传递
                 if let Ok(Some(user_token)) = crate::modules::user_token_db::get_token_by_value(token) {
+                    // [NEW] 用量限制：模型白名单 → 日/月 token 预算 → RPM
+                    let request = match enforce_token_limits(&user_token, request, &path).await {
+                        Ok(request) => request,
+                        Err(response) => return Ok(response),
+                    };
+
                      let identity = UserTokenIdentity {
                         token_id: user_token.id,
                         token: user_token.token,
@@ -202,7 +241,7 @@ async fn auth_middleware_internal(
                     let request = Request::from_parts(parts, body);
                     
                     // 执行请求
-                    let response = next.run(request).await;
+                    let response = auth_span.proceed("user_token", next, request).await;
                     
                     Ok(response)
                 } else {
@@ -212,19 +251,8 @@ async fn auth_middleware_internal(
             Ok((false, reason)) => {
                 let reason_str = reason.unwrap_or_else(|| "Access denied".to_string());
                 tracing::warn!("UserToken rejected: {}", reason_str);
-                let body = serde_json::json!({
-                    "error": {
-                        "message": reason_str,
-                        "type": "token_rejected",
-                        "code": "token_rejected"
-                    }
-                });
-                let response = axum::response::Response::builder()
-                    .status(StatusCode::FORBIDDEN)
-                    .header("Content-Type", "application/json")
-                    .body(axum::body::Body::from(serde_json::to_string(&body).unwrap()))
-                    .unwrap();
-                Ok(response)
+                record_auth_failure(&request, &security);
+                Ok(token_rejected_response(&reason_str))
             }
             Err(e) => {
                 tracing::error!("UserToken validation error: {}", e);
@@ -232,10 +260,573 @@ async fn auth_middleware_internal(
             }
         }
     } else {
+        record_auth_failure(&request, &security);
         Err(StatusCode::UNAUTHORIZED)
     }
 }
 
+/// 从 header 中提取凭据 (Authorization: Bearer / x-api-key / x-goog-api-key)
+fn extract_api_key(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
+        .or_else(|| {
+            request
+                .headers()
+                .get("x-api-key")
+                .and_then(|h| h.to_str().ok())
+        })
+        .or_else(|| {
+            request
+                .headers()
+                .get("x-goog-api-key")
+                .and_then(|h| h.to_str().ok())
+        })
+}
+
+/// 管理接口鉴权
+///
+/// - admin_password (未设置时回退 api_key) 视为内置 owner，兼容原有的单密码登录
+/// - `agsess_` 开头的凭据按管理用户会话校验，再检查该路由所需的最低角色
+async fn admin_authenticate(
+    security: &ProxySecurityConfig,
+    admin_path: String,
+    auth_span: AuthSpan,
+    next: Next,
+    request: Request,
+) -> Result<Response, StatusCode> {
+    let legacy_secret = match &security.admin_password {
+        Some(pwd) if !pwd.is_empty() => pwd.as_str(),
+        // 回退使用 api_key
+        _ => security.api_key.as_str(),
+    };
+    let credential = extract_api_key(&request);
+
+    let identity = match credential {
+        Some(k) if !legacy_secret.is_empty() && k == legacy_secret => Some(AdminIdentity::admin_password()),
+        Some(k) if k.starts_with(crate::modules::admin_user_db::SESSION_TOKEN_PREFIX) => {
+            match crate::modules::admin_user_db::get_session_user(k) {
+                Ok(user) => user.map(AdminIdentity::from_user),
+                Err(e) => {
+                    tracing::error!("Admin session lookup error: {}", e);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let Some(identity) = identity else {
+        if legacy_secret.is_empty() && credential.is_none() {
+            tracing::error!("Admin auth is required but both api_key and admin_password are empty; denying request");
+        }
+        record_auth_failure(&request, security);
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let required = required_admin_role(request.method(), &admin_path);
+    if identity.role < required {
+        tracing::warn!(
+            "[Admin] {} ({}) denied {} {}: requires {}",
+            identity.username,
+            identity.role.as_str(),
+            request.method(),
+            admin_path,
+            required.as_str()
+        );
+        if is_mutating(request.method()) {
+            let client_ip = crate::proxy::middleware::client_ip::extract_client_ip(
+                &request,
+                &security.security_monitor.trusted_proxies,
+            );
+            let action = format!("{} {}", request.method(), admin_path);
+            record_admin_audit(&identity, action, client_ip, StatusCode::FORBIDDEN);
+        }
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let outcome = if identity.user_id.is_some() { "admin_session" } else { "api_key" };
+    Ok(proceed_admin(identity, security, admin_path, auth_span, outcome, next, request).await)
+}
+
+/// 注入管理身份后执行下游，变更类请求写入审计日志
+async fn proceed_admin(
+    identity: AdminIdentity,
+    security: &ProxySecurityConfig,
+    admin_path: String,
+    auth_span: AuthSpan,
+    outcome: &'static str,
+    next: Next,
+    request: Request,
+) -> Response {
+    let mutating = is_mutating(request.method());
+    let action = format!("{} {}", request.method(), admin_path);
+    let client_ip = crate::proxy::middleware::client_ip::extract_client_ip(
+        &request,
+        &security.security_monitor.trusted_proxies,
+    );
+    // [NEW] 处理函数中记录的账号 / 配置变更归属到该管理身份
+    let actor = crate::modules::audit::AuditActor::admin(
+        identity.actor_type,
+        &identity.username,
+        identity.role.as_str(),
+        client_ip.clone(),
+    );
+
+    let (mut parts, body) = request.into_parts();
+    parts.extensions.insert(identity.clone());
+    let response = crate::modules::audit::scope(
+        actor,
+        auth_span.proceed(outcome, next, Request::from_parts(parts, body)),
+    )
+    .await;
+    if mutating {
+        record_admin_audit(&identity, action, client_ip, response.status());
+    }
+    response
+}
+
+fn is_mutating(method: &axum::http::Method) -> bool {
+    !matches!(
+        *method,
+        axum::http::Method::GET | axum::http::Method::HEAD | axum::http::Method::OPTIONS
+    )
+}
+
+/// 写入管理接口审计日志
+pub fn record_admin_audit(
+    identity: &AdminIdentity,
+    action: String,
+    client_ip: Option<String>,
+    status: StatusCode,
+) {
+    crate::modules::audit_db::record_async(crate::modules::audit_db::AuditEntry {
+        id: 0,
+        timestamp: chrono::Utc::now().timestamp(),
+        actor_type: identity.actor_type.to_string(),
+        actor: identity.username.clone(),
+        role: Some(identity.role.as_str().to_string()),
+        action,
+        target: None,
+        client_ip,
+        status: Some(status.as_u16() as i32),
+        before: None,
+        after: None,
+    });
+}
+
+/// 请求的完整路径 (嵌套路由内 uri 已去掉 /api 前缀)
+fn original_path(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<axum::extract::OriginalUri>()
+        .map(|uri| uri.0.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string())
+}
+
+/// 去掉 /api 前缀与末尾斜杠后的管理路由
+fn admin_route(path: &str) -> &str {
+    let path = path
+        .strip_prefix("/api")
+        .filter(|p| p.starts_with('/'))
+        .unwrap_or(path);
+    match path.trim_end_matches('/') {
+        "" => "/",
+        p => p,
+    }
+}
+
+/// 运维角色可执行的变更接口 (`:` 开头的段匹配任意值)
+const OPERATOR_ROUTES: &[&str] = &[
+    "/proxy/start",
+    "/proxy/stop",
+    "/proxy/rate-limits",
+    "/proxy/rate-limits/:accountId",
+    "/proxy/session-bindings/clear",
+    "/proxy/health-check/trigger",
+    "/proxy/monitor/toggle",
+    "/proxy/cloudflared/start",
+    "/proxy/cloudflared/stop",
+    "/accounts/refresh",
+    "/accounts/warmup",
+    "/accounts/:accountId/warmup",
+    "/debug/enable",
+    "/debug/disable",
+    "/debug/logs/clear",
+];
+
+/// 返回密钥 / 令牌明文或会发起账号授权的只读接口，仅限 owner
+const OWNER_ONLY_READS: &[&str] = &["/config", "/user-tokens", "/proxy/pool/config", "/auth/url", "/audit"];
+
+fn route_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern_segments = pattern.split('/');
+    let mut path_segments = path.split('/');
+    loop {
+        match (pattern_segments.next(), path_segments.next()) {
+            (None, None) => return true,
+            (Some(p), Some(s)) if p.starts_with(':') && !s.is_empty() => {}
+            (Some(p), Some(s)) if p == s => {}
+            _ => return false,
+        }
+    }
+}
+
+/// [NEW] 管理接口所需的最低角色
+///
+/// - viewer: 只读接口 (统计、日志、状态)
+/// - operator: 启停反代、预热、刷新配额、清除限流 / 会话绑定等运维操作
+/// - owner: 其余所有变更 (账号、导入导出、配置、安全设置、用户令牌、管理用户)，以及含密钥的只读接口
+pub fn required_admin_role(method: &axum::http::Method, path: &str) -> AdminRole {
+    let route = admin_route(path);
+    if route == "/auth/me" || route == "/auth/logout" {
+        return AdminRole::Viewer;
+    }
+    if route.starts_with("/admin-users") {
+        return AdminRole::Owner;
+    }
+    if !is_mutating(method) {
+        if OWNER_ONLY_READS.contains(&route) {
+            AdminRole::Owner
+        } else {
+            AdminRole::Viewer
+        }
+    } else if OPERATOR_ROUTES.iter().any(|p| route_matches(p, route)) {
+        AdminRole::Operator
+    } else {
+        AdminRole::Owner
+    }
+}
+
+/// 内部端点访问校验：TCP 对端与解析出的客户端均为回环地址，或携带正确的进程内部密钥
+fn internal_access_allowed(request: &Request, security: &ProxySecurityConfig) -> bool {
+    let has_secret = request
+        .headers()
+        .get(crate::proxy::security::INTERNAL_SECRET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            crate::modules::admin_user_db::constant_time_eq(
+                v.as_bytes(),
+                crate::proxy::security::internal_secret().as_bytes(),
+            )
+        });
+    if has_secret {
+        return true;
+    }
+
+    // 经本机反向代理 (如 cloudflared) 转发的外部请求，对端同样是回环地址，因此还需校验解析出的客户端
+    let peer_is_loopback = request
+        .extensions()
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+        .is_some_and(|info| crate::proxy::middleware::client_ip::is_loopback(info.0.ip()));
+    let client_is_loopback = crate::proxy::middleware::client_ip::extract_client_ip(
+        request,
+        &security.security_monitor.trusted_proxies,
+    )
+    .and_then(|ip| ip.parse().ok())
+    .is_some_and(crate::proxy::middleware::client_ip::is_loopback);
+    peer_is_loopback && client_is_loopback
+}
+
+/// 拒绝内部端点访问：写入安全日志并计入鉴权失败
+fn reject_internal_access(request: &Request, security: &ProxySecurityConfig) {
+    let client_ip = crate::proxy::middleware::client_ip::extract_client_ip(
+        request,
+        &security.security_monitor.trusted_proxies,
+    )
+    .unwrap_or_else(|| "unknown".to_string());
+    tracing::warn!(
+        "[Security] Rejected internal endpoint access from {}: {}",
+        client_ip,
+        request.uri().path()
+    );
+
+    let log = crate::modules::security_db::IpAccessLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        client_ip,
+        timestamp: chrono::Utc::now().timestamp(),
+        method: Some(request.method().to_string()),
+        path: Some(request.uri().to_string()),
+        user_agent: request
+            .headers()
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string()),
+        status: Some(403),
+        duration: Some(0),
+        api_key_hash: None,
+        blocked: true,
+        block_reason: Some("Internal endpoint: not loopback and no valid internal secret".to_string()),
+        username: None,
+    };
+    tokio::spawn(async move {
+        if let Err(e) = crate::modules::security_db::save_ip_access_log(&log) {
+            tracing::error!("[Security] Failed to save internal access log: {}", e);
+        }
+    });
+
+    record_auth_failure(request, security);
+}
+
+/// [NEW] 记录鉴权失败，供自动封禁规则统计
+fn record_auth_failure(request: &Request, security: &ProxySecurityConfig) {
+    if let Some(mark) = request.extensions().get::<crate::proxy::auto_ban::AuthFailureMark>() {
+        mark.set();
+    }
+    if let Some(ip) = crate::proxy::middleware::client_ip::extract_client_ip(
+        request,
+        &security.security_monitor.trusted_proxies,
+    ) {
+        crate::proxy::auto_ban::record_auth_failure(&ip, &security.security_monitor);
+    }
+}
+
+/// 不消耗模型的 POST 接口 (批次文件 / 批次管理 / MCP / 客户端埋点)；批次中的每一行在执行时单独校验
+const MODEL_FREE_ROUTE_PREFIXES: &[&str] = &["/v1/files", "/v1/batches", "/mcp/", "/v1/api/event_logging"];
+
+/// 请求是否需要确定模型才能执行白名单检查
+fn requires_model(request: &Request, path: &str) -> bool {
+    request.method() == axum::http::Method::POST
+        && !MODEL_FREE_ROUTE_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// [NEW] 校验用户令牌的用量限制 (模型白名单 → 日/月 token 预算 → RPM)
+///
+/// 通过时返回请求 (配置了模型白名单时请求体已被缓冲)，否则返回拒绝响应。
+/// 白名单令牌无法确定请求模型时拒绝 (fail closed)。
+async fn enforce_token_limits(
+    user_token: &crate::modules::user_token_db::UserToken,
+    request: Request,
+    path: &str,
+) -> Result<Request, Response> {
+    // 仅在配置了模型白名单时才需要缓冲请求体读取 model
+    let model_required = !user_token.allowed_models.is_empty() && requires_model(&request, path);
+    let (request, model) = if model_required {
+        extract_request_model(request, path).await?
+    } else {
+        (request, None)
+    };
+
+    match check_usage_limits(user_token, model.as_deref(), model_required) {
+        Some(violation) => Err(token_limit_response(path, &violation)),
+        None => Ok(request),
+    }
+}
+
+/// 用户令牌用量限制检查 (auth_middleware 与批处理逐行执行共用)
+///
+/// `model_required` 表示该请求必须确定模型：此时白名单令牌无法解析模型直接拒绝。
+/// 统计失败时放行，避免数据库异常导致整体不可用。会访问数据库，异步调用方应放在阻塞线程中执行。
+pub(crate) fn check_usage_limits(
+    user_token: &crate::modules::user_token_db::UserToken,
+    model: Option<&str>,
+    model_required: bool,
+) -> Option<TokenLimitViolation> {
+    let violation = if model_required && model.is_none() && !user_token.allowed_models.is_empty() {
+        Some(TokenLimitViolation::ModelUnresolved)
+    } else {
+        match crate::modules::user_token_db::check_token_limits(user_token, model) {
+            Ok(violation) => violation,
+            Err(e) => {
+                tracing::error!("UserToken limit check error: {}", e);
+                None
+            }
+        }
+    };
+    if let Some(violation) = &violation {
+        tracing::warn!("UserToken {} limited: {}", user_token.username, violation.message());
+    }
+    violation
+}
+
+/// 用户令牌被拒绝 (禁用 / 过期 / 宵禁 / 无效) 时的响应
+pub(crate) fn token_rejected_response(reason: &str) -> Response {
+    let body = serde_json::json!({
+        "error": {
+            "message": reason,
+            "type": "token_rejected",
+            "code": "token_rejected"
+        }
+    });
+    axum::response::Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("Content-Type", "application/json")
+        .body(axum::body::Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// 从请求中提取客户端请求的模型名 (Gemini 从路径解析，其余协议读取 JSON body 的 model 字段)
+///
+/// 请求体超出上限时返回 413，读取失败时返回 400 (不会以空请求体继续转发)
+async fn extract_request_model(request: Request, path: &str) -> Result<(Request, Option<String>), Response> {
+    if let Some(rest) = path.split("/v1beta/models/").nth(1) {
+        let model = rest.split(':').next().unwrap_or(rest).to_string();
+        return Ok((request, Some(model)));
+    }
+
+    let declared_len = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared_len.is_some_and(|len| len > MAX_MODEL_PEEK_BODY_SIZE) {
+        return Err(body_error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large"));
+    }
+
+    let (parts, body) = request.into_parts();
+    match read_body_limited(body, MAX_MODEL_PEEK_BODY_SIZE).await {
+        Ok(Some(bytes)) => {
+            let model = serde_json::from_slice::<serde_json::Value>(&bytes)
+                .ok()
+                .and_then(|v| v.get("model").and_then(|m| m.as_str()).map(|s| s.to_string()))
+                .filter(|m| !m.is_empty());
+            Ok((Request::from_parts(parts, axum::body::Body::from(bytes)), model))
+        }
+        // 未声明 Content-Length (chunked) 的请求体在读取过程中超限
+        Ok(None) => Err(body_error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large")),
+        Err(e) => {
+            tracing::warn!("Failed to read request body for model check: {}", e);
+            Err(body_error_response(
+                StatusCode::BAD_REQUEST,
+                &format!("Failed to read request body: {}", e),
+            ))
+        }
+    }
+}
+
+/// 读取请求体；累计长度超过 `limit` 时立即停止并返回 None
+async fn read_body_limited(body: axum::body::Body, limit: usize) -> Result<Option<Vec<u8>>, axum::Error> {
+    use futures::StreamExt;
+
+    let mut stream = body.into_data_stream();
+    let mut buf = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf))
+}
+
+fn body_error_response(status: StatusCode, message: &str) -> Response {
+    let body = serde_json::json!({
+        "error": {
+            "message": message,
+            "type": "invalid_request_error",
+            "code": status.as_u16()
+        }
+    });
+    axum::response::Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(axum::body::Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// 按请求协议构造令牌限额错误响应 (Claude / Gemini / OpenAI 各自的错误格式)
+pub(crate) fn token_limit_response(path: &str, violation: &TokenLimitViolation) -> Response {
+    let forbidden = violation.is_forbidden();
+    let status = if forbidden {
+        StatusCode::FORBIDDEN
+    } else {
+        StatusCode::TOO_MANY_REQUESTS
+    };
+    let message = violation.message();
+
+    let body = if path.starts_with("/v1/messages") {
+        serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": if forbidden { "permission_error" } else { "rate_limit_error" },
+                "message": message
+            }
+        })
+    } else if path.starts_with("/v1beta/") {
+        serde_json::json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": message,
+                "status": if forbidden { "PERMISSION_DENIED" } else { "RESOURCE_EXHAUSTED" }
+            }
+        })
+    } else {
+        let (error_type, code) = match violation {
+            TokenLimitViolation::ModelNotAllowed(_) | TokenLimitViolation::ModelUnresolved => {
+                ("invalid_request_error", "model_not_allowed")
+            }
+            TokenLimitViolation::RpmExceeded { .. } => ("requests", "rate_limit_exceeded"),
+            _ => ("insufficient_quota", "insufficient_quota"),
+        };
+        serde_json::json!({
+            "error": {
+                "message": message,
+                "type": error_type,
+                "param": null,
+                "code": code
+            }
+        })
+    };
+
+    let mut builder = axum::response::Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json");
+    if let Some(secs) = violation.retry_after_secs() {
+        builder = builder.header("Retry-After", secs.to_string());
+    }
+    builder
+        .body(axum::body::Body::from(serde_json::to_string(&body).unwrap()))
+        .unwrap()
+}
+
+/// [NEW] 管理接口身份 (注入到请求 extensions，供处理函数与审计使用)
+#[derive(Clone, Debug)]
+pub struct AdminIdentity {
+    /// 管理用户 ID；管理密码 / 鉴权关闭时为 None
+    pub user_id: Option<String>,
+    pub username: String,
+    pub role: AdminRole,
+    /// 审计日志中的操作者类型
+    pub actor_type: &'static str,
+}
+
+impl AdminIdentity {
+    pub fn from_user(user: AdminUser) -> Self {
+        Self {
+            user_id: Some(user.id),
+            username: user.username,
+            role: user.role,
+            actor_type: "admin_user",
+        }
+    }
+
+    /// 使用 admin_password (或回退 api_key) 登录的内置 owner
+    fn admin_password() -> Self {
+        Self {
+            user_id: None,
+            username: "admin".to_string(),
+            role: AdminRole::Owner,
+            actor_type: "admin_password",
+        }
+    }
+
+    /// auth_mode = off 时的匿名访问 (与原行为一致，拥有全部权限)
+    fn anonymous() -> Self {
+        Self {
+            user_id: None,
+            username: "anonymous".to_string(),
+            role: AdminRole::Owner,
+            actor_type: "anonymous",
+        }
+    }
+}
+
+/// [NEW] 登录请求的客户端 IP (登录接口不经过鉴权，由中间件注入)
+#[derive(Clone, Debug)]
+pub struct AdminClientIp(pub Option<String>);
+
 /// 用户令牌身份信息 (传递给 Monitor 使用)
 #[derive(Clone, Debug)]
 pub struct UserTokenIdentity {
@@ -272,8 +863,135 @@ mod tests {
         // 我们在 auth_middleware_internal 基础上做了逻辑校验即可
     }
 
+    #[test]
+    fn test_required_admin_role() {
+        use axum::http::Method;
+
+        assert_eq!(required_admin_role(&Method::GET, "/api/stats/summary"), AdminRole::Viewer);
+        assert_eq!(required_admin_role(&Method::GET, "/logs/42"), AdminRole::Viewer);
+        assert_eq!(required_admin_role(&Method::GET, "/api/config"), AdminRole::Owner);
+        assert_eq!(required_admin_role(&Method::GET, "/api/user-tokens"), AdminRole::Owner);
+        assert_eq!(required_admin_role(&Method::POST, "/api/proxy/stop"), AdminRole::Operator);
+        assert_eq!(required_admin_role(&Method::POST, "/api/accounts/abc/warmup"), AdminRole::Operator);
+        assert_eq!(required_admin_role(&Method::DELETE, "/api/proxy/rate-limits/abc"), AdminRole::Operator);
+        // 未列出的变更接口默认需要 owner
+        assert_eq!(required_admin_role(&Method::POST, "/api/accounts/export"), AdminRole::Owner);
+        assert_eq!(required_admin_role(&Method::DELETE, "/api/accounts/abc"), AdminRole::Owner);
+        assert_eq!(required_admin_role(&Method::POST, "/api/config"), AdminRole::Owner);
+        assert_eq!(required_admin_role(&Method::GET, "/api/admin-users"), AdminRole::Owner);
+        assert_eq!(required_admin_role(&Method::GET, "/api/audit"), AdminRole::Owner);
+        assert_eq!(required_admin_role(&Method::POST, "/api/auth/logout"), AdminRole::Viewer);
+        // "/apix" 不是 /api 前缀
+        assert_eq!(admin_route("/apix/config"), "/apix/config");
+        assert_eq!(admin_route("/api/"), "/");
+    }
+
+    #[test]
+    fn test_internal_access_requires_loopback_or_secret() {
+        let security = ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Off,
+            api_key: "sk-api".to_string(),
+            admin_password: None,
+            allow_lan_access: true,
+            port: 8045,
+            security_monitor: crate::proxy::config::SecurityMonitorConfig::default(),
+        };
+        let request = |peer: Option<&str>, xff: Option<&str>, secret: Option<&str>| {
+            let mut builder = Request::builder().uri("/internal/warmup");
+            if let Some(xff) = xff {
+                builder = builder.header("x-forwarded-for", xff);
+            }
+            if let Some(secret) = secret {
+                builder = builder.header(crate::proxy::security::INTERNAL_SECRET_HEADER, secret);
+            }
+            let mut req = builder.body(axum::body::Body::empty()).unwrap();
+            if let Some(peer) = peer {
+                let addr: std::net::SocketAddr = peer.parse().unwrap();
+                req.extensions_mut().insert(axum::extract::ConnectInfo(addr));
+            }
+            req
+        };
+
+        assert!(internal_access_allowed(&request(Some("127.0.0.1:5000"), None, None), &security));
+        assert!(!internal_access_allowed(&request(Some("192.168.1.20:5000"), None, None), &security));
+        assert!(!internal_access_allowed(&request(None, None, None), &security));
+        // 本机反向代理转发的外部请求
+        assert!(!internal_access_allowed(&request(Some("127.0.0.1:5000"), Some("203.0.113.9"), None), &security));
+        let secret = crate::proxy::security::internal_secret();
+        assert!(internal_access_allowed(&request(Some("192.168.1.20:5000"), None, Some(secret)), &security));
+        assert!(!internal_access_allowed(&request(Some("192.168.1.20:5000"), None, Some("guess")), &security));
+    }
+
     #[test]
     fn test_auth_placeholder() {
         assert!(true);
     }
+
+    #[test]
+    fn test_token_limit_response_protocol_shapes() {
+        let budget = TokenLimitViolation::DailyBudgetExceeded { used: 10, limit: 10 };
+        let resp = token_limit_response("/v1/messages", &budget);
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let rpm = TokenLimitViolation::RpmExceeded { limit: 5, retry_after_secs: 12 };
+        let resp = token_limit_response("/v1/chat/completions", &rpm);
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get("Retry-After").unwrap(), "12");
+
+        let model = TokenLimitViolation::ModelNotAllowed("gemini-3-pro".to_string());
+        let resp = token_limit_response("/v1beta/models/gemini-3-pro:generateContent", &model);
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let resp = token_limit_response("/v1/audio/transcriptions", &TokenLimitViolation::ModelUnresolved);
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_extract_request_model_rejects_unreadable_body() {
+        let request = |body: axum::body::Body, len: Option<&str>| {
+            let mut builder = Request::builder().method("POST").uri("/v1/chat/completions");
+            if let Some(len) = len {
+                builder = builder.header(header::CONTENT_LENGTH, len);
+            }
+            builder.body(body).unwrap()
+        };
+
+        let (_, model) = extract_request_model(
+            request(axum::body::Body::from(r#"{"model":"gemini-3-flash"}"#), None),
+            "/v1/chat/completions",
+        )
+        .await
+        .unwrap();
+        assert_eq!(model.as_deref(), Some("gemini-3-flash"));
+
+        let (_, model) = extract_request_model(request(axum::body::Body::from("not json"), None), "/v1/chat/completions")
+            .await
+            .unwrap();
+        assert_eq!(model, None);
+
+        let too_large = (MAX_MODEL_PEEK_BODY_SIZE + 1).to_string();
+        let err = extract_request_model(request(axum::body::Body::empty(), Some(&too_large)), "/v1/chat/completions")
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let broken = axum::body::Body::from_stream(futures::stream::iter(vec![
+            Ok::<_, std::io::Error>(bytes::Bytes::from_static(b"{\"model\":")),
+            Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset")),
+        ]));
+        let err = extract_request_model(request(broken, None), "/v1/chat/completions")
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+
+        // 未声明长度的分块请求体按实际读取的字节数判断是否超限
+        let chunked = || {
+            axum::body::Body::from_stream(futures::stream::iter(vec![
+                Ok::<_, std::io::Error>(bytes::Bytes::from_static(b"0123")),
+                Ok(bytes::Bytes::from_static(b"4567")),
+            ]))
+        };
+        assert_eq!(read_body_limited(chunked(), 8).await.unwrap().as_deref(), Some(&b"01234567"[..]));
+        assert!(read_body_limited(chunked(), 7).await.unwrap().is_none());
+    }
 }
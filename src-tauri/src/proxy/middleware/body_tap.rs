@@ -0,0 +1,719 @@
+// 监控用的流式 Body 采样与增量解析
+//
+// monitor 中间件以 tee 方式旁路观察请求/响应体，不再整体缓冲：
+// - BodySample: 仅保留有界的头部/尾部样本用于日志展示
+// - JsonFieldScanner: 增量扫描 JSON 顶层字段 (请求 model、响应 usage)
+// - UsageTokens: 各协议 usage 字段归一化 (缓存 / reasoning 明细)
+// - SseStreamParser: 逐行解析 SSE 事件，提取用量、汇总内容并识别首个 token
+// 所有结构的内存占用都有上限，与 Body 大小无关。
+
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, VecDeque};
+
+/// 日志样本头部保留字节数
+pub const SAMPLE_HEAD_BYTES: usize = 64 * 1024;
+/// 日志样本尾部保留字节数
+pub const SAMPLE_TAIL_BYTES: usize = 16 * 1024;
+/// 单行 SSE 事件上限，超出 (如内联大图) 的行直接跳过
+const MAX_SSE_LINE_BYTES: usize = 1024 * 1024;
+/// JSON 顶层字段值的捕获上限
+const MAX_CAPTURED_FIELD_BYTES: usize = 64 * 1024;
+/// 汇总的 thinking / content / 工具参数上限
+const MAX_CONSOLIDATED_TEXT: usize = 64 * 1024;
+const MAX_TOOL_CALLS: usize = 64;
+
+/// 有界 head/tail 采样
+#[derive(Debug)]
+pub struct BodySample {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    total: usize,
+    head_cap: usize,
+    tail_cap: usize,
+}
+
+impl BodySample {
+    pub fn new(head_cap: usize, tail_cap: usize) -> Self {
+        Self {
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            total: 0,
+            head_cap,
+            tail_cap,
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.total += chunk.len();
+        let room = self.head_cap.saturating_sub(self.head.len()).min(chunk.len());
+        let (head, rest) = chunk.split_at(room);
+        self.head.extend_from_slice(head);
+        if rest.is_empty() || self.tail_cap == 0 {
+            return;
+        }
+        if rest.len() >= self.tail_cap {
+            self.tail.clear();
+            self.tail.extend(&rest[rest.len() - self.tail_cap..]);
+        } else {
+            self.tail.extend(rest);
+            let excess = self.tail.len().saturating_sub(self.tail_cap);
+            self.tail.drain(..excess);
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// 头部样本是否为二进制 (末尾被截断的多字节字符不算)
+    fn is_binary(&self) -> bool {
+        matches!(std::str::from_utf8(&self.head), Err(e) if e.error_len().is_some())
+    }
+
+    /// 渲染为日志文本；超出上限时中间以省略标记代替
+    pub fn render(&self) -> String {
+        if self.is_binary() {
+            return format!("[Binary Data: {} bytes]", self.total);
+        }
+        let omitted = self.total - self.head.len() - self.tail.len();
+        let tail: Vec<u8> = self.tail.iter().copied().collect();
+        if omitted == 0 {
+            let mut all = self.head.clone();
+            all.extend_from_slice(&tail);
+            return String::from_utf8_lossy(&all).into_owned();
+        }
+        format!(
+            "{}\n...[{} bytes omitted]...\n{}",
+            String::from_utf8_lossy(&self.head),
+            omitted,
+            String::from_utf8_lossy(&tail)
+        )
+    }
+}
+
+/// 增量扫描 JSON 顶层对象中的指定字段
+///
+/// 只跟踪嵌套深度与字符串状态，被关注字段的原始值在上限内缓存，值结束时解析为 `Value`。
+#[derive(Debug)]
+pub struct JsonFieldScanner {
+    wanted: &'static [&'static str],
+    found: HashMap<&'static str, Value>,
+    depth: u32,
+    root_is_object: bool,
+    in_string: bool,
+    escape: bool,
+    expect_key: bool,
+    reading_key: bool,
+    key: Vec<u8>,
+    in_value: bool,
+    capture: Option<&'static str>,
+    value: Vec<u8>,
+    overflow: bool,
+    done: bool,
+}
+
+impl JsonFieldScanner {
+    pub fn new(wanted: &'static [&'static str]) -> Self {
+        Self {
+            wanted,
+            found: HashMap::new(),
+            depth: 0,
+            root_is_object: false,
+            in_string: false,
+            escape: false,
+            expect_key: false,
+            reading_key: false,
+            key: Vec::new(),
+            in_value: false,
+            capture: None,
+            value: Vec::new(),
+            overflow: false,
+            done: false,
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.found.get(field)
+    }
+
+    fn capture_byte(&mut self, b: u8) {
+        if self.capture.is_some() {
+            if self.value.len() < MAX_CAPTURED_FIELD_BYTES {
+                self.value.push(b);
+            } else {
+                self.overflow = true;
+            }
+        }
+    }
+
+    fn finish_value(&mut self) {
+        if let Some(field) = self.capture.take() {
+            if !self.overflow {
+                if let Ok(v) = serde_json::from_slice::<Value>(&self.value) {
+                    self.found.insert(field, v);
+                }
+            }
+        }
+        self.in_value = false;
+        self.value.clear();
+        self.overflow = false;
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        for &b in chunk {
+            if self.done {
+                return;
+            }
+            if self.in_string {
+                if self.reading_key {
+                    if !self.escape && b == b'"' {
+                        self.reading_key = false;
+                    } else if self.key.len() < 128 {
+                        self.key.push(b);
+                    }
+                } else {
+                    self.capture_byte(b);
+                }
+                if self.escape {
+                    self.escape = false;
+                } else if b == b'\\' {
+                    self.escape = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match b {
+                b'"' => {
+                    self.in_string = true;
+                    if self.depth == 1 && self.root_is_object && self.expect_key {
+                        self.reading_key = true;
+                        self.expect_key = false;
+                        self.key.clear();
+                    } else {
+                        self.capture_byte(b);
+                    }
+                }
+                b'{' | b'[' if self.depth == 0 => {
+                    self.depth = 1;
+                    self.root_is_object = b == b'{';
+                    self.expect_key = self.root_is_object;
+                }
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    self.capture_byte(b);
+                }
+                b'}' | b']' if self.depth == 1 => {
+                    self.finish_value();
+                    self.depth = 0;
+                    self.done = true;
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.capture_byte(b);
+                }
+                b':' if self.depth == 1 && self.root_is_object && !self.in_value => {
+                    self.in_value = true;
+                    let key = std::str::from_utf8(&self.key).unwrap_or("");
+                    self.capture = self.wanted.iter().copied().find(|w| *w == key);
+                }
+                b',' if self.depth == 1 => {
+                    self.finish_value();
+                    self.expect_key = self.root_is_object;
+                }
+                _ => self.capture_byte(b),
+            }
+        }
+    }
+}
+
+/// 单次请求的 token 用量
+///
+/// 对外统一为 OpenAI 语义：缓存读取/写入包含在 input 内，reasoning 包含在 output 内。
+/// Anthropic 的 `cache_*_input_tokens` 与 `input_tokens` 分开计数，Gemini 的 `thoughtsTokenCount`
+/// 与 `candidatesTokenCount` 分开计数，在读取时按来源格式换算。
+#[derive(Debug, Clone, Default)]
+pub struct UsageTokens {
+    input: Option<u32>,
+    output: Option<u32>,
+    total: Option<u32>,
+    cache_read: Option<u32>,
+    cache_creation: Option<u32>,
+    reasoning: Option<u32>,
+    /// 缓存 token 不包含在 input 内 (Anthropic)
+    cache_separate: bool,
+    /// reasoning token 不包含在 output 内 (Gemini)
+    reasoning_separate: bool,
+}
+
+impl UsageTokens {
+    /// 合并一个 usage 对象 (兼容 OpenAI / Responses API / Anthropic / Gemini 字段名)
+    ///
+    /// 仅覆盖本次出现的字段，避免 Claude `message_delta` 只带 output_tokens 时抹掉之前的 input_tokens。
+    pub fn apply(&mut self, usage: &Value) {
+        let read = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|k| usage.get(*k).and_then(|v| v.as_u64()))
+                .map(|v| v as u32)
+        };
+        let read_nested = |parents: &[&str], key: &str| {
+            parents
+                .iter()
+                .find_map(|p| usage.get(*p).and_then(|d| d.get(key)).and_then(|v| v.as_u64()))
+                .map(|v| v as u32)
+        };
+
+        if let Some(v) = read(&["prompt_tokens", "input_tokens", "promptTokenCount"]) {
+            self.input = Some(v);
+        }
+        if let Some(v) = read(&["completion_tokens", "output_tokens", "candidatesTokenCount"]) {
+            self.output = Some(v);
+        }
+        if let Some(v) = read(&["total_tokens", "totalTokenCount"]) {
+            self.total = Some(v);
+        }
+
+        if let Some(v) = read(&["cache_read_input_tokens"]) {
+            self.cache_read = Some(v);
+            self.cache_separate = true;
+        } else if let Some(v) = read(&["cachedContentTokenCount"])
+            .or_else(|| read_nested(&["prompt_tokens_details", "input_tokens_details"], "cached_tokens"))
+        {
+            self.cache_read = Some(v);
+        }
+        if let Some(v) = read(&["cache_creation_input_tokens"]) {
+            self.cache_creation = Some(v);
+            self.cache_separate = true;
+        }
+
+        if let Some(v) = read(&["thoughtsTokenCount"]) {
+            self.reasoning = Some(v);
+            self.reasoning_separate = true;
+        } else if let Some(v) =
+            read_nested(&["completion_tokens_details", "output_tokens_details"], "reasoning_tokens")
+        {
+            self.reasoning = Some(v);
+        }
+    }
+
+    /// 输入 token (含缓存读取/写入)
+    pub fn input_tokens(&self) -> Option<u32> {
+        let input = self.input?;
+        if self.cache_separate {
+            Some(
+                input
+                    .saturating_add(self.cache_read.unwrap_or(0))
+                    .saturating_add(self.cache_creation.unwrap_or(0)),
+            )
+        } else {
+            Some(input)
+        }
+    }
+
+    /// 输出 token (含 reasoning)；input/output 均缺失时回退到 total
+    ///
+    /// 只有 input 与 total 的用量 (Embeddings) 按 total - input 计算
+    pub fn output_tokens(&self) -> Option<u32> {
+        if self.input.is_none() && self.output.is_none() {
+            return self.total;
+        }
+        let Some(output) = self.output else {
+            return self.total.map(|total| total.saturating_sub(self.input.unwrap_or(0)));
+        };
+        if self.reasoning_separate {
+            Some(output.saturating_add(self.reasoning.unwrap_or(0)))
+        } else {
+            Some(output)
+        }
+    }
+
+    pub fn cache_read_tokens(&self) -> Option<u32> {
+        self.cache_read
+    }
+
+    pub fn cache_creation_tokens(&self) -> Option<u32> {
+        self.cache_creation
+    }
+
+    pub fn reasoning_tokens(&self) -> Option<u32> {
+        self.reasoning
+    }
+}
+
+fn push_capped(buf: &mut String, text: &str) {
+    let room = MAX_CONSOLIDATED_TEXT.saturating_sub(buf.len());
+    if room == 0 {
+        return;
+    }
+    if text.len() <= room {
+        buf.push_str(text);
+    } else {
+        let mut end = room;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        buf.push_str(&text[..end]);
+    }
+}
+
+/// 增量 SSE 解析器
+#[derive(Debug, Default)]
+pub struct SseStreamParser {
+    line: Vec<u8>,
+    skipping_line: bool,
+    pub usage: UsageTokens,
+    thinking: String,
+    content: String,
+    signature: String,
+    tool_calls: Vec<Value>,
+    saw_token: bool,
+}
+
+impl SseStreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 是否已出现首个内容 token (文本 / 思考 / 工具调用)
+    pub fn saw_token(&self) -> bool {
+        self.saw_token
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        let mut rest = chunk;
+        while let Some(pos) = rest.iter().position(|b| *b == b'\n') {
+            let (segment, tail) = rest.split_at(pos);
+            rest = &tail[1..];
+            if self.skipping_line {
+                self.skipping_line = false;
+                self.line.clear();
+                continue;
+            }
+            self.line.extend_from_slice(segment);
+            let line = std::mem::take(&mut self.line);
+            self.handle_line(&line);
+        }
+        if !rest.is_empty() && !self.skipping_line {
+            if self.line.len() + rest.len() > MAX_SSE_LINE_BYTES {
+                self.skipping_line = true;
+                self.line.clear();
+            } else {
+                self.line.extend_from_slice(rest);
+            }
+        }
+    }
+
+    fn handle_line(&mut self, line: &[u8]) {
+        let Ok(line) = std::str::from_utf8(line) else { return };
+        let Some(data) = line.trim_end_matches('\r').strip_prefix("data:") else {
+            return;
+        };
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            return;
+        }
+        if let Ok(event) = serde_json::from_str::<Value>(data) {
+            self.handle_event(&event);
+        }
+    }
+
+    fn push_content(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.saw_token = true;
+        }
+        push_capped(&mut self.content, text);
+    }
+
+    fn push_thinking(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.saw_token = true;
+        }
+        push_capped(&mut self.thinking, text);
+    }
+
+    /// 取得 (必要时创建) 指定下标的工具调用槽位
+    fn tool_call_slot(&mut self, idx: usize) -> Option<&mut Value> {
+        if idx >= MAX_TOOL_CALLS {
+            return None;
+        }
+        while self.tool_calls.len() <= idx {
+            self.tool_calls.push(Value::Null);
+        }
+        self.saw_token = true;
+        Some(&mut self.tool_calls[idx])
+    }
+
+    fn append_tool_args(call: &mut Value, args: &str) {
+        let mut current = call["function"]["arguments"].as_str().unwrap_or("").to_string();
+        push_capped(&mut current, args);
+        call["function"]["arguments"] = Value::String(current);
+    }
+
+    fn handle_event(&mut self, event: &Value) {
+        // OpenAI format: choices[].delta.content / reasoning_content / tool_calls
+        if let Some(choices) = event.get("choices").and_then(|c| c.as_array()) {
+            for delta in choices.iter().filter_map(|c| c.get("delta")) {
+                if let Some(thinking) = delta.get("reasoning_content").and_then(|v| v.as_str()) {
+                    self.push_thinking(thinking);
+                }
+                if let Some(content) = delta.get("content").and_then(|v| v.as_str()) {
+                    self.push_content(content);
+                }
+                for tc in delta.get("tool_calls").and_then(|t| t.as_array()).into_iter().flatten() {
+                    let Some(idx) = tc.get("index").and_then(|i| i.as_u64()) else { continue };
+                    let Some(slot) = self.tool_call_slot(idx as usize) else { continue };
+                    if slot.is_null() {
+                        *slot = json!({ "id": "", "type": "function", "function": { "name": "", "arguments": "" } });
+                    }
+                    if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
+                        slot["id"] = Value::String(id.to_string());
+                    }
+                    if let Some(func) = tc.get("function") {
+                        if let Some(name) = func.get("name").and_then(|v| v.as_str()) {
+                            slot["function"]["name"] = Value::String(name.to_string());
+                        }
+                        if let Some(args) = func.get("arguments").and_then(|v| v.as_str()) {
+                            Self::append_tool_args(slot, args);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Gemini format: candidates[].content.parts[] (可能带 v1internal 的 response 包裹)
+        let gemini = event.get("response").unwrap_or(event);
+        for candidate in gemini.get("candidates").and_then(|c| c.as_array()).into_iter().flatten() {
+            for part in candidate["content"]["parts"].as_array().into_iter().flatten() {
+                if let Some(text) = part.get("text").and_then(|v| v.as_str()) {
+                    if part.get("thought").and_then(|v| v.as_bool()) == Some(true) {
+                        self.push_thinking(text);
+                    } else {
+                        self.push_content(text);
+                    }
+                }
+                if let Some(sig) = part.get("thoughtSignature").and_then(|v| v.as_str()) {
+                    self.signature = sig.to_string();
+                }
+                if let Some(call) = part.get("functionCall") {
+                    let idx = self.tool_calls.len();
+                    if let Some(slot) = self.tool_call_slot(idx) {
+                        *slot = json!({
+                            "id": call.get("id").cloned().unwrap_or(json!("")),
+                            "type": "function",
+                            "function": {
+                                "name": call.get("name").cloned().unwrap_or(json!("")),
+                                "arguments": call.get("args").map(|a| a.to_string()).unwrap_or_default()
+                            }
+                        });
+                    }
+                }
+            }
+        }
+
+        // Claude/Anthropic format & OpenAI Responses API
+        let msg_type = event.get("type").and_then(|t| t.as_str());
+        match msg_type {
+            Some("content_block_start") => {
+                if let (Some(index), Some(block)) = (
+                    event.get("index").and_then(|i| i.as_u64()),
+                    event.get("content_block"),
+                ) {
+                    if block.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
+                        let id = block.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                        let name = block.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                        if let Some(slot) = self.tool_call_slot(index as usize) {
+                            *slot = json!({
+                                "id": id,
+                                "type": "function",
+                                "function": { "name": name, "arguments": "" }
+                            });
+                        }
+                    }
+                }
+            }
+            Some("content_block_delta") => {
+                if let (Some(index), Some(delta)) = (
+                    event.get("index").and_then(|i| i.as_u64()),
+                    event.get("delta"),
+                ) {
+                    // Tool use input delta
+                    if let Some(partial) = delta
+                        .get("partial_json")
+                        .or(delta.get("input_json_delta"))
+                        .and_then(|v| v.as_str())
+                    {
+                        let idx = index as usize;
+                        if idx < self.tool_calls.len() && !self.tool_calls[idx].is_null() {
+                            Self::append_tool_args(&mut self.tool_calls[idx], partial);
+                        }
+                    }
+                    if let Some(thinking) = delta.get("thinking").and_then(|v| v.as_str()) {
+                        self.push_thinking(thinking);
+                    }
+                    if let Some(sig) = delta.get("signature").and_then(|v| v.as_str()) {
+                        self.signature = sig.to_string();
+                    }
+                    if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
+                        self.push_content(text);
+                    }
+                }
+            }
+            Some("message_start") => {
+                if let Some(usage) = event.get("message").and_then(|m| m.get("usage")) {
+                    self.usage.apply(usage);
+                }
+            }
+            Some("response.output_text.delta") => {
+                if let Some(text) = event.get("delta").and_then(|v| v.as_str()) {
+                    self.push_content(text);
+                }
+            }
+            Some("response.reasoning_summary_text.delta") => {
+                if let Some(text) = event.get("delta").and_then(|v| v.as_str()) {
+                    self.push_thinking(text);
+                }
+            }
+            Some(_) => {}
+            None => {
+                // Legacy Claude delta (for older implementations or simplified streams)
+                if let Some(delta) = event.get("delta") {
+                    if let Some(thinking) = delta.get("thinking").and_then(|v| v.as_str()) {
+                        self.push_thinking(thinking);
+                    }
+                    if let Some(sig) = delta.get("signature").and_then(|v| v.as_str()) {
+                        self.signature = sig.to_string();
+                    }
+                    if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
+                        self.push_content(text);
+                    }
+                }
+            }
+        }
+
+        // Token usage extraction
+        if let Some(usage) = event
+            .get("usage")
+            .or(event.get("usageMetadata"))
+            .or(event.get("response").and_then(|r| r.get("usage").or(r.get("usageMetadata"))))
+            .filter(|u| u.is_object())
+        {
+            self.usage.apply(usage);
+        }
+    }
+
+    /// 汇总为可读的 JSON 文本；未解析到任何内容时返回 None
+    pub fn consolidated(&self) -> Option<String> {
+        let mut consolidated = Map::new();
+        if !self.thinking.is_empty() {
+            consolidated.insert("thinking".to_string(), Value::String(self.thinking.clone()));
+        }
+        if !self.signature.is_empty() {
+            consolidated.insert("thinking_signature".to_string(), Value::String(self.signature.clone()));
+        }
+        if !self.content.is_empty() {
+            consolidated.insert("content".to_string(), Value::String(self.content.clone()));
+        }
+        let tool_calls: Vec<Value> = self.tool_calls.iter().filter(|v| !v.is_null()).cloned().collect();
+        if !tool_calls.is_empty() {
+            consolidated.insert("tool_calls".to_string(), Value::Array(tool_calls));
+        }
+        if let Some(input) = self.usage.input_tokens() {
+            consolidated.insert("input_tokens".to_string(), Value::Number(input.into()));
+        }
+        if let Some(output) = self.usage.output_tokens() {
+            consolidated.insert("output_tokens".to_string(), Value::Number(output.into()));
+        }
+        if consolidated.is_empty() {
+            return None;
+        }
+        serde_json::to_string_pretty(&Value::Object(consolidated)).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_sample_is_bounded() {
+        let mut sample = BodySample::new(8, 4);
+        sample.push(b"0123");
+        sample.push(b"4567ABCDEFGH");
+        sample.push(b"IJ");
+        assert_eq!(sample.total(), 18);
+        assert_eq!(sample.render(), "01234567\n...[6 bytes omitted]...\nGHIJ");
+
+        let mut small = BodySample::new(8, 4);
+        small.push("héllo".as_bytes());
+        assert_eq!(small.render(), "héllo");
+    }
+
+    #[test]
+    fn test_json_scanner_across_chunks() {
+        let body = br#"{"messages":[{"role":"user","content":"say \"model\": x"}],"nested":{"model":"inner"},"model":"claude-sonnet-4-5","usage":{"input_tokens":12,"output_tokens":7}}"#;
+        let mut scanner = JsonFieldScanner::new(&["model", "usage"]);
+        for chunk in body.chunks(5) {
+            scanner.push(chunk);
+        }
+        assert_eq!(scanner.get("model"), Some(&json!("claude-sonnet-4-5")));
+        assert_eq!(scanner.get("usage").unwrap()["output_tokens"], 7);
+    }
+
+    #[test]
+    fn test_sse_parser_usage_and_first_token() {
+        let mut parser = SseStreamParser::new();
+        parser.push(b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":21}}}\n\n");
+        assert!(!parser.saw_token());
+
+        // 事件跨 chunk 切分
+        parser.push(b"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"te");
+        parser.push(b"xt\":\"Hello\"}}\n\n");
+        assert!(parser.saw_token());
+
+        // 超长行被跳过，不影响后续事件
+        let mut huge = b"data: {\"type\":\"ping\",\"pad\":\"".to_vec();
+        huge.extend(std::iter::repeat(b'x').take(MAX_SSE_LINE_BYTES + 10));
+        parser.push(&huge);
+        parser.push(b"\"}\n\ndata: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":5}}\n\n");
+
+        assert_eq!(parser.usage.input_tokens(), Some(21));
+        assert_eq!(parser.usage.output_tokens(), Some(5));
+        let summary: Value = serde_json::from_str(&parser.consolidated().unwrap()).unwrap();
+        assert_eq!(summary["content"], "Hello");
+    }
+
+    #[test]
+    fn test_usage_breakdown_is_normalized() {
+        // Anthropic: 缓存 token 与 input_tokens 分开计数
+        let mut claude = UsageTokens::default();
+        claude.apply(&json!({"input_tokens": 10, "cache_read_input_tokens": 80, "cache_creation_input_tokens": 5}));
+        claude.apply(&json!({"output_tokens": 30}));
+        assert_eq!(claude.input_tokens(), Some(95));
+        assert_eq!(claude.output_tokens(), Some(30));
+        assert_eq!(claude.cache_read_tokens(), Some(80));
+        assert_eq!(claude.cache_creation_tokens(), Some(5));
+
+        // Gemini: 思考 token 与 candidatesTokenCount 分开计数，缓存包含在 prompt 内
+        let mut gemini = UsageTokens::default();
+        gemini.apply(&json!({"promptTokenCount": 100, "candidatesTokenCount": 20, "cachedContentTokenCount": 60, "thoughtsTokenCount": 50}));
+        assert_eq!(gemini.input_tokens(), Some(100));
+        assert_eq!(gemini.output_tokens(), Some(70));
+        assert_eq!(gemini.cache_read_tokens(), Some(60));
+        assert_eq!(gemini.reasoning_tokens(), Some(50));
+
+        // OpenAI: 明细已包含在 prompt/completion 内
+        let mut openai = UsageTokens::default();
+        openai.apply(&json!({"prompt_tokens": 100, "completion_tokens": 70, "prompt_tokens_details": {"cached_tokens": 60}, "completion_tokens_details": {"reasoning_tokens": 50}}));
+        assert_eq!(openai.input_tokens(), Some(100));
+        assert_eq!(openai.output_tokens(), Some(70));
+        assert_eq!(openai.cache_read_tokens(), Some(60));
+        assert_eq!(openai.reasoning_tokens(), Some(50));
+
+        // Embeddings: 只有 prompt/total
+        let mut embeddings = UsageTokens::default();
+        embeddings.apply(&json!({"prompt_tokens": 12, "total_tokens": 12}));
+        assert_eq!(embeddings.input_tokens(), Some(12));
+        assert_eq!(embeddings.output_tokens(), Some(0));
+    }
+}
@@ -0,0 +1,170 @@
+// 客户端 IP 提取 (IP 过滤、监控日志、UserToken 校验共用)
+//
+// 仅当 TCP 对端 (ConnectInfo) 属于 trusted_proxies 时才信任 X-Forwarded-For / X-Real-IP，
+// 并从 X-Forwarded-For 右侧开始跳过可信代理，取第一个不可信的跳点，防止客户端伪造来源 IP。
+
+use axum::extract::{ConnectInfo, Request};
+use std::net::{IpAddr, SocketAddr};
+
+/// 单个 CIDR 或 IP 是否包含指定地址 (支持 IPv4 / IPv6，IPv4-mapped IPv6 按 IPv4 处理)
+pub fn ip_in_cidr(ip: IpAddr, cidr: &str) -> bool {
+    let cidr = cidr.trim();
+    let (network, prefix) = match cidr.split_once('/') {
+        Some((net, len)) => match len.parse::<u8>() {
+            Ok(len) => (net, Some(len)),
+            Err(_) => return false,
+        },
+        None => (cidr, None),
+    };
+    let Ok(network) = network.parse::<IpAddr>() else {
+        return false;
+    };
+
+    match (canonical(ip), canonical(network)) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let prefix = prefix.unwrap_or(32);
+            if prefix > 32 {
+                return false;
+            }
+            let mask = if prefix == 0 { 0 } else { !0u32 << (32 - prefix) };
+            (u32::from(ip) & mask) == (u32::from(net) & mask)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let prefix = prefix.unwrap_or(128);
+            if prefix > 128 {
+                return false;
+            }
+            let mask = if prefix == 0 { 0 } else { !0u128 << (128 - prefix) };
+            (u128::from(ip) & mask) == (u128::from(net) & mask)
+        }
+        _ => false,
+    }
+}
+
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        other => other,
+    }
+}
+
+/// 是否为本机回环地址 (含 IPv4-mapped IPv6)
+pub fn is_loopback(ip: IpAddr) -> bool {
+    canonical(ip).is_loopback()
+}
+
+fn is_trusted(ip: IpAddr, trusted_proxies: &[String]) -> bool {
+    trusted_proxies.iter().any(|cidr| ip_in_cidr(ip, cidr))
+}
+
+/// 解析转发头中的单个地址 (兼容 "1.2.3.4:5678" 与 "[::1]:5678" 形式)
+fn parse_hop(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    value.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
+
+/// 根据 TCP 对端与转发头解析客户端 IP
+///
+/// - 对端不可信：直接使用对端地址，忽略所有转发头
+/// - 对端可信：从 X-Forwarded-For 右侧向左跳过可信代理，返回第一个不可信跳点；
+///   全部可信时返回最左侧地址。无 X-Forwarded-For 时依次回退 X-Real-IP、对端地址
+pub fn resolve_client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    real_ip: Option<&str>,
+    trusted_proxies: &[String],
+) -> Option<IpAddr> {
+    let peer = canonical(peer?);
+    if !is_trusted(peer, trusted_proxies) {
+        return Some(peer);
+    }
+
+    if let Some(xff) = forwarded_for {
+        let hops: Vec<IpAddr> = xff.split(',').filter_map(parse_hop).map(canonical).collect();
+        if let Some(hop) = hops.iter().rev().find(|ip| !is_trusted(**ip, trusted_proxies)) {
+            return Some(*hop);
+        }
+        if let Some(first) = hops.first() {
+            return Some(*first);
+        }
+    }
+
+    real_ip
+        .and_then(parse_hop)
+        .map(canonical)
+        .or(Some(peer))
+}
+
+/// 从请求中提取客户端 IP
+pub fn extract_client_ip(request: &Request, trusted_proxies: &[String]) -> Option<String> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+    };
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+
+    resolve_client_ip(peer, header("x-forwarded-for"), header("x-real-ip"), trusted_proxies)
+        .map(|ip| ip.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_forwarding_headers() {
+        let trusted = vec!["127.0.0.1/32".to_string()];
+        assert_eq!(
+            resolve_client_ip(ip("192.168.1.50"), Some("8.8.8.8"), Some("9.9.9.9"), &trusted),
+            ip("192.168.1.50")
+        );
+        // 未配置可信代理时同样忽略转发头
+        assert_eq!(
+            resolve_client_ip(ip("127.0.0.1"), Some("8.8.8.8"), None, &[]),
+            ip("127.0.0.1")
+        );
+    }
+
+    #[test]
+    fn test_trusted_peer_uses_rightmost_untrusted_hop() {
+        let trusted = vec!["10.0.0.0/8".to_string(), "::1".to_string()];
+        // 客户端伪造的最左侧地址不应被采用
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), Some("1.1.1.1, 203.0.113.7, 10.0.0.9"), None, &trusted),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("::1"), Some("10.1.1.1, 10.2.2.2"), None, &trusted),
+            ip("10.1.1.1")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("::ffff:10.0.0.2"), None, Some("198.51.100.4"), &trusted),
+            ip("198.51.100.4")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), Some("garbage"), None, &trusted),
+            ip("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn test_ip_in_cidr() {
+        assert!(ip_in_cidr("192.168.1.7".parse().unwrap(), "192.168.0.0/16"));
+        assert!(!ip_in_cidr("192.169.1.7".parse().unwrap(), "192.168.0.0/16"));
+        assert!(ip_in_cidr("fd00::1".parse().unwrap(), "fd00::/8"));
+        assert!(ip_in_cidr("8.8.8.8".parse().unwrap(), "0.0.0.0/0"));
+        assert!(!ip_in_cidr("8.8.8.8".parse().unwrap(), "8.8.8.8/33"));
+    }
+}
@@ -5,24 +5,27 @@ use axum::{
     http::StatusCode,
 };
 use crate::proxy::server::AppState;
-use crate::modules::security_db;
+use crate::modules::{ip_acl, security_db};
+use crate::proxy::auto_ban;
+use crate::proxy::middleware::client_ip::extract_client_ip;
 
 /// IP 黑白名单过滤中间件
 pub async fn ip_filter_middleware(
     State(state): State<AppState>,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Response {
-    // 提取客户端 IP
-    let client_ip = extract_client_ip(&request);
+    // 读取安全配置 (复制后立即释放读锁，避免请求处理期间阻塞配置热更新)
+    let security_monitor = state.security.read().await.security_monitor.clone();
+
+    // 提取客户端 IP (仅信任来自 trusted_proxies 的转发头)
+    let client_ip = extract_client_ip(&request, &security_monitor.trusted_proxies);
     
     if let Some(ip) = &client_ip {
-        // 读取安全配置
-        let security_config = state.security.read().await;
         
         // 1. 检查白名单 (如果启用白名单模式,只允许白名单 IP)
-        if security_config.security_monitor.whitelist.enabled {
-            match security_db::is_ip_in_whitelist(ip) {
+        if security_monitor.whitelist.enabled {
+            match ip_acl::is_whitelisted(ip) {
                 Ok(true) => {
                     // 在白名单中,直接放行
                     tracing::debug!("[IP Filter] IP {} is in whitelist, allowing", ip);
@@ -42,8 +45,8 @@ pub async fn ip_filter_middleware(
             }
         } else {
             // 白名单优先模式: 如果在白名单中,跳过黑名单检查
-            if security_config.security_monitor.whitelist.whitelist_priority {
-                match security_db::is_ip_in_whitelist(ip) {
+            if security_monitor.whitelist.whitelist_priority {
+                match ip_acl::is_whitelisted(ip) {
                     Ok(true) => {
                         tracing::debug!("[IP Filter] IP {} is in whitelist (priority mode), skipping blacklist check", ip);
                         return next.run(request).await;
@@ -58,43 +61,15 @@ pub async fn ip_filter_middleware(
             }
         }
 
-        // 2. 检查黑名单
-        if security_config.security_monitor.blacklist.enabled {
-            match security_db::get_blacklist_entry_for_ip(ip) {
+        // 2. 检查黑名单 (内存前缀树，命中计数批量写回)
+        if security_monitor.blacklist.enabled {
+            match ip_acl::blacklist_entry(ip) {
                 Ok(Some(entry)) => {
                     tracing::warn!("[IP Filter] IP {} is in blacklist, blocking", ip);
                     
-                    // 构建详细的封禁消息
                     let reason = entry.reason.as_deref().unwrap_or("Malicious activity detected");
-                    let ban_type = if let Some(expires_at) = entry.expires_at {
-                        let now = chrono::Utc::now().timestamp();
-                        let remaining_seconds = expires_at - now;
-                        
-                        if remaining_seconds > 0 {
-                            let hours = remaining_seconds / 3600;
-                            let minutes = (remaining_seconds % 3600) / 60;
-                            
-                            if hours > 24 {
-                                let days = hours / 24;
-                                format!("Temporary ban. Please try again after {} day(s).", days)
-                            } else if hours > 0 {
-                                format!("Temporary ban. Please try again after {} hour(s) and {} minute(s).", hours, minutes)
-                            } else {
-                                format!("Temporary ban. Please try again after {} minute(s).", minutes)
-                            }
-                        } else {
-                            "Temporary ban (expired, will be removed soon).".to_string()
-                        }
-                    } else {
-                        "Permanent ban.".to_string()
-                    };
-                    
-                    let detailed_message = format!(
-                        "Access denied. Reason: {}. {}",
-                        reason,
-                        ban_type
-                    );
-                    
+                    let detailed_message = ban_message(&entry);
+
                     // 记录被封禁的访问日志
                     let log = security_db::IpAccessLog {
                         id: uuid::Uuid::new_v4().to_string(),
@@ -135,38 +110,80 @@ pub async fn ip_filter_middleware(
                 }
             }
         }
+
+        // 3. [NEW] 自动封禁：请求速率规则
+        if let Some(entry) = auto_ban::record_request(ip, &security_monitor) {
+            return create_blocked_response(ip, &ban_message(&entry));
+        }
     } else {
         tracing::warn!("[IP Filter] Unable to extract client IP from request");
     }
 
     // 放行请求
-    next.run(request).await
+    let auth_failure = auto_ban::AuthFailureMark::default();
+    request.extensions_mut().insert(auth_failure.clone());
+    let response = next.run(request).await;
+
+    // [NEW] 自动封禁：4xx 爆发规则 (鉴权失败由 auth_middleware 记录，不重复计入)
+    if let Some(ip) = &client_ip {
+        auto_ban::record_response(ip, response.status().as_u16(), &auth_failure, &security_monitor);
+    }
+    response
+}
+
+/// 登录接口 (/api/auth/login) 的黑名单检查
+///
+/// 管理接口不经过 ip_filter_middleware，但登录失败会计入自动封禁，因此被封禁的 IP 必须在
+/// 口令校验 (PBKDF2) 之前被拒绝，否则封禁对暴力破解无效。白名单优先模式下白名单 IP 不受影响
+pub fn login_blocked_response(ip: &str, security_monitor: &crate::proxy::config::SecurityMonitorConfig) -> Option<Response> {
+    if !security_monitor.blacklist.enabled {
+        return None;
+    }
+    if (security_monitor.whitelist.enabled || security_monitor.whitelist.whitelist_priority)
+        && matches!(ip_acl::is_whitelisted(ip), Ok(true))
+    {
+        return None;
+    }
+    match ip_acl::blacklist_entry(ip) {
+        Ok(Some(entry)) => {
+            tracing::warn!("[IP Filter] Blacklisted IP {} attempted admin login, blocking", ip);
+            Some(create_blocked_response(ip, &ban_message(&entry)))
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::error!("[IP Filter] Failed to check blacklist: {}", e);
+            None
+        }
+    }
 }
 
-/// 从请求中提取客户端 IP
-fn extract_client_ip(request: &Request) -> Option<String> {
-    // 1. 优先从 X-Forwarded-For 提取 (取第一个 IP)
-    request
-        .headers()
-        .get("x-forwarded-for")
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.split(',').next().unwrap_or(s).trim().to_string())
-        .or_else(|| {
-            // 2. 备选从 X-Real-IP 提取
-            request
-                .headers()
-                .get("x-real-ip")
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.to_string())
-        })
-        .or_else(|| {
-            // 3. 最后尝试从 ConnectInfo 获取 (TCP 连接 IP)
-            // 这可以解决本地开发/测试时没有代理头导致 IP 获取失败的问题
-            request
-                .extensions()
-                .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
-                .map(|info| info.0.ip().to_string())
-        })
+/// 构建详细的封禁消息
+fn ban_message(entry: &security_db::IpBlacklistEntry) -> String {
+    let reason = entry.reason.as_deref().unwrap_or("Malicious activity detected");
+    let ban_type = if let Some(expires_at) = entry.expires_at {
+        let now = chrono::Utc::now().timestamp();
+        let remaining_seconds = expires_at - now;
+
+        if remaining_seconds > 0 {
+            let hours = remaining_seconds / 3600;
+            let minutes = (remaining_seconds % 3600) / 60;
+
+            if hours > 24 {
+                let days = hours / 24;
+                format!("Temporary ban. Please try again after {} day(s).", days)
+            } else if hours > 0 {
+                format!("Temporary ban. Please try again after {} hour(s) and {} minute(s).", hours, minutes)
+            } else {
+                format!("Temporary ban. Please try again after {} minute(s).", minutes)
+            }
+        } else {
+            "Temporary ban (expired, will be removed soon).".to_string()
+        }
+    } else {
+        "Permanent ban.".to_string()
+    };
+
+    format!("Access denied. Reason: {}. {}", reason, ban_type)
 }
 
 /// 创建被封禁的响应
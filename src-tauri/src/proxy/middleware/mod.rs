@@ -1,15 +1,21 @@
 // Middleware 模块 - Axum 中间件
 
+pub mod admission;
 pub mod auth;
+pub mod body_tap;
+pub mod client_ip;
 pub mod cors;
 pub mod logging;
 pub mod monitor;
 pub mod ip_filter;
 
 pub mod service_status;
+pub mod trace;
 
+pub use admission::admission_middleware;
 pub use cors::cors_layer;
 pub use monitor::monitor_middleware;
 pub use service_status::service_status_middleware;
 pub use auth::{auth_middleware, admin_auth_middleware};
 pub use ip_filter::ip_filter_middleware;
+pub use trace::trace_middleware;
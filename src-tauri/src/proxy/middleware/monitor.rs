@@ -2,17 +2,29 @@ use axum::{
     extract::{Request, State},
     middleware::Next,
     response::Response,
-    body::Body,
+    body::{Body, Bytes},
 };
-use std::time::Instant;
+use futures::Stream;
+use parking_lot::Mutex;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use crate::proxy::server::AppState;
-use crate::proxy::monitor::ProxyRequestLog;
-use serde_json::Value;
+use crate::proxy::monitor::{ProxyMonitor, ProxyRequestLog};
 use crate::proxy::middleware::auth::UserTokenIdentity;
-use futures::StreamExt;
+use crate::proxy::middleware::body_tap::{
+    BodySample, JsonFieldScanner, SseStreamParser, UsageTokens, SAMPLE_HEAD_BYTES, SAMPLE_TAIL_BYTES,
+};
 
-const MAX_REQUEST_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB
-const MAX_RESPONSE_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB for image responses
+/// 将归一化后的 token 用量写入日志
+fn apply_usage_to_log(usage: &UsageTokens, log: &mut ProxyRequestLog) {
+    log.input_tokens = usage.input_tokens();
+    log.output_tokens = usage.output_tokens();
+    log.cache_read_tokens = usage.cache_read_tokens();
+    log.cache_creation_tokens = usage.cache_creation_tokens();
+    log.reasoning_tokens = usage.reasoning_tokens();
+}
 
 /// Helper function to record User Token usage
 fn record_user_token_usage(
@@ -33,45 +45,271 @@ fn record_user_token_usage(
     }
 }
 
+/// [NEW] 上报 Prometheus 请求计数与延迟直方图
+fn record_request_metrics(log: &ProxyRequestLog, model_label: &str, ttfb: Duration, total: Duration, ttft: Option<Duration>) {
+    crate::proxy::metrics::record_request(
+        log.protocol.as_deref().unwrap_or("other"),
+        model_label,
+        log.status,
+        log.account_email.as_deref().unwrap_or(""),
+        ttfb,
+        total,
+        ttft,
+    );
+}
+
+/// Prometheus model 标签
+///
+/// X-Mapped-Model 并非总是有限取值：未知模型 ID 会被原样透传并回显在该头中 (包括错误路径)，
+/// 因此只有已知模型 (内置 / 自定义映射 / 路由规则 / 动态下发) 保留原名，其余归为 "other"，
+/// 缺少该头时记为 "unknown"，避免客户端通过随机 model 让标签基数无限增长
+async fn metrics_model_label(state: &AppState, mapped_model: Option<&str>) -> String {
+    let Some(model) = mapped_model else {
+        return "unknown".to_string();
+    };
+    let custom_mapping = state.custom_mapping.read().await;
+    if crate::proxy::common::model_mapping::is_known_model(model, &custom_mapping, Some(state.token_manager.as_ref())) {
+        model.to_string()
+    } else {
+        "other".to_string()
+    }
+}
+
+/// Body 数据块观察者
+trait ChunkObserver {
+    fn on_chunk(&mut self, chunk: &Bytes);
+    fn on_error(&mut self, _error: &axum::Error) {}
+    fn on_end(&mut self) {}
+}
+
+/// 透传 Body 数据流，同时把每个数据块交给观察者 (tee)，不做任何缓冲
+struct TappedStream<S, O> {
+    inner: S,
+    observer: O,
+}
+
+impl<S, O> Stream for TappedStream<S, O>
+where
+    S: Stream<Item = Result<Bytes, axum::Error>> + Unpin,
+    O: ChunkObserver + Unpin,
+{
+    type Item = Result<Bytes, axum::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => this.observer.on_chunk(chunk),
+            Poll::Ready(Some(Err(e))) => this.observer.on_error(e),
+            Poll::Ready(None) => this.observer.on_end(),
+            Poll::Pending => {}
+        }
+        poll
+    }
+}
+
+fn tap_body<O: ChunkObserver + Unpin + Send + 'static>(body: Body, observer: O) -> Body {
+    Body::from_stream(TappedStream {
+        inner: body.into_data_stream(),
+        observer,
+    })
+}
+
+/// 请求体旁路采样：有界样本 + 顶层 model 字段
+struct RequestCapture {
+    sample: BodySample,
+    scanner: JsonFieldScanner,
+}
+
+struct RequestTap(Arc<Mutex<RequestCapture>>);
+
+impl ChunkObserver for RequestTap {
+    fn on_chunk(&mut self, chunk: &Bytes) {
+        let mut capture = self.0.lock();
+        capture.sample.push(chunk);
+        capture.scanner.push(chunk);
+    }
+}
+
+enum ResponseKind {
+    Sse(SseStreamParser),
+    Json(JsonFieldScanner),
+    Text,
+    Other(String),
+}
+
+/// 响应体旁路观察者；流结束或客户端断开 (Drop) 时写入监控日志
+struct ResponseTap {
+    log: Option<ProxyRequestLog>,
+    kind: ResponseKind,
+    sample: BodySample,
+    request: Option<Arc<Mutex<RequestCapture>>>,
+    start: Instant,
+    headers_elapsed: Duration,
+    first_byte_at: Option<Duration>,
+    first_token_at: Option<Duration>,
+    stream_error: Option<String>,
+    completed: bool,
+    monitor: Arc<ProxyMonitor>,
+    user_token_identity: Option<UserTokenIdentity>,
+    user_agent: Option<String>,
+    /// Prometheus model 标签 (见 metrics_model_label)
+    metrics_model: String,
+}
+
+impl ChunkObserver for ResponseTap {
+    fn on_chunk(&mut self, chunk: &Bytes) {
+        if chunk.is_empty() {
+            return;
+        }
+        if self.first_byte_at.is_none() {
+            self.first_byte_at = Some(self.start.elapsed());
+        }
+        match &mut self.kind {
+            ResponseKind::Sse(parser) => {
+                parser.push(chunk);
+                if self.first_token_at.is_none() && parser.saw_token() {
+                    self.first_token_at = Some(self.start.elapsed());
+                }
+            }
+            ResponseKind::Json(scanner) => scanner.push(chunk),
+            ResponseKind::Text => {}
+            ResponseKind::Other(_) => return,
+        }
+        self.sample.push(chunk);
+    }
+
+    fn on_error(&mut self, error: &axum::Error) {
+        self.stream_error = Some(error.to_string());
+    }
+
+    fn on_end(&mut self) {
+        self.completed = true;
+    }
+}
+
+impl ResponseTap {
+    fn finish(&mut self) {
+        let Some(mut log) = self.log.take() else { return };
+        let total = self.start.elapsed();
+        log.duration = total.as_millis() as u64;
+
+        if let Some(request) = &self.request {
+            let capture = request.lock();
+            if capture.sample.total() > 0 {
+                log.request_body = Some(capture.sample.render());
+            }
+            if log.model.is_none() {
+                log.model = capture
+                    .scanner
+                    .get("model")
+                    .and_then(|m| m.as_str())
+                    .map(|s| s.to_string());
+            }
+        }
+
+        match &self.kind {
+            ResponseKind::Sse(parser) => {
+                apply_usage_to_log(&parser.usage, &mut log);
+                // Fallback: store raw SSE sample if parsing failed
+                log.response_body = parser.consolidated().or_else(|| Some(self.sample.render()));
+                log.ttft_ms = self.first_token_at.map(|d| d.as_millis() as u64);
+                if log.status >= 400 {
+                    log.error = Some("Stream Error or Failed".to_string());
+                }
+            }
+            ResponseKind::Json(scanner) => {
+                // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
+                if let Some(usage) = scanner.get("usage").or(scanner.get("usageMetadata")) {
+                    let mut tokens = UsageTokens::default();
+                    tokens.apply(usage);
+                    apply_usage_to_log(&tokens, &mut log);
+                }
+                log.response_body = Some(self.sample.render());
+                if log.status >= 400 {
+                    log.error = log.response_body.clone();
+                }
+            }
+            ResponseKind::Text => {
+                log.response_body = Some(self.sample.render());
+                if log.status >= 400 {
+                    log.error = log.response_body.clone();
+                }
+            }
+            ResponseKind::Other(content_type) => {
+                log.response_body = Some(format!("[{}]", content_type));
+            }
+        }
+
+        if let Some(e) = self.stream_error.take() {
+            log.error = Some(e);
+        } else if !self.completed {
+            tracing::debug!("[Monitor] Response body dropped before completion: {}", log.url);
+        }
+
+        record_request_metrics(
+            &log,
+            &self.metrics_model,
+            self.first_byte_at.unwrap_or(self.headers_elapsed),
+            total,
+            self.first_token_at,
+        );
+
+        let monitor = self.monitor.clone();
+        let identity = self.user_token_identity.take();
+        let user_agent = self.user_agent.take();
+        let persist = async move {
+            // Record User Token Usage
+            record_user_token_usage(&identity, &log, user_agent);
+            monitor.log_request(log).await;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(persist);
+            }
+            Err(_) => tracing::warn!("[Monitor] No runtime available, dropping request log"),
+        }
+    }
+}
+
+impl Drop for ResponseTap {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
 pub async fn monitor_middleware(
     State(state): State<AppState>,
     request: Request,
     next: Next,
 ) -> Response {
     let _logging_enabled = state.monitor.is_enabled();
-    
+
     let method = request.method().to_string();
     let uri = request.uri().to_string();
-    
-    if uri.contains("event_logging") || uri.contains("/api/") || uri.starts_with("/internal/") {
+
+    if uri.contains("event_logging")
+        || uri.contains("/api/")
+        || uri.starts_with("/internal/")
+        || uri.starts_with("/metrics")
+    {
         return next.run(request).await;
     }
-    
+
     let start = Instant::now();
-    
-    // Extract client IP from headers (X-Forwarded-For or X-Real-IP)
+
+    // Extract client IP (shared extractor: forwarding headers are only honoured from trusted proxies)
     // IMPORTANT: Extract from Request headers, not Response headers (since we want the client's IP)
-    // Note: We need to do this BEFORE consuming the request body if possible, or extract it from the original request
-    let client_ip = request
-        .headers()
-        .get("x-forwarded-for")
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.split(',').next().unwrap_or(s).trim().to_string())
-        .or_else(|| {
-            request
-                .headers()
-                .get("x-real-ip")
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.to_string())
-        });
-        
+    let trusted_proxies = state.security.read().await.security_monitor.trusted_proxies.clone();
+    let client_ip = crate::proxy::middleware::client_ip::extract_client_ip(&request, &trusted_proxies);
+
     let user_agent = request
         .headers()
         .get("user-agent")
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
-    let mut model = if uri.contains("/v1beta/models/") {
+    let model = if uri.contains("/v1beta/models/") {
         uri.split("/v1beta/models/")
             .nth(1)
             .and_then(|s| s.split(':').next())
@@ -80,45 +318,28 @@ pub async fn monitor_middleware(
         None
     };
 
-    let request_body_str;
-    
     // [FIX] 从请求 extensions 提取 UserTokenIdentity (由 Auth 中间件注入)
-    // 必须在处理 request body 之前提取，因为 into_parts() 后需要保留这个值
     let user_token_identity = request.extensions().get::<UserTokenIdentity>().cloned();
-    
-    let request = if method == "POST" {
+
+    // 请求体以 tee 方式透传给 handler，只保留有界样本与 model 字段
+    let (request, request_capture) = if method == "POST" {
+        let capture = Arc::new(Mutex::new(RequestCapture {
+            sample: BodySample::new(SAMPLE_HEAD_BYTES, SAMPLE_TAIL_BYTES),
+            scanner: JsonFieldScanner::new(&["model"]),
+        }));
         let (parts, body) = request.into_parts();
-        match axum::body::to_bytes(body, MAX_REQUEST_LOG_SIZE).await {
-            Ok(bytes) => {
-                if model.is_none() {
-                    model = serde_json::from_slice::<Value>(&bytes).ok().and_then(|v|
-                        v.get("model").and_then(|m| m.as_str()).map(|s| s.to_string())
-                    );
-                }
-                request_body_str = if let Ok(s) = std::str::from_utf8(&bytes) {
-                    Some(s.to_string())
-                } else {
-                    Some("[Binary Request Data]".to_string())
-                };
-                Request::from_parts(parts, Body::from(bytes))
-            }
-            Err(_) => {
-                request_body_str = None;
-                Request::from_parts(parts, Body::empty())
-            }
-        }
+        let body = tap_body(body, RequestTap(capture.clone()));
+        (Request::from_parts(parts, body), Some(capture))
     } else {
-        request_body_str = None;
-        request
+        (request, None)
     };
-    
+
     let response = next.run(request).await;
-    
-    // user_token_identity 已在上面从请求 extensions 中提取
-    
-    let duration = start.elapsed().as_millis() as u64;
+
+    // 响应头返回的时刻；首字节/首 token 时间在转发数据块时记录
+    let headers_elapsed = start.elapsed();
     let status = response.status().as_u16();
-    
+
     let content_type = response.headers().get("content-type")
         .and_then(|v| v.to_str().ok())
         .unwrap_or("")
@@ -138,6 +359,8 @@ pub async fn monitor_middleware(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    let metrics_model = metrics_model_label(&state, mapped_model.as_deref()).await;
+
     // Determine protocol from URL path
     let protocol = if uri.contains("/v1/messages") {
         Some("anthropic".to_string())
@@ -149,353 +372,61 @@ pub async fn monitor_middleware(
         None
     };
 
-    // Client IP has been extracted at the beginning of the function
-
     // Extract username from UserTokenIdentity if present
     let username = user_token_identity.as_ref().map(|identity| identity.username.clone());
 
-    let monitor = state.monitor.clone();
-    let mut log = ProxyRequestLog {
+    let log = ProxyRequestLog {
         id: uuid::Uuid::new_v4().to_string(),
         timestamp: chrono::Utc::now().timestamp_millis(),
         method,
         url: uri,
         status,
-        duration,
+        duration: headers_elapsed.as_millis() as u64,
         model,
         mapped_model,
         account_email,
         client_ip,
         error: None,
-        request_body: request_body_str,
+        request_body: None,
         response_body: None,
         input_tokens: None,
         output_tokens: None,
         protocol,
         username,
+        ttft_ms: None,
+        cache_read_tokens: None,
+        cache_creation_tokens: None,
+        reasoning_tokens: None,
+        trace_id: crate::proxy::telemetry::current_trace_id(),
     };
 
-
-    if content_type.contains("text/event-stream") {
-        let (parts, body) = response.into_parts();
-        let mut stream = body.into_data_stream();
-        let (tx, rx) = tokio::sync::mpsc::channel(64);
-        
-        tokio::spawn(async move {
-            let mut all_stream_data = Vec::new();
-            let mut last_few_bytes = Vec::new();
-            
-            while let Some(chunk_res) = stream.next().await {
-                if let Ok(chunk) = chunk_res {
-                    all_stream_data.extend_from_slice(&chunk);
-                    
-                    if chunk.len() > 8192 {
-                        last_few_bytes = chunk.slice(chunk.len()-8192..).to_vec();
-                    } else {
-                        last_few_bytes.extend_from_slice(&chunk);
-                        if last_few_bytes.len() > 8192 {
-                            last_few_bytes.drain(0..last_few_bytes.len()-8192);
-                        }
-                    }
-                    let _ = tx.send(Ok::<_, axum::Error>(chunk)).await;
-                } else if let Err(e) = chunk_res {
-                    let _ = tx.send(Err(axum::Error::new(e))).await;
-                }
-            }
-            
-            // Parse and consolidate stream data into readable format
-            if let Ok(full_response) = std::str::from_utf8(&all_stream_data) {
-                let mut thinking_content = String::new();
-                let mut response_content = String::new();
-                let mut thinking_signature = String::new();
-                let mut tool_calls: Vec<Value> = Vec::new();
-                
-                for line in full_response.lines() {
-                    if !line.starts_with("data: ") {
-                        continue;
-                    }
-                    let json_str = line.trim_start_matches("data: ").trim();
-                    if json_str == "[DONE]" {
-                        continue;
-                    }
-                    
-                    if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                        // OpenAI format: choices[0].delta.content / reasoning_content / tool_calls
-                        if let Some(choices) = json.get("choices").and_then(|c| c.as_array()) {
-                            for choice in choices {
-                                if let Some(delta) = choice.get("delta") {
-                                    // Thinking/reasoning content
-                                    if let Some(thinking) = delta.get("reasoning_content").and_then(|v| v.as_str()) {
-                                        thinking_content.push_str(thinking);
-                                    }
-                                    // Main response content
-                                    if let Some(content) = delta.get("content").and_then(|v| v.as_str()) {
-                                        response_content.push_str(content);
-                                    }
-                                    // Tool calls
-                                    if let Some(delta_tool_calls) = delta.get("tool_calls").and_then(|t| t.as_array()) {
-                                        for tc in delta_tool_calls {
-                                            if let Some(index) = tc.get("index").and_then(|i| i.as_u64()) {
-                                                let idx = index as usize;
-                                                while tool_calls.len() <= idx {
-                                                    tool_calls.push(serde_json::json!({
-                                                        "id": "",
-                                                        "type": "function",
-                                                        "function": { "name": "", "arguments": "" }
-                                                    }));
-                                                }
-                                                let current_tc = &mut tool_calls[idx];
-                                                if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
-                                                    current_tc["id"] = Value::String(id.to_string());
-                                                }
-                                                if let Some(func) = tc.get("function") {
-                                                    if let Some(name) = func.get("name").and_then(|v| v.as_str()) {
-                                                        current_tc["function"]["name"] = Value::String(name.to_string());
-                                                    }
-                                                    if let Some(args) = func.get("arguments").and_then(|v| v.as_str()) {
-                                                        let old_args = current_tc["function"]["arguments"].as_str().unwrap_or("");
-                                                        current_tc["function"]["arguments"] = Value::String(format!("{}{}", old_args, args));
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        
-                        // Claude/Anthropic format: content_block_start, content_block_delta, etc.
-                        let msg_type = json.get("type").and_then(|t| t.as_str());
-                        match msg_type {
-                            Some("content_block_start") => {
-                                if let (Some(index), Some(block)) = (json.get("index").and_then(|i| i.as_u64()), json.get("content_block")) {
-                                    let idx = index as usize;
-                                    if block.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                                        let id = block.get("id").and_then(|v| v.as_str()).unwrap_or("");
-                                        let name = block.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                                        while tool_calls.len() <= idx {
-                                            tool_calls.push(Value::Null);
-                                        }
-                                        tool_calls[idx] = serde_json::json!({
-                                            "id": id,
-                                            "type": "function",
-                                            "function": { "name": name, "arguments": "" }
-                                        });
-                                    }
-                                }
-                            }
-                            Some("content_block_delta") => {
-                                if let (Some(index), Some(delta)) = (json.get("index").and_then(|i| i.as_u64()), json.get("delta")) {
-                                    let idx = index as usize;
-                                    
-                                    // Tool use input delta
-                                    if let Some(delta_json) = delta.get("input_json_delta").and_then(|v| v.as_str()) {
-                                        if idx < tool_calls.len() && !tool_calls[idx].is_null() {
-                                            let old_args = tool_calls[idx]["function"]["arguments"].as_str().unwrap_or("");
-                                            tool_calls[idx]["function"]["arguments"] = Value::String(format!("{}{}", old_args, delta_json));
-                                        }
-                                    }
-                                    // Legacy/Native thinking block
-                                    if let Some(thinking) = delta.get("thinking").and_then(|v| v.as_str()) {
-                                        thinking_content.push_str(thinking);
-                                    }
-                                    // Text content
-                                    if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
-                                        response_content.push_str(text);
-                                    }
-                                }
-                            }
-                            Some("message_delta") => {
-                                if let Some(delta) = json.get("delta") {
-                                    if let Some(usage) = delta.get("usage") {
-                                        if let Some(output_tokens) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
-                                            log.output_tokens = Some(output_tokens as u32);
-                                        }
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
-                        
-                        // Legacy Claude delta (for older implementations or simplified streams)
-                        if msg_type.is_none() {
-                            if let Some(delta) = json.get("delta") {
-                                // Thinking block
-                                if let Some(thinking) = delta.get("thinking").and_then(|v| v.as_str()) {
-                                    thinking_content.push_str(thinking);
-                                }
-                                // Thinking signature
-                                if let Some(sig) = delta.get("signature").and_then(|v| v.as_str()) {
-                                    thinking_signature = sig.to_string();
-                                }
-                                // Text content
-                                if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
-                                    response_content.push_str(text);
-                                }
-                            }
-                        }
-                        
-                        // Token usage extraction
-                        if let Some(usage) = json.get("usage")
-                            .or(json.get("usageMetadata"))
-                            .or(json.get("response").and_then(|r| r.get("usage")))
-                        {
-                            log.input_tokens = usage.get("prompt_tokens")
-                                .or(usage.get("input_tokens"))
-                                .or(usage.get("promptTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            log.output_tokens = usage.get("completion_tokens")
-                                .or(usage.get("output_tokens"))
-                                .or(usage.get("candidatesTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage.get("total_tokens")
-                                    .or(usage.get("totalTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
-                        }
-                    }
-                }
-                
-                // Build consolidated response object
-                let mut consolidated = serde_json::Map::new();
-                
-                if !thinking_content.is_empty() {
-                    consolidated.insert("thinking".to_string(), Value::String(thinking_content));
-                }
-                if !thinking_signature.is_empty() {
-                    consolidated.insert("thinking_signature".to_string(), Value::String(thinking_signature));
-                }
-                if !response_content.is_empty() {
-                    consolidated.insert("content".to_string(), Value::String(response_content));
-                }
-                
-                if !tool_calls.is_empty() {
-                    let clean_tool_calls: Vec<Value> = tool_calls.into_iter().filter(|v| !v.is_null()).collect();
-                    if !clean_tool_calls.is_empty() {
-                        consolidated.insert("tool_calls".to_string(), Value::Array(clean_tool_calls));
-                    }
-                }
-                if let Some(input) = log.input_tokens {
-                    consolidated.insert("input_tokens".to_string(), Value::Number(input.into()));
-                }
-                if let Some(output) = log.output_tokens {
-                    consolidated.insert("output_tokens".to_string(), Value::Number(output.into()));
-                }
-                
-                if consolidated.is_empty() {
-                    // Fallback: store raw SSE data if parsing failed
-                    log.response_body = Some(full_response.to_string());
-                } else {
-                    log.response_body = Some(serde_json::to_string_pretty(&Value::Object(consolidated)).unwrap_or_else(|_| full_response.to_string()));
-                }
-            } else {
-                log.response_body = Some(format!("[Binary Stream Data: {} bytes]", all_stream_data.len()));
-            }
-            
-            // Fallback token extraction from tail if not already extracted
-            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                if let Ok(full_tail) = std::str::from_utf8(&last_few_bytes) {
-                    for line in full_tail.lines().rev() {
-                        if line.starts_with("data: ") && (line.contains("\"usage\"") || line.contains("\"usageMetadata\"")) {
-                            let json_str = line.trim_start_matches("data: ").trim();
-                            if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                                if let Some(usage) = json.get("usage")
-                                    .or(json.get("usageMetadata"))
-                                    .or(json.get("response").and_then(|r| r.get("usage")))
-                                {
-                                    log.input_tokens = usage.get("prompt_tokens")
-                                        .or(usage.get("input_tokens"))
-                                        .or(usage.get("promptTokenCount"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
-                                    log.output_tokens = usage.get("completion_tokens")
-                                        .or(usage.get("output_tokens"))
-                                        .or(usage.get("candidatesTokenCount"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            
-            if log.status >= 400 {
-                log.error = Some("Stream Error or Failed".to_string());
-            }
-
-            // Record User Token Usage
-            record_user_token_usage(&user_token_identity, &log, user_agent.clone());
-
-            monitor.log_request(log).await;
-        });
-
-        Response::from_parts(parts, Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx)))
-    } else if content_type.contains("application/json") || content_type.contains("text/") {
-        let (parts, body) = response.into_parts();
-        match axum::body::to_bytes(body, MAX_RESPONSE_LOG_SIZE).await {
-            Ok(bytes) => {
-                if let Ok(s) = std::str::from_utf8(&bytes) {
-                    if let Ok(json) = serde_json::from_str::<Value>(&s) {
-                        // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
-                        if let Some(usage) = json.get("usage").or(json.get("usageMetadata")) {
-                            log.input_tokens = usage.get("prompt_tokens")
-                                .or(usage.get("input_tokens"))
-                                .or(usage.get("promptTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            log.output_tokens = usage.get("completion_tokens")
-                                .or(usage.get("output_tokens"))
-                                .or(usage.get("candidatesTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                                
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage.get("total_tokens")
-                                    .or(usage.get("totalTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
-                        }
-                    }
-                    log.response_body = Some(s.to_string());
-                } else {
-                    log.response_body = Some("[Binary Response Data]".to_string());
-                }
-                
-                if log.status >= 400 {
-                    log.error = log.response_body.clone();
-                }
-
-                // Record User Token Usage
-                record_user_token_usage(&user_token_identity, &log, user_agent.clone());
-
-                monitor.log_request(log).await;
-                Response::from_parts(parts, Body::from(bytes))
-            }
-            Err(_) => {
-                log.response_body = Some("[Response too large (>100MB)]".to_string());
-
-                // Record User Token Usage (even if too large)
-                record_user_token_usage(&user_token_identity, &log, user_agent.clone());
-
-                monitor.log_request(log).await;
-                Response::from_parts(parts, Body::empty())
-            }
-        }
+    let kind = if content_type.contains("text/event-stream") {
+        ResponseKind::Sse(SseStreamParser::new())
+    } else if content_type.contains("application/json") {
+        ResponseKind::Json(JsonFieldScanner::new(&["usage", "usageMetadata"]))
+    } else if content_type.contains("text/") {
+        ResponseKind::Text
     } else {
-        log.response_body = Some(format!("[{}]", content_type));
+        ResponseKind::Other(content_type)
+    };
 
-        // Record User Token Usage
-        record_user_token_usage(&user_token_identity, &log, user_agent);
+    let tap = ResponseTap {
+        log: Some(log),
+        kind,
+        sample: BodySample::new(SAMPLE_HEAD_BYTES, SAMPLE_TAIL_BYTES),
+        request: request_capture,
+        start,
+        headers_elapsed,
+        first_byte_at: None,
+        first_token_at: None,
+        stream_error: None,
+        completed: false,
+        monitor: state.monitor.clone(),
+        user_token_identity,
+        user_agent,
+        metrics_model,
+    };
 
-        monitor.log_request(log).await;
-        response
-    }
+    let (parts, body) = response.into_parts();
+    Response::from_parts(parts, tap_body(body, tap))
 }
@@ -0,0 +1,29 @@
+// 请求链路追踪中间件 (最外层)
+//
+// 为每个请求创建根 span 并确定 trace id：启用 OpenTelemetry 时使用导出的 trace id，
+// 否则生成随机 id，保证 ProxyRequestLog 与 debug 日志文件名始终可关联。
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use tracing::Instrument;
+
+use crate::proxy::telemetry::{self, SPAN_TARGET};
+
+pub async fn trace_middleware(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    let span = tracing::info_span!(
+        target: SPAN_TARGET,
+        "proxy_request",
+        otel.name = %format!("{} {}", method, path),
+        http.method = %method,
+        http.route = %path,
+        http.status_code = tracing::field::Empty,
+    );
+    let trace_id = telemetry::trace_id_of(&span)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let response = telemetry::scope_trace_id(trace_id, next.run(request).instrument(span.clone())).await;
+    span.record("http.status_code", response.status().as_u16());
+    response
+}
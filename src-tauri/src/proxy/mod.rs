@@ -8,13 +8,18 @@ pub mod server;
 pub mod token_manager;
 
 // 新架构模块
+pub mod admission; // 准入排队 (账号池全部锁定时公平等待)
 pub mod audio; // 音频处理模块
+pub mod auto_ban; // 自动临时封禁
+pub mod batch; // Batch API 后台任务
 pub mod cli_sync; // CLI 配置同步 (v3.3.35)
 pub mod droid_sync; // Droid (Factory CLI) 配置同步
 pub mod common; // 公共工具
+pub mod concurrency; // 账号并发控制 (在途请求计数)
 pub mod debug_logger;
 pub mod handlers; // API 端点处理器
 pub mod mappers; // 协议转换器
+pub mod metrics; // Prometheus 指标
 pub mod middleware; // Axum 中间件
 pub mod monitor; // 监控
 pub mod opencode_sync; // OpenCode 配置同步
@@ -25,13 +30,21 @@ pub mod model_specs; // 模型规格管理 (v4.1.28)
 pub mod session_manager; // 会话指纹管理
 pub mod signature_cache; // Signature Cache (v3.3.16)
 pub mod sticky_config; // 粘性调度配置
+pub mod telemetry; // OpenTelemetry 链路追踪
 pub mod upstream; // 上游客户端
 pub mod zai_vision_mcp; // Built-in Vision MCP server state
 pub mod zai_vision_tools; // Built-in Vision MCP tools (z.ai vision API) // 调试日志
 
+pub use config::update_account_concurrency;
+pub use config::update_admission_config;
+pub use config::update_batch_config;
 pub use config::update_global_system_prompt_config;
 pub use config::update_thinking_budget_config;
 pub use config::update_image_thinking_mode;
+pub use config::update_model_fallbacks;
+pub use config::update_model_prices;
+pub use config::update_routing_rules;
+pub use config::update_upstream_endpoints;
 pub use config::ProxyAuthMode;
 pub use config::ProxyConfig;
 pub use config::ProxyPoolConfig;
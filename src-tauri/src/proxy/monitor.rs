@@ -23,6 +23,17 @@ pub struct ProxyRequestLog {
     pub output_tokens: Option<u32>,
     pub protocol: Option<String>,     // 协议类型: "openai", "anthropic", "gemini"
     pub username: Option<String>,     // User token username
+    #[serde(default)]
+    pub ttft_ms: Option<u64>,         // 流式响应首 token 耗时 (ms)
+    // 以下 token 明细均为 input/output 的子集 (OpenAI 语义)
+    #[serde(default)]
+    pub cache_read_tokens: Option<u32>,     // 命中缓存的输入 token
+    #[serde(default)]
+    pub cache_creation_tokens: Option<u32>, // 写入缓存的输入 token
+    #[serde(default)]
+    pub reasoning_tokens: Option<u32>,      // 思考 (reasoning) 输出 token
+    #[serde(default)]
+    pub trace_id: Option<String>,           // 链路追踪 ID (与 debug 日志文件名/OTLP trace 一致)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -86,8 +97,16 @@ impl ProxyMonitor {
         ) {
             let model = log.model.clone().unwrap_or_else(|| "unknown".to_string());
             let account = account.clone();
+            let username = log.username.clone();
+            let usage = crate::modules::token_stats::TokenUsage {
+                input_tokens: input,
+                output_tokens: output,
+                cache_read_tokens: log.cache_read_tokens.unwrap_or(0),
+                cache_creation_tokens: log.cache_creation_tokens.unwrap_or(0),
+                reasoning_tokens: log.reasoning_tokens.unwrap_or(0),
+            };
             tokio::spawn(async move {
-                if let Err(e) = crate::modules::token_stats::record_usage(&account, &model, input, output) {
+                if let Err(e) = crate::modules::token_stats::record_usage(&account, &model, username.as_deref(), &usage) {
                     tracing::debug!("Failed to record token stats: {}", e);
                 }
             });
@@ -145,18 +164,6 @@ impl ProxyMonitor {
                      tracing::error!("Failed to save security log: {}", e);
                 }
             }
-
-            // Record token stats if available
-            if let (Some(account), Some(input), Some(output)) = (
-                &log_to_save.account_email,
-                log_to_save.input_tokens,
-                log_to_save.output_tokens,
-            ) {
-                let model = log_to_save.model.clone().unwrap_or_else(|| "unknown".to_string());
-                if let Err(e) = crate::modules::token_stats::record_usage(account, &model, input, output) {
-                    tracing::debug!("Failed to record token stats: {}", e);
-                }
-            }
         });
 
         // Emit event (send summary only, without body to reduce memory)
@@ -179,6 +186,11 @@ impl ProxyMonitor {
                 output_tokens: log.output_tokens,
                 protocol: log.protocol.clone(),
                 username: log.username.clone(),
+                ttft_ms: log.ttft_ms,
+                cache_read_tokens: log.cache_read_tokens,
+                cache_creation_tokens: log.cache_creation_tokens,
+                reasoning_tokens: log.reasoning_tokens,
+                trace_id: log.trace_id.clone(),
             };
             let _ = app.emit("proxy://request", &log_summary);
         }
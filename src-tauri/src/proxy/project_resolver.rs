@@ -3,8 +3,8 @@ use serde_json::Value;
 /// 使用 Antigravity 的 loadCodeAssist API 获取 project_id
 /// 这是获取 cloudaicompanionProject 的正确方式
 pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
-    // 使用 Sandbox 环境，避免 Prod 环境的 429 错误
-    let url = "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal:loadCodeAssist";
+    // 默认使用 Sandbox 环境，避免 Prod 环境的 429 错误 (可通过 upstream_endpoints 配置)
+    let url = crate::proxy::config::get_upstream_endpoints().load_code_assist_url();
     
     let request_body = serde_json::json!({
         "metadata": {
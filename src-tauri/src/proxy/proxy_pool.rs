@@ -306,6 +306,13 @@ impl ProxyPoolManager {
         
         // 添加认证
         if let Some(auth) = &entry.auth {
+            // 口令未能解密时仍是密文，绝不能作为凭据发给代理
+            if crate::utils::crypto::is_encrypted(&auth.password) {
+                return Err(format!(
+                    "Proxy {} password could not be decrypted, please re-enter it",
+                    entry.id
+                ));
+            }
             proxy = proxy.basic_auth(&auth.username, &auth.password);
         }
         
@@ -375,6 +382,11 @@ impl ProxyPoolManager {
             .collect()
     }
 
+    /// 获取代理列表快照 (含健康状态与延迟，用于 /metrics)
+    pub async fn proxies_snapshot(&self) -> Vec<ProxyEntry> {
+        self.config.read().await.proxies.clone()
+    }
+
     /// 持久化绑定关系到配置文件
     async fn persist_bindings(&self) {
         // 获取当前绑定快照
@@ -1,7 +1,11 @@
 use dashmap::DashMap;
+use std::path::Path;
+use std::sync::mpsc;
 use std::time::{SystemTime, Duration};
 use regex::Regex;
 
+use crate::modules::rate_limit_db::{self, FailureRow, LimitRow};
+
 /// 限流原因类型
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RateLimitReason {
@@ -17,6 +21,29 @@ pub enum RateLimitReason {
     Unknown,
 }
 
+impl RateLimitReason {
+    /// 持久化使用的稳定字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitReason::QuotaExhausted => "QUOTA_EXHAUSTED",
+            RateLimitReason::RateLimitExceeded => "RATE_LIMIT_EXCEEDED",
+            RateLimitReason::ModelCapacityExhausted => "MODEL_CAPACITY_EXHAUSTED",
+            RateLimitReason::ServerError => "SERVER_ERROR",
+            RateLimitReason::Unknown => "UNKNOWN",
+        }
+    }
+
+    pub fn from_str_lossy(s: &str) -> Self {
+        match s {
+            "QUOTA_EXHAUSTED" => RateLimitReason::QuotaExhausted,
+            "RATE_LIMIT_EXCEEDED" => RateLimitReason::RateLimitExceeded,
+            "MODEL_CAPACITY_EXHAUSTED" => RateLimitReason::ModelCapacityExhausted,
+            "SERVER_ERROR" => RateLimitReason::ServerError,
+            _ => RateLimitReason::Unknown,
+        }
+    }
+}
+
 /// 限流信息
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
@@ -41,11 +68,35 @@ pub struct RateLimitInfo {
 /// 失败计数过期时间：1小时（超过此时间未失败则重置计数）
 const FAILURE_COUNT_EXPIRY_SECONDS: u64 = 3600;
 
+/// 持久化写操作，由后台线程串行写入 SQLite，避免在异步运行时中阻塞
+enum PersistOp {
+    UpsertLimit(String, RateLimitInfo),
+    DeleteLimit(String),
+    DeleteExpiredLimits(SystemTime),
+    ClearLimits,
+    UpsertFailure(String, u32, SystemTime),
+    DeleteFailure(String),
+    #[cfg(test)]
+    Flush(mpsc::Sender<()>),
+}
+
+fn to_unix_ms(t: SystemTime) -> i64 {
+    t.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn from_unix_ms(ms: i64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(ms.max(0) as u64)
+}
+
 /// 限流跟踪器
 pub struct RateLimitTracker {
     limits: DashMap<String, RateLimitInfo>,
     /// 连续失败计数（用于智能指数退避），带时间戳用于自动过期
     failure_counts: DashMap<String, (u32, SystemTime)>,
+    /// [NEW] 持久化通道 (None 表示仅内存)
+    persist_tx: Option<mpsc::Sender<PersistOp>>,
 }
 
 impl RateLimitTracker {
@@ -53,6 +104,130 @@ impl RateLimitTracker {
         Self {
             limits: DashMap::new(),
             failure_counts: DashMap::new(),
+            persist_tx: None,
+        }
+    }
+
+    /// 创建带 SQLite 持久化的跟踪器
+    ///
+    /// 从 `data_dir/rate_limits.db` 恢复未过期的锁定 (含 "account:model" 模型级 Key) 与退避计数，
+    /// 过期记录在加载时清理。数据目录不存在或数据库不可用时退化为纯内存模式。
+    pub fn with_persistence(data_dir: &Path) -> Self {
+        let mut tracker = Self::new();
+        if !data_dir.is_dir() {
+            return tracker;
+        }
+
+        let db_path = rate_limit_db::get_db_path(data_dir);
+        let conn = match rate_limit_db::connect_db(&db_path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("[RateLimit] Failed to open {:?}, persistence disabled: {}", db_path, e);
+                return tracker;
+            }
+        };
+
+        let now = SystemTime::now();
+        match rate_limit_db::load_and_prune(
+            &conn,
+            to_unix_ms(now),
+            (FAILURE_COUNT_EXPIRY_SECONDS * 1000) as i64,
+        ) {
+            Ok((limits, failures)) => {
+                for row in limits {
+                    tracker.limits.insert(
+                        row.key,
+                        RateLimitInfo {
+                            reset_time: from_unix_ms(row.reset_time_ms),
+                            retry_after_sec: row.retry_after_sec.max(0) as u64,
+                            detected_at: from_unix_ms(row.detected_at_ms),
+                            reason: RateLimitReason::from_str_lossy(&row.reason),
+                            model: row.model,
+                        },
+                    );
+                }
+                for row in failures {
+                    tracker
+                        .failure_counts
+                        .insert(row.key, (row.count, from_unix_ms(row.last_failure_ms)));
+                }
+                if !tracker.limits.is_empty() {
+                    tracing::info!(
+                        "[RateLimit] Restored {} active lockout(s) and {} failure counter(s) from disk",
+                        tracker.limits.len(),
+                        tracker.failure_counts.len()
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::warn!("[RateLimit] Failed to load persisted lockouts: {}", e);
+            }
+        }
+
+        let (tx, rx) = mpsc::channel::<PersistOp>();
+        let spawn_result = std::thread::Builder::new()
+            .name("rate-limit-persist".to_string())
+            .spawn(move || {
+                for op in rx {
+                    let result = match op {
+                        PersistOp::UpsertLimit(key, info) => rate_limit_db::upsert_limit(
+                            &conn,
+                            &LimitRow {
+                                key,
+                                reset_time_ms: to_unix_ms(info.reset_time),
+                                retry_after_sec: info.retry_after_sec as i64,
+                                detected_at_ms: to_unix_ms(info.detected_at),
+                                reason: info.reason.as_str().to_string(),
+                                model: info.model,
+                            },
+                        ),
+                        PersistOp::DeleteLimit(key) => rate_limit_db::delete_limit(&conn, &key),
+                        PersistOp::DeleteExpiredLimits(now) => {
+                            rate_limit_db::delete_expired_limits(&conn, to_unix_ms(now)).map(|_| ())
+                        }
+                        PersistOp::ClearLimits => rate_limit_db::clear_limits(&conn),
+                        PersistOp::UpsertFailure(key, count, ts) => rate_limit_db::upsert_failure(
+                            &conn,
+                            &FailureRow {
+                                key,
+                                count,
+                                last_failure_ms: to_unix_ms(ts),
+                            },
+                        ),
+                        PersistOp::DeleteFailure(key) => rate_limit_db::delete_failure(&conn, &key),
+                        #[cfg(test)]
+                        PersistOp::Flush(ack) => {
+                            let _ = ack.send(());
+                            Ok(())
+                        }
+                    };
+                    if let Err(e) = result {
+                        tracing::warn!("[RateLimit] Persist failed: {}", e);
+                    }
+                }
+            });
+
+        match spawn_result {
+            Ok(_) => tracker.persist_tx = Some(tx),
+            Err(e) => tracing::warn!("[RateLimit] Failed to spawn persist thread: {}", e),
+        }
+        tracker
+    }
+
+    fn persist(&self, op: PersistOp) {
+        if let Some(tx) = &self.persist_tx {
+            let _ = tx.send(op);
+        }
+    }
+
+    /// 等待后台线程写完所有待持久化操作
+    #[cfg(test)]
+    fn flush_persistence(&self) {
+        if let Some(tx) = &self.persist_tx {
+            let (ack_tx, ack_rx) = mpsc::channel();
+            if tx.send(PersistOp::Flush(ack_tx)).is_ok() {
+                let _ = ack_rx.recv_timeout(Duration::from_secs(5));
+            }
         }
     }
     
@@ -98,9 +273,12 @@ impl RateLimitTracker {
     pub fn mark_success(&self, account_id: &str) {
         if self.failure_counts.remove(account_id).is_some() {
             tracing::debug!("账号 {} 请求成功，已重置失败计数", account_id);
+            self.persist(PersistOp::DeleteFailure(account_id.to_string()));
         }
         // 清除账号级限流
-        self.limits.remove(account_id);
+        if self.limits.remove(account_id).is_some() {
+            self.persist(PersistOp::DeleteLimit(account_id.to_string()));
+        }
         // 注意：我们暂时无法清除该账号下的所有模型级锁，因为我们不知道哪些模型被锁了
         // 除非遍历 limits。考虑到模型级锁通常是 QuotaExhausted，让其自然过期也是可以接受的。
         // 或者我们可以引入索引，但为了简单，暂时只清除 Account 级锁。
@@ -129,7 +307,9 @@ impl RateLimitTracker {
         };
         
         let key = self.get_limit_key(account_id, model.as_deref());
+        self.persist(PersistOp::UpsertLimit(key.clone(), info.clone()));
         self.limits.insert(key, info);
+        crate::proxy::metrics::record_rate_limit_lockout(reason.as_str());
         
         if let Some(m) = &model {
             tracing::info!(
@@ -243,6 +423,7 @@ impl RateLimitTracker {
                     }
                     entry.0 += 1;
                     entry.1 = now;
+                    self.persist(PersistOp::UpsertFailure(account_id.to_string(), entry.0, now));
                     entry.0
                 } else {
                     // ServerError (5xx) 使用固定值 1，不累加，避免污染 429 的退避阶梯
@@ -314,7 +495,9 @@ impl RateLimitTracker {
             account_id.to_string()
         };
 
+        self.persist(PersistOp::UpsertLimit(key.clone(), info.clone()));
         self.limits.insert(key, info.clone());
+        crate::proxy::metrics::record_rate_limit_lockout(reason.as_str());
         
         tracing::warn!(
             "账号 {} [{}] 限流类型: {:?}, 重置延时: {}秒",
@@ -540,6 +723,7 @@ impl RateLimitTracker {
         
         if count > 0 {
             tracing::debug!("清除了 {} 个过期的限流记录", count);
+            self.persist(PersistOp::DeleteExpiredLimits(now));
         }
         
         count
@@ -547,7 +731,11 @@ impl RateLimitTracker {
     
     /// 清除指定账号的限流记录
     pub fn clear(&self, account_id: &str) -> bool {
-        self.limits.remove(account_id).is_some()
+        let removed = self.limits.remove(account_id).is_some();
+        if removed {
+            self.persist(PersistOp::DeleteLimit(account_id.to_string()));
+        }
+        removed
     }
     
     /// 清除所有限流记录 (乐观重置策略)
@@ -557,6 +745,7 @@ impl RateLimitTracker {
     pub fn clear_all(&self) {
         let count = self.limits.len();
         self.limits.clear();
+        self.persist(PersistOp::ClearLimits);
         tracing::warn!("🔄 Optimistic reset: Cleared all {} rate limit record(s)", count);
     }
 }
@@ -571,6 +760,76 @@ impl Default for RateLimitTracker {
 mod tests {
     use super::*;
     
+    fn temp_data_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rate_limit_persist_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_persisted_lockouts_survive_restart() {
+        let dir = temp_data_dir();
+        {
+            let tracker = RateLimitTracker::with_persistence(&dir);
+            // 模型级 QUOTA_EXHAUSTED 锁 + 退避计数
+            tracker.parse_from_error(
+                "acc1",
+                429,
+                None,
+                r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#,
+                Some("gemini-2.5-pro".to_string()),
+                &[600, 1800],
+            );
+            tracker.set_lockout_until(
+                "acc2",
+                SystemTime::now() + Duration::from_secs(300),
+                RateLimitReason::RateLimitExceeded,
+                None,
+            );
+            tracker.flush_persistence();
+        }
+
+        let reloaded = RateLimitTracker::with_persistence(&dir);
+        assert!(reloaded.is_rate_limited("acc1", Some("gemini-2.5-pro")));
+        assert!(!reloaded.is_rate_limited("acc1", Some("claude-sonnet-4-5")));
+        assert!(reloaded.is_rate_limited("acc2", None));
+        assert_eq!(reloaded.failure_counts.get("acc1").map(|e| e.0), Some(1));
+        assert_eq!(
+            reloaded.get("acc2").map(|i| i.reason),
+            Some(RateLimitReason::RateLimitExceeded)
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_expired_lockouts_pruned_on_load() {
+        let dir = temp_data_dir();
+        {
+            let tracker = RateLimitTracker::with_persistence(&dir);
+            tracker.set_lockout_until(
+                "expired",
+                SystemTime::now() - Duration::from_secs(10),
+                RateLimitReason::QuotaExhausted,
+                None,
+            );
+            tracker.set_lockout_until(
+                "active",
+                SystemTime::now() + Duration::from_secs(120),
+                RateLimitReason::QuotaExhausted,
+                None,
+            );
+            tracker.mark_success("active");
+            tracker.flush_persistence();
+        }
+
+        let reloaded = RateLimitTracker::with_persistence(&dir);
+        assert!(reloaded.get("expired").is_none());
+        assert!(reloaded.get("active").is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_parse_retry_time_minutes_seconds() {
         let tracker = RateLimitTracker::new();
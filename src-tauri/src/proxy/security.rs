@@ -1,4 +1,20 @@
 use crate::proxy::config::{ProxyAuthMode, ProxyConfig, SecurityMonitorConfig};
+use std::sync::OnceLock;
+
+/// 内部端点 (/internal/*) 的进程内部密钥 Header
+pub const INTERNAL_SECRET_HEADER: &str = "x-internal-secret";
+
+/// 进程内部密钥 (每次启动随机生成，仅供本进程调用内部端点)
+pub fn internal_secret() -> &'static str {
+    static SECRET: OnceLock<String> = OnceLock::new();
+    SECRET.get_or_init(|| {
+        format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        )
+    })
+}
 
 #[derive(Debug, Clone)]
 pub struct ProxySecurityConfig {
@@ -283,6 +283,12 @@ impl AxumServer {
         tracing::info!("User-Agent 配置已热更新: {:?}", config.user_agent_override);
     }
 
+    /// 仅测试使用：让本实例的 v1internal 请求发往指定端点 (不修改全局端点配置)
+    #[cfg(test)]
+    pub async fn override_v1internal_base_urls(&self, base_urls: Vec<String>) {
+        self.upstream.set_v1internal_base_urls_override(Some(base_urls)).await;
+    }
+
     pub async fn set_running(&self, running: bool) {
         let mut r = self.is_running.write().await;
         *r = running;
@@ -316,6 +322,7 @@ impl AxumServer {
     // Start health check loop
     proxy_pool_manager.clone().start_health_check_loop();
         let security_state = Arc::new(RwLock::new(security_config));
+        crate::proxy::auto_ban::set_notifier(integration.clone()); // [NEW] 自动封禁通知
         let zai_state = Arc::new(RwLock::new(zai_config));
         let provider_rr = Arc::new(AtomicUsize::new(0));
         let zai_vision_mcp_state = Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
@@ -364,14 +371,15 @@ impl AxumServer {
         // 构建路由 - 使用新架构的 handlers！
         use crate::proxy::handlers;
         use crate::proxy::middleware::{
-            admin_auth_middleware, auth_middleware, cors_layer, ip_filter_middleware,
-            monitor_middleware, service_status_middleware,
+            admin_auth_middleware, admission_middleware, auth_middleware, cors_layer, ip_filter_middleware,
+            monitor_middleware, service_status_middleware, trace_middleware,
         };
 
         // 1. 构建主 AI 代理路由 (遵循 auth_mode 配置)
         let proxy_routes = Router::new()
             .route("/health", get(health_check_handler))
             .route("/healthz", get(health_check_handler))
+            .route("/metrics", get(handlers::metrics::handle_metrics)) // Prometheus 指标 (受 auth_mode 保护)
             // OpenAI Protocol
             .route("/v1/models", get(handlers::openai::handle_list_models))
             .route(
@@ -383,6 +391,15 @@ impl AxumServer {
                 post(handlers::openai::handle_completions),
             )
             .route("/v1/responses", post(handlers::openai::handle_completions)) // 兼容 Codex CLI
+            .route(
+                "/v1/responses/:response_id",
+                get(handlers::openai::handle_get_response)
+                    .delete(handlers::openai::handle_delete_response),
+            )
+            .route(
+                "/v1/responses/:response_id/input_items",
+                get(handlers::openai::handle_list_response_input_items),
+            )
             .route(
                 "/v1/images/generations",
                 post(handlers::openai::handle_images_generations),
@@ -395,6 +412,31 @@ impl AxumServer {
                 "/v1/audio/transcriptions",
                 post(handlers::audio::handle_audio_transcription),
             ) // 音频转录 API
+            .route(
+                "/v1/embeddings",
+                post(handlers::embeddings::handle_embeddings),
+            ) // Embeddings API
+            .route(
+                "/v1/files",
+                get(handlers::batches::handle_list_files).post(handlers::batches::handle_upload_file),
+            ) // Batch API 输入/结果文件
+            .route(
+                "/v1/files/:file_id",
+                get(handlers::batches::handle_get_file).delete(handlers::batches::handle_delete_file),
+            )
+            .route(
+                "/v1/files/:file_id/content",
+                get(handlers::batches::handle_get_file_content),
+            )
+            .route(
+                "/v1/batches",
+                get(handlers::batches::handle_list_batches).post(handlers::batches::handle_create_batch),
+            )
+            .route("/v1/batches/:batch_id", get(handlers::batches::handle_get_batch))
+            .route(
+                "/v1/batches/:batch_id/cancel",
+                post(handlers::batches::handle_cancel_batch),
+            )
             // Claude Protocol
             .route("/v1/messages", post(handlers::claude::handle_messages))
             .route(
@@ -417,7 +459,7 @@ impl AxumServer {
             )
             // Gemini Protocol (Native)
             .route("/v1beta/models", get(handlers::gemini::handle_list_models))
-            // Handle both GET (get info) and POST (generateContent with colon) at the same route
+            // Handle both GET (get info) and POST (generateContent / embedContent with colon) at the same route
             .route(
                 "/v1beta/models/:model",
                 get(handlers::gemini::handle_get_model).post(handlers::gemini::handle_generate),
@@ -435,9 +477,11 @@ impl AxumServer {
             .route("/v1/api/event_logging", post(silent_ok_handler))
             // 应用 AI 服务特定的层
             // 注意：Axum layer 执行顺序是从下往上（洋葱模型）
-            // 请求: ip_filter -> auth -> monitor -> handler
-            // 响应: handler -> monitor -> auth -> ip_filter
-            // monitor 需要在 auth 之后执行才能获取 UserTokenIdentity
+            // 请求: trace -> ip_filter -> auth -> monitor -> admission -> handler
+            // 响应: handler -> admission -> monitor -> auth -> ip_filter -> trace
+            // monitor / admission 需要在 auth 之后执行才能获取 UserTokenIdentity
+            // trace 位于最外层，为整条链路创建根 span 与 trace id
+            .layer(axum::middleware::from_fn(admission_middleware))
             .layer(axum::middleware::from_fn_with_state(
                 state.clone(),
                 monitor_middleware,
@@ -449,7 +493,27 @@ impl AxumServer {
             .layer(axum::middleware::from_fn_with_state(
                 state.clone(),
                 ip_filter_middleware,
-            ));
+            ))
+            .layer(axum::middleware::from_fn(trace_middleware));
+
+        // 1.1 Batch API 内部路由：后台任务逐行派发请求 (批次创建时已鉴权，身份由任务注入)
+        let batch_routes = Router::new()
+            .route(
+                "/v1/chat/completions",
+                post(handlers::openai::handle_chat_completions),
+            )
+            .route(
+                "/v1/embeddings",
+                post(handlers::embeddings::handle_embeddings),
+            )
+            .layer(axum::middleware::from_fn(admission_middleware))
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                monitor_middleware,
+            ))
+            .layer(axum::middleware::from_fn(trace_middleware))
+            .with_state(state.clone());
+        let batch_worker = crate::proxy::batch::spawn_worker(state.clone(), batch_routes);
 
         // 2. 构建管理 API (强制鉴权)
         let admin_routes = Router::new()
@@ -501,6 +565,7 @@ impl AxumServer {
             .route("/stats/weekly", get(admin_get_token_stats_weekly))
             .route("/stats/accounts", get(admin_get_token_stats_by_account))
             .route("/stats/models", get(admin_get_token_stats_by_model))
+            .route("/stats/users", get(admin_get_token_stats_by_user))
             .route("/config", get(admin_get_config).post(admin_save_config))
             .route("/proxy/cli/status", post(admin_get_cli_sync_status))
             .route("/proxy/cli/sync", post(admin_execute_cli_sync))
@@ -581,6 +646,7 @@ impl AxumServer {
             )
             .route("/stats/token/summary", get(admin_get_token_stats_summary))
             .route("/stats/token/by-model", get(admin_get_token_stats_by_model))
+            .route("/stats/token/by-user", get(admin_get_token_stats_by_user))
             .route(
                 "/stats/token/model-trend/hourly",
                 get(admin_get_token_stats_model_trend_hourly),
@@ -652,6 +718,14 @@ impl AxumServer {
             .route("/user-tokens/:id", delete(admin_delete_user_token).patch(admin_update_user_token))
             // OAuth (Web) - Admin 接口
             .route("/auth/url", get(admin_prepare_oauth_url_web))
+            // [NEW] 管理用户与会话
+            .route("/auth/login", post(admin_login))
+            .route("/auth/logout", post(admin_logout))
+            .route("/auth/me", get(admin_whoami))
+            .route("/admin-users", get(admin_list_admin_users).post(admin_create_admin_user))
+            .route("/admin-users/:id", delete(admin_delete_admin_user).patch(admin_update_admin_user))
+            // [NEW] 审计日志
+            .route("/audit", get(admin_get_audit_logs))
             // 应用管理特定鉴权层 (强制校验)
             .layer(axum::middleware::from_fn_with_state(
                 state.clone(),
@@ -757,6 +831,8 @@ impl AxumServer {
                         }
                     }
                     _ = &mut shutdown_rx => {
+                        batch_worker.abort();
+                        crate::modules::ip_acl::flush_hits();
                         tracing::info!("反代服务器停止监听");
                         break;
                     }
@@ -1309,6 +1385,21 @@ async fn admin_save_config(
         *pool = new_config.clone().proxy.proxy_pool;
     }
 
+    // 更新上游端点配置
+    crate::proxy::update_upstream_endpoints(new_config.proxy.upstream_endpoints.clone());
+    // 更新模型路由规则
+    crate::proxy::update_routing_rules(new_config.proxy.routing_rules.clone());
+    // 更新跨模型降级链
+    crate::proxy::update_model_fallbacks(new_config.proxy.model_fallbacks.clone());
+    // 更新模型单价表
+    crate::proxy::update_model_prices(new_config.proxy.model_prices.clone());
+    // 更新批处理配置
+    crate::proxy::update_batch_config(new_config.proxy.batch.clone());
+    // 更新账号并发上限
+    crate::proxy::update_account_concurrency(new_config.proxy.account_concurrency.clone());
+    // 更新准入排队配置
+    crate::proxy::update_admission_config(new_config.proxy.admission.clone());
+
     Ok(StatusCode::OK)
 }
 
@@ -1403,6 +1494,17 @@ async fn admin_get_proxy_status(
         "port": state.port,
         "base_url": format!("http://127.0.0.1:{}", state.port),
         "active_accounts": active_accounts,
+        "admission": crate::proxy::admission::snapshot(),
+        "in_flight": state
+            .token_manager
+            .in_flight_snapshot()
+            .into_iter()
+            .map(|(account, family, count)| serde_json::json!({
+                "account": account,
+                "family": family,
+                "count": count,
+            }))
+            .collect::<Vec<_>>(),
     })))
 }
 
@@ -1759,6 +1861,214 @@ async fn admin_create_user_token(
     Ok(Json(token))
 }
 
+// ============================================================================
+// [NEW] 管理用户与会话
+// ============================================================================
+
+#[derive(Deserialize)]
+struct AdminLoginRequest {
+    username: String,
+    password: String,
+}
+
+async fn admin_login(
+    State(state): State<AppState>,
+    axum::Extension(client_ip): axum::Extension<crate::proxy::middleware::auth::AdminClientIp>,
+    Json(payload): Json<AdminLoginRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let username = payload.username.trim().to_string();
+
+    // [FIX] 同一用户名 + IP 连续失败后退避，退避期内不执行口令派生
+    if let Some(secs) = crate::modules::admin_user_db::login_retry_after(&username, client_ip.0.as_deref()) {
+        tracing::warn!("[Admin] Login for '{}' from {:?} throttled for {}s", username, client_ip.0, secs);
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                error: format!("Too many failed login attempts. Try again in {} second(s).", secs),
+            }),
+        ));
+    }
+
+    // PBKDF2 较慢，放到阻塞线程执行
+    let session = tokio::task::spawn_blocking(move || {
+        crate::modules::admin_user_db::login(&payload.username, &payload.password)
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r)
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )
+    })?;
+
+    let Some(session) = session else {
+        tracing::warn!("[Admin] Failed login for '{}' from {:?}", username, client_ip.0);
+        crate::modules::admin_user_db::record_login_failure(&username, client_ip.0.as_deref());
+        if let Some(ip) = &client_ip.0 {
+            let security = state.security.read().await;
+            crate::proxy::auto_ban::record_auth_failure(ip, &security.security_monitor);
+        }
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid username or password".to_string(),
+            }),
+        ));
+    };
+
+    crate::modules::admin_user_db::clear_login_failures(&username, client_ip.0.as_deref());
+    let identity = crate::proxy::middleware::auth::AdminIdentity::from_user(session.user.clone());
+    crate::proxy::middleware::auth::record_admin_audit(
+        &identity,
+        "POST /api/auth/login".to_string(),
+        client_ip.0,
+        StatusCode::OK,
+    );
+    Ok(Json(session))
+}
+
+async fn admin_logout(headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer "))
+        .filter(|t| t.starts_with(crate::modules::admin_user_db::SESSION_TOKEN_PREFIX));
+    // 管理密码登录没有会话可注销
+    if let Some(token) = token {
+        crate::modules::admin_user_db::revoke_session(token).map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            )
+        })?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn admin_whoami(
+    axum::Extension(identity): axum::Extension<crate::proxy::middleware::auth::AdminIdentity>,
+) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "username": identity.username,
+        "role": identity.role,
+        "actor_type": identity.actor_type,
+    }))
+}
+
+async fn admin_list_admin_users() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let users = crate::modules::admin_user_db::list_users().map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )
+    })?;
+    Ok(Json(users))
+}
+
+#[derive(Deserialize)]
+struct CreateAdminUserRequest {
+    username: String,
+    password: String,
+    role: crate::modules::admin_user_db::AdminRole,
+}
+
+async fn admin_create_admin_user(
+    Json(payload): Json<CreateAdminUserRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let user = tokio::task::spawn_blocking(move || {
+        crate::modules::admin_user_db::create_user(&payload.username, &payload.password, payload.role)
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r)
+    .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))?;
+    crate::modules::audit::record_change(
+        "admin_user.create",
+        Some(user.username.clone()),
+        None,
+        Some(serde_json::json!({ "id": user.id, "role": user.role })),
+    );
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+#[derive(Deserialize)]
+struct UpdateAdminUserRequest {
+    password: Option<String>,
+    role: Option<crate::modules::admin_user_db::AdminRole>,
+    disabled: Option<bool>,
+}
+
+async fn admin_update_admin_user(
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateAdminUserRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let before = crate::modules::admin_user_db::list_users()
+        .ok()
+        .and_then(|users| users.into_iter().find(|u| u.id == id));
+    let password_changed = payload.password.is_some();
+    let user = tokio::task::spawn_blocking(move || {
+        crate::modules::admin_user_db::update_user(
+            &id,
+            payload.password.as_deref(),
+            payload.role,
+            payload.disabled,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r)
+    .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))?;
+    let snapshot = |u: &crate::modules::admin_user_db::AdminUser| {
+        serde_json::json!({ "role": u.role, "disabled": u.disabled })
+    };
+    let mut after = snapshot(&user);
+    if password_changed {
+        after["password_changed"] = serde_json::Value::Bool(true);
+    }
+    crate::modules::audit::record_change(
+        "admin_user.update",
+        Some(user.username.clone()),
+        before.as_ref().map(snapshot),
+        Some(after),
+    );
+    Ok(Json(user))
+}
+
+async fn admin_delete_admin_user(
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let before = crate::modules::admin_user_db::list_users()
+        .ok()
+        .and_then(|users| users.into_iter().find(|u| u.id == id));
+    crate::modules::admin_user_db::delete_user(&id)
+        .map_err(|e| (StatusCode::NOT_FOUND, Json(ErrorResponse { error: e })))?;
+    if let Some(user) = before {
+        crate::modules::audit::record_change(
+            "admin_user.delete",
+            Some(user.username.clone()),
+            Some(serde_json::json!({ "id": user.id, "role": user.role })),
+            None,
+        );
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn admin_get_audit_logs(
+    Query(query): Query<crate::modules::audit_db::AuditQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let entries = crate::commands::audit::get_audit_logs(Some(query))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            )
+        })?;
+    Ok(Json(entries))
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct RenewTokenRequest {
@@ -1966,6 +2276,27 @@ async fn admin_get_token_stats_by_account(
     }
 }
 
+async fn admin_get_token_stats_by_user(
+    Query(p): Query<StatsPeriodQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let hours = p.hours.unwrap_or(168);
+    let res = tokio::task::spawn_blocking(move || token_stats::get_user_token_stats(hours)).await;
+
+    match res {
+        Ok(Ok(stats)) => Ok(Json(stats)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
 async fn admin_get_token_stats_summary(
     Query(p): Query<StatsPeriodQuery>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -0,0 +1,146 @@
+// OpenTelemetry 链路追踪 - OTLP/HTTP 导出
+//
+// - 追踪层在 logger 初始化时按 ProxyConfig.telemetry 安装 (未启用时不安装，零开销)
+// - 请求链路的 span 统一使用 `otel` target，控制台/文件日志层会过滤掉这些 span，避免改变日志格式
+// - 每个请求的 trace id 通过 task-local 传递给 monitor (ProxyRequestLog) 与 handler (debug 日志文件名)
+
+use futures::Stream;
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use tracing::Subscriber;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::proxy::config::TelemetryConfig;
+
+/// 请求链路 span 使用的 target
+pub const SPAN_TARGET: &str = "otel";
+
+static PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+tokio::task_local! {
+    static TRACE_ID: String;
+}
+
+/// 根据配置构建 OpenTelemetry 追踪层；未启用时返回 Ok(None)
+///
+/// 该函数在全局 subscriber 安装之前调用，此时 tracing 事件会被丢弃，因此错误交由调用方在初始化后记录
+pub fn init_layer<S>(
+    config: &TelemetryConfig,
+) -> Result<Option<OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>>, String>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    if !config.enabled {
+        return Ok(None);
+    }
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(config.otlp_endpoint.clone())
+        .build()
+        .map_err(|e| format!("Failed to build OTLP exporter: {}", e))?;
+
+    // Batch 导出需要在 tokio 运行时上下文中创建 (后台任务运行在 tauri 运行时)
+    let sample_ratio = config.sample_ratio.clamp(0.0, 1.0);
+    let service_name = config.service_name.clone();
+    let provider = tauri::async_runtime::block_on(async move {
+        TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(sample_ratio))))
+            .with_resource(opentelemetry_sdk::Resource::new(vec![KeyValue::new(
+                "service.name",
+                service_name,
+            )]))
+            .build()
+    });
+
+    let tracer = provider.tracer("antigravity-proxy");
+    let _ = PROVIDER.set(provider);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+/// 应用退出时刷新并关闭导出器
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!("[Telemetry] Failed to shutdown tracer provider: {}", e);
+        }
+    }
+}
+
+/// 控制台/文件日志层的过滤器：隐藏链路追踪 span，事件不受影响
+pub fn is_not_trace_span(metadata: &tracing::Metadata<'_>) -> bool {
+    !(metadata.is_span() && metadata.target() == SPAN_TARGET)
+}
+
+/// span 所属的 OpenTelemetry trace id (追踪未启用时返回 None)
+pub fn trace_id_of(span: &tracing::Span) -> Option<String> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// 在指定 trace id 作用域内执行请求
+pub async fn scope_trace_id<F: std::future::Future>(trace_id: String, fut: F) -> F::Output {
+    TRACE_ID.scope(trace_id, fut).await
+}
+
+/// 当前请求的 trace id (由 trace 中间件设置)
+pub fn current_trace_id() -> Option<String> {
+    TRACE_ID.try_with(|id| id.clone()).ok()
+}
+
+/// 在 span 内轮询的流包装，用于覆盖流式转换的完整生命周期
+struct InstrumentedStream<S> {
+    inner: S,
+    span: tracing::Span,
+}
+
+impl<S: Stream + Unpin> Stream for InstrumentedStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let _enter = this.span.enter();
+        Pin::new(&mut this.inner).poll_next(cx)
+    }
+}
+
+/// 流式转换 span (上游 SSE -> 客户端协议)
+pub fn stream_conversion_span(protocol: &'static str, model: &str) -> tracing::Span {
+    tracing::info_span!(
+        target: SPAN_TARGET,
+        "stream_conversion",
+        protocol = protocol,
+        model = %model,
+    )
+}
+
+/// 将流包装在 span 中 (span 在流被丢弃时结束)
+pub fn instrument_stream<S>(stream: S, span: tracing::Span) -> Pin<Box<dyn Stream<Item = S::Item> + Send>>
+where
+    S: Stream + Unpin + Send + 'static,
+{
+    Box::pin(InstrumentedStream { inner: stream, span })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trace_id_scope() {
+        assert_eq!(current_trace_id(), None);
+        let inner = scope_trace_id("abc123".to_string(), async { current_trace_id() }).await;
+        assert_eq!(inner.as_deref(), Some("abc123"));
+        assert_eq!(current_trace_id(), None);
+    }
+}
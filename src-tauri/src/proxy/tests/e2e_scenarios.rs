@@ -0,0 +1,319 @@
+//! 端到端场景测试：真实 AxumServer + 假 v1internal 上游
+//! 覆盖 /v1/messages、/v1/chat/completions、/v1beta/models/:model 的账号轮换、重试、签名与用量统计
+
+use serde_json::{json, Value};
+
+use super::fake_upstream::{FakeReply, TestProxy};
+
+/// 签名需足够长才会被视为有效签名
+const FAKE_SIGNATURE: &str =
+    "fake-thought-signature-0123456789abcdefghijklmnopqrstuvwxyz-ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn claude_request(stream: bool) -> Value {
+    json!({
+        "model": "claude-sonnet-4-5",
+        "max_tokens": 256,
+        "stream": stream,
+        "messages": [{ "role": "user", "content": "Say hello" }]
+    })
+}
+
+/// 拼接 Claude 响应中所有 text 块
+fn claude_text(resp: &Value) -> String {
+    resp["content"]
+        .as_array()
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter(|b| b["type"] == "text")
+                .filter_map(|b| b["text"].as_str())
+                .collect::<String>()
+        })
+        .unwrap_or_default()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_claude_stream_roundtrip() {
+    let proxy = TestProxy::start(1).await;
+    proxy.script(vec![FakeReply::text(&["Hello", " world"], 12, 7)]);
+
+    let resp = proxy.post_json("/v1/messages", claude_request(true)).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .starts_with("text/event-stream"));
+
+    let body = resp.text().await.unwrap();
+    assert!(body.contains("event: message_start"), "missing message_start: {}", body);
+    assert!(body.contains("Hello"));
+    assert!(body.contains(" world"));
+    assert!(body.contains("event: message_stop"), "missing message_stop: {}", body);
+
+    let reqs = proxy.upstream_requests();
+    assert_eq!(reqs.len(), 1);
+    assert_eq!(reqs[0].method, "streamGenerateContent");
+    assert_eq!(reqs[0].base, "/primary");
+
+    // 流式响应的用量在流结束后写入请求日志与 token_stats
+    let email = proxy.email_for_token(&reqs[0].access_token);
+    let logs = proxy.request_logs(1).await;
+    assert_eq!(logs[0].status, 200);
+    assert_eq!(logs[0].account_email.as_deref(), Some(email.as_str()));
+    assert_eq!(logs[0].output_tokens, Some(7));
+    let stats = proxy.account_token_stats(&email).await;
+    assert_eq!(stats.total_output_tokens, 7);
+    assert_eq!(stats.request_count, 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_claude_429_rotates_account() {
+    let proxy = TestProxy::start(2).await;
+    // 429 会先在 primary → fallback 端点间降级，两次都失败后才交给 handler 轮换账号
+    proxy.script(vec![
+        FakeReply::rate_limited("0.1s"),
+        FakeReply::rate_limited("0.1s"),
+        FakeReply::text(&["Hello from the second account"], 12, 7),
+    ]);
+
+    let resp = proxy.post_json("/v1/messages", claude_request(false)).await;
+    assert_eq!(resp.status(), 200);
+    let email = resp
+        .headers()
+        .get("X-Account-Email")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let body: Value = resp.json().await.unwrap();
+
+    let reqs = proxy.upstream_requests();
+    assert_eq!(reqs.len(), 3);
+    assert_eq!(reqs[0].base, "/primary");
+    assert_eq!(reqs[1].base, "/fallback");
+    assert_eq!(reqs[0].access_token, reqs[1].access_token);
+    assert_ne!(reqs[1].access_token, reqs[2].access_token, "429 should rotate to another account");
+    assert_eq!(email, proxy.email_for_token(&reqs[2].access_token));
+
+    assert_eq!(claude_text(&body), "Hello from the second account");
+    assert_eq!(body["usage"]["output_tokens"], 7);
+    assert!(body["usage"]["input_tokens"].as_u64().unwrap_or(0) > 0);
+
+    // 用量只记到实际服务的账号上
+    let logs = proxy.request_logs(1).await;
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].account_email.as_deref(), Some(email.as_str()));
+    assert_eq!(logs[0].output_tokens, Some(7));
+    assert!(logs[0].input_tokens.unwrap_or(0) > 0);
+    let stats = proxy.account_token_stats(&email).await;
+    assert_eq!(stats.total_output_tokens, 7);
+    assert_eq!(stats.request_count, 1);
+    let rate_limited = proxy.email_for_token(&reqs[0].access_token);
+    let rate_limited_stats = tokio::task::spawn_blocking(|| crate::modules::token_stats::get_account_stats(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(rate_limited_stats.iter().all(|s| s.account_email != rate_limited));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_claude_validation_required_blocks_account() {
+    let proxy = TestProxy::start(2).await;
+    proxy.script(vec![
+        FakeReply::validation_required(),
+        FakeReply::text(&["ok"], 5, 1),
+    ]);
+
+    let resp = proxy.post_json("/v1/messages", claude_request(false)).await;
+    assert_eq!(resp.status(), 200);
+
+    let reqs = proxy.upstream_requests();
+    assert_eq!(reqs.len(), 2, "403 must not trigger endpoint fallback");
+    assert_ne!(reqs[0].access_token, reqs[1].access_token);
+
+    let blocked = proxy.account_json(&reqs[0].access_token);
+    assert_eq!(blocked["validation_blocked"], true);
+    assert!(blocked["validation_blocked_until"].as_i64().unwrap_or(0) > chrono::Utc::now().timestamp());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_claude_invalid_signature_retries_without_thinking() {
+    let proxy = TestProxy::start(1).await;
+    proxy.script(vec![
+        FakeReply::invalid_signature(),
+        FakeReply::text(&["recovered"], 20, 3),
+    ]);
+
+    let request = json!({
+        "model": "claude-sonnet-4-5-thinking",
+        "max_tokens": 1024,
+        "stream": false,
+        "thinking": { "type": "enabled", "budget_tokens": 512 },
+        "messages": [
+            { "role": "user", "content": "First question" },
+            { "role": "assistant", "content": [
+                { "type": "thinking", "thinking": "Earlier reasoning", "signature": FAKE_SIGNATURE },
+                { "type": "text", "text": "Earlier answer" }
+            ]},
+            { "role": "user", "content": "Follow-up question" }
+        ]
+    });
+
+    let resp = proxy.post_json("/v1/messages", request).await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(claude_text(&body), "recovered");
+
+    let reqs = proxy.upstream_requests();
+    assert_eq!(reqs.len(), 2);
+    let retried = reqs[1].body.to_string();
+    assert!(!retried.contains(FAKE_SIGNATURE), "retry must drop stale thinking signatures");
+    assert!(retried.contains("Earlier reasoning"), "thinking content should be kept as text");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_claude_thinking_signature_passthrough() {
+    let proxy = TestProxy::start(1).await;
+    proxy.script(vec![FakeReply::thinking_then_text(
+        "Let me think",
+        FAKE_SIGNATURE,
+        "The answer is 4",
+    )]);
+
+    let mut request = claude_request(false);
+    request["model"] = json!("claude-sonnet-4-5-thinking");
+    request["max_tokens"] = json!(1024);
+    request["thinking"] = json!({ "type": "enabled", "budget_tokens": 512 });
+
+    let resp = proxy.post_json("/v1/messages", request).await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+
+    let thinking = body["content"]
+        .as_array()
+        .and_then(|blocks| blocks.iter().find(|b| b["type"] == "thinking"))
+        .cloned()
+        .expect("thinking block in response");
+    assert_eq!(thinking["thinking"], "Let me think");
+    assert_eq!(thinking["signature"], FAKE_SIGNATURE);
+    assert_eq!(claude_text(&body), "The answer is 4");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_openai_503_falls_back_to_next_endpoint() {
+    let proxy = TestProxy::start(1).await;
+    proxy.script(vec![
+        FakeReply::unavailable(),
+        FakeReply::text(&["Hello", " world"], 9, 7),
+    ]);
+
+    let resp = proxy
+        .post_json(
+            "/v1/chat/completions",
+            json!({
+                "model": "gemini-2.5-flash",
+                "messages": [{ "role": "user", "content": "Say hello" }]
+            }),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+
+    let reqs = proxy.upstream_requests();
+    assert_eq!(reqs.len(), 2);
+    assert_eq!(reqs[0].base, "/primary");
+    assert_eq!(reqs[1].base, "/fallback");
+    assert_eq!(reqs[0].access_token, reqs[1].access_token);
+
+    let content = body["choices"][0]["message"]["content"].as_str().unwrap_or("");
+    assert!(content.contains("Hello world"), "unexpected content: {}", body);
+    assert_eq!(body["usage"]["completion_tokens"], 7);
+
+    let logs = proxy.request_logs(1).await;
+    assert_eq!(logs[0].protocol.as_deref(), Some("openai"));
+    assert_eq!(logs[0].input_tokens, Some(9));
+    assert_eq!(logs[0].output_tokens, Some(7));
+    let stats = proxy.account_token_stats(&proxy.email_for_token(&reqs[0].access_token)).await;
+    assert_eq!(stats.total_input_tokens, 9);
+    assert_eq!(stats.total_output_tokens, 7);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_openai_model_fallback_on_each_attempt() {
+    // 假账号没有该模型的配额，只能沿降级链改用 gemini-3-flash (键名唯一，不影响其他测试)
+    const TARGET: &str = "gemini-e2e-fallback-target";
+    let mut chains = crate::proxy::config::get_model_fallbacks();
+    chains.insert(TARGET.to_string(), vec!["gemini-3-flash".to_string()]);
+    crate::proxy::update_model_fallbacks(chains);
+
+    let proxy = TestProxy::start(2).await;
+    // 第一个账号两个端点都 429，handler 换账号重试：重试时重新从目标模型开始降级
+    proxy.script(vec![
+        FakeReply::rate_limited("0.1s"),
+        FakeReply::rate_limited("0.1s"),
+        FakeReply::text(&["Served by fallback"], 5, 3),
+    ]);
+
+    let resp = proxy
+        .post_json(
+            "/v1/chat/completions",
+            json!({
+                "model": TARGET,
+                "messages": [{ "role": "user", "content": "Say hello" }]
+            }),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("X-Mapped-Model").and_then(|v| v.to_str().ok()),
+        Some("gemini-3-flash")
+    );
+    let body: Value = resp.json().await.unwrap();
+    let content = body["choices"][0]["message"]["content"].as_str().unwrap_or("");
+    assert!(content.contains("Served by fallback"), "unexpected content: {}", body);
+
+    let reqs = proxy.upstream_requests();
+    assert_eq!(reqs.len(), 3);
+    assert_ne!(reqs[1].access_token, reqs[2].access_token, "429 should rotate to another account");
+    assert!(
+        reqs.iter().all(|r| r.body["model"] == "gemini-3-flash"),
+        "every attempt should be served by the fallback model"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_gemini_generate_content_usage() {
+    let proxy = TestProxy::start(1).await;
+    proxy.script(vec![FakeReply::text(&["Native", " Gemini"], 11, 7)]);
+
+    let resp = proxy
+        .post_json(
+            "/v1beta/models/gemini-2.5-flash:generateContent",
+            json!({ "contents": [{ "role": "user", "parts": [{ "text": "Hi" }] }] }),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    // 兼容返回体是否带 response 包裹
+    let inner = body.get("response").cloned().unwrap_or(body);
+
+    let text: String = inner["candidates"][0]["content"]["parts"]
+        .as_array()
+        .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect())
+        .unwrap_or_default();
+    assert_eq!(text, "Native Gemini");
+    assert_eq!(inner["usageMetadata"]["candidatesTokenCount"], 7);
+
+    let reqs = proxy.upstream_requests();
+    assert_eq!(reqs.len(), 1);
+    assert!(reqs[0].body.get("request").is_some(), "v1internal body should wrap the request");
+
+    let logs = proxy.request_logs(1).await;
+    assert_eq!(logs[0].protocol.as_deref(), Some("gemini"));
+    assert_eq!(logs[0].input_tokens, Some(11));
+    assert_eq!(logs[0].output_tokens, Some(7));
+    let stats = proxy.account_token_stats(&proxy.email_for_token(&reqs[0].access_token)).await;
+    assert_eq!(stats.total_input_tokens, 11);
+    assert_eq!(stats.total_output_tokens, 7);
+}
@@ -0,0 +1,499 @@
+//! 端到端测试工具：假 v1internal 上游 + 真实 AxumServer
+//!
+//! - `FakeUpstream` 是进程级单例，运行在独立线程的 runtime 上，按脚本回放响应
+//!   (SSE 流、429 retryDelay、403 VALIDATION_REQUIRED、503 等)
+//! - 每个 `TestProxy` 只把自己的 UpstreamClient 指向假上游的两个基础路径
+//!   (`/primary/v1internal` → `/fallback/v1internal`)，不修改全局上游端点配置
+//! - 每个 `TestProxy` 拥有独立的场景 ID、临时数据目录与账号邮箱，账号 access_token 形如 `{scenario}:{n}`，
+//!   假上游据此把请求路由到对应场景的脚本队列，因此各测试可并行执行
+//! - 用量统计通过请求日志 (`ProxyRequestLog`) 与 token_stats 校验，而不只是响应体
+//! - 测试进程中 `get_data_dir()` 指向进程级临时目录，token_stats / proxy_db / security_db 不会触及
+//!   开发者的 `~/.antigravity_tools`；token_stats 按本场景独有的账号邮箱过滤
+//!
+//! 注意：脚本按到达顺序消费，不区分端点。429/503 会先触发端点降级 (primary → fallback)，
+//! 因此"账号级"的 429 需要连续写两条。
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde_json::{json, Value};
+
+use crate::modules::token_stats::AccountTokenStats;
+use crate::proxy::monitor::{ProxyMonitor, ProxyRequestLog};
+use crate::proxy::{AxumServer, ProxyAuthMode, ProxyConfig, ProxySecurityConfig, TokenManager};
+
+/// 假上游的一条脚本化响应
+#[derive(Debug, Clone)]
+pub enum FakeReply {
+    /// 成功响应：每个元素是一段 Gemini 响应 (candidates / usageMetadata)，
+    /// 流式请求逐条作为 SSE 事件发送，非流式请求合并为单个 JSON
+    Success(Vec<Value>),
+    /// 错误响应 (Google RPC 错误格式)
+    Error { status: u16, body: Value },
+}
+
+impl FakeReply {
+    /// 纯文本回复，最后一段携带 finishReason 与 usageMetadata
+    pub fn text(pieces: &[&str], prompt_tokens: u32, output_tokens: u32) -> Self {
+        let mut chunks: Vec<Value> = pieces.iter().map(|p| text_chunk(json!([{ "text": p }]))).collect();
+        if let Some(last) = chunks.last_mut() {
+            finish_chunk(last, prompt_tokens, output_tokens);
+        }
+        FakeReply::Success(chunks)
+    }
+
+    /// 先输出带签名的思维块，再输出正文
+    pub fn thinking_then_text(thought: &str, signature: &str, text: &str) -> Self {
+        let mut answer = text_chunk(json!([{ "text": text }]));
+        finish_chunk(&mut answer, 10, 20);
+        FakeReply::Success(vec![
+            text_chunk(json!([{ "text": thought, "thought": true, "thoughtSignature": signature }])),
+            answer,
+        ])
+    }
+
+    /// 429 RESOURCE_EXHAUSTED，带 RetryInfo.retryDelay (如 "0.2s")
+    pub fn rate_limited(retry_delay: &str) -> Self {
+        FakeReply::Error {
+            status: 429,
+            body: json!({
+                "error": {
+                    "code": 429,
+                    "message": "Resource has been exhausted (e.g. check quota).",
+                    "status": "RESOURCE_EXHAUSTED",
+                    "details": [{
+                        "@type": "type.googleapis.com/google.rpc.RetryInfo",
+                        "retryDelay": retry_delay
+                    }]
+                }
+            }),
+        }
+    }
+
+    /// 403 VALIDATION_REQUIRED (账号需人工验证)
+    pub fn validation_required() -> Self {
+        FakeReply::Error {
+            status: 403,
+            body: json!({
+                "error": {
+                    "code": 403,
+                    "message": "VALIDATION_REQUIRED: Please verify your account to continue.",
+                    "status": "PERMISSION_DENIED",
+                    "details": [{
+                        "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                        "reason": "VALIDATION_REQUIRED",
+                        "metadata": { "validation_url": "https://example.invalid/verify" }
+                    }]
+                }
+            }),
+        }
+    }
+
+    /// 503 UNAVAILABLE
+    pub fn unavailable() -> Self {
+        FakeReply::Error {
+            status: 503,
+            body: json!({
+                "error": { "code": 503, "message": "The service is currently unavailable.", "status": "UNAVAILABLE" }
+            }),
+        }
+    }
+
+    /// 400 思维签名无效
+    pub fn invalid_signature() -> Self {
+        FakeReply::Error {
+            status: 400,
+            body: json!({
+                "error": {
+                    "code": 400,
+                    "message": "messages.1.content.0: Invalid `signature` in `thinking` block",
+                    "status": "INVALID_ARGUMENT"
+                }
+            }),
+        }
+    }
+}
+
+fn text_chunk(parts: Value) -> Value {
+    json!({
+        "candidates": [{ "content": { "role": "model", "parts": parts }, "index": 0 }],
+        "modelVersion": "fake-upstream"
+    })
+}
+
+fn finish_chunk(chunk: &mut Value, prompt_tokens: u32, output_tokens: u32) {
+    chunk["candidates"][0]["finishReason"] = json!("STOP");
+    chunk["usageMetadata"] = json!({
+        "promptTokenCount": prompt_tokens,
+        "candidatesTokenCount": output_tokens,
+        "totalTokenCount": prompt_tokens + output_tokens
+    });
+}
+
+/// 非流式请求：合并所有分段的 parts，保留最后的 finishReason / usageMetadata
+fn merge_chunks(chunks: &[Value]) -> Value {
+    let mut parts: Vec<Value> = Vec::new();
+    let mut merged = chunks.last().cloned().unwrap_or_else(|| text_chunk(json!([])));
+    for chunk in chunks {
+        if let Some(p) = chunk.pointer("/candidates/0/content/parts").and_then(|p| p.as_array()) {
+            parts.extend(p.iter().cloned());
+        }
+    }
+    merged["candidates"][0]["content"]["parts"] = json!(parts);
+    merged
+}
+
+/// 假上游收到的一次请求
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub scenario: String,
+    pub access_token: String,
+    /// 端点基础路径，如 "/primary" 或 "/fallback"
+    pub base: String,
+    /// v1internal 方法，如 "streamGenerateContent"
+    pub method: String,
+    pub body: Value,
+}
+
+#[derive(Default)]
+struct FakeState {
+    scripts: Mutex<HashMap<String, VecDeque<FakeReply>>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+/// 进程级假 v1internal 上游
+pub struct FakeUpstream {
+    pub base_url: String,
+    state: Arc<FakeState>,
+}
+
+static FAKE_UPSTREAM: OnceLock<FakeUpstream> = OnceLock::new();
+
+impl FakeUpstream {
+    /// 获取 (首次调用时启动) 假上游
+    pub fn global() -> &'static FakeUpstream {
+        FAKE_UPSTREAM.get_or_init(|| {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind fake upstream");
+            listener.set_nonblocking(true).expect("set_nonblocking");
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let state = Arc::new(FakeState::default());
+
+            let app = Router::new().fallback(handle_fake_request).with_state(state.clone());
+            std::thread::Builder::new()
+                .name("fake-upstream".to_string())
+                .spawn(move || {
+                    let rt = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .expect("fake upstream runtime");
+                    rt.block_on(async move {
+                        let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                        axum::serve(listener, app).await.unwrap();
+                    });
+                })
+                .expect("spawn fake upstream");
+
+            FakeUpstream { base_url, state }
+        })
+    }
+
+    /// v1internal 端点降级顺序：primary → fallback
+    fn v1internal_base_urls(&self) -> Vec<String> {
+        vec![
+            format!("{}/primary/v1internal", self.base_url),
+            format!("{}/fallback/v1internal", self.base_url),
+        ]
+    }
+
+    fn push_script(&self, scenario: &str, replies: Vec<FakeReply>) {
+        let mut scripts = self.state.scripts.lock().unwrap();
+        scripts.entry(scenario.to_string()).or_default().extend(replies);
+    }
+
+    fn requests_for(&self, scenario: &str) -> Vec<RecordedRequest> {
+        self.state
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.scenario == scenario)
+            .cloned()
+            .collect()
+    }
+}
+
+async fn handle_fake_request(
+    State(state): State<Arc<FakeState>>,
+    uri: Uri,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    let path = uri.path();
+    let Some((base, method)) = path.split_once("/v1internal:") else {
+        return (StatusCode::NOT_FOUND, format!("fake upstream: unknown path {}", path)).into_response();
+    };
+
+    let access_token = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("")
+        .to_string();
+    let scenario = access_token.split(':').next().unwrap_or("").to_string();
+    let body_json: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    // 非生成类方法返回固定响应，不消耗脚本
+    match method {
+        "loadCodeAssist" => {
+            return axum::Json(json!({
+                "cloudaicompanionProject": "fake-project",
+                "currentTier": { "id": "free-tier", "name": "Free" }
+            }))
+            .into_response();
+        }
+        "fetchAvailableModels" => return axum::Json(json!({ "models": {} })).into_response(),
+        "countTokens" => return axum::Json(json!({ "totalTokens": 42 })).into_response(),
+        "generateContent" | "streamGenerateContent" => {}
+        _ => return (StatusCode::NOT_FOUND, format!("fake upstream: unknown method {}", method)).into_response(),
+    }
+
+    state.requests.lock().unwrap().push(RecordedRequest {
+        scenario: scenario.clone(),
+        access_token,
+        base: base.to_string(),
+        method: method.to_string(),
+        body: body_json,
+    });
+
+    let reply = state
+        .scripts
+        .lock()
+        .unwrap()
+        .get_mut(&scenario)
+        .and_then(|q| q.pop_front());
+
+    match reply {
+        Some(FakeReply::Success(chunks)) if method == "streamGenerateContent" => {
+            let mut sse = String::new();
+            for chunk in &chunks {
+                sse.push_str(&format!(
+                    "data: {}\n\n",
+                    json!({ "response": chunk, "traceId": "fake-trace" })
+                ));
+            }
+            Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/event-stream")
+                .body(Body::from(sse))
+                .unwrap()
+        }
+        Some(FakeReply::Success(chunks)) => {
+            axum::Json(json!({ "response": merge_chunks(&chunks), "traceId": "fake-trace" })).into_response()
+        }
+        Some(FakeReply::Error { status, body }) => (
+            StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            axum::Json(body),
+        )
+            .into_response(),
+        // 400 不会触发端点降级或账号轮换，便于测试快速失败
+        None => (
+            StatusCode::BAD_REQUEST,
+            axum::Json(json!({
+                "error": {
+                    "code": 400,
+                    "message": format!("fake upstream: no scripted reply for scenario {}", scenario),
+                    "status": "INVALID_ARGUMENT"
+                }
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// 连接假上游的完整反代实例
+pub struct TestProxy {
+    pub base_url: String,
+    pub scenario: String,
+    pub data_dir: PathBuf,
+    server: AxumServer,
+    monitor: Arc<ProxyMonitor>,
+}
+
+impl TestProxy {
+    /// 启动一个带 `accounts` 个假账号的反代实例 (鉴权关闭)
+    pub async fn start(accounts: usize) -> Self {
+        let upstream = FakeUpstream::global();
+        let scenario = format!("e2e-{}", uuid::Uuid::new_v4().simple());
+        let data_dir = std::env::temp_dir().join(format!("abv_{}", scenario));
+        let accounts_dir = data_dir.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        for i in 0..accounts {
+            let account = json!({
+                "id": format!("acc-{}", i),
+                "email": account_email(&scenario, i),
+                "name": format!("Fake {}", i),
+                "token": {
+                    "access_token": format!("{}:{}", scenario, i),
+                    "refresh_token": "fake-refresh-token",
+                    "expires_in": 3600,
+                    "expiry_timestamp": now + 86400,
+                    "token_type": "Bearer",
+                    "project_id": "fake-project"
+                },
+                // 调度只选择拥有目标模型配额的账号
+                "quota": {
+                    "models": [
+                        { "name": "claude-sonnet-4-6", "percentage": 100 },
+                        { "name": "gemini-3-flash", "percentage": 100 },
+                        { "name": "gemini-3-pro-high", "percentage": 100 },
+                        { "name": "gemini-3-pro-image", "percentage": 100 }
+                    ],
+                    "last_updated": now
+                },
+                "disabled": false,
+                "proxy_disabled": false,
+                "created_at": now,
+                "last_used": now
+            });
+            std::fs::write(
+                accounts_dir.join(format!("acc-{}.json", i)),
+                serde_json::to_string_pretty(&account).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let token_manager = std::sync::Arc::new(TokenManager::new(data_dir.clone()));
+        let loaded = token_manager.load_accounts().await.expect("load fake accounts");
+        assert_eq!(loaded, accounts, "all fake accounts should load");
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ProxyConfig::default();
+        config.port = port;
+        config.auth_mode = ProxyAuthMode::Off;
+
+        // 开启监控以便校验请求日志中的用量
+        let monitor = Arc::new(ProxyMonitor::new(100, None));
+        monitor.set_enabled(true);
+
+        let (server, _handle) = AxumServer::start(
+            "127.0.0.1".to_string(),
+            port,
+            token_manager,
+            config.custom_mapping.clone(),
+            config.request_timeout,
+            config.upstream_proxy.clone(),
+            None,
+            ProxySecurityConfig::from_proxy_config(&config),
+            config.zai.clone(),
+            monitor.clone(),
+            config.experimental.clone(),
+            config.debug_logging.clone(),
+            crate::modules::integration::SystemManager::Headless,
+            std::sync::Arc::new(crate::commands::cloudflared::CloudflaredState::new()),
+            config.proxy_pool.clone(),
+        )
+        .await
+        .expect("start test proxy");
+        server.override_v1internal_base_urls(upstream.v1internal_base_urls()).await;
+
+        Self {
+            base_url: format!("http://127.0.0.1:{}", port),
+            scenario,
+            data_dir,
+            server,
+            monitor,
+        }
+    }
+
+    /// 追加本场景的脚本化上游响应
+    pub fn script(&self, replies: Vec<FakeReply>) {
+        FakeUpstream::global().push_script(&self.scenario, replies);
+    }
+
+    /// 本场景中假上游收到的生成请求 (按到达顺序)
+    pub fn upstream_requests(&self) -> Vec<RecordedRequest> {
+        FakeUpstream::global().requests_for(&self.scenario)
+    }
+
+    /// 由 access_token 反查假账号邮箱
+    pub fn email_for_token(&self, access_token: &str) -> String {
+        let idx = access_token.rsplit(':').next().unwrap_or("");
+        account_email(&self.scenario, idx)
+    }
+
+    /// 等待监控记录到 `count` 条请求日志 (流式响应在流结束后才记录)，按时间先后返回
+    pub async fn request_logs(&self, count: usize) -> Vec<ProxyRequestLog> {
+        for _ in 0..50 {
+            let logs = self.monitor.logs.read().await;
+            if logs.len() >= count {
+                return logs.iter().rev().cloned().collect();
+            }
+            drop(logs);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("expected {} request log(s) for scenario {}", count, self.scenario);
+    }
+
+    /// 等待 token_stats 中出现该账号的用量 (异步写入)
+    pub async fn account_token_stats(&self, email: &str) -> AccountTokenStats {
+        for _ in 0..50 {
+            let email_owned = email.to_string();
+            let found = tokio::task::spawn_blocking(move || {
+                crate::modules::token_stats::get_account_stats(1)
+                    .ok()
+                    .and_then(|stats| stats.into_iter().find(|s| s.account_email == email_owned))
+            })
+            .await
+            .unwrap();
+            if let Some(stats) = found {
+                return stats;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("expected token stats for {}", email);
+    }
+
+    /// 读取磁盘上的账号文件
+    pub fn account_json(&self, access_token: &str) -> Value {
+        let idx = access_token.rsplit(':').next().unwrap_or("");
+        let path = self.data_dir.join("accounts").join(format!("acc-{}.json", idx));
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    pub async fn post_json(&self, path: &str, body: Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}{}", self.base_url, path))
+            .json(&body)
+            .send()
+            .await
+            .expect("request to test proxy")
+    }
+}
+
+/// 邮箱带场景 ID，保证 token_stats 中各测试的账号互不干扰
+fn account_email(scenario: &str, idx: impl std::fmt::Display) -> String {
+    format!("fake{}.{}@example.com", idx, scenario)
+}
+
+impl Drop for TestProxy {
+    fn drop(&mut self) {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            self.server.stop();
+        }
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
@@ -38,6 +38,38 @@ pub struct ProxyToken {
     pub model_limits: HashMap<String, u64>, // [NEW] max_output_tokens per model from quota data
 }
 
+/// 获取 Token 失败的类别 (供调用方决定是否降级/重试，避免匹配错误文本)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenErrorKind {
+    /// 账号池为空 (或全部账号被禁用/移除)
+    PoolEmpty,
+    /// 账号授权失效 (refresh_token 被撤销，invalid_grant)
+    AuthRevoked,
+    /// 账号暂不可用 (限流、配额保护、并发上限、超时等)
+    Unavailable,
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenError {
+    pub kind: TokenErrorKind,
+    pub message: String,
+}
+
+impl TokenError {
+    fn new(kind: TokenErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>, // account_id -> ProxyToken
     current_index: Arc<AtomicUsize>,
@@ -52,17 +84,18 @@ pub struct TokenManager {
     /// 支持优雅关闭时主动 abort 后台任务
     auto_cleanup_handle: Arc<tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
     cancel_token: CancellationToken,
+    in_flight: crate::proxy::concurrency::InFlightTracker, // [NEW] 每个账号 × 模型族的在途请求数
 }
 
 impl TokenManager {
-    /// 创建新的 TokenManager
+    /// 创建新的 TokenManager (限流状态仅保存在内存中)
     pub fn new(data_dir: PathBuf) -> Self {
         Self {
             tokens: Arc::new(DashMap::new()),
             current_index: Arc::new(AtomicUsize::new(0)),
             last_used_account: Arc::new(tokio::sync::Mutex::new(None)),
-            data_dir,
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
+            data_dir,
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
             session_accounts: Arc::new(DashMap::new()),
             preferred_account_id: Arc::new(tokio::sync::RwLock::new(None)), // [FIX #820]
@@ -72,9 +105,17 @@ impl TokenManager {
             )),
             auto_cleanup_handle: Arc::new(tokio::sync::Mutex::new(None)),
             cancel_token: CancellationToken::new(),
+            in_flight: crate::proxy::concurrency::InFlightTracker::default(),
         }
     }
 
+    /// [NEW] 启用限流状态持久化：从 `data_dir/rate_limits.db` 恢复锁定与退避计数，
+    /// 避免重启后再次撞上 QUOTA_EXHAUSTED。仅反代服务使用，需在加载账号前调用
+    pub fn with_rate_limit_persistence(mut self) -> Self {
+        self.rate_limit_tracker = Arc::new(RateLimitTracker::with_persistence(&self.data_dir));
+        self
+    }
+
     /// 启动限流记录自动清理后台任务（每15秒检查并清除过期记录）
     pub async fn start_auto_cleanup(&self) {
         let tracker = self.rate_limit_tracker.clone();
@@ -434,13 +475,14 @@ impl TokenManager {
         let token_obj = account["token"].as_object()
             .ok_or("缺少 token 字段")?;
 
-        let access_token = token_obj["access_token"].as_str()
-            .ok_or("缺少 access_token")?
-            .to_string();
+        // [NEW] token 落盘加密，兼容旧版明文
+        let access_token = crate::utils::crypto::decrypt_if_encrypted(
+            token_obj["access_token"].as_str().ok_or("缺少 access_token")?,
+        )?;
 
-        let refresh_token = token_obj["refresh_token"].as_str()
-            .ok_or("缺少 refresh_token")?
-            .to_string();
+        let refresh_token = crate::utils::crypto::decrypt_if_encrypted(
+            token_obj["refresh_token"].as_str().ok_or("缺少 refresh_token")?,
+        )?;
 
         let expires_in = token_obj["expires_in"].as_i64()
             .ok_or("缺少 expires_in")?;
@@ -858,7 +900,7 @@ impl TokenManager {
     const P2C_POOL_SIZE: usize = 5;
 
     /// Power of 2 Choices (P2C) 选择算法
-    /// 从前 5 个候选中随机选 2 个，选择配额更高的 -> 避免热点
+    /// 从前 5 个候选中随机选 2 个，选择在途负载更低、其次配额更高的 -> 避免热点
     /// 返回选中的索引
     ///
     /// # 参数
@@ -866,12 +908,14 @@ impl TokenManager {
     /// * `attempted` - 已尝试失败的账号 ID 集合
     /// * `normalized_target` - 归一化后的目标模型名
     /// * `quota_protection_enabled` - 是否启用配额保护
+    /// * `family` - 目标模型所属的并发模型族 (None 表示不限制并发)
     fn select_with_p2c<'a>(
         &self,
         candidates: &'a [ProxyToken],
         attempted: &HashSet<String>,
         normalized_target: &str,
         quota_protection_enabled: bool,
+        family: Option<&crate::proxy::concurrency::ModelFamily>,
     ) -> Option<&'a ProxyToken> {
         use rand::Rng;
 
@@ -879,6 +923,7 @@ impl TokenManager {
         let available: Vec<&ProxyToken> = candidates.iter()
             .filter(|t| !attempted.contains(&t.account_id))
             .filter(|t| !quota_protection_enabled || !t.protected_models.contains(normalized_target))
+            .filter(|t| !self.in_flight.is_saturated(&t.account_id, family))
             .collect();
 
         if available.is_empty() { return None; }
@@ -900,8 +945,12 @@ impl TokenManager {
         let c1 = available[pick1];
         let c2 = available[pick2];
 
-        // 选择配额更高的
-        let selected = if c1.remaining_quota.unwrap_or(0) >= c2.remaining_quota.unwrap_or(0) {
+        // [NEW] 优先选择在途负载更低的，负载相同时选择配额更高的
+        let load1 = self.in_flight.load(&c1.account_id, family);
+        let load2 = self.in_flight.load(&c2.account_id, family);
+        let selected = if load1 != load2 {
+            if load1 < load2 { c1 } else { c2 }
+        } else if c1.remaining_quota.unwrap_or(0) >= c2.remaining_quota.unwrap_or(0) {
             c1
         } else {
             c2
@@ -966,6 +1015,12 @@ impl TokenManager {
     /// 参数 `force_rotate` 为 true 时将忽略锁定，强制切换账号
     /// 参数 `session_id` 用于跨请求维持会话粘性
     /// 参数 `target_model` 用于检查配额保护 (Issue #621)
+    #[tracing::instrument(
+        target = "otel",
+        name = "token_manager.get_token",
+        skip(self, session_id),
+        fields(sticky = session_id.is_some())
+    )]
     pub async fn get_token(
         &self,
         quota_group: &str,
@@ -973,6 +1028,19 @@ impl TokenManager {
         session_id: Option<&str>,
         target_model: &str,
     ) -> Result<(String, String, String, String, u64), String> {
+        self.acquire_token(quota_group, force_rotate, session_id, target_model)
+            .await
+            .map_err(|e| e.message)
+    }
+
+    /// 与 `get_token` 相同，但失败时返回带类别的错误
+    pub async fn acquire_token(
+        &self,
+        quota_group: &str,
+        force_rotate: bool,
+        session_id: Option<&str>,
+        target_model: &str,
+    ) -> Result<(String, String, String, String, u64), TokenError> {
         // [FIX] 检查并处理待重新加载的账号（配额保护同步）
         let pending_reload = crate::proxy::server::take_pending_reload_accounts();
         for account_id in pending_reload {
@@ -1005,9 +1073,10 @@ impl TokenManager {
         .await
         {
             Ok(result) => result,
-            Err(_) => Err(
-                "Token acquisition timeout (5s) - system too busy or deadlock detected".to_string(),
-            ),
+            Err(_) => Err(TokenError::new(
+                TokenErrorKind::Unavailable,
+                "Token acquisition timeout (5s) - system too busy or deadlock detected",
+            )),
         }
     }
 
@@ -1018,12 +1087,12 @@ impl TokenManager {
         force_rotate: bool,
         session_id: Option<&str>,
         target_model: &str,
-    ) -> Result<(String, String, String, String, u64), String> {
+    ) -> Result<(String, String, String, String, u64), TokenError> {
         let mut tokens_snapshot: Vec<ProxyToken> =
             self.tokens.iter().map(|e| e.value().clone()).collect();
         let mut total = tokens_snapshot.len();
         if total == 0 {
-            return Err("Token pool is empty".to_string());
+            return Err(TokenError::new(TokenErrorKind::PoolEmpty, "Token pool is empty"));
         }
 
         // [NEW] 1. 动态能力过滤 (Capability Filter)
@@ -1047,9 +1116,12 @@ impl TokenManager {
             if candidate_count_before > 0 {
                 // 如果过滤前有账号，过滤后没了，说明所有账号都没有该模型的配额
                 tracing::warn!("No accounts have satisfied quota for model: {}", normalized_target);
-                return Err(format!("No accounts available with quota for model: {}", normalized_target));
+                return Err(TokenError::new(
+                    TokenErrorKind::Unavailable,
+                    format!("No accounts available with quota for model: {}", normalized_target),
+                ));
             }
-            return Err("Token pool is empty".to_string());
+            return Err(TokenError::new(TokenErrorKind::PoolEmpty, "Token pool is empty"));
         }
 
         tokens_snapshot.sort_by(|a, b| {
@@ -1119,6 +1191,12 @@ impl TokenManager {
             )).collect::<Vec<_>>()
         );
 
+        // [NEW] 目标模型所属的并发模型族 (未配置上限时为 None)
+        let family = crate::proxy::concurrency::resolve_family(
+            target_model,
+            &crate::proxy::config::get_account_concurrency(),
+        );
+
         // 0. 读取当前调度配置
         let scheduling = self.sticky_config.read().await.clone();
         use crate::proxy::sticky_config::SchedulingMode;
@@ -1156,7 +1234,7 @@ impl TokenManager {
                         }
 
                         if total == 0 {
-                            return Err("Token pool is empty".to_string());
+                            return Err(TokenError::new(TokenErrorKind::PoolEmpty, "Token pool is empty"));
                         }
                     }
                     OnDiskAccountState::Unknown => {
@@ -1168,7 +1246,7 @@ impl TokenManager {
                         tokens_snapshot.retain(|t| t.account_id != preferred_token.account_id);
                         total = tokens_snapshot.len();
                         if total == 0 {
-                            return Err("Token pool is empty".to_string());
+                            return Err(TokenError::new(TokenErrorKind::PoolEmpty, "Token pool is empty"));
                         }
                     }
                     OnDiskAccountState::Enabled => {
@@ -1186,7 +1264,14 @@ impl TokenManager {
                         .protected_models
                         .contains(&normalized_target);
 
-                if !is_rate_limited && !is_quota_protected {
+                // [NEW] 固定账号同样受并发上限约束，饱和时本次回退到轮询
+                let lease = if !is_rate_limited && !is_quota_protected {
+                    self.in_flight.try_acquire(&preferred_token.account_id, family.as_ref())
+                } else {
+                    None
+                };
+
+                if let Some(lease) = lease {
                     tracing::info!(
                         "🔒 [FIX #820] Using preferred account: {} (fixed mode)",
                         preferred_token.email
@@ -1246,12 +1331,15 @@ impl TokenManager {
                         }
                     };
 
+                    crate::proxy::concurrency::attach_to_current_request(lease);
                     return Ok((token.access_token, project_id, token.email, token.account_id, 0));
                 } else {
                     if is_rate_limited {
                         tracing::warn!("🔒 [FIX #820] Preferred account {} is rate-limited, falling back to round-robin", preferred_token.email);
-                    } else {
+                    } else if is_quota_protected {
                         tracing::warn!("🔒 [FIX #820] Preferred account {} is quota-protected for {}, falling back to round-robin", preferred_token.email, target_model);
+                    } else {
+                        tracing::debug!("🔒 [FIX #820] Preferred account {} is at its concurrency limit, falling back to round-robin", preferred_token.email);
                     }
                 }
                     }
@@ -1272,7 +1360,7 @@ impl TokenManager {
         };
 
         let mut attempted: HashSet<String> = HashSet::new();
-        let mut last_error: Option<String> = None;
+        let mut last_error: Option<TokenError> = None;
         let mut need_update_last_used: Option<(String, std::time::Instant)> = None;
 
         for attempt in 0..total {
@@ -1315,6 +1403,7 @@ impl TokenManager {
                         } else if !attempted.contains(&bound_id)
                             && !(quota_protection_enabled
                                 && bound_token.protected_models.contains(&normalized_target))
+                            && !self.in_flight.is_saturated(&bound_id, family.as_ref())
                         {
                             // 3. 账号可用且未被标记为尝试失败，优先复用
                             tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", bound_token.email, sid);
@@ -1356,6 +1445,7 @@ impl TokenManager {
                                 .await
                                 && !(quota_protection_enabled
                                     && found.protected_models.contains(&normalized_target))
+                                && !self.in_flight.is_saturated(&found.account_id, family.as_ref())
                             {
                                 tracing::debug!(
                                     "60s Window: Force reusing last account: {}",
@@ -1371,8 +1461,12 @@ impl TokenManager {
                                         "60s Window: Last account {} is rate-limited, skipping",
                                         found.email
                                     );
-                                } else {
+                                } else if quota_protection_enabled
+                                    && found.protected_models.contains(&normalized_target)
+                                {
                                     tracing::debug!("60s Window: Last account {} is quota-protected for model {} [{}], skipping", found.email, normalized_target, target_model);
+                                } else {
+                                    tracing::debug!("60s Window: Last account {} is at its concurrency limit, skipping", found.email);
                                 }
                             }
                         }
@@ -1390,16 +1484,18 @@ impl TokenManager {
                     }
 
                     if let Some(selected) = self.select_with_p2c(
-                        &non_limited, &attempted, &normalized_target, quota_protection_enabled
+                        &non_limited, &attempted, &normalized_target, quota_protection_enabled, family.as_ref()
                     ) {
                         target_token = Some(selected.clone());
                         need_update_last_used = Some((selected.account_id.clone(), std::time::Instant::now()));
 
                         // 如果是会话首次分配且需要粘性，在此建立绑定
+                        // [NEW] 绑定账号仅因并发饱和被跳过时保留原绑定，避免丢失缓存亲和性
                         if let Some(sid) = session_id {
                             if scheduling.mode != SchedulingMode::PerformanceFirst {
                                 self.session_accounts
-                                    .insert(sid.to_string(), selected.account_id.clone());
+                                    .entry(sid.to_string())
+                                    .or_insert_with(|| selected.account_id.clone());
                                 tracing::debug!(
                                     "Sticky Session: Bound new account {} to session {}",
                                     selected.email,
@@ -1425,7 +1521,7 @@ impl TokenManager {
                 }
 
                 if let Some(selected) = self.select_with_p2c(
-                    &non_limited, &attempted, &normalized_target, quota_protection_enabled
+                    &non_limited, &attempted, &normalized_target, quota_protection_enabled, family.as_ref()
                 ) {
                     tracing::debug!("  {} - SELECTED via P2C", selected.email);
                     target_token = Some(selected.clone());
@@ -1462,6 +1558,7 @@ impl TokenManager {
                             let retry_token = tokens_snapshot.iter()
                                 .find(|t| !attempted.contains(&t.account_id) 
                                     && !self.is_rate_limited_sync(&t.account_id, Some(&normalized_target))
+                                    && !self.in_flight.is_saturated(&t.account_id, family.as_ref())
                                     && !(quota_protection_enabled && t.protected_models.contains(&normalized_target)));
 
                             if let Some(t) = retry_token {
@@ -1484,6 +1581,7 @@ impl TokenManager {
                                 let final_token = tokens_snapshot
                                     .iter()
                                     .find(|t| !attempted.contains(&t.account_id)
+                                        && !self.in_flight.is_saturated(&t.account_id, family.as_ref())
                                         && !(quota_protection_enabled && t.protected_models.contains(&normalized_target)));
 
                                 if let Some(t) = final_token {
@@ -1493,20 +1591,49 @@ impl TokenManager {
                                     );
                                     t.clone()
                                 } else {
-                                    return Err(
-                                        "All accounts failed after optimistic reset.".to_string()
-                                    );
+                                    return Err(TokenError::new(
+                                        TokenErrorKind::Unavailable,
+                                        "All accounts failed after optimistic reset.",
+                                    ));
                                 }
                             }
                         } else {
-                            return Err(format!("All accounts limited. Wait {}s.", wait_sec));
+                            return Err(TokenError::new(
+                                TokenErrorKind::Unavailable,
+                                format!("All accounts limited. Wait {}s.", wait_sec),
+                            ));
                         }
+                    } else if let Some(f) = family.as_ref().filter(|f| {
+                        tokens_snapshot
+                            .iter()
+                            .any(|t| self.in_flight.is_saturated(&t.account_id, Some(f)))
+                    }) {
+                        return Err(TokenError::new(
+                            TokenErrorKind::Unavailable,
+                            format!(
+                                "All accounts busy: concurrency limit ({} per account for {}) reached.",
+                                f.limit, f.name
+                            ),
+                        ));
                     } else {
-                        return Err("All accounts failed or unhealthy.".to_string());
+                        return Err(TokenError::new(
+                            TokenErrorKind::Unavailable,
+                            "All accounts failed or unhealthy.",
+                        ));
                     }
                 }
             };
 
+            // [NEW] 占用该账号在模型族上的并发名额 (并发请求可能刚好占满，换下一个账号)
+            let Some(lease) = self.in_flight.try_acquire(&token.account_id, family.as_ref()) else {
+                tracing::debug!(
+                    "Account {} reached its concurrency limit concurrently, trying next",
+                    token.email
+                );
+                attempted.insert(token.account_id.clone());
+                continue;
+            };
+
             // Safety net: avoid selecting an account that has been disabled on disk but still
             // exists in the in-memory snapshot (e.g. stale cache + sticky session binding).
             match Self::get_account_state_on_disk(&token.account_path).await {
@@ -1562,7 +1689,8 @@ impl TokenManager {
                     }
                     Err(e) => {
                         tracing::error!("Token 刷新失败 ({}): {}，尝试下一个账号", token.email, e);
-                        if e.contains("\"invalid_grant\"") || e.contains("invalid_grant") {
+                        let revoked = e.contains("\"invalid_grant\"") || e.contains("invalid_grant");
+                        if revoked {
                             tracing::error!(
                                 "Disabling account due to invalid_grant ({}): refresh_token likely revoked/expired",
                                 token.email
@@ -1576,7 +1704,12 @@ impl TokenManager {
                             self.tokens.remove(&token.account_id);
                         }
                         // Avoid leaking account emails to API clients; details are still in logs.
-                        last_error = Some(format!("Token refresh failed: {}", e));
+                        let kind = if revoked {
+                            TokenErrorKind::AuthRevoked
+                        } else {
+                            TokenErrorKind::Unavailable
+                        };
+                        last_error = Some(TokenError::new(kind, format!("Token refresh failed: {}", e)));
                         attempted.insert(token.account_id.clone());
 
                         // 【优化】标记需要清除锁定，避免在循环内加锁
@@ -1635,10 +1768,13 @@ impl TokenManager {
                 }
             }
 
+            crate::proxy::concurrency::attach_to_current_request(lease);
             return Ok((token.access_token, project_id, token.email, token.account_id, 0));
         }
 
-        Err(last_error.unwrap_or_else(|| "All accounts failed".to_string()))
+        Err(last_error.unwrap_or_else(|| {
+            TokenError::new(TokenErrorKind::Unavailable, "All accounts failed")
+        }))
     }
 
     async fn disable_account(&self, account_id: &str, reason: &str) -> Result<(), String> {
@@ -1704,6 +1840,7 @@ impl TokenManager {
         let now = chrono::Utc::now().timestamp();
 
         content["token"]["access_token"] = serde_json::Value::String(token_response.access_token.clone());
+        crate::modules::account::encrypt_account_tokens(&mut content)?;
         content["token"]["expires_in"] = serde_json::Value::Number(token_response.expires_in.into());
         content["token"]["expiry_timestamp"] = serde_json::Value::Number((now + token_response.expires_in).into());
 
@@ -1852,6 +1989,72 @@ impl TokenManager {
         self.rate_limit_tracker.is_rate_limited(account_id, model)
     }
 
+    /// 当前在途请求 (email, 模型族, 数量)
+    pub fn in_flight_snapshot(&self) -> Vec<(String, String, usize)> {
+        self.in_flight
+            .snapshot()
+            .into_iter()
+            .map(|(account_id, family, count)| {
+                let email = self
+                    .tokens
+                    .get(&account_id)
+                    .map(|t| t.email.clone())
+                    .unwrap_or(account_id);
+                (email, family, count)
+            })
+            .collect()
+    }
+
+    /// 目标模型在所有账号上的最短限流等待 (秒)，存在未被锁定的账号时返回 0
+    ///
+    /// 供 Batch 后台任务在全部账号被锁定时暂停派发，避免把请求消耗在必然失败的重试上
+    pub async fn min_rate_limit_wait(&self, target_model: &str) -> u64 {
+        match self.pool_availability(target_model).await {
+            (0, wait) => wait,
+            _ => 0,
+        }
+    }
+
+    /// 目标模型的账号可用情况：(未被锁定且未达并发上限的账号数, 全部不可用时预计等待秒数)
+    ///
+    /// 仅统计拥有该模型配额的账号 (与 get_token 的候选过滤一致)；没有候选账号时返回 (0, 0)。
+    /// 仅因并发饱和而不可用时等待秒数为 1 (名额释放时准入队列会被提前唤醒)
+    pub async fn pool_availability(&self, target_model: &str) -> (usize, u64) {
+        let normalized = crate::proxy::common::model_mapping::normalize_to_standard_id(target_model)
+            .unwrap_or_else(|| target_model.to_string());
+        let breaker_enabled = self.circuit_breaker_config.read().await.enabled;
+        let family = crate::proxy::concurrency::resolve_family(
+            target_model,
+            &crate::proxy::config::get_account_concurrency(),
+        );
+
+        let mut free = 0;
+        let mut min_wait: Option<u64> = None;
+        for entry in self.tokens.iter() {
+            let token = entry.value();
+            if !token.model_quotas.contains_key(&normalized) {
+                continue;
+            }
+            let wait = if breaker_enabled {
+                self.rate_limit_tracker
+                    .get_remaining_wait(&token.account_id, Some(&normalized))
+            } else {
+                0
+            };
+            let wait = if wait == 0 && self.in_flight.is_saturated(&token.account_id, family.as_ref()) {
+                1
+            } else {
+                wait
+            };
+            if wait == 0 {
+                free += 1;
+            } else {
+                min_wait = Some(min_wait.map_or(wait, |m| m.min(wait)));
+            }
+        }
+        (free, if free > 0 { 0 } else { min_wait.unwrap_or(0) })
+    }
+
     /// 获取距离限流重置还有多少秒
     #[allow(dead_code)]
     pub fn get_rate_limit_reset_seconds(&self, account_id: &str) -> Option<u64> {
@@ -2403,6 +2606,11 @@ impl TokenManager {
         all_models
     }
 
+    /// 是否有账号的动态额度数据包含该模型
+    pub fn has_collected_model(&self, model_id: &str) -> bool {
+        self.tokens.iter().any(|entry| entry.value().model_quotas.contains_key(model_id))
+    }
+
     /// [NEW] 从指定账号的动态额度数据中获取特定模型的 max_output_tokens
     ///
     /// # 返回
@@ -3045,7 +3253,7 @@ mod tests {
 
         // 运行多次确保选择高配额账号
         for _ in 0..10 {
-            let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false);
+            let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false, None);
             assert!(result.is_some());
             // P2C 从两个候选中选择配额更高的
             // 由于只有两个候选，应该总是选择 high_quota
@@ -3065,7 +3273,7 @@ mod tests {
         let mut attempted: HashSet<String> = HashSet::new();
         attempted.insert("a@test.com".to_string());
 
-        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false);
+        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false, None);
         assert!(result.is_some());
         assert_eq!(result.unwrap().email, "b@test.com");
     }
@@ -3084,7 +3292,7 @@ mod tests {
         let candidates = vec![protected_account, normal_account];
         let attempted: HashSet<String> = HashSet::new();
 
-        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", true);
+        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", true, None);
         assert!(result.is_some());
         assert_eq!(result.unwrap().email, "normal@test.com");
     }
@@ -3098,7 +3306,7 @@ mod tests {
         let candidates = vec![token];
         let attempted: HashSet<String> = HashSet::new();
 
-        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false);
+        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false, None);
         assert!(result.is_some());
         assert_eq!(result.unwrap().email, "single@test.com");
     }
@@ -3111,7 +3319,7 @@ mod tests {
         let candidates: Vec<ProxyToken> = vec![];
         let attempted: HashSet<String> = HashSet::new();
 
-        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false);
+        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false, None);
         assert!(result.is_none());
     }
 
@@ -3128,7 +3336,7 @@ mod tests {
         attempted.insert("a@test.com".to_string());
         attempted.insert("b@test.com".to_string());
 
-        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false);
+        let result = manager.select_with_p2c(&candidates, &attempted, "claude-sonnet", false, None);
         assert!(result.is_none());
     }
 
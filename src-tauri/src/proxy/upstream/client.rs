@@ -7,6 +7,7 @@ use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::time::Duration;
+use tracing::Instrument;
 
 /// 端点降级尝试的记录信息
 #[derive(Debug, Clone)]
@@ -43,24 +44,13 @@ pub fn mask_email(email: &str) -> String {
     }
 }
 
-// Cloud Code v1internal endpoints (fallback order: Sandbox → Daily → Prod)
-// 优先使用 Sandbox/Daily 环境以避免 Prod环境的 429 错误 (Ref: Issue #1176)
-const V1_INTERNAL_BASE_URL_PROD: &str = "https://cloudcode-pa.googleapis.com/v1internal";
-const V1_INTERNAL_BASE_URL_DAILY: &str = "https://daily-cloudcode-pa.googleapis.com/v1internal";
-const V1_INTERNAL_BASE_URL_SANDBOX: &str =
-    "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal";
-
-const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 3] = [
-    V1_INTERNAL_BASE_URL_SANDBOX, // 优先级 1: Sandbox (已知有效且稳定)
-    V1_INTERNAL_BASE_URL_DAILY,   // 优先级 2: Daily (备用)
-    V1_INTERNAL_BASE_URL_PROD,    // 优先级 3: Prod (仅作为兜底)
-];
-
 pub struct UpstreamClient {
     default_client: Client,
     proxy_pool: Option<Arc<crate::proxy::proxy_pool::ProxyPoolManager>>,
     client_cache: DashMap<String, Client>, // proxy_id -> Client
     user_agent_override: RwLock<Option<String>>,
+    /// 实例级 v1internal 端点 (None 时使用全局 upstream_endpoints 配置)，供端到端测试隔离假上游
+    v1internal_base_urls_override: RwLock<Option<Vec<String>>>,
 }
 
 impl UpstreamClient {
@@ -93,6 +83,7 @@ impl UpstreamClient {
             proxy_pool,
             client_cache: DashMap::new(),
             user_agent_override: RwLock::new(None),
+            v1internal_base_urls_override: RwLock::new(None),
         }
     }
 
@@ -162,6 +153,12 @@ impl UpstreamClient {
         tracing::debug!("UpstreamClient User-Agent override updated: {:?}", lock);
     }
 
+    /// 仅对当前实例覆盖 v1internal 端点，不修改全局配置
+    #[cfg(test)]
+    pub async fn set_v1internal_base_urls_override(&self, base_urls: Option<Vec<String>>) {
+        *self.v1internal_base_urls_override.write().await = base_urls;
+    }
+
     /// Get current User-Agent
     pub async fn get_user_agent(&self) -> String {
         let ua_override = self.user_agent_override.read().await;
@@ -332,18 +329,41 @@ impl UpstreamClient {
         // [NEW] 收集降级尝试记录
         let mut fallback_attempts: Vec<FallbackAttemptLog> = Vec::new();
 
+        // 端点降级顺序来自 ProxyConfig.upstream_endpoints (可被环境变量覆盖)
+        let base_urls = match self.v1internal_base_urls_override.read().await.clone() {
+            Some(urls) => urls,
+            None => crate::proxy::config::get_upstream_endpoints().v1internal_base_urls,
+        };
+
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in base_urls.iter().enumerate() {
             let url = Self::build_url(base_url, method, query_string);
-            let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
-
+            let has_next = idx + 1 < base_urls.len();
+
+            // 每个端点尝试对应一个链路追踪 span (含降级)
+            let attempt_span = tracing::info_span!(
+                target: crate::proxy::telemetry::SPAN_TARGET,
+                "upstream.attempt",
+                upstream.endpoint = %base_url,
+                upstream.method = %method,
+                upstream.fallback_index = idx,
+                http.status_code = tracing::field::Empty,
+                error = tracing::field::Empty,
+            );
             let response = client
                 .post(&url)
                 .headers(headers.clone())
                 .json(&body)
                 .send()
+                .instrument(attempt_span.clone())
                 .await;
 
+            match &response {
+                Ok(resp) => attempt_span.record("http.status_code", resp.status().as_u16()),
+                Err(e) => attempt_span.record("error", tracing::field::display(e)),
+            };
+            drop(attempt_span);
+
             match response {
                 Ok(resp) => {
                     let status = resp.status();
@@ -353,7 +373,7 @@ impl UpstreamClient {
                                 "✓ Upstream fallback succeeded | Endpoint: {} | Status: {} | Next endpoints available: {}",
                                 base_url,
                                 status,
-                                V1_INTERNAL_BASE_URL_FALLBACKS.len() - idx - 1
+                                base_urls.len() - idx - 1
                             );
                         } else {
                             tracing::debug!(
@@ -1,13 +1,65 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Nonce,
 };
 use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Deserializer, Serializer};
 use sha2::Digest;
+use std::sync::Mutex;
 
+/// [DEPRECATED] v1 固定 nonce，仅用于解密旧版密文，新数据一律使用随机 nonce
 const FIXED_NONCE: &[u8; 12] = b"antigravsalt";
 const ENCRYPTED_PREFIX: &str = "ag_enc_";
+/// v2 格式: `ag_enc_v2:<key_id>:<base64(nonce || ciphertext)>`
+const ENCRYPTED_PREFIX_V2: &str = "ag_enc_v2:";
+const NONCE_LEN: usize = 12;
+
+/// 用户口令环境变量 (设置后新数据使用口令派生的密钥加密)
+const PASSPHRASE_ENV: &str = "ABV_ENCRYPTION_PASSPHRASE";
+const PASSPHRASE_SALT: &[u8] = b"antigravity-manager/token-encryption/v2";
+const PASSPHRASE_ROUNDS: u32 = 100_000;
+
+/// 密钥来源，ID 写入密文头部，解密时据此选择密钥
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeySource {
+    /// 设备 ID 派生 (默认)
+    Machine,
+    /// 用户口令 (PBKDF2-SHA256)
+    Passphrase,
+    /// 系统密钥环中保存的随机密钥 (需启用 `os-keyring` feature)
+    Keyring,
+}
+
+impl KeySource {
+    fn id(self) -> &'static str {
+        match self {
+            KeySource::Machine => "m",
+            KeySource::Passphrase => "p",
+            KeySource::Keyring => "k",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "m" => Some(KeySource::Machine),
+            "p" => Some(KeySource::Passphrase),
+            "k" => Some(KeySource::Keyring),
+            _ => None,
+        }
+    }
+
+    fn key(self) -> Result<[u8; 32], String> {
+        match self {
+            KeySource::Machine => Ok(get_encryption_key()),
+            KeySource::Passphrase => {
+                let passphrase = configured_passphrase()
+                    .ok_or_else(|| format!("{} is not set", PASSPHRASE_ENV))?;
+                Ok(derive_passphrase_key(&passphrase))
+            }
+            KeySource::Keyring => keyring_key(),
+        }
+    }
+}
 
 /// 生成加密密钥 (基于设备 ID)
 fn get_encryption_key() -> [u8; 32] {
@@ -19,6 +71,88 @@ fn get_encryption_key() -> [u8; 32] {
     key
 }
 
+fn configured_passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV).ok().filter(|s| !s.is_empty())
+}
+
+/// PBKDF2 较慢，按口令缓存派生结果
+static PASSPHRASE_KEY_CACHE: Mutex<Option<(String, [u8; 32])>> = Mutex::new(None);
+
+fn derive_passphrase_key(passphrase: &str) -> [u8; 32] {
+    let mut cache = PASSPHRASE_KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached, key)) = cache.as_ref() {
+        if cached == passphrase {
+            return *key;
+        }
+    }
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), PASSPHRASE_SALT, PASSPHRASE_ROUNDS, &mut key);
+    *cache = Some((passphrase.to_string(), key));
+    key
+}
+
+#[cfg(feature = "os-keyring")]
+fn keyring_key() -> Result<[u8; 32], String> {
+    static KEYRING_KEY: std::sync::OnceLock<[u8; 32]> = std::sync::OnceLock::new();
+    if let Some(key) = KEYRING_KEY.get() {
+        return Ok(*key);
+    }
+
+    let entry = keyring::Entry::new("antigravity_tools", "token-encryption-key")
+        .map_err(|e| format!("Keyring unavailable: {}", e))?;
+    let encoded = match entry.get_password() {
+        Ok(v) => v,
+        Err(keyring::Error::NoEntry) => {
+            // 首次使用：生成随机密钥并写入密钥环
+            let key = Aes256Gcm::generate_key(&mut OsRng);
+            let encoded = general_purpose::STANDARD.encode(key);
+            entry
+                .set_password(&encoded)
+                .map_err(|e| format!("Failed to store key in keyring: {}", e))?;
+            encoded
+        }
+        Err(e) => return Err(format!("Keyring read failed: {}", e)),
+    };
+
+    let bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid keyring key: {}", e))?;
+    let key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "Invalid keyring key length".to_string())?;
+    Ok(*KEYRING_KEY.get_or_init(|| key))
+}
+
+#[cfg(not(feature = "os-keyring"))]
+fn keyring_key() -> Result<[u8; 32], String> {
+    Err("Built without os-keyring support".to_string())
+}
+
+/// 新数据使用的密钥来源：用户口令 > 系统密钥环 > 设备 ID
+fn active_key_source() -> KeySource {
+    if configured_passphrase().is_some() {
+        return KeySource::Passphrase;
+    }
+    if cfg!(feature = "os-keyring") && keyring_key().is_ok() {
+        return KeySource::Keyring;
+    }
+    KeySource::Machine
+}
+
+/// 是否为本模块生成的密文 (v1 或 v2)
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// 带前缀的密文解密，明文原样返回 (用于兼容尚未迁移的账号文件)
+pub fn decrypt_if_encrypted(value: &str) -> Result<String, String> {
+    if is_encrypted(value) {
+        decrypt_string(value)
+    } else {
+        Ok(value.to_string())
+    }
+}
+
 pub fn serialize_password<S>(password: &str, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -41,6 +175,19 @@ where
         return Ok(raw);
     }
 
+    // [NEW] v2 格式 (随机 nonce)
+    if let Some(body) = raw.strip_prefix(ENCRYPTED_PREFIX_V2) {
+        return match decrypt_v2(body) {
+            Ok(plaintext) => Ok(plaintext),
+            Err(e) => {
+                // 解密失败 (口令/密钥环不可用) 时与 v1 一致保留原始密文，避免保存配置时丢失；
+                // 使用方需通过 is_encrypted 判断，不能把密文当作密码发给上游代理
+                tracing::warn!("Failed to decrypt stored password, keeping ciphertext: {}", e);
+                Ok(raw)
+            }
+        };
+    }
+
     // [FIX #1738] 检查魔术前缀
     if raw.starts_with(ENCRYPTED_PREFIX) {
         // 新版格式：去前缀后解密
@@ -69,21 +216,51 @@ where
     }
 }
 
+/// 加密字符串 (v2 格式：每个值使用随机 nonce，并记录密钥来源)
 pub fn encrypt_string(password: &str) -> Result<String, String> {
-    let key = get_encryption_key();
+    let source = active_key_source();
+    let key = source.key()?;
     let cipher = Aes256Gcm::new(&key.into());
-    // In production, we should use a random nonce and prepend it to the ciphertext
-    // For simplicity in this demo, we use a fixed nonce (NOT SECURE for repeats)
-    // improving security: use random nonce
-    let nonce = Nonce::from_slice(FIXED_NONCE);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
 
     let ciphertext = cipher
-        .encrypt(nonce, password.as_bytes())
+        .encrypt(&nonce, password.as_bytes())
         .map_err(|e| format!("Encryption failed: {}", e))?;
 
-    let base64_ciphertext = general_purpose::STANDARD.encode(ciphertext);
-    // [FIX #1738] 添加魔术前缀
-    Ok(format!("{}{}", ENCRYPTED_PREFIX, base64_ciphertext))
+    let mut payload = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+
+    Ok(format!(
+        "{}{}:{}",
+        ENCRYPTED_PREFIX_V2,
+        source.id(),
+        general_purpose::STANDARD.encode(payload)
+    ))
+}
+
+/// 解密 v2 密文 (输入不含 `ag_enc_v2:` 前缀)
+fn decrypt_v2(body: &str) -> Result<String, String> {
+    let (key_id, encoded) = body
+        .split_once(':')
+        .ok_or("Malformed v2 ciphertext")?;
+    let source = KeySource::from_id(key_id).ok_or_else(|| format!("Unknown key id: {}", key_id))?;
+    let key = source.key()?;
+    let cipher = Aes256Gcm::new(&key.into());
+
+    let payload = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Base64 decode failed: {}", e))?;
+    if payload.len() <= NONCE_LEN {
+        return Err("Ciphertext too short".to_string());
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|e| format!("Decryption failed: {}", e))?;
+
+    String::from_utf8(plaintext).map_err(|e| format!("UTF-8 conversion failed: {}", e))
 }
 
 /// 内部解密函数 (输入必须是纯 Base64 密文，不含前缀)
@@ -104,7 +281,9 @@ fn decrypt_string_internal(encrypted_base64: &str) -> Result<String, String> {
 }
 
 pub fn decrypt_string(encrypted: &str) -> Result<String, String> {
-    if encrypted.starts_with(ENCRYPTED_PREFIX) {
+    if let Some(body) = encrypted.strip_prefix(ENCRYPTED_PREFIX_V2) {
+        decrypt_v2(body)
+    } else if encrypted.starts_with(ENCRYPTED_PREFIX) {
         decrypt_string_internal(&encrypted[ENCRYPTED_PREFIX.len()..])
     } else {
         decrypt_string_internal(encrypted)
@@ -127,6 +306,55 @@ mod tests {
         assert_eq!(password, decrypted);
     }
 
+    #[test]
+    fn test_v2_uses_random_nonce() {
+        let a = encrypt_string("same_value").unwrap();
+        let b = encrypt_string("same_value").unwrap();
+
+        assert!(a.starts_with(ENCRYPTED_PREFIX_V2));
+        assert_ne!(a, b, "identical plaintexts must not produce identical ciphertexts");
+        assert_eq!(decrypt_string(&a).unwrap(), "same_value");
+        assert_eq!(decrypt_string(&b).unwrap(), "same_value");
+    }
+
+    #[test]
+    fn test_v1_prefixed_and_tampered_values() {
+        // v1 带前缀格式仍可解密
+        let key = get_encryption_key();
+        let cipher = Aes256Gcm::new(&key.into());
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(FIXED_NONCE), b"v1_password".as_ref())
+            .unwrap();
+        let v1 = format!("{}{}", ENCRYPTED_PREFIX, general_purpose::STANDARD.encode(ciphertext));
+        assert_eq!(decrypt_string(&v1).unwrap(), "v1_password");
+
+        // 篡改后的 v2 密文必须解密失败
+        let mut tampered = encrypt_string("secret").unwrap();
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == 'A' { 'B' } else { 'A' });
+        assert!(decrypt_string(&tampered).is_err());
+        assert!(decrypt_string("ag_enc_v2:z:AAAA").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_password_keeps_undecryptable_v2() {
+        #[derive(Deserialize)]
+        struct Auth {
+            #[serde(deserialize_with = "deserialize_password")]
+            password: String,
+        }
+
+        let sealed = encrypt_string("proxy_pw").unwrap();
+        let ok: Auth = serde_json::from_value(serde_json::json!({"password": sealed})).unwrap();
+        assert_eq!(ok.password, "proxy_pw");
+
+        // 解密失败不应让整个配置加载失败，原值保留以便原样写回
+        let kept: Auth =
+            serde_json::from_value(serde_json::json!({"password": "ag_enc_v2:z:AAAA"})).unwrap();
+        assert_eq!(kept.password, "ag_enc_v2:z:AAAA");
+        assert!(is_encrypted(&kept.password));
+    }
+
     #[test]
     fn test_legacy_compatibility() {
         // 模拟旧版加密（手动调用内部逻辑生成无前缀密文）
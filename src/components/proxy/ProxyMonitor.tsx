@@ -28,6 +28,11 @@ interface ProxyRequestLog {
     output_tokens?: number;
     account_email?: string;
     protocol?: string;  // "openai" | "anthropic" | "gemini"
+    ttft_ms?: number;   // time to first token (streaming only)
+    cache_read_tokens?: number;      // subset of input_tokens
+    cache_creation_tokens?: number;  // subset of input_tokens
+    reasoning_tokens?: number;       // subset of output_tokens
+    trace_id?: string;               // matches debug log filenames / OTLP trace id
 }
 
 interface ProxyStats {
@@ -584,6 +589,7 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
                                 <span className={`badge badge-sm text-white border-none ${selectedLog.status >= 200 && selectedLog.status < 400 ? 'badge-success' : 'badge-error'}`}>{selectedLog.status}</span>
                                 <span className="font-mono font-bold text-gray-900 dark:text-base-content text-sm">{selectedLog.method}</span>
                                 <span className="text-xs text-gray-500 dark:text-gray-400 font-mono truncate max-w-md hidden sm:inline">{selectedLog.url}</span>
+                                {selectedLog.trace_id && <span className="text-[10px] text-gray-400 dark:text-gray-500 font-mono hidden md:inline select-all" title="Trace ID">{selectedLog.trace_id}</span>}
                             </div>
                             <button onClick={() => setSelectedLog(null)} className="btn btn-ghost btn-sm btn-circle text-gray-500 dark:text-gray-400 hover:dark:bg-base-300"><X size={18} /></button>
                         </div>
@@ -599,13 +605,15 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
                                     </div>
                                     <div className="space-y-1.5">
                                         <span className="block text-gray-500 dark:text-gray-400 uppercase font-black text-[10px] tracking-widest">{t('monitor.details.duration')}</span>
-                                        <span className="font-mono font-semibold text-gray-900 dark:text-base-content text-xs">{selectedLog.duration}ms</span>
+                                        <span className="font-mono font-semibold text-gray-900 dark:text-base-content text-xs">{selectedLog.duration}ms{selectedLog.ttft_ms != null && ` (TTFT ${selectedLog.ttft_ms}ms)`}</span>
                                     </div>
                                     <div className="space-y-1.5">
                                         <span className="block text-gray-500 dark:text-gray-400 uppercase font-black text-[10px] tracking-widest">{t('monitor.details.tokens')}</span>
                                         <div className="font-mono text-[11px] flex gap-2">
                                             <span className="text-blue-700 dark:text-blue-300 bg-blue-100 dark:bg-blue-900/40 px-2.5 py-1 rounded-md border border-blue-200 dark:border-blue-800/50 font-bold">In: {formatCompactNumber(selectedLog.input_tokens ?? 0)}</span>
                                             <span className="text-green-700 dark:text-green-300 bg-green-100 dark:bg-green-900/40 px-2.5 py-1 rounded-md border border-green-200 dark:border-green-800/50 font-bold">Out: {formatCompactNumber(selectedLog.output_tokens ?? 0)}</span>
+                                            {!!selectedLog.cache_read_tokens && <span className="text-sky-700 dark:text-sky-300 bg-sky-100 dark:bg-sky-900/40 px-2.5 py-1 rounded-md border border-sky-200 dark:border-sky-800/50 font-bold">Cache: {formatCompactNumber(selectedLog.cache_read_tokens)}</span>}
+                                            {!!selectedLog.reasoning_tokens && <span className="text-purple-700 dark:text-purple-300 bg-purple-100 dark:bg-purple-900/40 px-2.5 py-1 rounded-md border border-purple-200 dark:border-purple-800/50 font-bold">Think: {formatCompactNumber(selectedLog.reasoning_tokens)}</span>}
                                         </div>
                                     </div>
                                 </div>
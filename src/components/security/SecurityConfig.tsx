@@ -14,9 +14,22 @@ interface IpWhitelistConfig {
     whitelist_priority: boolean;
 }
 
+interface AutoBanConfig {
+    enabled: boolean;
+    ban_duration_minutes: number;
+    auth_failure_threshold: number;
+    auth_failure_window_secs: number;
+    client_error_threshold: number;
+    client_error_window_secs: number;
+    max_requests_per_minute: number;
+    exempt_loopback: boolean;
+}
+
 interface SecurityMonitorConfig {
     blacklist: IpBlacklistConfig;
     whitelist: IpWhitelistConfig;
+    trusted_proxies?: string[];
+    auto_ban?: AutoBanConfig;
 }
 
 export const SecurityConfig: React.FC = () => {
@@ -9,6 +9,10 @@ interface TokenStatsAggregated {
     total_input_tokens: number;
     total_output_tokens: number;
     total_tokens: number;
+    total_cache_read_tokens?: number;
+    total_cache_creation_tokens?: number;
+    total_reasoning_tokens?: number;
+    estimated_cost?: number;
     request_count: number;
 }
 
@@ -17,6 +21,10 @@ interface AccountTokenStats {
     total_input_tokens: number;
     total_output_tokens: number;
     total_tokens: number;
+    total_cache_read_tokens?: number;
+    total_cache_creation_tokens?: number;
+    total_reasoning_tokens?: number;
+    estimated_cost?: number;
     request_count: number;
 }
 
@@ -25,6 +33,10 @@ interface ModelTokenStats {
     total_input_tokens: number;
     total_output_tokens: number;
     total_tokens: number;
+    total_cache_read_tokens?: number;
+    total_cache_creation_tokens?: number;
+    total_reasoning_tokens?: number;
+    estimated_cost?: number;
     request_count: number;
 }
 
@@ -42,6 +54,10 @@ interface TokenStatsSummary {
     total_input_tokens: number;
     total_output_tokens: number;
     total_tokens: number;
+    total_cache_read_tokens?: number;
+    total_cache_creation_tokens?: number;
+    total_reasoning_tokens?: number;
+    estimated_cost?: number;
     total_requests: number;
     unique_accounts: number;
 }
@@ -371,6 +387,11 @@ const TokenStats: React.FC = () => {
                             <div className="text-2xl font-bold text-gray-800 dark:text-white">
                                 {formatNumber(summary.total_tokens)}
                             </div>
+                            {!!summary.estimated_cost && (
+                                <div className="text-xs text-gray-400 mt-1">
+                                    {t('token_stats.estimated_cost', '预估成本')} ≈ ${summary.estimated_cost.toFixed(2)}
+                                </div>
+                            )}
                         </div>
                         <div className="bg-gradient-to-br from-blue-50/50 to-white dark:from-blue-900/10 dark:to-gray-800 rounded-xl p-4 shadow-sm border border-blue-100 dark:border-blue-900/30 hover:shadow-md transition-shadow">
                             <div className="flex items-center gap-2 text-blue-600/80 dark:text-blue-400/80 text-sm mb-2">
@@ -382,6 +403,11 @@ const TokenStats: React.FC = () => {
                             <div className="text-2xl font-bold text-blue-600 dark:text-blue-400">
                                 {formatNumber(summary.total_input_tokens)}
                             </div>
+                            {!!summary.total_cache_read_tokens && (
+                                <div className="text-xs text-blue-400 mt-1">
+                                    {t('token_stats.cache_read_tokens', '缓存命中')} {formatNumber(summary.total_cache_read_tokens)}
+                                </div>
+                            )}
                         </div>
                         <div className="bg-gradient-to-br from-purple-50/50 to-white dark:from-purple-900/10 dark:to-gray-800 rounded-xl p-4 shadow-sm border border-purple-100 dark:border-purple-900/30 hover:shadow-md transition-shadow">
                             <div className="flex items-center gap-2 text-purple-600/80 dark:text-purple-400/80 text-sm mb-2">
@@ -393,6 +419,11 @@ const TokenStats: React.FC = () => {
                             <div className="text-2xl font-bold text-purple-600 dark:text-purple-400">
                                 {formatNumber(summary.total_output_tokens)}
                             </div>
+                            {!!summary.total_reasoning_tokens && (
+                                <div className="text-xs text-purple-400 mt-1">
+                                    {t('token_stats.reasoning_tokens', '思考')} {formatNumber(summary.total_reasoning_tokens)}
+                                </div>
+                            )}
                         </div>
                         <div className="bg-gradient-to-br from-green-50/50 to-white dark:from-green-900/10 dark:to-gray-800 rounded-xl p-4 shadow-sm border border-green-100 dark:border-green-900/30 hover:shadow-md transition-shadow">
                             <div className="flex items-center gap-2 text-green-600/80 dark:text-green-400/80 text-sm mb-2">
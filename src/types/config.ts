@@ -107,6 +107,19 @@ export interface QuotaProtectionConfig {
     monitored_models: string[];
 }
 
+export interface AutoSwitchConfig {
+    enabled: boolean;
+    threshold_percentage: number; // 0-99
+    monitored_models: string[];
+    quiet_hours_enabled: boolean;
+    quiet_hours_start: string; // "HH:MM"
+    quiet_hours_end: string; // "HH:MM"
+}
+
+export interface AuditLogConfig {
+    retention_days: number; // 最少保留 7 天
+}
+
 export interface PinnedQuotaModelsConfig {
     models: string[];
 }
@@ -140,10 +153,12 @@ export interface AppConfig {
     hidden_menu_items?: string[]; // 隐藏的菜单项路径列表
     scheduled_warmup: ScheduledWarmupConfig;
     quota_protection: QuotaProtectionConfig; // [NEW] 配额保护配置
+    auto_switch?: AutoSwitchConfig; // [NEW] 自动切换账号配置
     pinned_quota_models: PinnedQuotaModelsConfig; // [NEW] 配额关注列表
     circuit_breaker: CircuitBreakerConfig; // [NEW] 熔断器配置
     proxy: ProxyConfig;
     cloudflared: CloudflaredConfig; // [NEW] Cloudflared 配置
+    audit_log?: AuditLogConfig; // [NEW] 审计日志保留策略
 }
 
 // ============================================================================